@@ -4,12 +4,14 @@ use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::Command;
 use tokio::sync::{mpsc, oneshot, Mutex as TokioMutex, RwLock as TokioRwLock};
 use tokio_util::sync::CancellationToken;
 
+use crate::process_group::{self, ProcessGroup};
+
 /// Token usage data from a Claude CLI session
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -17,6 +19,9 @@ pub struct TokenUsage {
     pub input_tokens: i64,
     pub output_tokens: i64,
     pub cached_input_tokens: i64,
+    /// Anthropic `cache_creation_input_tokens` (billed above the input rate)
+    #[serde(default)]
+    pub cache_creation_input_tokens: i64,
     pub reasoning_output_tokens: i64,
     pub total_tokens: i64,
 }
@@ -28,6 +33,10 @@ pub struct TokenUsageInfo {
     pub session_id: Option<String>,
     pub last_token_usage: TokenUsage,
     pub total_token_usage: TokenUsage,
+    /// Whether `total_token_usage` is a running session total. Agents that only report
+    /// per-turn usage copy `last_token_usage` into it and leave this false.
+    #[serde(default)]
+    pub totals_cumulative: bool,
     pub model_context_window: Option<i64>,
 }
 
@@ -80,6 +89,7 @@ impl AgentCliKind {
 struct CodexAppServerClient {
     // Keep the child handle so the process stays owned and can be terminated.
     _child: TokioMutex<tokio::process::Child>,
+    pid: Option<u32>,
     stdin: TokioMutex<tokio::process::ChildStdin>,
     notif_rx: TokioMutex<mpsc::UnboundedReceiver<Value>>,
     server_req_rx: TokioMutex<mpsc::UnboundedReceiver<Value>>,
@@ -96,6 +106,7 @@ struct CodexAppServerClient {
 /// ACP uses JSON-RPC 2.0 over newline-delimited JSON on stdio.
 struct AcpClient {
     _child: tokio::process::Child,
+    pid: Option<u32>,
     stdin: tokio::process::ChildStdin,
     pending: std::sync::Arc<std::sync::Mutex<HashMap<u64, oneshot::Sender<Value>>>>,
     next_id: std::sync::Arc<std::sync::atomic::AtomicU64>,
@@ -115,8 +126,10 @@ impl AcpClient {
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
+        process_group::isolate(&mut cmd);
 
         let mut child = cmd.spawn().context("failed to spawn ACP agent")?;
+        let pid = child.id();
         let stdin = child.stdin.take().context("missing stdin")?;
         let stdout = child.stdout.take().context("missing stdout")?;
         let stderr = child.stderr.take().context("missing stderr")?;
@@ -156,6 +169,7 @@ impl AcpClient {
 
         Ok(Self {
             _child: child,
+            pid,
             stdin,
             pending,
             next_id,
@@ -244,8 +258,10 @@ impl CodexAppServerClient {
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
+        process_group::isolate(&mut cmd);
 
         let mut child = cmd.spawn().context("failed to spawn codex app-server")?;
+        let pid = child.id();
         let stdin = child.stdin.take().context("missing stdin")?;
         let stdout = child.stdout.take().context("missing stdout")?;
         let stderr = child.stderr.take().context("missing stderr")?;
@@ -313,6 +329,7 @@ impl CodexAppServerClient {
 
         Ok(Self {
             _child: TokioMutex::new(child),
+            pid,
             stdin: TokioMutex::new(stdin),
             notif_rx: TokioMutex::new(notif_rx),
             server_req_rx: TokioMutex::new(server_req_rx),
@@ -332,6 +349,15 @@ impl CodexAppServerClient {
     }
 
     async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let resp = self.send_request(method, params).await?;
+        if resp.get("error").is_some() {
+            return Err(anyhow::anyhow!("codex rpc error: {}", resp));
+        }
+        Ok(resp)
+    }
+
+    /// Send a request and wait for its response, which may be a JSON-RPC error.
+    async fn send_request(&self, method: &str, params: Value) -> Result<Value> {
         let id = self
             .next_id
             .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
@@ -358,11 +384,22 @@ impl CodexAppServerClient {
         stdin.write_all(b"\n").await?;
         stdin.flush().await?;
 
-        let resp = rx.await.context("codex response dropped")?;
-        if resp.get("error").is_some() {
-            return Err(anyhow::anyhow!("codex rpc error: {}", resp));
+        rx.await.context("codex response dropped")
+    }
+
+    /// Whether the app-server is running and answers a cheap request within `wait`.
+    /// Any reply counts, including an error.
+    async fn ping(&self, wait: std::time::Duration) -> bool {
+        let exited = match self._child.try_lock() {
+            Ok(mut child) => !matches!(child.try_wait(), Ok(None)),
+            // Held by shutdown
+            Err(_) => return false,
+        };
+        if exited {
+            return false;
         }
-        Ok(resp)
+        let reply = tokio::time::timeout(wait, self.send_request("model/list", json!({}))).await;
+        matches!(reply, Ok(Ok(_)))
     }
 
     async fn respond_server_request(&self, request_id: &str, result: Value) -> Result<()> {
@@ -439,7 +476,7 @@ impl CodexAppServerClient {
         model: Option<&str>,
         reasoning_effort: Option<&str>,
         _mode: Option<&str>,
-    ) -> Result<String> {
+    ) -> Result<LoadSessionResult> {
         let mut params = json!({
             "threadId": thread_id,
             "cwd": cwd.to_string_lossy(),
@@ -452,13 +489,27 @@ impl CodexAppServerClient {
             params["reasoningEffort"] = json!(effort);
         }
         let resp = self.request("thread/resume", params).await?;
-        let thread_id = resp
+        let thread = resp
             .get("result")
             .and_then(|r| r.get("thread"))
-            .and_then(|t| t.get("id"))
+            .context("missing thread")?;
+        let thread_id = thread
+            .get("id")
             .and_then(|x| x.as_str())
             .context("missing thread id")?;
-        Ok(thread_id.to_string())
+        Ok(LoadSessionResult {
+            session_id: thread_id.to_string(),
+            restored: true,
+            cwd: thread
+                .get("cwd")
+                .and_then(|x| x.as_str())
+                .map(|s| s.to_string()),
+            created_at: thread.get("createdAt").and_then(|x| x.as_i64()),
+            preview: thread
+                .get("preview")
+                .and_then(|x| x.as_str())
+                .map(|s| s.to_string()),
+        })
     }
 
     async fn turn_interrupt_active(&self, thread_id: &str) -> Result<bool> {
@@ -659,7 +710,11 @@ impl CodexAppServerClient {
                                         {
                                             on_update(StreamingUpdate::ToolCall {
                                                 name: "edit_file".to_string(),
-                                                arguments: json!({"path": path}).to_string(),
+                                                arguments: json!({
+                                                    "path": path,
+                                                    "kind": change.get("kind"),
+                                                })
+                                                .to_string(),
                                             });
                                         }
                                     }
@@ -1122,6 +1177,17 @@ pub struct AgentProcessClient {
     codex_mode: std::sync::Mutex<Option<String>>,
     codex_app_server: Option<std::sync::Arc<CodexAppServerClient>>,
     acp_client: Option<TokioMutex<AcpClient>>,
+    /// Pids of the long-lived agent processes above, for force-killing on app exit.
+    process_ids: Vec<u32>,
+    /// Process groups of the long-lived agent processes, so whatever they started can be
+    /// killed with them.
+    process_groups: Vec<ProcessGroup>,
+    /// Pid of the last per-prompt CLI process (0 before the first prompt).
+    prompt_pid: AtomicU32,
+    /// Process group of the last per-prompt CLI process.
+    prompt_group: std::sync::Mutex<Option<std::sync::Arc<ProcessGroup>>>,
+    /// When the latest prompt started.
+    turn_started: std::sync::Mutex<Option<std::time::SystemTime>>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -1318,6 +1384,15 @@ pub struct LoadSessionResult {
     pub session_id: String,
     #[serde(default)]
     pub restored: bool,
+    /// Working directory the agent reports for the restored session, when exposed.
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Creation time (unix seconds) the agent reports for the restored session.
+    #[serde(default)]
+    pub created_at: Option<i64>,
+    /// Preview of the first user message in the restored session.
+    #[serde(default)]
+    pub preview: Option<String>,
 }
 
 impl AgentProcessClient {
@@ -1328,6 +1403,8 @@ impl AgentProcessClient {
         env: &[(String, String)],
         cli_kind: AgentCliKind,
     ) -> Result<Self> {
+        let mut process_ids = Vec::new();
+        let mut process_groups = Vec::new();
         // Codex uses the JSON-RPC app-server protocol (not stream-json prompts).
         let codex_app_server = if std::path::Path::new(command)
             .file_name()
@@ -1336,6 +1413,8 @@ impl AgentProcessClient {
         {
             let client = CodexAppServerClient::start(cwd, env).await?;
             client.initialize().await?;
+            process_ids.extend(client.pid);
+            process_groups.extend(ProcessGroup::attach(&*client._child.lock().await));
             Some(std::sync::Arc::new(client))
         } else {
             None
@@ -1350,6 +1429,8 @@ impl AgentProcessClient {
             let is_acp = filename.contains("claude-code-acp");
             if is_acp {
                 let client = AcpClient::start(command, args, cwd, env).await?;
+                process_ids.extend(client.pid);
+                process_groups.extend(ProcessGroup::attach(&client._child));
                 Some(TokioMutex::new(client))
             } else {
                 None
@@ -1371,6 +1452,11 @@ impl AgentProcessClient {
             codex_mode: std::sync::Mutex::new(None),
             codex_app_server,
             acp_client,
+            process_ids,
+            process_groups,
+            prompt_pid: AtomicU32::new(0),
+            prompt_group: std::sync::Mutex::new(None),
+            turn_started: std::sync::Mutex::new(None),
         })
     }
 
@@ -1409,6 +1495,85 @@ impl AgentProcessClient {
         Ok(())
     }
 
+    /// Whether the agent process this client keeps between prompts is alive and, for Codex,
+    /// still answers a request within `wait`. Clients that spawn a process per prompt have
+    /// nothing to check.
+    pub async fn health_check(&self, wait: std::time::Duration) -> bool {
+        if let Some(codex) = &self.codex_app_server {
+            return codex.ping(wait).await;
+        }
+        if let Some(acp) = &self.acp_client {
+            return match acp.try_lock() {
+                Ok(mut acp) => matches!(acp._child.try_wait(), Ok(None)),
+                // Busy with a request
+                Err(_) => true,
+            };
+        }
+        true
+    }
+
+    /// Pids of the agent processes this client keeps running between prompts.
+    pub fn process_ids(&self) -> &[u32] {
+        &self.process_ids
+    }
+
+    /// Pid of the process spawned for the latest prompt, for CLIs that run one per prompt.
+    /// It may have exited since.
+    pub fn prompt_process_id(&self) -> Option<u32> {
+        Some(self.prompt_pid.load(Ordering::Relaxed)).filter(|pid| *pid != 0)
+    }
+
+    fn set_prompt_process(&self, child: &tokio::process::Child) {
+        self.prompt_pid
+            .store(child.id().unwrap_or(0), Ordering::Relaxed);
+        let group = ProcessGroup::attach(child).map(std::sync::Arc::new);
+        *self.prompt_group.lock().unwrap_or_else(|e| e.into_inner()) = group;
+    }
+
+    fn prompt_group(&self) -> Option<std::sync::Arc<ProcessGroup>> {
+        self.prompt_group
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Once the latest per-prompt CLI process has exited, kill whatever it left running in
+    /// its process group: SIGTERM, then SIGKILL after `grace`. Returns whether anything was
+    /// left.
+    pub async fn kill_prompt_leftovers(&self, grace: std::time::Duration) -> bool {
+        match self.prompt_group() {
+            Some(group) => group.kill_leftovers(grace).await,
+            None => false,
+        }
+    }
+
+    /// After a cancelled turn: `kill_prompt_leftovers`, plus whatever the long-lived agent
+    /// processes started during the turn. The agents themselves and what they started
+    /// earlier (e.g. MCP servers) keep running.
+    pub async fn kill_turn_leftovers(&self, grace: std::time::Duration) -> bool {
+        let since = *self.turn_started.lock().unwrap_or_else(|e| e.into_inner());
+        let followers = self
+            .process_groups
+            .iter()
+            .filter_map(|group| since.map(|since| group.kill_followers_since(since, grace)));
+        let (prompt, followers) = tokio::join!(
+            self.kill_prompt_leftovers(grace),
+            futures_util::future::join_all(followers)
+        );
+        prompt || followers.into_iter().any(|killed| killed)
+    }
+
+    /// Like `kill_prompt_leftovers`, but also for the groups of the long-lived agent
+    /// processes. Call after `shutdown`.
+    pub async fn kill_leftovers(&self, grace: std::time::Duration) -> bool {
+        let prompt = self.prompt_group();
+        let groups = self.process_groups.iter().chain(prompt.as_deref());
+        futures_util::future::join_all(groups.map(|group| group.kill_leftovers(grace)))
+            .await
+            .into_iter()
+            .any(|running| running)
+    }
+
     /// Check if this client is connected to a Codex app-server
     pub fn is_codex(&self) -> bool {
         self.codex_app_server.is_some()
@@ -1506,7 +1671,7 @@ impl AgentProcessClient {
                 .lock()
                 .unwrap_or_else(|e| e.into_inner())
                 .clone();
-            let thread_id = codex
+            return codex
                 .thread_resume(
                     session_id,
                     &self.cwd,
@@ -1514,16 +1679,15 @@ impl AgentProcessClient {
                     reasoning_effort.as_deref(),
                     codex_mode.as_deref(),
                 )
-                .await?;
-            return Ok(LoadSessionResult {
-                session_id: thread_id,
-                restored: true,
-            });
+                .await;
         }
 
         Ok(LoadSessionResult {
             session_id: session_id.to_string(),
             restored: true,
+            cwd: None,
+            created_at: None,
+            preview: None,
         })
     }
 
@@ -1677,6 +1841,8 @@ impl AgentProcessClient {
     where
         F: FnMut(StreamingUpdate),
     {
+        *self.turn_started.lock().unwrap_or_else(|e| e.into_inner()) =
+            Some(std::time::SystemTime::now());
         // Codex uses the app-server JSON-RPC protocol and keeps a long-lived process.
         if let Some(codex) = &self.codex_app_server {
             let model = self.model.lock().unwrap_or_else(|e| e.into_inner()).clone();
@@ -1946,8 +2112,10 @@ impl AgentProcessClient {
             .stdin(Stdio::null())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
+        process_group::isolate(&mut cmd);
 
         let mut child = cmd.spawn().context("failed to spawn agent CLI")?;
+        self.set_prompt_process(&child);
         // (tokio::process::Child does not support kill_on_drop on this version)
 
         let stdout = child
@@ -2470,8 +2638,10 @@ impl AgentProcessClient {
             .stdin(Stdio::null())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
+        process_group::isolate(&mut cmd);
 
         let mut child = cmd.spawn().context("failed to spawn claude --sdk-url")?;
+        self.set_prompt_process(&child);
         let stdout = child.stdout.take().context("missing stdout")?;
         let stderr = child.stderr.take().context("missing stderr")?;
 
@@ -3743,6 +3913,12 @@ fn parse_token_usage(value: &Value, session_id: Option<String>) -> Option<TokenU
     let cached_input_tokens = usage
         .get("cached_input_tokens")
         .or_else(|| usage.get("cachedInputTokens"))
+        .or_else(|| usage.get("cache_read_input_tokens"))
+        .and_then(|v| v.as_i64())
+        .unwrap_or(0);
+    let cache_creation_input_tokens = usage
+        .get("cache_creation_input_tokens")
+        .or_else(|| usage.get("cacheCreationInputTokens"))
         .and_then(|v| v.as_i64())
         .unwrap_or(0);
     let reasoning_output_tokens = usage
@@ -3762,6 +3938,7 @@ fn parse_token_usage(value: &Value, session_id: Option<String>) -> Option<TokenU
             input_tokens,
             output_tokens,
             cached_input_tokens,
+            cache_creation_input_tokens,
             reasoning_output_tokens,
             total_tokens,
         },
@@ -3769,9 +3946,11 @@ fn parse_token_usage(value: &Value, session_id: Option<String>) -> Option<TokenU
             input_tokens,
             output_tokens,
             cached_input_tokens,
+            cache_creation_input_tokens,
             reasoning_output_tokens,
             total_tokens,
         },
+        totals_cumulative: false,
         model_context_window: None,
     })
 }
@@ -3801,6 +3980,7 @@ fn parse_codex_token_usage(value: &Value) -> Option<TokenUsageInfo> {
             .or_else(|| last.get("cachedInputTokens"))
             .and_then(|v| v.as_i64())
             .unwrap_or(0),
+        cache_creation_input_tokens: 0,
         reasoning_output_tokens: last
             .get("reasoning_output_tokens")
             .or_else(|| last.get("reasoningOutputTokens"))
@@ -3815,6 +3995,7 @@ fn parse_codex_token_usage(value: &Value) -> Option<TokenUsageInfo> {
 
     // Parse total (cumulative) usage
     let total = value.get("total");
+    let totals_cumulative = total.is_some();
     let total_usage = total
         .map(|t| TokenUsage {
             input_tokens: t
@@ -3832,6 +4013,7 @@ fn parse_codex_token_usage(value: &Value) -> Option<TokenUsageInfo> {
                 .or_else(|| t.get("cachedInputTokens"))
                 .and_then(|v| v.as_i64())
                 .unwrap_or(0),
+            cache_creation_input_tokens: 0,
             reasoning_output_tokens: t
                 .get("reasoning_output_tokens")
                 .or_else(|| t.get("reasoningOutputTokens"))
@@ -3849,6 +4031,7 @@ fn parse_codex_token_usage(value: &Value) -> Option<TokenUsageInfo> {
         session_id: None,
         last_token_usage: last_usage,
         total_token_usage: total_usage,
+        totals_cumulative,
         model_context_window: value
             .get("model_context_window")
             .or_else(|| value.get("modelContextWindow"))
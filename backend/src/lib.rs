@@ -1,5 +1,6 @@
 pub mod cli;
 pub mod models;
+pub mod process_group;
 pub mod ws_bridge;
 pub mod ws_types;
 
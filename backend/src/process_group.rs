@@ -0,0 +1,455 @@
+//! Agent processes run in their own process group (Unix) or Job Object (Windows), so the
+//! agent and everything it started can be signalled together, including processes that
+//! were reparented after their parent exited.
+
+use std::time::{Duration, SystemTime};
+use tokio::process::{Child, Command};
+
+/// Start `cmd` as the leader of a new process group. Call before spawning.
+pub fn isolate(cmd: &mut Command) {
+    #[cfg(unix)]
+    cmd.process_group(0);
+    #[cfg(not(unix))]
+    let _ = cmd;
+}
+
+/// The process group (or job) of one spawned agent process, its leader.
+#[derive(Debug)]
+pub struct ProcessGroup {
+    #[cfg(unix)]
+    pgid: libc::pid_t,
+    #[cfg(windows)]
+    job: windows_sys::Win32::Foundation::HANDLE,
+    #[cfg(windows)]
+    leader: windows_sys::Win32::Foundation::HANDLE,
+    #[cfg(windows)]
+    leader_pid: u32,
+}
+
+// Both handles are only used through thread-safe Win32 calls.
+#[cfg(windows)]
+unsafe impl Send for ProcessGroup {}
+#[cfg(windows)]
+unsafe impl Sync for ProcessGroup {}
+
+impl ProcessGroup {
+    /// The group of a child spawned after `isolate`. None if it has already exited or the
+    /// platform has no way to group it.
+    #[cfg(unix)]
+    pub fn attach(child: &Child) -> Option<Self> {
+        let pid = child.id()?;
+        Some(Self {
+            pgid: pid as libc::pid_t,
+        })
+    }
+
+    #[cfg(windows)]
+    pub fn attach(child: &Child) -> Option<Self> {
+        use windows_sys::Win32::Foundation::CloseHandle;
+        use windows_sys::Win32::System::JobObjects::{AssignProcessToJobObject, CreateJobObjectW};
+        use windows_sys::Win32::System::Threading::{
+            OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+        };
+
+        let process = child.raw_handle()?;
+        let pid = child.id()?;
+        unsafe {
+            let job = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+            if job.is_null() {
+                return None;
+            }
+            if AssignProcessToJobObject(job, process) == 0 {
+                CloseHandle(job);
+                return None;
+            }
+            // Our own handle, so the leader can be checked after tokio drops the child.
+            let leader = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if leader.is_null() {
+                CloseHandle(job);
+                return None;
+            }
+            Some(Self {
+                job,
+                leader,
+                leader_pid: pid,
+            })
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    pub fn attach(_child: &Child) -> Option<Self> {
+        None
+    }
+
+    /// Whether any process of the group is still running.
+    #[cfg(unix)]
+    pub fn is_alive(&self) -> bool {
+        unsafe { libc::killpg(self.pgid, 0) == 0 }
+    }
+
+    #[cfg(windows)]
+    pub fn is_alive(&self) -> bool {
+        use windows_sys::Win32::System::JobObjects::{
+            JobObjectBasicAccountingInformation, QueryInformationJobObject,
+            JOBOBJECT_BASIC_ACCOUNTING_INFORMATION,
+        };
+
+        unsafe {
+            let mut info: JOBOBJECT_BASIC_ACCOUNTING_INFORMATION = std::mem::zeroed();
+            let ok = QueryInformationJobObject(
+                self.job,
+                JobObjectBasicAccountingInformation,
+                &mut info as *mut _ as *mut _,
+                std::mem::size_of::<JOBOBJECT_BASIC_ACCOUNTING_INFORMATION>() as u32,
+                std::ptr::null_mut(),
+            );
+            ok != 0 && info.ActiveProcesses > 0
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    pub fn is_alive(&self) -> bool {
+        false
+    }
+
+    /// Whether the process the group was created for has exited. On Unix it also has to
+    /// have been reaped, which tokio does once the `Child` is waited on or killed.
+    #[cfg(unix)]
+    pub fn leader_exited(&self) -> bool {
+        unsafe { libc::kill(self.pgid, 0) != 0 }
+    }
+
+    #[cfg(windows)]
+    pub fn leader_exited(&self) -> bool {
+        use windows_sys::Win32::Foundation::STILL_ACTIVE;
+        use windows_sys::Win32::System::Threading::GetExitCodeProcess;
+
+        let mut code = 0u32;
+        unsafe { GetExitCodeProcess(self.leader, &mut code) == 0 || code != STILL_ACTIVE as u32 }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    pub fn leader_exited(&self) -> bool {
+        true
+    }
+
+    #[cfg(unix)]
+    fn leader_pid(&self) -> u32 {
+        self.pgid as u32
+    }
+
+    #[cfg(windows)]
+    fn leader_pid(&self) -> u32 {
+        self.leader_pid
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    fn leader_pid(&self) -> u32 {
+        0
+    }
+
+    /// Running processes of the group with their start times.
+    #[cfg(unix)]
+    async fn members(&self) -> Vec<(u32, SystemTime)> {
+        let Ok(output) = Command::new("ps")
+            .args(["-A", "-o", "pid=,pgid=,etime=,stat="])
+            .output()
+            .await
+        else {
+            return Vec::new();
+        };
+        let now = SystemTime::now();
+        parse_ps_members(&String::from_utf8_lossy(&output.stdout), self.pgid as u32)
+            .into_iter()
+            .map(|(pid, elapsed)| (pid, now - elapsed))
+            .collect()
+    }
+
+    #[cfg(windows)]
+    async fn members(&self) -> Vec<(u32, SystemTime)> {
+        use windows_sys::Win32::Foundation::{CloseHandle, FILETIME};
+        use windows_sys::Win32::System::JobObjects::{
+            JobObjectBasicProcessIdList, QueryInformationJobObject, JOBOBJECT_BASIC_PROCESS_ID_LIST,
+        };
+        use windows_sys::Win32::System::Threading::{
+            GetProcessTimes, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+        };
+
+        #[repr(C)]
+        struct ProcessIdList {
+            list: JOBOBJECT_BASIC_PROCESS_ID_LIST,
+            more: [usize; 1023],
+        }
+
+        unsafe {
+            let mut ids: ProcessIdList = std::mem::zeroed();
+            let ok = QueryInformationJobObject(
+                self.job,
+                JobObjectBasicProcessIdList,
+                &mut ids as *mut _ as *mut _,
+                std::mem::size_of::<ProcessIdList>() as u32,
+                std::ptr::null_mut(),
+            );
+            if ok == 0 {
+                return Vec::new();
+            }
+            let first = std::ptr::addr_of!(ids.list.ProcessIdList) as *const usize;
+            let count = (ids.list.NumberOfProcessIdsInList as usize).min(1024);
+            (0..count)
+                .filter_map(|i| {
+                    let pid = *first.add(i) as u32;
+                    let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+                    if process.is_null() {
+                        return None;
+                    }
+                    let mut times: [FILETIME; 4] = std::mem::zeroed();
+                    let [created, exited, kernel, user] = &mut times;
+                    let ok = GetProcessTimes(process, created, exited, kernel, user);
+                    CloseHandle(process);
+                    (ok != 0).then(|| (pid, filetime_to_system_time(&times[0])))
+                })
+                .collect()
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    async fn members(&self) -> Vec<(u32, SystemTime)> {
+        Vec::new()
+    }
+
+    /// Processes of the group other than the leader that started at or after `since`.
+    pub async fn followers_since(&self, since: SystemTime) -> Vec<u32> {
+        let leader = self.leader_pid();
+        self.members()
+            .await
+            .into_iter()
+            .filter(|(pid, started)| *pid != leader && *started >= since)
+            .map(|(pid, _)| pid)
+            .collect()
+    }
+
+    /// SIGTERM the processes `followers_since(since)` lists, then SIGKILL the ones still
+    /// running after `grace`. The leader keeps running. Returns whether there were any.
+    pub async fn kill_followers_since(&self, since: SystemTime, grace: Duration) -> bool {
+        let mut left = self.followers_since(since).await;
+        if left.is_empty() {
+            return false;
+        }
+        left.iter().for_each(|pid| signal_process(*pid, false));
+        let deadline = tokio::time::Instant::now() + grace;
+        while !left.is_empty() && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            let running = self.followers_since(since).await;
+            left.retain(|pid| running.contains(pid));
+        }
+        left.iter().for_each(|pid| signal_process(*pid, true));
+        true
+    }
+
+    /// Ask every process of the group to exit (SIGTERM). Windows has no graceful
+    /// equivalent for arbitrary processes, so the job is terminated outright.
+    pub fn terminate(&self) {
+        #[cfg(unix)]
+        unsafe {
+            libc::killpg(self.pgid, libc::SIGTERM);
+        }
+        #[cfg(windows)]
+        self.kill();
+    }
+
+    /// Kill every process of the group.
+    pub fn kill(&self) {
+        #[cfg(unix)]
+        unsafe {
+            libc::killpg(self.pgid, libc::SIGKILL);
+        }
+        #[cfg(windows)]
+        unsafe {
+            windows_sys::Win32::System::JobObjects::TerminateJobObject(self.job, 1);
+        }
+    }
+
+    /// SIGTERM the group, then SIGKILL whatever is still running after `grace`. Returns
+    /// whether anything was running.
+    pub async fn shut_down(&self, grace: Duration) -> bool {
+        if !self.is_alive() {
+            return false;
+        }
+        self.terminate();
+        let deadline = tokio::time::Instant::now() + grace;
+        while self.is_alive() && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        if self.is_alive() {
+            self.kill();
+        }
+        true
+    }
+
+    /// Give the leader, which its owner is already stopping, up to `grace` to exit, then
+    /// `shut_down` whatever it left running. Returns whether anything was left.
+    pub async fn kill_leftovers(&self, grace: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + grace;
+        while !self.leader_exited() && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        self.shut_down(grace).await
+    }
+}
+
+/// SIGTERM (or with `force` SIGKILL) one process. Windows can only terminate it.
+#[cfg(unix)]
+fn signal_process(pid: u32, force: bool) {
+    let signal = if force { libc::SIGKILL } else { libc::SIGTERM };
+    unsafe {
+        libc::kill(pid as libc::pid_t, signal);
+    }
+}
+
+#[cfg(windows)]
+fn signal_process(pid: u32, _force: bool) {
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{OpenProcess, TerminateProcess, PROCESS_TERMINATE};
+
+    unsafe {
+        let process = OpenProcess(PROCESS_TERMINATE, 0, pid);
+        if !process.is_null() {
+            TerminateProcess(process, 1);
+            CloseHandle(process);
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn signal_process(_pid: u32, _force: bool) {}
+
+#[cfg(windows)]
+fn filetime_to_system_time(time: &windows_sys::Win32::Foundation::FILETIME) -> SystemTime {
+    // 100ns ticks since 1601-01-01
+    const UNIX_EPOCH_TICKS: u64 = 116_444_736_000_000_000;
+    let ticks = (u64::from(time.dwHighDateTime) << 32) | u64::from(time.dwLowDateTime);
+    SystemTime::UNIX_EPOCH + Duration::from_nanos(ticks.saturating_sub(UNIX_EPOCH_TICKS) * 100)
+}
+
+/// Pids in process group `pgid` with how long they have been running, from
+/// `ps -A -o pid=,pgid=,etime=,stat=` output. Zombies have already exited and are skipped.
+#[cfg(unix)]
+fn parse_ps_members(output: &str, pgid: u32) -> Vec<(u32, Duration)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let group: u32 = fields.next()?.parse().ok()?;
+            let elapsed = parse_etime(fields.next()?)?;
+            let stat = fields.next()?;
+            (group == pgid && !stat.starts_with('Z')).then_some((pid, elapsed))
+        })
+        .collect()
+}
+
+/// ps's `[[dd-]hh:]mm:ss` elapsed time.
+#[cfg(unix)]
+fn parse_etime(value: &str) -> Option<Duration> {
+    let (days, clock) = match value.split_once('-') {
+        Some((days, clock)) => (days.parse::<u64>().ok()?, clock),
+        None => (0, value),
+    };
+    let mut secs = 0;
+    for part in clock.split(':') {
+        secs = secs * 60 + part.parse::<u64>().ok()?;
+    }
+    Some(Duration::from_secs(days * 86_400 + secs))
+}
+
+#[cfg(windows)]
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        use windows_sys::Win32::Foundation::CloseHandle;
+
+        unsafe {
+            CloseHandle(self.leader);
+            CloseHandle(self.job);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn spawn_sh(script: &str) -> (Child, ProcessGroup) {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", script]).stdout(std::process::Stdio::null());
+        isolate(&mut cmd);
+        let child = cmd.spawn().expect("spawn sh");
+        let group = ProcessGroup::attach(&child).expect("group");
+        (child, group)
+    }
+
+    #[tokio::test]
+    async fn test_kill_leftovers_kills_reparented_processes() {
+        // The shell backgrounds a sleeper and exits; the sleeper is only reachable
+        // through the group.
+        let (mut child, group) = spawn_sh("sleep 30 & echo started");
+        child.wait().await.expect("sh exits");
+
+        assert!(group.leader_exited());
+        assert!(group.is_alive(), "backgrounded sleeper outlives its parent");
+        assert!(group.kill_leftovers(Duration::from_secs(2)).await);
+        assert!(!group.is_alive());
+    }
+
+    #[tokio::test]
+    async fn test_kill_followers_since_spares_the_leader_and_older_followers() {
+        // `read` keeps the shell itself waiting on stdin.
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "sleep 30 & read line"])
+            .stdin(std::process::Stdio::piped());
+        isolate(&mut cmd);
+        let mut child = cmd.spawn().expect("spawn sh");
+        let group = ProcessGroup::attach(&child).expect("group");
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let future = SystemTime::now() + Duration::from_secs(60);
+        assert!(group.followers_since(future).await.is_empty());
+        let since = SystemTime::now() - Duration::from_secs(60);
+        assert_eq!(
+            group.followers_since(since).await.len(),
+            1,
+            "just the sleeper"
+        );
+
+        assert!(
+            group
+                .kill_followers_since(since, Duration::from_secs(2))
+                .await
+        );
+        assert!(group.followers_since(since).await.is_empty());
+        assert!(
+            child.try_wait().expect("try_wait").is_none(),
+            "leader still runs"
+        );
+        child.kill().await.expect("kill sh");
+    }
+
+    #[test]
+    fn test_parse_ps_members() {
+        let output = "  10    10    01:02 Ss\n  11    10 1-00:00:05 S\n  12    10    00:01 Z\n  13    99    00:01 S\n";
+        assert_eq!(
+            parse_ps_members(output, 10),
+            vec![
+                (10, Duration::from_secs(62)),
+                (11, Duration::from_secs(86_405)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_kill_leftovers_ignores_a_clean_exit() {
+        let (mut child, group) = spawn_sh("true");
+        child.wait().await.expect("sh exits");
+
+        assert!(!group.kill_leftovers(Duration::from_secs(2)).await);
+    }
+}
@@ -258,6 +258,8 @@
 
   let currentTaskId = null;
   let currentTaskPath = null; // Resolved path for "Open in..." functionality
+  let earliestMessageId = null; // Oldest loaded message id while older history remains
+  let loadingOlderMessages = false;
   let codeReviewLoading = false;
   let autoScroll = true;
   let elapsedTimer = null;
@@ -579,6 +581,43 @@
     }
   }
 
+  const AUTH_METHOD_LABELS = {
+    "api-key": "API key",
+    chatgpt: "ChatGPT login",
+    oauth: "OAuth token",
+    cli: "CLI login",
+    none: "No credentials",
+  };
+
+  // Show which credentials the task's agent was launched with
+  async function refreshLaunchInfo() {
+    if (!currentTaskId) return;
+    if (!ipcRenderer || !ipcRenderer.invoke) return;
+    const taskId = currentTaskId;
+    try {
+      const info = await ipcRenderer.invoke("getTaskLaunchInfo", taskId);
+      if (taskId !== currentTaskId) return;
+      const method = info && info.authMethod;
+      const el = document.getElementById("launchProvenance");
+      if (!el) return;
+      if (!method) {
+        el.style.display = "none";
+        return;
+      }
+      document.getElementById("launchAuthMethod").textContent =
+        AUTH_METHOD_LABELS[method] || method;
+      el.setAttribute(
+        "title",
+        "Launched with " + (AUTH_METHOD_LABELS[method] || method) +
+          (info.model ? " · model " + info.model : "") +
+          (info.claudeRuntime ? " · " + info.claudeRuntime + " runtime" : ""),
+      );
+      el.style.display = "inline-flex";
+    } catch (err) {
+      console.warn("[ChatLog] Failed to load launch info:", err);
+    }
+  }
+
   // Check for existing PR on the current branch
   async function checkExistingPr(projectPath, branch) {
     if (!projectPath || !branch) {
@@ -630,14 +669,19 @@
     const prDropdownToggle = $("#prDropdownToggle");
     const existingLink = $("#existingPrLink");
     const existingText = $("#existingPrText");
+    const importFeedbackBtn = $("#importPrFeedback");
 
     // If a PR exists on this branch, show the PR link and hide PR creation controls.
     // UX: once a PR is created, the dropdown should not remain visible.
     if (currentPrInfo && currentPrInfo.number && currentPrInfo.url) {
       existingLink.attr("href", currentPrInfo.url);
-      existingText.text(`PR #${currentPrInfo.number}`);
+      const prState = currentPrInfo.state && currentPrInfo.state !== "OPEN"
+        ? ` (${currentPrInfo.state.toLowerCase()})`
+        : "";
+      existingText.text(`PR #${currentPrInfo.number}${prState}`);
       existingLink.attr("title", currentPrInfo.title);
       existingLink.show();
+      importFeedbackBtn.toggle(!currentPrInfo.state || currentPrInfo.state === "OPEN");
       createBtn.hide();
       prDropdownContainer.removeClass("open");
       prDropdownToggle.attr("aria-expanded", "false");
@@ -645,6 +689,7 @@
     } else {
       // Show Create PR button
       existingLink.hide();
+      importFeedbackBtn.hide();
       prDropdownContainer.show();
       createBtn.show();
     }
@@ -741,6 +786,16 @@
       clearMessages();
     });
 
+    $("#exportTranscript").on("click", async function (e) {
+      if (!ipcRenderer || !currentTaskId) return;
+      try {
+        const path = await ipcRenderer.invoke("exportTaskTranscript", currentTaskId, e.altKey);
+        if (path) addSystemMessage("Transcript exported to " + path);
+      } catch (err) {
+        addSystemMessage("Transcript export failed: " + err);
+      }
+    });
+
     // PR Dropdown Toggle Handler
     const prDropdownContainer = $("#prDropdownContainer");
     const prDropdownToggle = $("#prDropdownToggle");
@@ -774,6 +829,31 @@
       await createDraftPr();
     });
 
+    // Push Branch - git push -u origin <branch>; offers a force push when it's rejected
+    $("#pushTaskBranch").on("click", async function () {
+      prDropdownContainer.removeClass("open");
+      prDropdownToggle.attr("aria-expanded", "false");
+      if (!currentTaskId) return;
+      try {
+        await ipcRenderer.invoke("pushTaskBranch", currentTaskId, false);
+      } catch (err) {
+        const message = String(err.message || err);
+        if (!/non-fast-forward|fetch first|rejected/i.test(message)) {
+          addSystemMessage(message);
+          return;
+        }
+        if (!window.confirm("The remote branch has commits this one doesn't. Force push (with lease) anyway?")) {
+          addSystemMessage(message);
+          return;
+        }
+        try {
+          await ipcRenderer.invoke("pushTaskBranch", currentTaskId, true);
+        } catch (forceErr) {
+          addSystemMessage(String(forceErr.message || forceErr));
+        }
+      }
+    });
+
     // Create PR Manually - opens GitHub in browser
     $("#createPrManually").on("click", async function () {
       prDropdownContainer.removeClass("open");
@@ -781,6 +861,22 @@
       await openGitHubPrPage();
     });
 
+    $("#importPrFeedback").on("click", async function () {
+      if (!currentTaskId) return;
+      const btn = $(this);
+      btn.prop("disabled", true);
+      updateStatus("Importing review feedback...", "running");
+      try {
+        await ipcRenderer.invoke("importPrFeedback", currentTaskId);
+      } catch (err) {
+        console.error("[ChatLog] PR feedback import error:", err);
+        addSystemMessage("Could not import review feedback: " + (err.message || err));
+        updateStatus("Ready", "idle");
+      } finally {
+        btn.prop("disabled", false);
+      }
+    });
+
     // Helper function to create draft PR via agent
     async function createDraftPr() {
       if (!currentTaskId || !currentTaskPath) {
@@ -841,13 +937,13 @@
           return;
         }
 
-        // Open the GitHub compare/PR creation page
+        // Open the provider's compare/PR creation page
         const url = await ipcRenderer.invoke("getGitHubPrUrl", currentTaskPath, prState.currentBranch, prState.baseBranch);
 
         if (url) {
           await ipcRenderer.invoke("openExternalUrl", url);
         } else {
-          addSystemMessage("Could not determine GitHub PR URL. Make sure the repository has a GitHub remote.");
+          addSystemMessage("Could not determine the PR URL. Make sure the repository has an origin remote.");
         }
       } catch (err) {
         console.error("[ChatLog] Error opening GitHub PR page:", err);
@@ -1035,6 +1131,13 @@
         container.scrollHeight - container.scrollTop <=
         container.clientHeight + 50;
       autoScroll = atBottom;
+      if (container.scrollTop < 40 && earliestMessageId !== null) {
+        loadOlderMessages();
+      }
+    });
+
+    $("#chatContainer").on("click", ".chat-load-older button", function () {
+      loadOlderMessages();
     });
 
     // Send button click
@@ -1080,7 +1183,8 @@
     });
 
     // Enter key to send (Shift+Enter for newline)
-    // Codex steer-mode: while running, Enter = steer (interrupt then run next), Tab = queue.
+    // While running, Enter queues a follow-up for after the current turn.
+    // Codex steer-mode: Enter = steer (interrupt then run next), Tab = queue.
     $("#chatInput").on("keydown", function (e) {
       const isEnter = e.key === "Enter" && !e.shiftKey;
       const isTab = e.key === "Tab" && !e.shiftKey;
@@ -1092,18 +1196,16 @@
         currentAgentId === "codex" && fullSettings && fullSettings.codexFeatureSteer;
       const isRunning = taskStatusState === "running" || isGenerating;
 
-      if (steerEnabled && isRunning && text) {
-        if (pendingAttachments && pendingAttachments.length > 0) {
-          e.preventDefault();
-          addSystemMessage("Finish current generation before sending attachments.");
-          return;
-        }
+      const hasAttachments = pendingAttachments.length > 0;
+      if (isRunning && (text || hasAttachments) && (isEnter || steerEnabled)) {
         e.preventDefault();
-        const disposition = isEnter ? "steer" : "queue";
+        const disposition = steerEnabled && isEnter ? "steer" : "queue";
         const clientMessageId = generateClientMessageId();
-        addQueuedUserPill(text, disposition, clientMessageId);
+        const attachments = chatAttachmentsForSend();
+        addQueuedUserPill(text, disposition, clientMessageId, attachments);
         input.val("");
         input.css("height", "auto");
+        clearPendingAttachments();
         if (ipcRenderer && currentTaskId) {
           ipcRenderer.send(
             "EnqueueChatMessage",
@@ -1111,6 +1213,7 @@
             text,
             clientMessageId,
             disposition,
+            attachments.map(attachmentRef),
           );
         }
         return;
@@ -1251,6 +1354,10 @@
       }
 
       sendPermissionResponse(requestId, responseId, card);
+      // Approving a plan moves a Codex task out of plan mode for the next turn
+      if (card.hasClass("plan-content") && responseId !== "deny") {
+        switchCodexToExecuteMode();
+      }
     });
 
     // AskUserQuestion: submit answer (delegated)
@@ -1517,6 +1624,15 @@
     });
   }
 
+  function switchCodexToExecuteMode() {
+    if (!ipcRenderer || !currentTaskId || currentAgentId !== "codex") {
+      return;
+    }
+    ipcRenderer.invoke("setCodexMode", currentTaskId, "default").catch((err) => {
+      console.error("[ChatLog] Failed to switch Codex mode:", err && err.kind, err);
+    });
+  }
+
   // Send permission response to backend
   function sendPermissionResponse(requestId, responseId, cardElement) {
     if (!ipcRenderer || !currentTaskId) {
@@ -1686,6 +1802,21 @@
         updateBranchIndicator(taskInfo.branch);
         updatePendingUI();
         refreshDiffStats();
+        refreshLaunchInfo();
+      }
+    });
+
+    ipcRenderer.on("PullRequestUpdate", function (e, taskId, pr) {
+      if (taskId === currentTaskId && pr) {
+        currentPrInfo = pr;
+        updatePrButton();
+      }
+    });
+
+    ipcRenderer.on("PrStatusUpdate", function (e, taskId, state) {
+      if (taskId === currentTaskId && currentPrInfo && state) {
+        currentPrInfo.state = state;
+        updatePrButton();
       }
     });
 
@@ -1706,9 +1837,20 @@
     });
 
   // Receive batch of messages (initial load)
-  ipcRenderer.on("ChatLogBatch", function (e, taskId, messages) {
+  ipcRenderer.on("ChatLogBatch", function (e, taskId, messages, page) {
     if (taskId === currentTaskId && Array.isArray(messages)) {
       clearMessages();
+      if (page && page.hasMore) {
+        // History is paged: keep the initial prompt on top, older messages load on demand.
+        earliestMessageId = page.earliestMessageId;
+        if (page.initialPrompt) {
+          addMessage(page.initialPrompt, false);
+          $("#chatContainer").children(".chat-message").last().addClass("initial-prompt");
+        }
+        $("#chatContainer").append(
+          '<div class="chat-load-older"><button type="button">Load earlier messages</button></div>'
+        );
+      }
       const totalMessages = messages.length;
       const startIndex = Math.max(0, totalMessages - MAX_RENDERED_MESSAGES);
       const visibleMessages = messages.slice(startIndex);
@@ -1774,6 +1916,17 @@
       }
     });
 
+    // Text the running generation streamed while this window was closed
+    ipcRenderer.on("StreamingCatchUp", function (e, taskId, catchUp) {
+      if (taskId !== currentTaskId || !catchUp) return;
+      if (catchUp.reasoning) {
+        handleStreamingUpdate({ message_type: "reasoning_chunk", content: catchUp.reasoning });
+      }
+      (catchUp.items || []).forEach(function (item) {
+        handleStreamingUpdate({ message_type: "text_chunk", content: item.text, item_id: item.itemId });
+      });
+    });
+
     // Handle available commands update from ACP (for slash command autocomplete)
     ipcRenderer.on("AvailableCommands", function (e, taskId, agentId, commands) {
       var resolvedCommands = commands;
@@ -1807,14 +1960,72 @@
       }
     });
 
+    // Double-click the header title to rename the task (overrides the AI summary)
+    $("#agentName").on("dblclick", function () {
+      if (!currentTaskId || this.isContentEditable) return;
+      const el = this;
+      const original = el.textContent;
+      const finish = (save) => {
+        $(el).off(".rename");
+        el.contentEditable = "false";
+        const title = el.textContent.trim();
+        if (!save || !title || title === original) {
+          el.textContent = original;
+          return;
+        }
+        ipcRenderer.invoke("renameTask", currentTaskId, title).catch((err) => {
+          console.warn("[ChatLog] Failed to rename task:", err);
+          el.textContent = original;
+        });
+      };
+      el.contentEditable = "true";
+      el.focus();
+      document.getSelection().selectAllChildren(el);
+      $(el).on("keydown.rename", (e) => {
+        if (e.key === "Enter") {
+          e.preventDefault();
+          finish(true);
+        } else if (e.key === "Escape") {
+          e.preventDefault();
+          finish(false);
+        }
+      });
+      $(el).on("blur.rename", () => finish(true));
+    });
+
     // Handle cost updates
-    ipcRenderer.on("CostUpdate", function (e, taskId, cost) {
+    ipcRenderer.on("CostUpdate", function (e, taskId, cost, breakdown) {
+      const formatCost = (value) => (value < 0.01 ? "<$0.01" : "$" + value.toFixed(2));
+      if (taskId === currentTaskId && breakdown && breakdown.billed === false) {
+        const equivalent = typeof breakdown.equivalentCost === "number" ? breakdown.equivalentCost : 0;
+        if (equivalent > 0) {
+          $("#sessionCost").text("Subscription (" + formatCost(equivalent) + " at API rates)");
+        }
+        return;
+      }
       if (taskId === currentTaskId && typeof cost === "number" && cost > 0) {
-        const formatted = cost < 0.01 ? "<$0.01" : "$" + cost.toFixed(2);
+        let formatted = formatCost(cost);
+        const cacheCost = breakdown && typeof breakdown.cacheCost === "number" ? breakdown.cacheCost : 0;
+        if (cacheCost > 0) {
+          formatted += " (incl. " + formatCost(cacheCost) + " cached)";
+        }
         $("#sessionCost").text(formatted);
       }
     });
 
+    // Withdraw a queued follow-up before it is sent
+    $(document).on("click", ".chat-message.user .queued-remove", function () {
+      const clientMessageId = $(this).closest(".chat-message").attr("data-client-message-id");
+      if (ipcRenderer && currentTaskId && clientMessageId) {
+        ipcRenderer.invoke("removeQueuedPrompt", currentTaskId, clientMessageId);
+      }
+    });
+
+    ipcRenderer.on("QueuedPromptRemoved", function (e, taskId, clientMessageId) {
+      if (taskId !== currentTaskId) return;
+      $(`.chat-message.user[data-client-message-id="${escapeSelector(clientMessageId)}"]`).remove();
+    });
+
     // Handle soft stop (generation cancelled but session still alive)
     ipcRenderer.on("GenerationStopped", function (e, taskId) {
       if (taskId === currentTaskId) {
@@ -2183,12 +2394,21 @@
         break;
 
       case "plan_content":
+        if (update.unchanged) {
+          // Identical re-emission of the current plan version
+          updateStatus("Plan unchanged", "running");
+          break;
+        }
         finalizeStreamingMessage();
         flushAccumulatedReasoning();
         addMessage({
           type: "plan_content",
           file_path: update.file_path,
           content: update.content,
+          version: update.version || null,
+          previous_version: update.previous_version || null,
+          added: update.added || 0,
+          removed: update.removed || 0,
           request_id: update.request_id || null,
           allowed_prompts: update.allowed_prompts || null,
         });
@@ -2895,7 +3115,7 @@
     );
   }
 
-  function addQueuedUserPill(text, disposition, clientMessageId) {
+  function addQueuedUserPill(text, disposition, clientMessageId, attachments) {
     addMessage(
       {
         message_type: "user_message",
@@ -2903,11 +3123,28 @@
         timestamp: new Date().toISOString(),
         queuedDisposition: disposition, // "queue" | "steer"
         clientMessageId: clientMessageId,
+        attachments: attachments || [],
       },
       true,
     );
   }
 
+  // Pending uploads as rendered in the chat (with data URLs for inline display)
+  function chatAttachmentsForSend() {
+    return pendingAttachments.map((att) => ({
+      id: att.id,
+      fileName: att.fileName,
+      mimeType: att.mimeType,
+      relativePath: att.relativePath,
+      dataUrl: att.dataUrl,
+    }));
+  }
+
+  // What the backend needs to attach an uploaded file to a message
+  function attachmentRef(att) {
+    return { id: att.id, relativePath: att.relativePath, mimeType: att.mimeType };
+  }
+
   // Send a message to the agent
   function sendMessage() {
     const input = $("#chatInput");
@@ -2922,19 +3159,18 @@
     $("#stopButton").show();
     isGenerating = true;
 
-    // Add user message to chat
+    // Add user message to chat. The client id lets the backend's echo (which carries the
+    // images) match this bubble instead of duplicating it.
+    const attachments = chatAttachmentsForSend();
+    const clientMessageId = hasAttachments ? generateClientMessageId() : null;
     const outgoing = {
       type: "user",
       content: message,
       timestamp: new Date().toISOString(),
     };
     if (hasAttachments) {
-      outgoing.attachments = pendingAttachments.map((att) => ({
-        id: att.id,
-        fileName: att.fileName,
-        mimeType: att.mimeType,
-        dataUrl: att.dataUrl,
-      }));
+      outgoing.attachments = attachments;
+      outgoing.clientMessageId = clientMessageId;
     }
     addMessage(outgoing);
 
@@ -2945,7 +3181,13 @@
 
     // Send to backend
     if (ipcRenderer && currentTaskId) {
-      ipcRenderer.send("SendChatMessage", currentTaskId, message);
+      ipcRenderer.send(
+        "SendChatMessage",
+        currentTaskId,
+        message,
+        attachments.map(attachmentRef),
+        clientMessageId,
+      );
       updateStatus("Sending...", "running");
     }
 
@@ -3105,9 +3347,10 @@
         `.chat-message.user[data-client-message-id="${selectorId}"]`,
       );
       if (existing.length > 0) {
+        // The backend echoes queued prompts as well; only a send clears the pill.
+        if (message.queuedDisposition) return;
         existing.removeClass("queued steer");
-        const label = existing.find(".queued-label");
-        if (label.length) label.remove();
+        existing.find(".queued-label").remove();
         return;
       }
     }
@@ -3399,7 +3642,12 @@
         } else {
           // Regular user message without images
           const labelHtml = queuedLabelText
-            ? '<div class="queued-label">' + escapeHtml(queuedLabelText) + "</div>"
+            ? '<div class="queued-label">' +
+              escapeHtml(queuedLabelText) +
+              (clientMessageId
+                ? '<button class="queued-remove" title="Remove from queue">&times;</button>'
+                : "") +
+              "</div>"
             : "";
           div.innerHTML = labelHtml + escapeHtml(content);
         }
@@ -3882,6 +4130,7 @@
         let filePath = message.file_path || "";
         const planRequestId = message.request_id || null;
         const allowedPrompts = message.allowed_prompts || null;
+        let planVersion = message;
 
         // Parse JSON payload if needed
         if (typeof message.content === "string" && message.content.startsWith("{")) {
@@ -3889,15 +4138,24 @@
             const payload = JSON.parse(message.content);
             planContent = payload.content || message.content;
             filePath = payload.file_path || "";
+            planVersion = payload;
           } catch (e) {
             // Use raw content
           }
         }
+        // e.g. "v3 · +12 −4 from v2"
+        let versionLabel = "";
+        if (planVersion.version) {
+          versionLabel = `v${planVersion.version}`;
+          if (planVersion.previous_version) {
+            versionLabel += ` · +${planVersion.added || 0} −${planVersion.removed || 0} from v${planVersion.previous_version}`;
+          }
+        }
 
         // Build header with file path and send button
         const sendMenuHtml = buildPlanSendMenu();
         const filePathHtml = filePath
-          ? `<span class="plan-file-path"><i class="fal fa-file-alt"></i> ${escapeHtml(filePath)}</span>`
+          ? `<span class="plan-file-path"><i class="fal fa-file-alt"></i> ${escapeHtml(filePath)}${versionLabel ? ` <span class="plan-version">${escapeHtml(versionLabel)}</span>` : ""}</span>`
           : '';
 
         // When from WS ExitPlanMode (has request_id), show approve/deny actions
@@ -3947,6 +4205,37 @@
         break;
       }
 
+      case "pr_feedback": {
+        let feedback = null;
+        try {
+          feedback = JSON.parse(content);
+        } catch (e) {
+          feedback = null;
+        }
+        if (!feedback) {
+          div.className += " system";
+          div.innerHTML = escapeHtml(content);
+          break;
+        }
+        div.className += " pr-feedback";
+        const threads = Array.isArray(feedback.threads) ? feedback.threads : [];
+        const reviews = Array.isArray(feedback.reviews) ? feedback.reviews : [];
+        const items = threads.map(function (thread) {
+          const location = thread.line ? `${thread.path}:${thread.line}` : thread.path;
+          const authors = Array.from(new Set((thread.comments || []).map(function (c) { return "@" + c.author; })));
+          return `<li><code>${escapeHtml(location)}</code> — ${escapeHtml(authors.join(", "))}</li>`;
+        }).concat(reviews.map(function (review) {
+          const reviewState = String(review.state || "").toLowerCase().replace(/_/g, " ");
+          return `<li>Review by @${escapeHtml(review.author)} (${escapeHtml(reviewState)})</li>`;
+        }));
+        div.innerHTML =
+          `<div class="pr-feedback-header"><i class="fal fa-comments"></i> Imported review feedback from PR #${escapeHtml(String(feedback.prNumber))}</div>` +
+          `<ul class="pr-feedback-list">${items.join("")}</ul>`;
+        break;
+      }
+
+      case "app_restart":
+      case "timeout":
       case "system":
       default:
         div.className += " system";
@@ -4164,8 +4453,64 @@
     return prompt;
   }
 
+  // Fetch the page before earliestMessageId and insert it above the loaded history
+  async function loadOlderMessages() {
+    if (loadingOlderMessages || earliestMessageId === null || !ipcRenderer) return;
+    loadingOlderMessages = true;
+    const taskId = currentTaskId;
+    const button = $("#chatContainer .chat-load-older button");
+    button.prop("disabled", true).text("Loading...");
+    try {
+      const result = await ipcRenderer.invoke("getOlderMessages", taskId, earliestMessageId);
+      if (!result || taskId !== currentTaskId) return;
+      prependMessages(Array.isArray(result.messages) ? result.messages : []);
+      if (result.has_more) {
+        earliestMessageId = result.earliest_message_id;
+      } else {
+        // The first page starts with the prompt itself.
+        earliestMessageId = null;
+        $("#chatContainer").children(".chat-load-older, .initial-prompt").remove();
+      }
+    } catch (err) {
+      console.error("[ChatLog] Failed to load earlier messages:", err);
+    } finally {
+      loadingOlderMessages = false;
+      button.prop("disabled", false).text("Load earlier messages");
+    }
+  }
+
+  function prependMessages(messages) {
+    const container = $("#chatContainer");
+    const el = container[0];
+    const loadRow = container.children(".chat-load-older");
+    if (!el || !loadRow.length || !messages.length) return;
+
+    const previousHeight = el.scrollHeight;
+    const previousTop = el.scrollTop;
+    const later = loadRow.nextAll().detach();
+
+    // Render with fresh bundling/dedupe state so live tool bundles aren't touched.
+    const savedBundle = toolCallBundle;
+    const savedRendered = renderedToolCalls;
+    const savedFinalized = lastFinalizedContent;
+    toolCallBundle = { calls: [], element: null, expanded: false, iconNames: new Set() };
+    renderedToolCalls = new Map();
+    lastFinalizedContent = "";
+    messages.forEach(function (msg) {
+      addMessageWithBundling(msg, false);
+    });
+    finalizeToolBundle();
+    toolCallBundle = savedBundle;
+    renderedToolCalls = savedRendered;
+    lastFinalizedContent = savedFinalized;
+
+    container.append(later);
+    el.scrollTop = previousTop + (el.scrollHeight - previousHeight);
+  }
+
   // Clear all messages
   function clearMessages() {
+    earliestMessageId = null;
     const container = $("#chatContainer");
     container.empty();
     container.append('<div class="chat-empty">No messages yet</div>');
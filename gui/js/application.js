@@ -477,6 +477,15 @@ function setAuthInputsEnabled(enabled) {
 // then async settings restore overwrites their edits before autosave is active.
 setAuthInputsEnabled(false);
 
+// Per-agent "prefer OAuth over API key" toggles, keyed by agent id.
+function collectPreferOAuth() {
+  const prefs = {};
+  document.querySelectorAll("[data-prefer-oauth]").forEach((input) => {
+    prefs[input.dataset.preferOauth] = input.checked;
+  });
+  return prefs;
+}
+
 function collectAuthInputs() {
   const auth = {};
   document.querySelectorAll("[data-auth-key]").forEach((input) => {
@@ -654,6 +663,58 @@ $(document).on("click", "#projectScriptsSaveBtn", function () {
   sendNotification("Project scripts saved", "green");
 });
 
+// "CHANNEL_ID=PROJECT_PATH" lines <-> the discordChannelProjects map.
+function parseDiscordChannelProjects(text) {
+  const projects = {};
+  (text || "").toString().split("\n").forEach((line) => {
+    const trimmed = line.trim();
+    if (!trimmed || trimmed.startsWith("#")) return;
+    const idx = trimmed.indexOf("=");
+    if (idx < 0) return;
+    const channelId = trimmed.slice(0, idx).trim();
+    const projectPath = trimmed.slice(idx + 1).trim();
+    if (channelId && projectPath) projects[channelId] = projectPath;
+  });
+  return projects;
+}
+
+function formatDiscordChannelProjects(projects) {
+  if (!projects || typeof projects !== "object") return "";
+  return Object.entries(projects)
+    .map(([channelId, projectPath]) => `${channelId}=${projectPath}`)
+    .join("\n");
+}
+
+// Discord bridge health: a status line in Settings, and a banner while it's down.
+function renderDiscordStatus(status) {
+  const enabled = $("#discordEnabled").is(":checked");
+  const connected = !!(status && status.connected);
+  const lastError = status && status.lastError;
+  let text = "Disabled";
+  if (enabled && connected) {
+    text = "Connected";
+    if (status.lastHeartbeat) {
+      text += ` · last heartbeat ${new Date(status.lastHeartbeat).toLocaleTimeString()}`;
+    }
+  } else if (enabled) {
+    text = lastError ? `Disconnected: ${lastError}` : "Connecting...";
+  }
+  $("#discordStatus").text(text);
+  $("#discordStatusBanner")
+    .text(`Discord bridge is down: ${lastError || ""}`)
+    .toggle(enabled && !connected && !!lastError);
+}
+
+async function refreshDiscordStatus() {
+  try {
+    renderDiscordStatus(await ipcRenderer.invoke("getDiscordStatus"));
+  } catch (err) {
+    console.warn("[Harness] get_discord_status failed", err);
+  }
+}
+
+ipcRenderer.on("DiscordStatusChanged", (e, status) => renderDiscordStatus(status));
+
 function updateProjectAllowlist(nextAllowlist) {
   currentSettings.taskProjectAllowlist = nextAllowlist;
   saveSettingsFromUi();
@@ -663,12 +724,21 @@ function updateProjectAllowlist(nextAllowlist) {
 async function saveSettingsFromUi() {
   let discordBotToken = $("#discordBotToken").val();
   let discordChannelId = $("#discordChannelId").val();
+  let discordApproverRoleId = $("#discordApproverRoleId").val();
   let retryDelay = $("#retryDelay").val();
   let errorDelay = $("#errorDelay").val();
   let mcpPortRaw = $("#mcpPort").val();
   let mcpTokenRaw = $("#mcpToken").val();
   let codexPathRaw = $("#codexPath").val();
   let codexPersonalityRaw = $("#codexPersonality").val();
+  let worktreeBaseDirRaw = $("#worktreeBaseDir").val();
+  let branchFetchIntervalRaw = parseInt($("#branchFetchInterval").val(), 10);
+  let prPollIntervalRaw = parseInt($("#prPollInterval").val(), 10);
+  let messageRetentionDaysRaw = parseInt($("#messageRetentionDays").val(), 10);
+  let keepArchivedTasksDaysRaw = parseInt($("#keepArchivedTasksDays").val(), 10);
+  let idleSessionTimeoutMinsRaw = parseInt($("#idleSessionTimeoutMins").val(), 10);
+  let promptTimeoutMinsRaw = parseInt($("#promptTimeoutMins").val(), 10);
+  let statusUpdateThrottleMsRaw = parseInt($("#statusUpdateThrottleMs").val(), 10);
   let taskProjectAllowlist = getProjectAllowlist();
   let agentNotificationTimeoutValue = 0;
   let parsedMcpPort = parseInt(mcpPortRaw, 10);
@@ -691,8 +761,12 @@ async function saveSettingsFromUi() {
     currentSettings,
     {
       discordEnabled: $("#discordEnabled").is(":checked"),
+      discordDiffSummary: $("#discordDiffSummary").is(":checked"),
+      discordDmOnComplete: $("#discordDmOnComplete").is(":checked"),
       discordBotToken: (discordBotToken || "").toString().trim(),
       discordChannelId: (discordChannelId || "").toString().trim(),
+      discordApproverRoleId: (discordApproverRoleId || "").toString().trim(),
+      discordChannelProjects: parseDiscordChannelProjects($("#discordChannelProjects").val()),
       retryDelay: retryDelay,
       errorDelay: errorDelay,
       ignoreDeclines: $("#ignoreDeclines").is(":checked"),
@@ -702,6 +776,30 @@ async function saveSettingsFromUi() {
       aiSummariesEnabled: $("#aiSummariesEnabled").is(":checked"),
       summariesAgent: summariesAgentDropdown ? summariesAgentDropdown.getValue() : "auto",
       taskProjectAllowlist: taskProjectAllowlist,
+      worktreeBaseDir: (worktreeBaseDirRaw || "").toString().trim() || null,
+      deleteBranchOnTaskDelete: $("#deleteBranchOnTaskDelete").is(":checked"),
+      branchFetchInterval: Number.isNaN(branchFetchIntervalRaw)
+        ? null
+        : Math.max(0, branchFetchIntervalRaw),
+      prPollInterval: Number.isNaN(prPollIntervalRaw)
+        ? null
+        : Math.max(0, prPollIntervalRaw),
+      messageRetentionDays: Number.isNaN(messageRetentionDaysRaw)
+        ? null
+        : Math.max(0, messageRetentionDaysRaw),
+      keepArchivedTasksDays: Number.isNaN(keepArchivedTasksDaysRaw)
+        ? null
+        : Math.max(0, keepArchivedTasksDaysRaw),
+      idleSessionTimeoutMins: Number.isNaN(idleSessionTimeoutMinsRaw)
+        ? null
+        : Math.max(0, idleSessionTimeoutMinsRaw),
+      promptTimeoutMins: Number.isNaN(promptTimeoutMinsRaw)
+        ? null
+        : Math.max(0, promptTimeoutMinsRaw),
+      statusUpdateThrottleMs: Number.isNaN(statusUpdateThrottleMsRaw)
+        ? null
+        : Math.min(1000, Math.max(16, statusUpdateThrottleMsRaw)),
+      restoreSessionsOnStartup: $("#restoreSessionsOnStartup").is(":checked"),
       mcpEnabled: $("#mcpEnabled").is(":checked"),
       mcpPort: parsedMcpPort,
       mcpToken: nextMcpToken,
@@ -714,6 +812,7 @@ async function saveSettingsFromUi() {
       codexFeatureCollab: $("#codexFeatureCollab").is(":checked"),
       codexFeatureApps: $("#codexFeatureApps").is(":checked"),
       taskProjectScripts: getProjectScriptsMap(),
+      preferOAuthOverApiKey: collectPreferOAuth(),
     },
     collectAuthInputs(),
   );
@@ -721,6 +820,7 @@ async function saveSettingsFromUi() {
   try {
     await ipcRenderer.invoke("saveSettings", pl);
     sendNotification("Settings saved", "green");
+    refreshDiscordStatus();
   } catch (err) {
     console.warn("[Harness] settings save failed", err);
     sendNotification("Settings save failed", "red");
@@ -728,8 +828,8 @@ async function saveSettingsFromUi() {
 }
 
 // Auto-save settings on any change (inputs and toggles)
-$("#discordBotToken, #discordChannelId, #retryDelay, #errorDelay, #mcpPort, #mcpToken, #codexPath, #codexPersonality").on("change", saveSettingsFromUi);
-$("#discordEnabled, #agentNotificationsEnabled, #agentNotificationStack, #agentNotificationTimeout, #aiSummariesEnabled, #mcpEnabled, #codexFeatureCollaborationModes, #codexFeatureSteer, #codexFeatureUnifiedExec, #codexFeatureCollab, #codexFeatureApps").on("change", saveSettingsFromUi);
+$("#discordBotToken, #discordChannelId, #discordApproverRoleId, #discordChannelProjects, #retryDelay, #errorDelay, #mcpPort, #mcpToken, #codexPath, #codexPersonality, #worktreeBaseDir, #branchFetchInterval, #prPollInterval, #messageRetentionDays, #keepArchivedTasksDays, #idleSessionTimeoutMins, #promptTimeoutMins, #statusUpdateThrottleMs").on("change", saveSettingsFromUi);
+$("#discordEnabled, #discordDiffSummary, #discordDmOnComplete, #agentNotificationsEnabled, #agentNotificationStack, #agentNotificationTimeout, #aiSummariesEnabled, #deleteBranchOnTaskDelete, #restoreSessionsOnStartup, #mcpEnabled, #codexFeatureCollaborationModes, #codexFeatureSteer, #codexFeatureUnifiedExec, #codexFeatureCollab, #codexFeatureApps, #preferOAuthCodex, #preferOAuthClaude").on("change", saveSettingsFromUi);
 
 // Show/hide summaries agent dropdown based on AI summaries toggle
 function updateSummariesAgentVisibility() {
@@ -1820,6 +1920,9 @@ function getSizes(s, e, sizeArray) {
 
 let tasksOnPage = [];
 let taskDataMap = {}; // Store full task data for sorting
+let showArchivedTasks = false;
+let projectFilter = ""; // projectKey shown in the task list ("" = all projects)
+let knownProjectsTimer = null;
 let startingTasks = {}; // Guard against rapid Start clicks per task
 let activeDeleteConfirm = null;
 let deleteConfirmTimer = null;
@@ -1889,10 +1992,13 @@ ipcRenderer.on("AddTask", (e, ID, Task) => {
   const cost = Task.cost || 0;
   const worktreePath = Task.worktreePath || Task.worktree_path || null;
   const projectPath = Task.projectPath || Task.project_path || null;
+  const projectKey = Task.projectKey || null;
   const branch = Task.branch || null; // Git branch name (may differ from folder after async rename)
   const contextId = Task.contextId || Task.context_id || null;
   const totalTokens = Task.totalTokens || null;
   const contextWindow = Task.contextWindow || null;
+  const archived = !!Task.archived;
+  const pinned = !!Task.pinned;
   const agentLogo = AGENT_LOGOS[agent] || AGENT_LOGOS["codex"];
   const animationClass = getAnimationClass(statusState);
 
@@ -1911,10 +2017,13 @@ ipcRenderer.on("AddTask", (e, ID, Task) => {
     statusState: statusState,
     cost: cost,
     worktree: worktreePath || "",
+    projectKey: projectKey,
     branch: branch,
     contextId: contextId,
     totalTokens: totalTokens,
     contextWindow: contextWindow,
+    archived: archived,
+    pinned: pinned,
     diffAdditions: 0,
     diffDeletions: 0,
   };
@@ -1940,13 +2049,13 @@ ipcRenderer.on("AddTask", (e, ID, Task) => {
     ? branch
     : (worktreePath ? worktreePath.split(/[\\/]/).pop() || worktreePath : "-");
   const worktreeTitle = worktreePath ? escapeHtml(worktreePath) : "";
-  let taskElement = `<tr id="task-${ID}" data-display-id="${displayId}" data-task-id="${ID}">
+  let taskElement = `<tr id="task-${ID}" class="${archived ? "archived" : ""}" data-display-id="${displayId}" data-task-id="${ID}">
         <th scope="row">${displayId}</th>
         <td class="agent-cell">
           <span class="agent-logo ${animationClass}" id="task-${ID}-Logo" data-agent="${agent}">${agentLogo}</span>
         </td>
         <td class="model-cell" id="task-${ID}-Model">${model}</td>
-        <td class="worktree-cell" id="task-${ID}-Worktree" title="${worktreeTitle}">${escapeHtml(worktreeLabel)}</td>
+        <td class="worktree-cell" id="task-${ID}-Worktree" title="${worktreeTitle}"><span class="worktree-label">${escapeHtml(worktreeLabel)}</span><span class="branch-divergence" id="task-${ID}-Divergence"></span><span class="files-changed" id="task-${ID}-FilesChanged"></span><a class="branch-checks" id="task-${ID}-Checks" data-action="fix-ci" data-task-id="${ID}" hidden></a><a class="task-pr-link" id="task-${ID}-Pr" data-action="open-pr" data-task-id="${ID}" hidden></a></td>
         <td class="status-cell ${thinkingClass} ${completedClass}" id="task-${ID}-Status" title="${status}">${status}</td>
         <td class="context-cell" id="task-${ID}-Context">
           <div class="context-ring ${contextRingClass}" style="--context-free: ${contextFreePercent}" data-tooltip="${contextTooltip}"></div>
@@ -1955,14 +2064,29 @@ ipcRenderer.on("AddTask", (e, ID, Task) => {
         <td class="actions-cell">
             <a class="play green-text" data-action="start" data-task-id="${ID}"><i class="far fa-play"></i></a>
             <a class="stop yellow-text" data-action="stop" data-task-id="${ID}"><i class="far fa-stop"></i></a>
+            <a class="retry" id="task-${ID}-Retry" data-action="retry" data-task-id="${ID}" title="Retry (Alt-click discards the failed answer)" ${statusState === "error" ? "" : "hidden"}><i class="far fa-redo"></i></a>
             <a class="view-log" data-action="view-log" data-task-id="${ID}"><i class="far fa-terminal"></i></a>
             <a class="save-context ${contextId ? 'has-context' : ''}" data-action="save-context" data-task-id="${ID}" data-context-id="${contextId || ''}"><i class="${contextId ? 'fas' : 'far'} fa-bookmark"></i></a>
+            <a class="duplicate" data-action="duplicate" data-task-id="${ID}" title="Duplicate (new task with the same setup)"><i class="far fa-clone"></i></a>
+            <a class="fork" data-action="fork" data-task-id="${ID}" title="Fork (new worktree and session that continues from this conversation)"><i class="far fa-code-branch"></i></a>
+            <a class="pin ${pinned ? "pinned" : ""}" data-action="pin" data-task-id="${ID}" title="${pinned ? "Pinned: kept by data retention" : "Pin (keep out of data retention)"}"><i class="${pinned ? "fas" : "far"} fa-thumbtack"></i></a>
+            <a class="archive" data-action="${archived ? "unarchive" : "archive"}" data-task-id="${ID}" title="${archived ? "Unarchive" : "Archive (Alt-click also removes the worktree)"}"><i class="far ${archived ? "fa-box-open" : "fa-archive"}"></i></a>
             <a class="delete red-text" data-action="delete" data-task-id="${ID}"><i class="far fa-trash-alt"></i></a>
         </td>
       </tr>`;
   taskElement = $.parseHTML(taskElement, false);
   $("#tasks-table").append(taskElement);
+  if (pinned) {
+    placePinnedRow(ID);
+  }
   tasksOnPage.push(ID);
+  applyProjectFilter(ID);
+  scheduleKnownProjectsRefresh();
+  applyBranchDivergence(ID, Task.branchAhead, Task.branchBehind);
+  applyPullRequest(ID, Task.prNumber, Task.prUrl, Task.prState, Task.prChecks);
+  if (branch && worktreePath) {
+    loadBranchChecks(ID);
+  }
 
   // Apply any pending status updates that arrived before task was in DOM
   if (pendingStatusUpdates[ID]) {
@@ -2016,6 +2140,7 @@ function applyStatusUpdate(id, message, color, statusState) {
 
   // Update animation class if statusState provided
   if (statusState) {
+    $(`#task-${id}-Retry`).prop("hidden", statusState !== "error");
     const logoEl = $(`#task-${id}-Logo`);
     logoEl.removeClass("idle running completed error");
     logoEl.addClass(getAnimationClass(statusState));
@@ -2051,9 +2176,31 @@ ipcRenderer.on(
   },
 );
 
+// ChatGPT-plan and Claude OAuth logins are not billed per token
+function isSubscriptionAuth(authMethod) {
+  return authMethod === "chatgpt" || authMethod === "oauth";
+}
+
+// Tooltip for a task cost cell from a CostUpdate breakdown
+function costUpdateTitle(breakdown) {
+  if (!breakdown) return null;
+  if (breakdown.billed === false) {
+    return `Subscription: not billed (${formatCost(breakdown.equivalentCost || 0)} at API rates)`;
+  }
+  if (breakdown.cacheCost > 0) {
+    return `Includes ${formatCost(breakdown.cacheCost)} for cached tokens`;
+  }
+  return null;
+}
+
 // Cost update handler
-ipcRenderer.on("CostUpdate", (e, id, cost) => {
-  $(`#task-${id}-Cost`).text(formatCost(cost));
+ipcRenderer.on("CostUpdate", (e, id, cost, breakdown) => {
+  const costEl = $(`#task-${id}-Cost`);
+  costEl.text(formatCost(cost));
+  const title = costUpdateTitle(breakdown);
+  if (title) {
+    costEl.attr("title", title);
+  }
 
   // Update task data map
   if (taskDataMap[id]) {
@@ -2075,11 +2222,111 @@ ipcRenderer.on("BranchUpdate", (e, id, branchName) => {
   // Update the worktree cell in the task list to show the branch name
   const worktreeCell = $(`#task-${id}-Worktree`);
   if (worktreeCell.length > 0) {
-    worktreeCell.text(escapeHtml(branchName));
+    worktreeCell.find(".worktree-label").text(escapeHtml(branchName));
     worktreeCell.attr("title", branchName);
   }
 });
 
+// Ahead/behind counts against the task's base branch (background fetch)
+function applyBranchDivergence(id, ahead, behind) {
+  const badge = $(`#task-${id}-Divergence`);
+  if (badge.length === 0) return;
+  const parts = [];
+  if (behind > 0) parts.push(`↓${behind}`);
+  if (ahead > 0) parts.push(`↑${ahead}`);
+  badge.text(parts.join(" "));
+  badge.toggleClass("is-behind", behind > 0);
+  badge.attr(
+    "title",
+    parts.length ? `${behind || 0} behind, ${ahead || 0} ahead of base` : "",
+  );
+}
+
+ipcRenderer.on("BranchDivergenceUpdate", (e, id, ahead, behind) => {
+  applyBranchDivergence(id, ahead, behind);
+});
+
+// Files the running generation changed so far, from its edit/write tool calls
+ipcRenderer.on("FileChanged", (e, id, change, changedFiles) => {
+  const badge = $(`#task-${id}-FilesChanged`);
+  if (badge.length === 0 || !changedFiles) return;
+  badge.text(`${changedFiles} file${changedFiles === 1 ? "" : "s"} changed`);
+  if (change && change.path) {
+    badge.attr("title", `Last: ${change.path} (${change.kind})`);
+  }
+});
+
+// Pull request opened for the task, with its last polled state and checks rollup
+function applyPullRequest(id, number, url, state, checks) {
+  const link = $(`#task-${id}-Pr`);
+  if (link.length === 0) return;
+  if (number && url) {
+    link.text(`#${number}`);
+    link.attr("data-url", url);
+    link.removeAttr("hidden");
+  } else if (!link.attr("data-url")) {
+    link.attr("hidden", true);
+    return;
+  }
+  if (state !== undefined) {
+    link.attr("data-state", (state || "OPEN").toLowerCase());
+  }
+  if (checks !== undefined) {
+    link.attr("data-checks", checks || "");
+  }
+  const parts = [link.attr("data-url")];
+  if (link.attr("data-state")) parts.push(link.attr("data-state"));
+  if (link.attr("data-checks")) parts.push(`checks ${link.attr("data-checks")}`);
+  link.attr("title", parts.join(" · "));
+}
+
+// CI checks on the task branch's pushed head; only shown once there are checks to report
+function applyBranchChecks(id, checks) {
+  const dot = $(`#task-${id}-Checks`);
+  if (dot.length === 0) return;
+  const shown = checks && ["passing", "failing", "pending"].includes(checks.state);
+  if (!shown) {
+    dot.attr("hidden", true);
+    return;
+  }
+  const lines = [`Checks ${checks.state}: ${checks.passed} passed, ${checks.failed} failed, ${checks.pending} pending`];
+  (checks.failing || []).forEach((check) => lines.push(`✗ ${check.name}`));
+  if (checks.state === "failing") lines.push("Click to ask the agent to fix");
+  dot.attr("data-state", checks.state);
+  dot.attr("title", lines.join("\n"));
+  dot.removeAttr("hidden");
+}
+
+function loadBranchChecks(id) {
+  ipcRenderer
+    .invoke("getBranchChecks", id)
+    .then((checks) => applyBranchChecks(id, checks))
+    .catch((err) => console.warn("[Harness] getBranchChecks failed:", id, err));
+}
+
+ipcRenderer.on("PullRequestUpdate", (e, id, pr) => {
+  if (!pr) return;
+  applyPullRequest(id, pr.number, pr.url, pr.state, null);
+  loadBranchChecks(id);
+});
+
+ipcRenderer.on("PrStatusUpdate", (e, id, state, checks) => {
+  applyPullRequest(id, null, null, state, checks ? checks.status : null);
+});
+
+// Files changed outside the agent while it was generating (e.g. saved from an editor)
+ipcRenderer.on("ExternalEditDetected", (e, id, payload) => {
+  const files = (payload && payload.files) || [];
+  if (files.length === 0 && !(payload && payload.truncated)) return;
+  const displayId = taskDataMap[id] ? taskDataMap[id].displayId : id;
+  const shown = files.slice(0, 3).join(", ");
+  const more = files.length > 3 || payload.truncated ? " and more" : "";
+  sendNotification(
+    `Task ${displayId}: edited outside the agent while running: ${shown}${more}`,
+    "yellow",
+  );
+});
+
 // Token usage update handler for context indicator
 ipcRenderer.on("TokenUsageUpdate", (e, id, usage) => {
   const ring = $(`#task-${id}-Context .context-ring`);
@@ -2168,8 +2415,74 @@ function DeleteTask(id) {
   performTaskDeletion(id);
 }
 
+function ArchiveTask(id, removeWorktree) {
+  const displayId = taskDataMap[id] ? taskDataMap[id].displayId : id;
+  ipcRenderer
+    .invoke("archiveTask", id, removeWorktree)
+    .then((result) => {
+      if (result && result.removedWorktree) {
+        sendNotification(`Task ${displayId} archived and its worktree removed`, "green");
+      }
+    })
+    .catch((err) => sendNotification(`Task ${displayId}: ${err}`, "red"));
+}
+
+// Archiving hides the row; unarchiving (or archiving while archived tasks are shown) re-renders it.
+ipcRenderer.on("ArchivedTask", (e, id, archived, task) => {
+  removeTaskRow(id);
+  if (task && (!archived || showArchivedTasks)) {
+    addPersistedTask(task);
+  }
+});
+
 function performTaskDeletion(id) {
   ipcRenderer.send("DeleteTask", id);
+  removeTaskRow(id);
+}
+
+function inProjectFilter(id) {
+  return !projectFilter || (taskDataMap[id] && taskDataMap[id].projectKey === projectFilter);
+}
+
+function applyProjectFilter(id) {
+  $(`#task-${id}`).toggleClass("project-hidden", !inProjectFilter(id));
+}
+
+// AddTask fires once per task while the list loads, so coalesce the refreshes.
+function scheduleKnownProjectsRefresh() {
+  clearTimeout(knownProjectsTimer);
+  knownProjectsTimer = setTimeout(refreshKnownProjects, 300);
+}
+
+function refreshKnownProjects() {
+  ipcRenderer
+    .invoke("listKnownProjects")
+    .then((projects) => {
+      if (!Array.isArray(projects)) return;
+      const $select = $("#globalProjectFilter");
+      $select.find("option:not(:first)").remove();
+      projects.forEach((project) => {
+        $("<option>")
+          .val(project.projectKey)
+          .text(`${project.name} (${project.taskCount})`)
+          .attr("title", project.projectKey)
+          .appendTo($select);
+      });
+      if (projectFilter && !projects.some((p) => p.projectKey === projectFilter)) {
+        projectFilter = "";
+        tasksOnPage.forEach(applyProjectFilter);
+      }
+      $select.val(projectFilter);
+    })
+    .catch((err) => console.warn("[Harness] listKnownProjects failed:", err));
+}
+
+$("#globalProjectFilter").on("change", function () {
+  projectFilter = $(this).val() || "";
+  tasksOnPage.forEach(applyProjectFilter);
+});
+
+function removeTaskRow(id) {
   $(`#task-${id}`).remove();
   let index = tasksOnPage.indexOf(id);
   if (index > -1) {
@@ -2177,6 +2490,7 @@ function performTaskDeletion(id) {
   }
   // Clean up task data map
   delete taskDataMap[id];
+  scheduleKnownProjectsRefresh();
 }
 
 function resetDeleteConfirm() {
@@ -2217,10 +2531,17 @@ function setDeleteConfirm(element, taskId) {
   if (!ipcRenderer) return;
   ipcRenderer.invoke("checkTaskUncommittedChanges", taskId)
     .then((result) => {
-      if (!result || !result.hasChanges || element.dataset.confirming !== "true") return;
+      if (!result || element.dataset.confirming !== "true") return;
+      const warnings = [];
+      if (result.hasChanges) {
+        warnings.push(`Uncommitted changes in ${result.worktreePath || "worktree"}.`);
+      }
+      if (result.openPr) {
+        warnings.push(`PR #${result.openPr.number} is still open.`);
+      }
+      if (warnings.length === 0) return;
       element.classList.add("confirm-delete-warning");
-      const worktreePath = result.worktreePath || "worktree";
-      element.title = `Uncommitted changes in ${worktreePath}. Click to delete.`;
+      element.title = `${warnings.join(" ")} Click to delete.`;
     })
     .catch((err) => {
       console.error("[Harness] Error checking uncommitted changes:", err);
@@ -2337,7 +2658,7 @@ document.addEventListener("click", (e) => {
 });
 
 // Task action handlers (avoid inline onclick for CSP/release builds)
-$("#tasks-table").on("click", "a.play, a.stop, a.view-log, a.save-context, a.delete", function (event) {
+$("#tasks-table").on("click", "a.play, a.stop, a.view-log, a.save-context, a.retry, a.duplicate, a.fork, a.pin, a.archive, a.delete, a.task-pr-link, a.branch-checks", function (event) {
   event.preventDefault();
   const action = this.dataset.action;
   const taskId = this.dataset.taskId || $(this).closest("tr").data("task-id");
@@ -2348,9 +2669,71 @@ $("#tasks-table").on("click", "a.play, a.stop, a.view-log, a.save-context, a.del
     StopTask(taskId);
   } else if (action === "view-log") {
     ViewTaskLog(taskId);
+  } else if (action === "open-pr") {
+    event.stopPropagation();
+    const url = this.dataset.url;
+    if (url) {
+      ipcRenderer.invoke("openExternalUrl", url).catch((err) => {
+        console.warn("[Harness] openExternalUrl failed", err);
+      });
+    }
+  } else if (action === "fix-ci") {
+    event.stopPropagation();
+    if (this.dataset.state !== "failing") return;
+    const displayId = taskDataMap[taskId] ? taskDataMap[taskId].displayId : taskId;
+    ipcRenderer
+      .invoke("fixCiFailures", taskId)
+      .then((result) => {
+        if (result && result.queued) {
+          sendNotification(`Task ${displayId}: CI fix queued after the current turn`, "yellow");
+        }
+      })
+      .catch((err) => sendNotification(`Task ${displayId}: ${err}`, "red"));
   } else if (action === "save-context") {
     event.stopPropagation();
     showContextPicker(this, taskId);
+  } else if (action === "retry") {
+    event.stopPropagation();
+    const displayId = taskDataMap[taskId] ? taskDataMap[taskId].displayId : taskId;
+    ipcRenderer
+      .invoke("retryTask", taskId, event.altKey)
+      .catch((err) => sendNotification(`Task ${displayId}: retry failed: ${err}`, "red"));
+  } else if (action === "duplicate") {
+    event.stopPropagation();
+    const displayId = taskDataMap[taskId] ? taskDataMap[taskId].displayId : taskId;
+    ipcRenderer
+      .invoke("duplicateTask", taskId)
+      .then((result) => {
+        sendNotification(`Task ${displayId} duplicated`, "green");
+        if (result && result.modelWarning) {
+          sendNotification(result.modelWarning, "yellow");
+        }
+      })
+      .catch((err) => sendNotification(`Failed to duplicate task: ${err}`, "red"));
+  } else if (action === "fork") {
+    event.stopPropagation();
+    const displayId = taskDataMap[taskId] ? taskDataMap[taskId].displayId : taskId;
+    ipcRenderer
+      .invoke("forkTask", taskId)
+      .then(() => sendNotification(`Task ${displayId} forked`, "green"))
+      .catch((err) => sendNotification(`Failed to fork task: ${err}`, "red"));
+  } else if (action === "pin") {
+    event.stopPropagation();
+    const task = taskDataMap[taskId];
+    if (!task) return;
+    const pinned = !task.pinned;
+    ipcRenderer
+      .invoke("setTaskPinned", taskId, pinned)
+      .then(() => applyTaskPinned(taskId, pinned))
+      .catch((err) => sendNotification(`Failed to pin task: ${err}`, "red"));
+  } else if (action === "archive") {
+    event.stopPropagation();
+    ArchiveTask(taskId, event.altKey);
+  } else if (action === "unarchive") {
+    event.stopPropagation();
+    ipcRenderer.invoke("unarchiveTask", taskId).catch((err) => {
+      sendNotification(`Failed to unarchive task: ${err}`, "red");
+    });
   } else if (action === "delete") {
     event.stopPropagation();
     if (this.dataset.confirming === "true") {
@@ -2367,6 +2750,38 @@ document.addEventListener("click", (event) => {
   resetDeleteConfirm();
 });
 
+// Pinned rows stay in a block at the top of the task list, in pin order.
+function placePinnedRow(taskId) {
+  const row = $(`#task-${taskId}`);
+  const lastPinned = $("#tasks-table tr")
+    .not(row)
+    .filter((_, el) => taskDataMap[$(el).data("task-id")]?.pinned)
+    .last();
+  if (lastPinned.length) {
+    row.insertAfter(lastPinned);
+  } else {
+    $("#tasks-table").prepend(row);
+  }
+}
+
+function applyTaskPinned(taskId, pinned) {
+  const task = taskDataMap[taskId];
+  if (!task || task.pinned === pinned) return;
+  task.pinned = pinned;
+  $(`#task-${taskId} a.pin`)
+    .toggleClass("pinned", pinned)
+    .attr("title", pinned ? "Pinned: kept by data retention" : "Pin (keep out of data retention)")
+    .find("i")
+    .toggleClass("fas", pinned)
+    .toggleClass("far", !pinned);
+  // Unpinned rows drop to just below the pinned block.
+  placePinnedRow(taskId);
+}
+
+ipcRenderer.on("TaskPinned", (e, taskId, pinned) => {
+  applyTaskPinned(taskId, !!pinned);
+});
+
 // Table sorting functionality
 function sortTasks(column) {
   if (currentSortState.column === column) {
@@ -2393,6 +2808,7 @@ function sortTasks(column) {
     const taskB = taskDataMap[idB];
 
     if (!taskA || !taskB) return 0;
+    if (!!taskA.pinned !== !!taskB.pinned) return taskA.pinned ? -1 : 1;
 
     let valA, valB;
 
@@ -2454,20 +2870,112 @@ $("#globalRestartTasks").click(() => {
   ipcRenderer.send("RestartAll");
 });
 $("#globalStopTasks").click(() => {
-  tasksOnPage.forEach((taskID) => {
+  tasksOnPage.filter(inProjectFilter).forEach((taskID) => {
     ipcRenderer.send("StopGeneration", taskID);
   });
 });
 $("#globalDeleteTasks").click(() => {
-  for (let e = tasksOnPage.length - 1; e >= 0; e--) {
-    const element = tasksOnPage[e];
-    DeleteTask(element);
+  const ids = tasksOnPage.filter(
+    (id) => inProjectFilter(id) && !(taskDataMap[id] && taskDataMap[id].archived),
+  );
+  if (!ids.length) return;
+  ids.forEach(removeTaskRow);
+  ipcRenderer
+    .invoke("deleteTasks", ids)
+    .catch((err) => sendNotification(`Failed to delete tasks: ${err}`, "red"));
+});
+
+// One summary per stopTasks/deleteTasks batch instead of a notification per task.
+ipcRenderer.on("BulkTaskResults", (e, batch) => {
+  const results = (batch && batch.results) || [];
+  const failed = results.filter((r) => r.error);
+  if (failed.length) {
+    sendNotification(
+      `Failed to ${batch.action} ${failed.length} of ${results.length} task(s): ${failed[0].error}`,
+      "red",
+    );
+  }
+  if (batch && batch.action === "delete") {
+    const kept = results.filter((r) => r.result && r.result.keptBranch).length;
+    if (kept) {
+      sendNotification(`Kept ${kept} branch(es) with unpushed commits`, "yellow");
+    }
   }
 });
+// CPU/memory of each task's agent and terminal, shown in the status tooltip while enabled.
+let showResourceUsage = false;
+
+function formatResourceBytes(bytes) {
+  if (bytes >= 1024 ** 3) return (bytes / 1024 ** 3).toFixed(1) + " GB";
+  return Math.round(bytes / 1024 ** 2) + " MB";
+}
+
+function applyResourceUsage(usage) {
+  let cpu = 0;
+  let rss = 0;
+  (usage || []).forEach((entry) => {
+    const trees = [entry.agent, entry.terminal].filter(Boolean);
+    const taskCpu = trees.reduce((sum, t) => sum + t.cpuPercent, 0);
+    const taskRss = trees.reduce((sum, t) => sum + t.rssBytes, 0);
+    cpu += taskCpu;
+    rss += taskRss;
+    const status = taskDataMap[entry.taskId]?.status || "";
+    $(`#task-${entry.taskId}-Status`).attr(
+      "title",
+      `${status}\nCPU ${Math.round(taskCpu)}% · ${formatResourceBytes(taskRss)}`,
+    );
+  });
+  $("#globalShowResources").text(
+    `Resources: ${Math.round(cpu)}% · ${formatResourceBytes(rss)}`,
+  );
+}
+
+ipcRenderer.on("ResourceUsageUpdate", (e, usage) => {
+  if (showResourceUsage) applyResourceUsage(usage);
+});
+
+$("#globalShowResources").click(async () => {
+  showResourceUsage = !showResourceUsage;
+  ipcRenderer.invoke("setResourceUsageWatch", showResourceUsage).catch(() => {});
+  if (!showResourceUsage) {
+    $("#globalShowResources").text("Show resources");
+    tasksOnPage.forEach((id) => {
+      $(`#task-${id}-Status`).attr("title", taskDataMap[id]?.status || "");
+    });
+    return;
+  }
+  try {
+    applyResourceUsage(await ipcRenderer.invoke("getSessionResourceUsage"));
+  } catch (err) {
+    showResourceUsage = false;
+    ipcRenderer.invoke("setResourceUsageWatch", false).catch(() => {});
+    sendNotification(`Resource usage unavailable: ${err}`, "red");
+  }
+});
+
+$("#globalShowArchived").click(() => {
+  showArchivedTasks = !showArchivedTasks;
+  $("#globalShowArchived").text(showArchivedTasks ? "Hide archived" : "Show archived");
+  if (!showArchivedTasks) {
+    tasksOnPage
+      .filter((id) => taskDataMap[id] && taskDataMap[id].archived)
+      .forEach(removeTaskRow);
+    return;
+  }
+  ipcRenderer
+    .invoke("loadTasks", true)
+    .then((tasks) => {
+      if (!showArchivedTasks || !Array.isArray(tasks)) return;
+      tasks
+        .filter((task) => task.archived && !taskDataMap[task.id])
+        .forEach(addPersistedTask);
+    })
+    .catch((err) => console.warn("[Harness] loadTasks (archived) failed:", err));
+});
 $("#globalStartTasks").click(() => {
-  tasksOnPage.forEach((taskID) => {
+  tasksOnPage.filter(inProjectFilter).forEach((taskID) => {
     const task = taskDataMap[taskID];
-    if (!task) return;
+    if (!task || task.archived) return;
     const isReady = task.status === "Ready" && task.statusState === "idle";
     if (!isReady) return;
     ipcRenderer.send("StartTask", taskID);
@@ -2476,7 +2984,16 @@ $("#globalStartTasks").click(() => {
 $("#globalFocusMode").click(() => {
   focusMode();
 });
-// Export/Import removed - tasks are now agent sessions
+$("#globalImportTranscript").click(() => {
+  ipcRenderer
+    .invoke("importTaskTranscript", getProjectPath())
+    .then((task) => {
+      if (!task) return;
+      addPersistedTask(task);
+      sendNotification("Imported transcript", "green");
+    })
+    .catch((err) => sendNotification(`Import failed: ${err}`, "red"));
+});
 
 $("#checkUpdates").click(() => {
   $("#statusMessage").removeClass("green-text");
@@ -2521,7 +3038,7 @@ function setNormal(color) {
 ipcRenderer.on("StartAllTB", (e) => {
   tasksOnPage.forEach((taskID) => {
     const task = taskDataMap[taskID];
-    if (!task) return;
+    if (!task || task.archived) return;
     const isReady = task.status === "Ready" && task.statusState === "idle";
     if (!isReady) return;
     ipcRenderer.send("StartTask", taskID);
@@ -2704,6 +3221,43 @@ function logEvent(event) {
   return;
 }
 
+// GitHub CLI install/auth state shown in Settings
+function renderGhStatus(status) {
+  const el = $("#ghStatus");
+  if (!status) {
+    el.text("Unknown");
+    return;
+  }
+  if (!status.installed) {
+    el.html('<i class="fal fa-times-circle text-danger"></i> Not installed');
+  } else if (!status.authenticated) {
+    el.html(
+      `<i class="fal fa-exclamation-circle text-warning"></i> Not connected to ${escapeHtml(status.host)} — run <code>gh auth login</code>`,
+    );
+  } else {
+    const version = status.version ? ` (gh ${escapeHtml(status.version)})` : "";
+    el.html(
+      `<i class="fal fa-check-circle text-success"></i> Connected to ${escapeHtml(status.host)} as ${escapeHtml(status.login || "user")}${version}`,
+    );
+  }
+  el.attr("title", status.error || status.path || "");
+}
+
+async function loadGhStatus(refresh) {
+  try {
+    const status = await ipcRenderer.invoke(refresh ? "refreshGhStatus" : "getGhStatus");
+    renderGhStatus(status);
+  } catch (err) {
+    console.warn("[Harness] gh status check failed", err);
+    renderGhStatus(null);
+  }
+}
+
+$("#refreshGhStatus").on("click", function () {
+  $("#ghStatus").text("Checking...");
+  loadGhStatus(true);
+});
+
 async function getSettings() {
   let settingsPayload = await ipcRenderer.invoke("getSettings");
 
@@ -2718,6 +3272,37 @@ async function getSettings() {
   if (settingsPayload.codexPersonality !== undefined) {
     $("#codexPersonality").val(settingsPayload.codexPersonality || "");
   }
+  $("#worktreeBaseDir").val(settingsPayload.worktreeBaseDir || "");
+  $("#branchFetchInterval").val(
+    settingsPayload.branchFetchInterval != null ? settingsPayload.branchFetchInterval : "",
+  );
+  $("#prPollInterval").val(
+    settingsPayload.prPollInterval != null ? settingsPayload.prPollInterval : "",
+  );
+  $("#messageRetentionDays").val(
+    settingsPayload.messageRetentionDays != null ? settingsPayload.messageRetentionDays : "",
+  );
+  $("#keepArchivedTasksDays").val(
+    settingsPayload.keepArchivedTasksDays != null ? settingsPayload.keepArchivedTasksDays : "",
+  );
+  $("#idleSessionTimeoutMins").val(
+    settingsPayload.idleSessionTimeoutMins != null ? settingsPayload.idleSessionTimeoutMins : "",
+  );
+  $("#promptTimeoutMins").val(
+    settingsPayload.promptTimeoutMins != null ? settingsPayload.promptTimeoutMins : "",
+  );
+  $("#statusUpdateThrottleMs").val(
+    settingsPayload.statusUpdateThrottleMs != null ? settingsPayload.statusUpdateThrottleMs : "",
+  );
+  loadGhStatus(false);
+  $("#deleteBranchOnTaskDelete").prop(
+    "checked",
+    settingsPayload.deleteBranchOnTaskDelete !== false,
+  );
+  $("#restoreSessionsOnStartup").prop(
+    "checked",
+    settingsPayload.restoreSessionsOnStartup === true,
+  );
   // Codex access mode dropdown
   if (codexAccessModeDropdown) {
     codexAccessModeDropdown.setValue(
@@ -2750,11 +3335,20 @@ async function getSettings() {
   if (settingsPayload.discordChannelId !== undefined) {
     $("#discordChannelId").val(settingsPayload.discordChannelId || "");
   }
+  if (settingsPayload.discordApproverRoleId !== undefined) {
+    $("#discordApproverRoleId").val(settingsPayload.discordApproverRoleId || "");
+  }
+  $("#discordChannelProjects").val(
+    formatDiscordChannelProjects(settingsPayload.discordChannelProjects),
+  );
   if (settingsPayload.discordEnabled !== undefined) {
     $("#discordEnabled").prop("checked", !!settingsPayload.discordEnabled);
   } else {
     $("#discordEnabled").prop("checked", false);
   }
+  $("#discordDiffSummary").prop("checked", !!settingsPayload.discordDiffSummary);
+  $("#discordDmOnComplete").prop("checked", !!settingsPayload.discordDmOnComplete);
+  refreshDiscordStatus();
   if (settingsPayload.retryDelay !== undefined) {
     $("#retryDelay").val(settingsPayload.retryDelay);
   }
@@ -2854,6 +3448,11 @@ async function getSettings() {
     }
   });
 
+  const preferOAuth = settingsPayload.preferOAuthOverApiKey || {};
+  document.querySelectorAll("[data-prefer-oauth]").forEach((input) => {
+    input.checked = !!preferOAuth[input.dataset.preferOauth];
+  });
+
   document.querySelectorAll("[data-auth-status]").forEach((node) => {
     const key = node.dataset.authStatus;
     if (key === "codex" && settingsPayload.codexAuthMethod) {
@@ -2997,6 +3596,9 @@ function restoreTaskSettings(settings) {
   if (settings.taskUseWorktree !== undefined) {
     $("#useWorktreeToggle").prop("checked", settings.taskUseWorktree);
   }
+  if (settings.taskSparseWorktree !== undefined) {
+    $("#sparseWorktreeToggle").prop("checked", settings.taskSparseWorktree);
+  }
   if (settings.taskBaseBranch !== undefined) {
     pendingBaseBranchValue = settings.taskBaseBranch;
   }
@@ -3071,6 +3673,7 @@ async function saveTaskSettingsCore(agentIdOverride) {
     taskProjectPath: projectPath,
     taskPlanMode: planModeVal,
     taskUseWorktree: $("#useWorktreeToggle").is(":checked"),
+    taskSparseWorktree: $("#sparseWorktreeToggle").is(":checked"),
     taskBaseBranch: baseBranch,
     taskContextId: contextId,
     taskLastAgent: agentId,
@@ -3194,6 +3797,30 @@ function restoreReasoningEffort(agentId) {
   }
 }
 
+// Emit AddTask for a task record loaded from the database
+function addPersistedTask(task) {
+  if (task.project_path) {
+    addRecentProjectPath(task.project_path);
+  }
+  window.tauriEmitEvent("AddTask", null, task.id, {
+    ID: task.id,
+    agent: task.agent_id,
+    // Don't show a model the agent never accepted.
+    model: task.modelApplied === false ? "default" : task.model,
+    Status: task.status,
+    statusState: task.status_state,
+    cost: task.cost,
+    worktreePath: task.worktreePath || null,
+    totalTokens: task.totalTokens,
+    contextWindow: task.contextWindow,
+    branchAhead: task.branchAhead,
+    branchBehind: task.branchBehind,
+    archived: task.archived,
+    pinned: task.pinned,
+    projectKey: task.projectKey,
+  });
+}
+
 function init() {
   initCustomDropdowns(); // Initialize custom dropdowns first
   getSettings();
@@ -3205,23 +3832,7 @@ function init() {
       .then(function (tasks) {
         if (Array.isArray(tasks)) {
           console.log("[Harness] Loading", tasks.length, "persisted tasks");
-          tasks.forEach(function (task) {
-            if (task.project_path) {
-              addRecentProjectPath(task.project_path);
-            }
-            // Emit AddTask for each persisted task
-            window.tauriEmitEvent("AddTask", null, task.id, {
-              ID: task.id,
-              agent: task.agent_id,
-              model: task.model,
-              Status: task.status,
-              statusState: task.status_state,
-              cost: task.cost,
-              worktreePath: task.worktreePath || null,
-              totalTokens: task.totalTokens,
-              contextWindow: task.contextWindow,
-            });
-          });
+          tasks.forEach(addPersistedTask);
           renderProjectAllowlist();
         }
       })
@@ -3279,7 +3890,12 @@ function init() {
       if (Array.isArray(payload) && payload.length >= 2) {
         var id = payload[0];
         var cost = payload[1];
+        var breakdown = payload[2];
         $("#task-" + id + "-Cost").text(formatCost(cost));
+        var title = costUpdateTitle(breakdown);
+        if (title) {
+          $("#task-" + id + "-Cost").attr("title", title);
+        }
         if (taskDataMap[id]) {
           taskDataMap[id].cost = cost;
         }
@@ -4109,6 +4725,7 @@ init();
 
   // Save settings when toggles change
   $("#useWorktreeToggle").on("change", saveTaskSettings);
+  $("#sparseWorktreeToggle").on("change", saveTaskSettings);
   $("#claudeDockerToggle").on("change", saveTaskSettings);
 
   // Note: Permission mode and model dropdown changes are handled
@@ -4224,6 +4841,7 @@ init();
         planMode: planMode,
         thinking: true,
         useWorktree: forceWorktree,
+        sparseWorktree: $("#sparseWorktreeToggle").is(":checked"),
         permissionMode: permissionMode,
         execModel: execModel,
         reasoningEffort: reasoningEffort !== "default" ? reasoningEffort : null,
@@ -4782,7 +5400,12 @@ init();
 
     // Estimated cost (range)
     const costEl = grid.querySelector('[data-stat="totalCost"]');
-    if (costEl) costEl.textContent = formatCost(stats.totalCost);
+    if (costEl) {
+      costEl.textContent = formatCost(stats.totalCost);
+      costEl.title = isSubscriptionAuth(snapshot.totals?.authMethod)
+        ? "API-rate equivalent; subscription usage is not billed"
+        : "";
+    }
 
     // Output tokens (range)
     const last30El = grid.querySelector('[data-stat="last30"]');
@@ -5166,7 +5789,12 @@ init();
 
     // Total cost (range)
     const costEl = grid.querySelector('[data-stat="totalCost"]');
-    if (costEl) costEl.textContent = formatCost(stats.totalCost);
+    if (costEl) {
+      costEl.textContent = formatCost(stats.totalCost);
+      costEl.title = isSubscriptionAuth(snapshot.totals?.authMethod)
+        ? "API-rate equivalent; subscription usage is not billed"
+        : "";
+    }
 
     // Peak date
     const peakDateEl = grid.closest(".phantom-agent-card")?.querySelector('[data-stat="peakDate"]');
@@ -6368,6 +6996,7 @@ init();
     var groups = [
       { title: 'Tasks', type: 'task' },
       { title: 'Notes', type: 'note' },
+      { title: 'Messages', type: 'message' },
     ];
 
     var flatIndex = 0;
@@ -6389,20 +7018,24 @@ init();
         row.setAttribute('role', 'option');
         row.dataset.index = String(flatIndex);
 
-        state.itemsFlat.push({ type: g.type, id: it.entityId });
+        // Message hits open the task they belong to.
+        state.itemsFlat.push({ type: g.type === 'message' ? 'task' : g.type, id: it.entityId });
 
         var iconHtml = g.type === 'task'
           ? '<div class="notes-palette-item-icon"><i class="fal fa-bolt"></i></div>'
-          : '<div class="notes-palette-item-icon meeting"><i class="fal fa-microphone-alt"></i></div>';
+          : g.type === 'message'
+            ? '<div class="notes-palette-item-icon"><i class="fal fa-comment-alt-lines"></i></div>'
+            : '<div class="notes-palette-item-icon meeting"><i class="fal fa-microphone-alt"></i></div>';
 
-        var title = it.title || (g.type === 'task' ? 'Untitled task' : 'Untitled note');
-        var sub = it.snippet || '';
+        var title = it.title || (g.type === 'note' ? 'Untitled note' : 'Untitled task');
+        // search_messages returns its snippet already escaped, with <mark> highlights.
+        var subHtml = g.type === 'message' ? (it.snippet || '') : escapeHtml(it.snippet || '');
 
         row.innerHTML =
           iconHtml +
           '<div class="notes-palette-item-text">' +
             '<div class="notes-palette-item-title">' + escapeHtml(title) + '</div>' +
-            '<div class="notes-palette-item-sub">' + escapeHtml(sub) + '</div>' +
+            '<div class="notes-palette-item-sub">' + subHtml + '</div>' +
           '</div>' +
           '<div class="notes-palette-item-meta">Open</div>';
 
@@ -6445,11 +7078,28 @@ init();
 
     var reqId = ++state.requestId;
     try {
-      var items = await ipcRenderer.invoke('semantic_search', {
-        req: { query: q, limit: 20, exact: !!state.exact }
-      });
+      var results = await Promise.all([
+        ipcRenderer.invoke('semantic_search', {
+          req: { query: q, limit: 20, exact: !!state.exact }
+        }),
+        // Message history search is an extra; don't fail the palette without it.
+        ipcRenderer.invoke('search_messages', { query: q, limit: 20 }).catch(function (err) {
+          console.warn('[CmdK] search_messages failed:', err);
+          return [];
+        }),
+      ]);
       if (reqId !== state.requestId) return;
-      renderResults(Array.isArray(items) ? items : []);
+      var items = Array.isArray(results[0]) ? results[0] : [];
+      var hits = Array.isArray(results[1]) ? results[1] : [];
+      hits.forEach(function (hit) {
+        items.push({
+          entityType: 'message',
+          entityId: hit.taskId,
+          title: hit.taskTitle,
+          snippet: hit.snippet,
+        });
+      });
+      renderResults(items);
     } catch (err) {
       if (reqId !== state.requestId) return;
       renderEmpty('Search failed');
@@ -10,7 +10,8 @@
     selectedProjectPath: null,
     selectedTaskId: null,
     selectedFilePath: null,
-    compareMode: "main",
+    // null restores the task's last-used compare mode from the backend
+    compareMode: null,
     viewMode: "split",
     commits: [],
     visibilityObserver: null,
@@ -101,6 +102,38 @@
       stats.appendChild(add);
       stats.appendChild(del);
 
+      if (file.comment_count > 0) {
+        const comments = document.createElement("span");
+        comments.className = "review-stat-comments";
+        if (file.unresolved_comment_count > 0) comments.classList.add("unresolved");
+        comments.title = `${file.unresolved_comment_count || 0} unresolved of ${file.comment_count}`;
+        comments.innerHTML = `<i class="fal fa-comment"></i>${file.comment_count}`;
+        stats.appendChild(comments);
+      }
+
+      const viewed = document.createElement("input");
+      viewed.type = "checkbox";
+      viewed.className = "review-file-viewed";
+      viewed.title = "Viewed";
+      viewed.checked = !!file.viewed;
+      if (file.viewed) item.classList.add("viewed");
+      viewed.addEventListener("click", (e) => e.stopPropagation());
+      viewed.addEventListener("change", async () => {
+        item.classList.toggle("viewed", viewed.checked);
+        try {
+          await ipcRenderer?.invoke("setFileViewed", {
+            taskId: state.selectedTaskId,
+            path: file.path,
+            viewed: viewed.checked,
+          });
+        } catch (err) {
+          console.warn("[Review] setFileViewed failed:", err);
+          viewed.checked = !viewed.checked;
+          item.classList.toggle("viewed", viewed.checked);
+        }
+      });
+
+      item.appendChild(viewed);
       item.appendChild(icon);
       item.appendChild(name);
       item.appendChild(stats);
@@ -210,6 +243,53 @@
     body.appendChild(wrap);
   }
 
+  function formatBytes(size) {
+    if (typeof size !== "number") return "—";
+    if (size < 1024) return `${size} B`;
+    if (size < 1024 * 1024) return `${(size / 1024).toFixed(1)} KB`;
+    return `${(size / (1024 * 1024)).toFixed(1)} MB`;
+  }
+
+  function buildImagePreview(image) {
+    const wrap = document.createElement("div");
+    wrap.className = "review-image-preview";
+    [
+      ["Before", image.old_data_url],
+      ["After", image.new_data_url],
+    ].forEach(([label, url]) => {
+      const side = document.createElement("figure");
+      side.className = "review-image-side";
+      if (url) {
+        const img = document.createElement("img");
+        img.src = url;
+        img.alt = label;
+        side.appendChild(img);
+      } else {
+        const missing = document.createElement("div");
+        missing.className = "review-diff-placeholder";
+        missing.textContent = image.truncated ? "Too large to preview" : "No image";
+        side.appendChild(missing);
+      }
+      const caption = document.createElement("figcaption");
+      caption.textContent = label;
+      side.appendChild(caption);
+      wrap.appendChild(side);
+    });
+    return wrap;
+  }
+
+  function renderBinaryDiff(result) {
+    const body = $("reviewDiffBody");
+    if (!body) return;
+    const info = result?.binary || {};
+    body.innerHTML = "";
+    const meta = document.createElement("div");
+    meta.className = "review-diff-placeholder";
+    meta.textContent = `Binary file · ${formatBytes(info.old_size)} → ${formatBytes(info.new_size)}`;
+    body.appendChild(meta);
+    if (result?.image) body.appendChild(buildImagePreview(result.image));
+  }
+
   function renderCommitTimeline(timeline) {
     const track = $("reviewTimelineTrack");
     const meta = $("reviewTimelineMeta");
@@ -333,6 +413,7 @@
         onChange: async function (value) {
           state.selectedTaskId = value || null;
           state.selectedFilePath = null;
+          state.compareMode = null;
           await refreshFiles();
           await refreshCommitTimeline();
         },
@@ -455,7 +536,8 @@
     }
   }
 
-  async function refreshFiles() {
+  // `force` bypasses the backend diff cache (Refresh button).
+  async function refreshFiles(force = false) {
     const empty = $("reviewFilesEmpty");
     if (!state.selectedTaskId) {
       renderFiles([]);
@@ -478,8 +560,14 @@
       const result = await ipcRenderer.invoke("getTaskDiffFiles", {
         taskId: state.selectedTaskId,
         compare: state.compareMode,
+        refresh: force,
       });
       const files = Array.isArray(result?.files) ? result.files : [];
+      if (result?.compare) {
+        state.compareMode = result.compare;
+        // Explicit refs have no button; the toggle is left unselected.
+        setActiveButton($("reviewCompareToggle"), `[data-compare="${CSS.escape(result.compare)}"]`);
+      }
       renderFiles(files);
     } catch (err) {
       console.warn("[Review] getTaskDiffFiles failed:", err);
@@ -521,11 +609,20 @@
         view: state.viewMode,
       });
 
+      if (result?.kind === "binary") {
+        renderBinaryDiff(result);
+        return;
+      }
       if (state.viewMode === "unified") {
         renderUnifiedDiff(result?.diff || "");
       } else {
         renderSplitDiff(result?.diff || { left: [], right: [] });
       }
+      // Text images (SVG) get a rendered preview above the diff.
+      const body = $("reviewDiffBody");
+      if (result?.image && body) {
+        body.insertBefore(buildImagePreview(result.image), body.firstChild);
+      }
     } catch (err) {
       console.warn("[Review] getTaskFileDiff failed:", err);
       if (state.viewMode === "unified") {
@@ -593,7 +690,7 @@
     $("reviewRefreshBtn")?.addEventListener("click", async () => {
       await loadProjectsIntoSelector();
       await loadTasksIntoSelector();
-      await refreshFiles();
+      await refreshFiles(true);
       await refreshCommitTimeline();
     });
 
@@ -23,6 +23,8 @@
       discordEnabled: false,
       discordBotToken: '',
       discordChannelId: '',
+      discordApproverRoleId: '',
+      discordChannelProjects: {},
       retryDelay: 1000,
       errorDelay: 2000,
       openaiApiKey: '',
@@ -109,6 +111,9 @@
 
               // Emit AddTask event - handler will append to DOM
               emitEvent('AddTask', null, result.task_id, agentTask);
+              if (result.modelWarning && typeof sendNotification === 'function') {
+                sendNotification(result.modelWarning, 'yellow');
+              }
 
               // Wait for DOM to settle before navigating (double RAF pattern)
               // First RAF waits for paint, second RAF ensures DOM mutations are complete
@@ -258,6 +263,14 @@
         case 'DeleteTask':
           if (tauriInvoke) {
             tauriInvoke('delete_task', { taskId: args[0] })
+              .then(function(result) {
+                var kept = result && result.keptBranch;
+                if (!kept) return;
+                var message = 'Branch ' + kept.branch + ' has ' + kept.unpushedCommits +
+                  ' unpushed commit(s). Delete it anyway?';
+                if (!window.confirm(message)) return;
+                return tauriInvoke('delete_task_branch', { taskId: args[0] });
+              })
               .catch(function(err) {
                 console.error('[Tauri Bridge] delete_task error:', err);
                 if (typeof sendNotification === 'function') {
@@ -292,7 +305,12 @@
         case 'SendChatMessage':
           console.log('[Tauri Bridge] SendChatMessage:', args[0], args[1]);
           if (tauriInvoke) {
-            tauriInvoke('send_chat_message', { taskId: args[0], message: args[1] })
+            tauriInvoke('send_chat_message', {
+              taskId: args[0],
+              message: args[1],
+              attachments: args[2] || null,
+              clientMessageId: args[3] || null
+            })
               .catch(function(err) {
                 console.error('[Tauri Bridge] send_chat_message error:', err);
               });
@@ -305,7 +323,8 @@
               taskId: args[0],
               message: args[1],
               clientMessageId: args[2],
-              disposition: args[3]
+              disposition: args[3],
+              attachments: args[4] || null
             })
               .catch(function(err) {
                 console.error('[Tauri Bridge] enqueue_chat_message error:', err);
@@ -387,12 +406,29 @@
                     });
                   });
                 }
-                // Emit ChatLogBatch with messages
+                // Emit ChatLogBatch with the latest page of messages
                 if (result && result.messages && eventListeners['ChatLogBatch']) {
                   eventListeners['ChatLogBatch'].forEach(function(cb) {
-                    cb(null, result.task_id, result.messages);
+                    cb(null, result.task_id, result.messages, {
+                      hasMore: !!result.has_more,
+                      earliestMessageId: result.earliest_message_id,
+                      initialPrompt: result.initial_prompt
+                    });
                   });
                 }
+                // Text streamed while the chat window was closed, drawn after the history
+                if (result && eventListeners['StreamingCatchUp']) {
+                  tauriInvoke('get_streaming_catch_up', { taskId: result.task_id })
+                    .then(function(catchUp) {
+                      if (!catchUp) return;
+                      eventListeners['StreamingCatchUp'].forEach(function(cb) {
+                        cb(null, result.task_id, catchUp);
+                      });
+                    })
+                    .catch(function(err) {
+                      console.error('[Tauri Bridge] get_streaming_catch_up error:', err);
+                    });
+                }
               })
               .catch(function(err) {
                 console.error('[Tauri Bridge] get_task_history error:', err);
@@ -460,6 +496,39 @@
         if (channel === 'checkExistingPr') {
           return tauriInvoke('check_existing_pr', { projectPath: args[0] || null, branch: args[1] });
         }
+        if (channel === 'getBranchChecks') {
+          return tauriInvoke('get_branch_checks', { taskId: args[0] });
+        }
+        if (channel === 'fixCiFailures') {
+          return tauriInvoke('fix_ci_failures', { taskId: args[0] });
+        }
+        if (channel === 'getGhStatus') {
+          return tauriInvoke('get_gh_status', { projectPath: args[0] || null });
+        }
+        if (channel === 'refreshGhStatus') {
+          return tauriInvoke('refresh_gh_status', { projectPath: args[0] || null });
+        }
+        if (channel === 'pushTaskBranch') {
+          return tauriInvoke('push_task_branch', { taskId: args[0], force: !!args[1] });
+        }
+        if (channel === 'exportTaskTranscript') {
+          return tauriInvoke('export_task_transcript', { taskId: args[0], includeReasoning: !!args[1] });
+        }
+        if (channel === 'importTaskTranscript') {
+          return tauriInvoke('import_task_transcript', { projectPath: args[0] || null });
+        }
+        if (channel === 'getOlderMessages') {
+          return tauriInvoke('get_task_history', { taskId: args[0], beforeId: args[1] });
+        }
+        if (channel === 'importPrFeedback') {
+          return tauriInvoke('import_pr_feedback', { taskId: args[0] });
+        }
+        if (channel === 'getTaskLaunchInfo') {
+          return tauriInvoke('get_task_launch_info', { taskId: args[0] });
+        }
+        if (channel === 'setCodexMode') {
+          return tauriInvoke('set_codex_mode', { taskId: args[0], mode: args[1] });
+        }
         if (channel === 'getGitHubPrUrl') {
           return tauriInvoke('get_github_pr_url', {
             projectPath: args[0] || null,
@@ -473,6 +542,88 @@
         if (channel === 'getSettings') {
           return tauriInvoke('get_settings');
         }
+        if (channel === 'getDiscordStatus') {
+          return tauriInvoke('get_discord_status');
+        }
+        if (channel === 'reloadPricing') {
+          return tauriInvoke('reload_pricing');
+        }
+        if (channel === 'refreshPricing') {
+          return tauriInvoke('refresh_pricing');
+        }
+        if (channel === 'getTaskCostBreakdown') {
+          return tauriInvoke('get_task_cost_breakdown', { taskId: args[0] });
+        }
+        if (channel === 'getSpendSummary') {
+          return tauriInvoke('get_spend_summary', { range: args[0] || null });
+        }
+        if (channel === 'exportUsageCsv') {
+          return tauriInvoke('export_usage_csv', { path: args[0] || null, range: args[1] || null });
+        }
+        if (channel === 'checkTaskConflicts') {
+          return tauriInvoke('check_task_conflicts', { taskId: args[0] });
+        }
+        if (channel === 'mergeTaskBranch') {
+          return tauriInvoke('merge_task_branch', {
+            taskId: args[0],
+            strategy: args[1] || 'merge',
+            deleteWorktree: !!args[2]
+          });
+        }
+        if (channel === 'rebaseTaskBranch') {
+          return tauriInvoke('rebase_task_branch', { taskId: args[0] });
+        }
+        if (channel === 'revertTaskFile') {
+          return tauriInvoke('revert_task_file', {
+            taskId: args[0],
+            path: args[1],
+            target: args[2]
+          });
+        }
+        if (channel === 'commitTaskChanges') {
+          return tauriInvoke('commit_task_changes', {
+            taskId: args[0],
+            push: !!args[1],
+            message: args[2] || null
+          });
+        }
+        if (channel === 'createPullRequest') {
+          const opts = args[1] || {};
+          return tauriInvoke('create_pull_request', {
+            taskId: args[0],
+            title: opts.title || null,
+            body: opts.body || null,
+            base: opts.base || null,
+            draft: !!opts.draft
+          });
+        }
+        if (channel === 'getWorktreeDiskUsage') {
+          return tauriInvoke('get_worktree_disk_usage', { taskId: args[0] });
+        }
+        if (channel === 'getAllWorktreeUsage') {
+          return tauriInvoke('get_all_worktree_usage');
+        }
+        if (channel === 'cancelWorktreeUsageScan') {
+          return tauriInvoke('cancel_worktree_usage_scan');
+        }
+        if (channel === 'getSessionResourceUsage') {
+          return tauriInvoke('get_session_resource_usage');
+        }
+        if (channel === 'setResourceUsageWatch') {
+          return tauriInvoke('set_resource_usage_watch', { watch: !!args[0] });
+        }
+        if (channel === 'getTaskMetrics') {
+          return tauriInvoke('get_task_metrics', { taskId: args[0] });
+        }
+        if (channel === 'getFullToolOutput') {
+          return tauriInvoke('get_full_tool_output', { messageId: args[0] });
+        }
+        if (channel === 'getTaskFileChanges') {
+          return tauriInvoke('get_task_file_changes', { taskId: args[0] });
+        }
+        if (channel === 'cleanupOrphanedWorktrees') {
+          return tauriInvoke('cleanup_orphaned_worktrees', { dryRun: !!args[0] });
+        }
         if (channel === 'saveSettings') {
           return tauriInvoke('save_settings', { settings: args[0] });
         }
@@ -568,7 +719,55 @@
           return tauriInvoke('claude_rate_limits');
         }
         if (channel === 'loadTasks') {
-          return tauriInvoke('load_tasks');
+          return tauriInvoke('load_tasks', { includeArchived: !!args[0] });
+        }
+        if (channel === 'listTasksByProject') {
+          return tauriInvoke('list_tasks_by_project', { projectKey: args[0], includeArchived: !!args[1] });
+        }
+        if (channel === 'listKnownProjects') {
+          return tauriInvoke('list_known_projects');
+        }
+        if (channel === 'getTaskQueue') {
+          return tauriInvoke('get_task_queue');
+        }
+        if (channel === 'getQueuedPrompts') {
+          return tauriInvoke('get_queued_prompts', { taskId: args[0] });
+        }
+        if (channel === 'removeQueuedPrompt') {
+          return tauriInvoke('remove_queued_prompt', { taskId: args[0], clientMessageId: args[1] });
+        }
+        if (channel === 'clearQueuedPrompts') {
+          return tauriInvoke('clear_queued_prompts', { taskId: args[0] });
+        }
+        if (channel === 'archiveTask') {
+          return tauriInvoke('archive_task', { taskId: args[0], removeWorktree: !!args[1] });
+        }
+        if (channel === 'setTaskPinned') {
+          return tauriInvoke('set_task_pinned', { taskId: args[0], pinned: !!args[1] });
+        }
+        if (channel === 'stopTasks') {
+          return tauriInvoke('stop_tasks', { taskIds: args[0] || [] });
+        }
+        if (channel === 'deleteTasks') {
+          return tauriInvoke('delete_tasks', { taskIds: args[0] || [] });
+        }
+        if (channel === 'retryTask') {
+          return tauriInvoke('retry_task', { taskId: args[0], discardPartial: !!args[1] });
+        }
+        if (channel === 'chainTask') {
+          return tauriInvoke('chain_task', { afterTaskId: args[0], payload: args[1] || {} });
+        }
+        if (channel === 'duplicateTask') {
+          return tauriInvoke('duplicate_task', { taskId: args[0], newPrompt: args[1] || null });
+        }
+        if (channel === 'forkTask') {
+          return tauriInvoke('fork_task', { taskId: args[0] });
+        }
+        if (channel === 'renameTask') {
+          return tauriInvoke('rename_task', { taskId: args[0], title: args[1] || '' });
+        }
+        if (channel === 'unarchiveTask') {
+          return tauriInvoke('unarchive_task', { taskId: args[0] });
         }
         if (channel === 'loadAutomations') {
           return tauriInvoke('load_automations');
@@ -591,6 +790,15 @@
         if (channel === 'runAutomationNow') {
           return tauriInvoke('run_automation_now', { automationId: args[0] });
         }
+        if (channel === 'createScheduledTask') {
+          return tauriInvoke('create_scheduled_task', { payload: args[0] || {} });
+        }
+        if (channel === 'listScheduledTasks') {
+          return tauriInvoke('list_scheduled_tasks');
+        }
+        if (channel === 'deleteScheduledTask') {
+          return tauriInvoke('delete_scheduled_task', { id: args[0] });
+        }
         if (channel === 'previewAutomationNextRun') {
           return tauriInvoke('preview_automation_next_run', { cron: args[0] || '' });
         }
@@ -600,11 +808,19 @@
         if (channel === 'getWorktreeDiffStats') {
           return tauriInvoke('get_task_diff_stats', { taskId: args[0] });
         }
+        if (channel === 'changeTaskModel') {
+          var changeModelPayload = args[0] || {};
+          return tauriInvoke('change_task_model', {
+            taskId: changeModelPayload.taskId,
+            model: changeModelPayload.model || ''
+          });
+        }
         if (channel === 'getTaskDiffFiles') {
           var diffFilesPayload = args[0] || {};
           return tauriInvoke('get_task_diff_files', {
             taskId: diffFilesPayload.taskId,
-            compare: diffFilesPayload.compare || null
+            compare: diffFilesPayload.compare || null,
+            refresh: !!diffFilesPayload.refresh
           });
         }
         if (channel === 'getTaskFileDiff') {
@@ -613,9 +829,47 @@
             taskId: fileDiffPayload.taskId,
             filePath: fileDiffPayload.filePath,
             compare: fileDiffPayload.compare || null,
-            view: fileDiffPayload.view || null
+            view: fileDiffPayload.view || null,
+            contextLines: typeof fileDiffPayload.contextLines === 'number' ? fileDiffPayload.contextLines : null,
+            ignoreWhitespace: !!fileDiffPayload.ignoreWhitespace,
+            refresh: !!fileDiffPayload.refresh
+          });
+        }
+        if (channel === 'setFileViewed') {
+          var viewedPayload = args[0] || {};
+          return tauriInvoke('set_file_viewed', {
+            taskId: viewedPayload.taskId,
+            path: viewedPayload.path,
+            viewed: !!viewedPayload.viewed
           });
         }
+        if (channel === 'addReviewComment') {
+          var addCommentPayload = args[0] || {};
+          return tauriInvoke('add_review_comment', {
+            taskId: addCommentPayload.taskId,
+            filePath: addCommentPayload.filePath,
+            side: addCommentPayload.side || 'new',
+            lineNumber: addCommentPayload.lineNumber,
+            body: addCommentPayload.body || ''
+          });
+        }
+        if (channel === 'listReviewComments') {
+          var listCommentsPayload = args[0] || {};
+          return tauriInvoke('list_review_comments', {
+            taskId: listCommentsPayload.taskId,
+            filePath: listCommentsPayload.filePath || null
+          });
+        }
+        if (channel === 'resolveReviewComment') {
+          var resolveCommentPayload = args[0] || {};
+          return tauriInvoke('resolve_review_comment', {
+            commentId: resolveCommentPayload.commentId,
+            resolved: resolveCommentPayload.resolved !== false
+          });
+        }
+        if (channel === 'deleteReviewComment') {
+          return tauriInvoke('delete_review_comment', { commentId: args[0] });
+        }
         if (channel === 'getReviewProjects') {
           return tauriInvoke('get_review_projects');
         }
@@ -788,6 +1042,9 @@
           case 'getSettings':
             resolve(mockData.settings);
             break;
+          case 'getDiscordStatus':
+            resolve({ connected: false, lastError: null, lastHeartbeat: null });
+            break;
           case 'saveSettings':
             mockData.settings = Object.assign({}, mockData.settings, args[0] || {});
             resolve(true);
@@ -861,9 +1118,36 @@
           case 'loadTasks':
             resolve(mockData.tasks);
             break;
+          case 'listKnownProjects':
+            resolve([]);
+            break;
+          case 'getTaskQueue':
+            resolve({ maxParallel: null, agentLimits: {}, inFlight: [], queued: [] });
+            break;
+          case 'getQueuedPrompts':
+            resolve([]);
+            break;
+          case 'removeQueuedPrompt':
+            resolve(false);
+            break;
+          case 'clearQueuedPrompts':
+            resolve(0);
+            break;
+          case 'getSessionResourceUsage':
+            resolve([]);
+            break;
+          case 'setResourceUsageWatch':
+            resolve(null);
+            break;
+          case 'getTaskMetrics':
+            resolve({ generations: 0, totalToolCalls: 0, totalOutputTokens: 0, history: [] });
+            break;
           case 'loadAutomations':
             resolve(mockData.automations || []);
             break;
+          case 'listScheduledTasks':
+            resolve([]);
+            break;
           case 'loadAutomationRuns':
             resolve(mockData.automationRuns || []);
             break;
@@ -0,0 +1,198 @@
+//! Async access to the task database. Closures run on tokio's blocking pool, so a slow
+//! query never parks a runtime worker. Writes go through the single writer connection
+//! (the one behind `AppState.db`, so transactions and `seq` allocation behave as before);
+//! reads use pooled read-only connections, which in WAL mode never wait on the writer.
+
+use rusqlite::{Connection, OpenFlags};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Idle read-only connections kept for reuse; extra ones are closed after use.
+const MAX_IDLE_READERS: usize = 4;
+
+#[derive(Clone)]
+pub struct AsyncDb {
+    writer: Arc<Mutex<Connection>>,
+    path: PathBuf,
+    readers: Arc<Mutex<Vec<Connection>>>,
+}
+
+impl AsyncDb {
+    /// `path` must be the file `writer` has open (in WAL mode, as `db::init_db` sets up).
+    pub fn new(writer: Arc<Mutex<Connection>>, path: PathBuf) -> Self {
+        Self {
+            writer,
+            path,
+            readers: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Run `f` on the writer connection.
+    pub async fn write<T, F>(&self, f: F) -> Result<T, String>
+    where
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let writer = self.writer.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = writer.lock().map_err(|e| e.to_string())?;
+            f(&mut conn).map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| format!("db worker failed: {e}"))?
+    }
+
+    /// Run `f` on a read-only connection. It sees everything committed before the call.
+    pub async fn read<T, F>(&self, f: F) -> Result<T, String>
+    where
+        F: FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let db = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = db.take_reader()?;
+            let result = f(&conn).map_err(|e| e.to_string());
+            db.put_reader(conn);
+            result
+        })
+        .await
+        .map_err(|e| format!("db worker failed: {e}"))?
+    }
+
+    fn take_reader(&self) -> Result<Connection, String> {
+        if let Some(conn) = self.readers.lock().map_err(|e| e.to_string())?.pop() {
+            return Ok(conn);
+        }
+        let conn = Connection::open_with_flags(
+            &self.path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(|e| e.to_string())?;
+        conn.busy_timeout(Duration::from_secs(5))
+            .map_err(|e| e.to_string())?;
+        Ok(conn)
+    }
+
+    fn put_reader(&self, conn: Connection) {
+        if let Ok(mut readers) = self.readers.lock() {
+            if readers.len() < MAX_IDLE_READERS {
+                readers.push(conn);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_stream_writes_and_task_reads() {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "phantom-harness-async-db-{}.sqlite",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let conn = db::init_db(&path).expect("init db");
+        for i in 0..4 {
+            conn.execute(
+                "INSERT INTO tasks (id, agent_id, model, created_at, updated_at) VALUES (?1, 'codex', 'gpt-5', 0, 0)",
+                [format!("task-{i}")],
+            )
+            .unwrap();
+        }
+        let store = AsyncDb::new(Arc::new(Mutex::new(conn)), path.clone());
+
+        // Four "streams" append tool calls while readers keep listing tasks and history.
+        let mut handles = Vec::new();
+        for i in 0..4 {
+            let store = store.clone();
+            handles.push(tokio::spawn(async move {
+                let task_id = format!("task-{i}");
+                for n in 0..100 {
+                    let task_id = task_id.clone();
+                    store
+                        .write(move |conn| {
+                            db::save_message_with_seq(
+                                conn,
+                                &task_id,
+                                "tool_call",
+                                None,
+                                None,
+                                Some("shell"),
+                                Some(&format!("{{\"n\":{n}}}")),
+                                None,
+                                "2026-01-01T00:00:00Z",
+                            )
+                        })
+                        .await
+                        .unwrap();
+                }
+            }));
+        }
+        for _ in 0..4 {
+            let store = store.clone();
+            handles.push(tokio::spawn(async move {
+                for _ in 0..50 {
+                    let tasks = store.read(db::list_tasks).await.unwrap();
+                    assert_eq!(tasks.len(), 4);
+                    let seqs: Vec<i64> = store
+                        .read(|conn| db::get_messages(conn, "task-0"))
+                        .await
+                        .unwrap()
+                        .iter()
+                        .map(|m| m["seq"].as_i64().unwrap())
+                        .collect();
+                    // Readers only ever see whole, in-order prefixes of the stream.
+                    assert!(seqs.iter().copied().eq(1..=seqs.len() as i64));
+                }
+            }));
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        for i in 0..4 {
+            let task_id = format!("task-{i}");
+            let messages = store
+                .read(move |conn| db::get_message_records(conn, &task_id))
+                .await
+                .unwrap();
+            assert_eq!(messages.len(), 100);
+        }
+        // A failed transaction on the writer is rolled back, as before.
+        let err = store
+            .write(|conn| {
+                let tx = conn.transaction()?;
+                db::save_message(
+                    &tx,
+                    "task-1",
+                    "user_message",
+                    Some("x"),
+                    None,
+                    None,
+                    None,
+                    None,
+                    "now",
+                )?;
+                tx.execute("INSERT INTO no_such_table VALUES (1)", [])?;
+                tx.commit()
+            })
+            .await;
+        assert!(err.is_err());
+        let count = store
+            .read(|conn| db::get_message_records(conn, "task-1"))
+            .await
+            .unwrap()
+            .len();
+        assert_eq!(count, 100);
+
+        drop(store);
+        let _ = std::fs::remove_file(&path);
+    }
+}
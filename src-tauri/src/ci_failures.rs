@@ -0,0 +1,140 @@
+//! Turns failing CI check logs into a prompt asking the task's agent to fix them.
+
+/// Lines kept from the end of each failing log.
+const MAX_EXCERPT_LINES: usize = 80;
+const MAX_EXCERPT_CHARS: usize = 6_000;
+/// Budget for all excerpts together; checks past it are listed without logs.
+const MAX_PROMPT_CHARS: usize = 24_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CiFailure {
+    pub name: String,
+    pub url: Option<String>,
+    pub excerpt: String,
+}
+
+/// The tail of a CI log, ending at the last `##[error]` annotation when there is one.
+/// Actions timestamps and ANSI colors are stripped.
+pub fn log_excerpt(log: &str) -> String {
+    let lines: Vec<String> = log.lines().map(clean_log_line).collect();
+    let end = lines
+        .iter()
+        .rposition(|line| line.contains("##[error]"))
+        .map(|i| i + 1)
+        .unwrap_or(lines.len());
+    let mut lines = &lines[..end];
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines = &lines[..lines.len() - 1];
+    }
+    let start = lines.len().saturating_sub(MAX_EXCERPT_LINES);
+    let excerpt = lines[start..].join("\n");
+    if excerpt.len() <= MAX_EXCERPT_CHARS {
+        return excerpt;
+    }
+    let mut cut = excerpt.len() - MAX_EXCERPT_CHARS;
+    while !excerpt.is_char_boundary(cut) {
+        cut += 1;
+    }
+    format!("…{}", &excerpt[cut..])
+}
+
+fn clean_log_line(line: &str) -> String {
+    // "2024-05-01T12:00:00.1234567Z message"
+    let line = match line.split_once(' ') {
+        Some((stamp, rest))
+            if stamp.len() >= 20 && stamp.ends_with('Z') && stamp.as_bytes()[10] == b'T' =>
+        {
+            rest
+        }
+        _ => line,
+    };
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // Skip "ESC [ ... letter"
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+pub fn fix_prompt(branch: &str, failures: &[CiFailure]) -> String {
+    let mut prompt = format!(
+        "CI is failing on branch `{}`. Fix the failures below, run the relevant checks locally where you can, then commit and push the fix.\n",
+        branch
+    );
+    let mut used = 0;
+    let mut omitted = Vec::new();
+    for failure in failures {
+        let heading = match &failure.url {
+            Some(url) => format!("\n## {} ({})\n", failure.name, url),
+            None => format!("\n## {}\n", failure.name),
+        };
+        if used + failure.excerpt.len() > MAX_PROMPT_CHARS {
+            omitted.push(failure.name.as_str());
+            continue;
+        }
+        used += failure.excerpt.len();
+        prompt.push_str(&heading);
+        if failure.excerpt.is_empty() {
+            prompt.push_str("(no log output available)\n");
+        } else {
+            prompt.push_str(&format!("```\n{}\n```\n", failure.excerpt));
+        }
+    }
+    if !omitted.is_empty() {
+        prompt.push_str(&format!(
+            "\nAlso failing (logs omitted for length): {}\n",
+            omitted.join(", ")
+        ));
+    }
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_excerpt_ends_at_last_error() {
+        let log = "2024-05-01T12:00:00.1234567Z Run cargo test\n\
+                   2024-05-01T12:00:01.0000000Z \u{1b}[31merror[E0308]\u{1b}[0m: mismatched types\n\
+                   2024-05-01T12:00:02.0000000Z ##[error]Process completed with exit code 101.\n\
+                   2024-05-01T12:00:03.0000000Z Post job cleanup.\n";
+        assert_eq!(
+            log_excerpt(log),
+            "Run cargo test\nerror[E0308]: mismatched types\n##[error]Process completed with exit code 101."
+        );
+
+        let long: String = (0..500).map(|i| format!("line {}\n", i)).collect();
+        let excerpt = log_excerpt(&long);
+        assert_eq!(excerpt.lines().count(), MAX_EXCERPT_LINES);
+        assert!(excerpt.ends_with("line 499"));
+    }
+
+    #[test]
+    fn test_fix_prompt_omits_past_budget() {
+        let big = CiFailure {
+            name: "build".to_string(),
+            url: Some("https://github.com/o/r/actions/runs/1/job/2".to_string()),
+            excerpt: "x".repeat(MAX_PROMPT_CHARS - 10),
+        };
+        let lint = CiFailure {
+            name: "lint".to_string(),
+            url: None,
+            excerpt: "y".repeat(20),
+        };
+        let prompt = fix_prompt("feat/login", &[big, lint]);
+        assert!(prompt.contains("branch `feat/login`"));
+        assert!(prompt.contains("## build (https://github.com/o/r/actions/runs/1/job/2)"));
+        assert!(!prompt.contains("## lint"));
+        assert!(prompt.contains("logs omitted for length): lint"));
+    }
+}
@@ -37,7 +37,13 @@ pub struct ClaudeUsageTotals {
     pub cache_hit_rate_percent: f64,
     pub peak_day: Option<String>,
     pub peak_day_tokens: i64,
+    /// Cost at API rates.
     pub total_cost: f64,
+    /// `total_cost`, or 0 when the current login is a subscription.
+    #[serde(default)]
+    pub billed_cost: f64,
+    #[serde(default)]
+    pub auth_method: Option<String>,
 }
 
 /// Token usage breakdown by model.
@@ -116,13 +122,32 @@ pub fn calculate_cost(
     input_cost + output_cost + cache_write_cost + cache_read_cost
 }
 
+/// The cache read + write part of `calculate_cost`.
+pub fn calculate_cache_cost(
+    model: &str,
+    cache_creation_tokens: i64,
+    cache_read_tokens: i64,
+) -> f64 {
+    let (_, _, cache_write_price, cache_read_price) = get_model_pricing(model);
+    (cache_creation_tokens as f64 / 1_000_000.0) * cache_write_price
+        + (cache_read_tokens as f64 / 1_000_000.0) * cache_read_price
+}
+
 /// Tauri command to fetch Claude Code local usage statistics.
 #[tauri::command]
-pub async fn claude_local_usage_snapshot(days: Option<u32>) -> Result<ClaudeUsageSnapshot, String> {
+pub async fn claude_local_usage_snapshot(
+    days: Option<u32>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<ClaudeUsageSnapshot, String> {
     let days = days.unwrap_or(30).clamp(1, 90);
-    let snapshot = tokio::task::spawn_blocking(move || scan_claude_local_usage(days))
+    let auth_method = crate::current_auth_method("claude-code", &*state.settings.lock().await);
+    let mut snapshot = tokio::task::spawn_blocking(move || scan_claude_local_usage(days))
         .await
         .map_err(|err| err.to_string())??;
+    if crate::pricing::is_subscription_auth(Some(auth_method)) {
+        snapshot.totals.billed_cost = 0.0;
+    }
+    snapshot.totals.auth_method = Some(auth_method.to_string());
     Ok(snapshot)
 }
 
@@ -261,6 +286,8 @@ fn build_snapshot(
             peak_day,
             peak_day_tokens,
             total_cost,
+            billed_cost: total_cost,
+            auth_method: None,
         },
         top_models,
     }
@@ -7,16 +7,18 @@
 //! Claude Code ACP doesn't emit `_tokenUsage` notifications like Codex does,
 //! so we watch the JSONL files that Claude writes in real-time instead.
 
-use crate::claude_local_usage::calculate_cost;
+use crate::async_db::AsyncDb;
+use crate::claude_local_usage::{calculate_cache_cost, calculate_cost};
+use crate::AppHandle;
 use glob::glob;
 use serde_json::Value;
 use std::collections::HashSet;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Seek, SeekFrom};
 use std::path::PathBuf;
-use std::sync::Arc;
-use tauri::{AppHandle, Emitter};
-use tokio::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use tauri::{Emitter, Manager};
 use tokio::time::{interval, Duration};
 
 /// Watcher state for a Claude Code session
@@ -24,10 +26,17 @@ pub struct ClaudeUsageWatcher {
     session_id: String,
     #[allow(dead_code)] // Kept for future logging enhancements
     task_id: String,
+    /// Overrides the Claude config directories to search (used by tests).
+    claude_dirs: Option<Vec<PathBuf>>,
     jsonl_path: Option<PathBuf>,
     last_position: u64,
-    cumulative_cost: f64,
-    processed_hashes: HashSet<String>,
+    /// Message ids already accounted for, kept across session rotations so
+    /// history copied into a resumed session file is not charged twice.
+    processed_ids: HashSet<String>,
+    /// Cache read/write share of the cost read since the last `take_cache_cost`.
+    pending_cache_cost: f64,
+    /// Whether the session file has been found and its existing content skipped.
+    primed: bool,
 }
 
 impl ClaudeUsageWatcher {
@@ -36,13 +45,25 @@ impl ClaudeUsageWatcher {
         Self {
             session_id,
             task_id,
+            claude_dirs: None,
             jsonl_path: None,
             last_position: 0,
-            cumulative_cost: 0.0,
-            processed_hashes: HashSet::new(),
+            processed_ids: HashSet::new(),
+            pending_cache_cost: 0.0,
+            primed: false,
         }
     }
 
+    /// Switch to a new session id, keeping the set of accounted message ids.
+    fn retarget(&mut self, session_id: String) {
+        if session_id == self.session_id {
+            return;
+        }
+        self.session_id = session_id;
+        self.jsonl_path = None;
+        self.last_position = 0;
+    }
+
     /// Find the JSONL file for this session
     fn find_jsonl_file(&mut self) -> Option<PathBuf> {
         // Check if we already have a valid path
@@ -54,7 +75,10 @@ impl ClaudeUsageWatcher {
 
         // Search for the JSONL file using glob pattern
         // Claude stores session files in ~/.claude/projects/**/{session_id}.jsonl
-        let claude_dirs = resolve_claude_paths();
+        let claude_dirs = self
+            .claude_dirs
+            .clone()
+            .unwrap_or_else(resolve_claude_paths);
 
         for claude_dir in claude_dirs {
             let pattern = format!(
@@ -74,8 +98,41 @@ impl ClaudeUsageWatcher {
         None
     }
 
-    /// Poll for new usage entries and return cumulative cost if changed
+    /// Mark everything already in the session file as accounted for without
+    /// charging it. Usage written before the watcher found the file was recorded
+    /// elsewhere: by an earlier watcher, or by the turn itself while the watcher
+    /// was not tracking. Returns false while the file does not exist yet.
+    fn prime(&mut self) -> bool {
+        if self.find_jsonl_file().is_none() {
+            return false;
+        }
+        let _ = self.read_new_entries();
+        self.pending_cache_cost = 0.0;
+        self.primed = true;
+        true
+    }
+
+    /// Cache cost accumulated by polls since the last call.
+    fn take_cache_cost(&mut self) -> f64 {
+        std::mem::take(&mut self.pending_cache_cost)
+    }
+
+    /// Poll for new usage entries and return the cost delta since the last poll.
+    /// The poll that first finds the session file primes it and returns None.
     fn poll_usage(&mut self) -> Option<f64> {
+        if !self.primed {
+            self.prime();
+            return None;
+        }
+        let delta = self.read_new_entries()?;
+        if delta > 0.0 {
+            Some(delta)
+        } else {
+            None
+        }
+    }
+
+    fn read_new_entries(&mut self) -> Option<f64> {
         let path = self.find_jsonl_file()?;
 
         let file = match File::open(&path) {
@@ -104,7 +161,7 @@ impl ClaudeUsageWatcher {
             }
         }
 
-        let mut cost_changed = false;
+        let mut delta = 0.0;
         let mut line = String::new();
 
         loop {
@@ -112,23 +169,30 @@ impl ClaudeUsageWatcher {
             match reader.read_line(&mut line) {
                 Ok(0) => break, // EOF
                 Ok(_) => {
+                    // Leave a partially written trailing line for the next poll
+                    if !line.ends_with('\n') {
+                        break;
+                    }
                     if let Some(cost) = self.process_line(&line) {
-                        self.cumulative_cost += cost;
-                        cost_changed = true;
+                        delta += cost;
                     }
                 }
                 Err(_) => break,
             }
         }
 
-        // Update position for next poll
-        self.last_position = reader.stream_position().unwrap_or(file_size);
-
-        if cost_changed {
-            Some(self.cumulative_cost)
-        } else {
-            None
-        }
+        // Update position for next poll (start of any partial line)
+        let consumed = reader
+            .stream_position()
+            .unwrap_or(file_size)
+            .saturating_sub(if line.ends_with('\n') {
+                0
+            } else {
+                line.len() as u64
+            });
+        self.last_position = consumed;
+
+        Some(delta)
     }
 
     /// Process a single JSONL line and return cost if it contains usage data
@@ -149,19 +213,23 @@ impl ClaudeUsageWatcher {
         let message = value.get("message")?.as_object()?;
         let usage = message.get("usage")?.as_object()?;
 
-        // Create unique hash for deduplication using message.id + requestId
-        let message_id = message.get("id").and_then(|v| v.as_str()).unwrap_or("");
-        let request_id = value
-            .get("requestId")
+        // Deduplicate by message id (falling back to requestId). Claude writes one
+        // line per content block with the same usage, and resumed sessions copy
+        // earlier messages into the new session file.
+        let dedupe_key = message
+            .get("id")
             .and_then(|v| v.as_str())
-            .unwrap_or("");
-
-        if !message_id.is_empty() && !request_id.is_empty() {
-            let hash = format!("{}:{}", message_id, request_id);
-            if self.processed_hashes.contains(&hash) {
+            .filter(|id| !id.is_empty())
+            .or_else(|| {
+                value
+                    .get("requestId")
+                    .and_then(|v| v.as_str())
+                    .filter(|id| !id.is_empty())
+            });
+        if let Some(key) = dedupe_key {
+            if !self.processed_ids.insert(key.to_string()) {
                 return None;
             }
-            self.processed_hashes.insert(hash);
         }
 
         // Extract token counts
@@ -193,6 +261,9 @@ impl ClaudeUsageWatcher {
             .and_then(|v| v.as_str())
             .unwrap_or("claude-sonnet");
 
+        self.pending_cache_cost +=
+            calculate_cache_cost(model, cache_creation_tokens, cache_read_tokens);
+
         // Calculate cost using shared pricing logic
         let cost = calculate_cost(
             model,
@@ -245,36 +316,61 @@ fn resolve_claude_paths() -> Vec<PathBuf> {
     paths
 }
 
-/// Handle type for stopping the watcher
+/// Handle for controlling a running watcher. Dropping the handle stops it.
 pub struct WatcherHandle {
-    #[allow(dead_code)] // Used for graceful shutdown in future
-    stop_flag: Arc<Mutex<bool>>,
+    stop_flag: Arc<AtomicBool>,
+    target_session_id: Arc<StdMutex<String>>,
+    tracking: Arc<AtomicBool>,
 }
 
 impl WatcherHandle {
     /// Signal the watcher to stop
-    #[allow(dead_code)] // Reserved for session cleanup
-    pub async fn stop(&self) {
-        let mut flag = self.stop_flag.lock().await;
-        *flag = true;
+    pub fn stop(&self) {
+        self.stop_flag.store(true, Ordering::SeqCst);
+    }
+
+    /// Point the watcher at a new session id (e.g. after the agent rotated its
+    /// session). The task's running total and accounted message ids carry over.
+    pub fn retarget(&self, session_id: &str) {
+        if let Ok(mut target) = self.target_session_id.lock() {
+            *target = session_id.to_string();
+        }
+    }
+
+    /// Whether the watcher has found the session file and is recording cost.
+    pub fn is_tracking(&self) -> bool {
+        self.tracking.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for WatcherHandle {
+    fn drop(&mut self) {
+        self.stop();
     }
 }
 
 /// Start watching a Claude Code session for usage updates
 ///
-/// Returns a handle that can be used to stop the watcher.
-/// The watcher will emit CostUpdate events to the frontend as it detects
-/// new usage entries in the JSONL file.
+/// Returns a handle that can be used to stop or retarget the watcher.
+/// Usage already present in the session file when the watcher finds it is
+/// treated as recorded. New usage is added to the task's cost as deltas and
+/// the resulting total is emitted as a CostUpdate event.
 pub fn start_watching(
     session_id: &str,
     task_id: &str,
     app: AppHandle,
-    db: Arc<std::sync::Mutex<rusqlite::Connection>>,
+    store: AsyncDb,
 ) -> WatcherHandle {
     let session_id = session_id.to_string();
     let task_id = task_id.to_string();
-    let stop_flag = Arc::new(Mutex::new(false));
-    let stop_flag_clone = stop_flag.clone();
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let target_session_id = Arc::new(StdMutex::new(session_id.clone()));
+    let tracking = Arc::new(AtomicBool::new(false));
+    let handle = WatcherHandle {
+        stop_flag: stop_flag.clone(),
+        target_session_id: target_session_id.clone(),
+        tracking: tracking.clone(),
+    };
 
     tauri::async_runtime::spawn(async move {
         let mut watcher = ClaudeUsageWatcher::new(session_id.clone(), task_id.clone());
@@ -292,42 +388,227 @@ pub fn start_watching(
             poll_interval.tick().await;
 
             // Check if we should stop
-            {
-                let flag = stop_flag_clone.lock().await;
-                if *flag {
-                    println!(
-                        "[ClaudeWatcher] Stopping watcher for session {}",
-                        session_id
-                    );
-                    break;
-                }
+            if stop_flag.load(Ordering::SeqCst) {
+                println!(
+                    "[ClaudeWatcher] Stopping watcher for session {}",
+                    watcher.session_id
+                );
+                break;
+            }
+
+            let target = target_session_id
+                .lock()
+                .map(|t| t.clone())
+                .unwrap_or_else(|_| watcher.session_id.clone());
+            if target != watcher.session_id {
+                println!(
+                    "[ClaudeWatcher] Retargeting task {} from session {} to {}",
+                    task_id, watcher.session_id, target
+                );
+                watcher.retarget(target);
             }
 
             // Poll for new usage data
-            if let Some(new_cost) = watcher.poll_usage() {
-                // Update database
-                if let Ok(conn) = db.lock() {
-                    let current_cost = crate::db::get_task_cost(&conn, &task_id).unwrap_or(0.0);
-                    // Only update if our cumulative is higher (avoid double-counting)
-                    if new_cost > current_cost {
-                        let _ = crate::db::update_task_cost(&conn, &task_id, new_cost);
-                    }
-                }
+            let delta = watcher.poll_usage();
+            if watcher.jsonl_path.is_some() {
+                tracking.store(true, Ordering::SeqCst);
+            }
+            if let Some(delta) = delta {
+                let cache_delta = watcher.take_cache_cost();
+                let id = task_id.clone();
+                let totals = store
+                    .write(move |conn| {
+                        // Claude OAuth (subscription) sessions are not billed per token.
+                        let auth_method = crate::db::get_task_auth_method(conn, &id).ok().flatten();
+                        let billed = !crate::pricing::is_subscription_auth(auth_method.as_deref());
+                        let equivalent_cost =
+                            crate::db::add_task_equivalent_cost(conn, &id, delta).unwrap_or(0.0);
+                        let total = if billed {
+                            crate::db::add_task_cost(conn, &id, delta)?
+                        } else {
+                            crate::db::get_task_cost(conn, &id)?
+                        };
+                        let cache_cost = if billed {
+                            crate::db::add_task_cache_cost(conn, &id, cache_delta)
+                        } else {
+                            crate::db::get_task_cache_cost(conn, &id)
+                        }
+                        .unwrap_or(0.0);
+                        let breakdown = serde_json::json!({
+                            "cacheCost": cache_cost,
+                            "equivalentCost": equivalent_cost,
+                            "billed": billed,
+                            "authMethod": auth_method,
+                        });
+                        Ok((total, breakdown))
+                    })
+                    .await
+                    .ok();
 
                 // Emit event to frontend
-                if let Err(e) = app.emit("CostUpdate", (&task_id, new_cost)) {
-                    eprintln!("[ClaudeWatcher] Failed to emit CostUpdate: {}", e);
+                if let Some((total, breakdown)) = totals {
+                    if let Err(e) = app.emit("CostUpdate", (&task_id, total, &breakdown)) {
+                        eprintln!("[ClaudeWatcher] Failed to emit CostUpdate: {}", e);
+                    }
+                    if let Some(state) = app.try_state::<crate::AppState>() {
+                        crate::spawn_cost_alert_check(&state, &task_id, total);
+                    }
+                    println!(
+                        "[ClaudeWatcher] Cost update for {}: +${:.4} (total ${:.4})",
+                        task_id, delta, total
+                    );
                 }
-
-                println!(
-                    "[ClaudeWatcher] Cost update for {}: ${:.4}",
-                    task_id, new_cost
-                );
             }
         }
 
-        println!("[ClaudeWatcher] Watcher stopped for session {}", session_id);
+        println!(
+            "[ClaudeWatcher] Watcher stopped for session {}",
+            watcher.session_id
+        );
     });
 
-    WatcherHandle { stop_flag }
+    handle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClaudeUsageWatcher;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_claude_dir(name: &str) -> PathBuf {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("phantom-claude-watcher-{name}-{suffix}"));
+        std::fs::create_dir_all(dir.join("projects").join("-repo")).expect("create projects dir");
+        dir
+    }
+
+    fn assistant_line(message_id: &str, output_tokens: i64) -> String {
+        format!(
+            "{{\"type\":\"assistant\",\"requestId\":\"req-{message_id}\",\"message\":{{\"id\":\"{message_id}\",\"model\":\"claude-sonnet-4-5\",\"usage\":{{\"input_tokens\":1000,\"output_tokens\":{output_tokens}}}}}}}\n"
+        )
+    }
+
+    fn append(dir: &Path, session_id: &str, lines: &[String]) {
+        let path = dir
+            .join("projects")
+            .join("-repo")
+            .join(format!("{session_id}.jsonl"));
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .expect("open session file");
+        for line in lines {
+            file.write_all(line.as_bytes()).expect("write line");
+        }
+    }
+
+    fn watcher(dir: &Path, session_id: &str) -> ClaudeUsageWatcher {
+        let mut watcher = ClaudeUsageWatcher::new(session_id.to_string(), "task-1".to_string());
+        watcher.claude_dirs = Some(vec![dir.to_path_buf()]);
+        watcher
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_poll_usage_returns_deltas_and_dedupes_blocks() {
+        let dir = temp_claude_dir("deltas");
+        append(&dir, "session-a", &[]);
+        let mut watcher = watcher(&dir, "session-a");
+        assert!(watcher.prime());
+        let one = watcher.process_line(&assistant_line("probe", 500)).unwrap();
+        watcher.processed_ids.clear();
+
+        // Two content blocks of the same message share usage; count it once.
+        append(
+            &dir,
+            "session-a",
+            &[assistant_line("msg-1", 500), assistant_line("msg-1", 500)],
+        );
+        assert!(close(watcher.poll_usage().unwrap(), one));
+        assert_eq!(watcher.poll_usage(), None);
+
+        append(&dir, "session-a", &[assistant_line("msg-2", 500)]);
+        assert!(close(watcher.poll_usage().unwrap(), one));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_retarget_preserves_total_across_session_rotation() {
+        let dir = temp_claude_dir("rotation");
+        append(&dir, "session-a", &[]);
+        let mut watcher = watcher(&dir, "session-a");
+        assert!(watcher.prime());
+        let one = watcher.process_line(&assistant_line("probe", 500)).unwrap();
+        watcher.processed_ids.clear();
+
+        append(
+            &dir,
+            "session-a",
+            &[assistant_line("msg-1", 500), assistant_line("msg-2", 500)],
+        );
+        let mut total = watcher.poll_usage().unwrap();
+        assert!(close(total, 2.0 * one));
+
+        // The rotated session file repeats the earlier messages before new ones.
+        append(
+            &dir,
+            "session-b",
+            &[
+                assistant_line("msg-1", 500),
+                assistant_line("msg-2", 500),
+                assistant_line("msg-3", 500),
+            ],
+        );
+        watcher.retarget("session-b".to_string());
+        total += watcher.poll_usage().unwrap();
+        assert!(close(total, 3.0 * one), "total={total}");
+
+        // Late writes to the superseded file are no longer read.
+        append(&dir, "session-a", &[assistant_line("msg-4", 500)]);
+        assert_eq!(watcher.poll_usage(), None);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_prime_skips_existing_usage() {
+        let dir = temp_claude_dir("prime");
+        append(&dir, "session-a", &[assistant_line("msg-1", 500)]);
+        let mut watcher = watcher(&dir, "session-a");
+        assert!(watcher.prime());
+        assert_eq!(watcher.poll_usage(), None);
+
+        append(&dir, "session-a", &[assistant_line("msg-2", 500)]);
+        assert!(watcher.poll_usage().is_some());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_file_created_after_start_is_primed_when_found() {
+        let dir = temp_claude_dir("late-file");
+        let mut watcher = watcher(&dir, "session-a");
+        assert_eq!(watcher.poll_usage(), None);
+        assert!(!watcher.primed);
+
+        // Usage written before the watcher found the file was billed by the turn.
+        append(&dir, "session-a", &[assistant_line("msg-1", 500)]);
+        assert_eq!(watcher.poll_usage(), None);
+        assert!(watcher.primed);
+
+        append(&dir, "session-a", &[assistant_line("msg-2", 500)]);
+        assert!(watcher.poll_usage().is_some());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -1,6 +1,6 @@
 use crate::automations;
 use crate::utils::safe_prefix;
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, Connection, OptionalExtension, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -16,6 +16,9 @@ pub struct TaskRecord {
     pub model: String,
     pub prompt: Option<String>,
     pub project_path: Option<String>,
+    /// `project_path` canonicalized and mapped from a linked worktree to its main checkout
+    #[serde(rename = "projectKey", default)]
+    pub project_key: Option<String>,
     #[serde(rename = "worktreePath")]
     pub worktree_path: Option<String>,
     /// Git branch name (may differ from folder name after async rename)
@@ -47,6 +50,44 @@ pub struct TaskRecord {
     /// Claude teammate-mode agent name (when using teammate controller integration)
     #[serde(rename = "claudeAgentName")]
     pub claude_agent_name: Option<String>,
+    /// Codex collaboration mode last set on the task (restored on reconnect)
+    #[serde(rename = "codexMode")]
+    pub codex_mode: Option<String>,
+    /// Whether the agent accepted `model` (None when no explicit model was applied)
+    #[serde(rename = "modelApplied")]
+    pub model_applied: Option<bool>,
+    /// Branch the task's worktree was created from (Review Center "base" comparisons)
+    #[serde(rename = "baseBranch")]
+    pub base_branch: Option<String>,
+    /// Commits on the task branch that are not on `origin/<base_branch>` (last background fetch)
+    #[serde(rename = "branchAhead")]
+    pub branch_ahead: Option<i64>,
+    /// Commits on `origin/<base_branch>` that the task branch is missing
+    #[serde(rename = "branchBehind")]
+    pub branch_behind: Option<i64>,
+    /// Git hooks mode applied to the task's worktree ("inherit", "disabled", "copied")
+    #[serde(rename = "gitHooks")]
+    pub git_hooks: Option<String>,
+    /// Pull request opened for the task branch from Phantom
+    #[serde(rename = "prNumber")]
+    pub pr_number: Option<i64>,
+    #[serde(rename = "prUrl")]
+    pub pr_url: Option<String>,
+    /// Last polled PR state ("OPEN", "MERGED", "CLOSED")
+    #[serde(rename = "prState")]
+    pub pr_state: Option<String>,
+    /// Last polled checks rollup ("passing", "failing", "pending"); None without checks
+    #[serde(rename = "prChecks")]
+    pub pr_checks: Option<String>,
+    /// Hidden from the task list; messages and record are kept
+    #[serde(default)]
+    pub archived: bool,
+    /// Never pruned by the retention job
+    #[serde(default)]
+    pub pinned: bool,
+    /// Worktree checks out only the project's subdirectory (sparse checkout)
+    #[serde(rename = "sparseWorktree", default)]
+    pub sparse_worktree: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -137,6 +178,120 @@ pub struct AttachmentRecord {
     pub byte_size: i64,
 }
 
+/// Inline review note on one line of a task diff (`side` is "old" or "new").
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewCommentRecord {
+    pub id: i64,
+    pub task_id: String,
+    pub file_path: String,
+    pub side: String,
+    pub line_number: i64,
+    pub body: String,
+    pub resolved: bool,
+    pub resolved_at: Option<i64>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Token usage and cost of one agent turn. `message_seq` is the task's last message
+/// seq when the turn was recorded, which ties the turn back to its messages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TurnUsageRecord {
+    pub id: i64,
+    pub task_id: String,
+    pub model: String,
+    pub input_tokens: i64,
+    pub cached_input_tokens: i64,
+    pub cache_write_tokens: i64,
+    pub output_tokens: i64,
+    /// Billed cost; $0 for subscription sessions.
+    pub cost: f64,
+    /// Cost at API rates, whether or not it was billed.
+    pub equivalent_cost: f64,
+    pub auth_method: Option<String>,
+    pub message_seq: i64,
+    pub created_at: i64,
+}
+
+/// How a task was launched, beyond what `TaskRecord` keeps. Used to duplicate tasks and to
+/// reapply the same modes when a session is reconnected.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaskLaunchOptions {
+    pub permission_mode: Option<String>,
+    pub reasoning_effort: Option<String>,
+    pub agent_mode: Option<String>,
+    pub plan_mode: bool,
+    /// `None` for tasks created before this was recorded.
+    pub use_worktree: Option<bool>,
+}
+
+/// One change to a task's cost. Ledger rows are kept after the task is deleted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CostLedgerEntry {
+    pub task_id: String,
+    pub agent_id: String,
+    pub model: String,
+    pub delta: f64,
+    pub created_at: i64,
+}
+
+/// A task to create and start once `parent_task_id` completes.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskChainRecord {
+    pub id: i64,
+    pub parent_task_id: String,
+    pub payload: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledTaskRecord {
+    pub id: String,
+    pub payload: String,
+    /// Recurrence; `None` for a one-off run at the initial `next_run_at`.
+    pub cron: Option<String>,
+    /// Run once on startup if a run was missed while the app was closed (otherwise skip it).
+    pub run_missed: bool,
+    pub next_run_at: Option<i64>,
+    pub last_run_at: Option<i64>,
+    pub last_task_id: Option<String>,
+    pub last_error: Option<String>,
+    pub created_at: i64,
+}
+
+/// A file changed by one of a task's generations, as named by its tool calls.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileChangeRecord {
+    pub path: String,
+    /// `created`, `modified` or `deleted`
+    pub kind: String,
+    /// Start of the generation (ms since the epoch)
+    pub generation_started_at: i64,
+}
+
+/// Timing of one generation, for comparing agents and models.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationMetricsRecord {
+    pub task_id: String,
+    pub agent_id: String,
+    pub model: String,
+    /// Prompt sent to first streamed text; `None` when no text was streamed.
+    pub time_to_first_token_ms: Option<i64>,
+    pub wall_time_ms: i64,
+    pub tool_calls: i64,
+    /// From the turn's token usage, when the agent reports it.
+    pub output_tokens: Option<i64>,
+    pub tokens_per_sec: Option<f64>,
+    pub created_at: i64,
+}
+
 /// Structured message record for history formatting
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageRecord {
@@ -177,9 +332,17 @@ pub struct MeetingSegmentRecord {
 
 pub fn init_db(path: &PathBuf) -> Result<Connection> {
     let conn = Connection::open(path)?;
+    // Writes are single statements or transactions that start by writing, so SQLite's
+    // busy handler covers every lock wait; no retry loop holding the connection is needed.
     conn.busy_timeout(Duration::from_secs(5))?;
     // PRAGMAs that return results - use query_row to consume them
-    let _ = conn.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()));
+    match conn.query_row("PRAGMA journal_mode = WAL", [], |row| {
+        row.get::<_, String>(0)
+    }) {
+        Ok(mode) if mode.eq_ignore_ascii_case("wal") => {}
+        Ok(mode) => eprintln!("[Harness] db: WAL unavailable, journal_mode={}", mode),
+        Err(e) => eprintln!("[Harness] db: failed to enable WAL: {}", e),
+    }
     let _ = conn.query_row("PRAGMA synchronous = NORMAL", [], |_| Ok(()));
     let _ = conn.query_row("PRAGMA temp_store = MEMORY", [], |_| Ok(()));
     let _ = conn.query_row("PRAGMA cache_size = -16000", [], |_| Ok(()));
@@ -339,6 +502,23 @@ pub fn init_db(path: &PathBuf) -> Result<Connection> {
         [],
     )?;
 
+    // One-off or cron tasks created from a stored `CreateAgentPayload` (JSON). A schedule
+    // with no `next_run_at` is done (one-off already fired or skipped).
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS scheduled_tasks (
+            id TEXT PRIMARY KEY,
+            payload TEXT NOT NULL,
+            cron TEXT,
+            run_missed INTEGER NOT NULL DEFAULT 0,
+            next_run_at INTEGER,
+            last_run_at INTEGER,
+            last_task_id TEXT,
+            last_error TEXT,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
     // Add missing columns to automation_runs table (migration).
     // Older databases may have automation_runs without created_at (and potentially other fields).
     conn.execute(
@@ -450,6 +630,27 @@ pub fn init_db(path: &PathBuf) -> Result<Connection> {
         [],
     )?;
 
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS agent_capabilities (
+            agent_id TEXT PRIMARY KEY,
+            supports_load_session INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    // Rolling summaries of older history, keyed by the last message each one covers
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS history_summaries (
+            task_id TEXT NOT NULL,
+            last_message_id INTEGER NOT NULL,
+            summary TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            PRIMARY KEY (task_id, last_message_id)
+        )",
+        [],
+    )?;
+
     // Analytics cache table for instant dashboard loading
     conn.execute(
         "CREATE TABLE IF NOT EXISTS analytics_cache (
@@ -470,6 +671,185 @@ pub fn init_db(path: &PathBuf) -> Result<Connection> {
         [],
     )?;
 
+    // Discord user who created a task; only they may answer its questions from Discord
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS discord_task_requesters (
+            task_id TEXT PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    // Last known chat window geometry, keyed by window label (chat-<sanitized task id>)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS chat_window_state (
+            window_label TEXT PRIMARY KEY,
+            x INTEGER NOT NULL,
+            y INTEGER NOT NULL,
+            width INTEGER NOT NULL,
+            height INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    // Plan file revisions emitted by agents (PlanContent), one chain per task + file
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS plan_versions (
+            task_id TEXT NOT NULL,
+            file_path TEXT NOT NULL,
+            version INTEGER NOT NULL,
+            content_hash TEXT NOT NULL,
+            content TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            PRIMARY KEY (task_id, file_path, version)
+        )",
+        [],
+    )?;
+
+    // Inline comments left in the Review Center, anchored to a diff line
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS review_comments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_id TEXT NOT NULL,
+            file_path TEXT NOT NULL,
+            side TEXT NOT NULL,
+            line_number INTEGER NOT NULL,
+            body TEXT NOT NULL,
+            resolved INTEGER NOT NULL DEFAULT 0,
+            resolved_at INTEGER,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    // Files marked viewed in the Review Center, with the content hash they were viewed at
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS review_viewed_files (
+            task_id TEXT NOT NULL,
+            file_path TEXT NOT NULL,
+            content_hash TEXT NOT NULL,
+            viewed_at INTEGER NOT NULL,
+            PRIMARY KEY (task_id, file_path),
+            FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    // Per-turn token usage, for the task cost breakdown
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS task_turn_usage (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_id TEXT NOT NULL,
+            model TEXT NOT NULL,
+            input_tokens INTEGER NOT NULL DEFAULT 0,
+            cached_input_tokens INTEGER NOT NULL DEFAULT 0,
+            cache_write_tokens INTEGER NOT NULL DEFAULT 0,
+            output_tokens INTEGER NOT NULL DEFAULT 0,
+            cost REAL NOT NULL DEFAULT 0,
+            equivalent_cost REAL NOT NULL DEFAULT 0,
+            auth_method TEXT,
+            message_seq INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_task_turn_usage_task_id ON task_turn_usage(task_id)",
+        [],
+    )?;
+
+    // Cost changes for spend summaries; no task foreign key so deleted tasks still count
+    let has_cost_ledger: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'cost_ledger')",
+        [],
+        |row| row.get(0),
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS cost_ledger (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_id TEXT NOT NULL,
+            agent_id TEXT NOT NULL,
+            model TEXT NOT NULL,
+            delta REAL NOT NULL,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_cost_ledger_created_at ON cost_ledger(created_at)",
+        [],
+    )?;
+    // Seed the ledger with existing task costs (migration)
+    if !has_cost_ledger {
+        conn.execute(
+            "INSERT INTO cost_ledger (task_id, agent_id, model, delta, created_at)
+             SELECT id, agent_id, model, cost, updated_at FROM tasks WHERE cost > 0",
+            [],
+        )
+        .ok();
+    }
+    // Highest cost-alert level already sent per task, so alerts survive restarts
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS cost_alerts (
+            task_id TEXT PRIMARY KEY,
+            alerted_amount REAL NOT NULL,
+            alerted_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    // Tasks to create and start once their parent completes (`CreateAgentPayload` JSON)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS task_chains (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            parent_task_id TEXT NOT NULL,
+            payload TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY (parent_task_id) REFERENCES tasks(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_task_chains_parent ON task_chains(parent_task_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS generation_metrics (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_id TEXT NOT NULL,
+            agent_id TEXT NOT NULL,
+            model TEXT NOT NULL,
+            time_to_first_token_ms INTEGER,
+            wall_time_ms INTEGER NOT NULL,
+            tool_calls INTEGER NOT NULL,
+            output_tokens INTEGER,
+            tokens_per_sec REAL,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_generation_metrics_task_id
+         ON generation_metrics(task_id, created_at)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS task_file_changes (
+            task_id TEXT NOT NULL,
+            generation_started_at INTEGER NOT NULL,
+            path TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            PRIMARY KEY (task_id, generation_started_at, path),
+            FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_tasks_created_at ON tasks(created_at)",
         [],
@@ -588,6 +968,13 @@ pub fn init_db(path: &PathBuf) -> Result<Connection> {
     conn.execute("ALTER TABLE tasks ADD COLUMN title_summary TEXT", [])
         .ok(); // Ignore error if column already exists
 
+    // Set once the user renames a task; AI summaries then leave the title alone (migration)
+    conn.execute(
+        "ALTER TABLE tasks ADD COLUMN title_user_set INTEGER NOT NULL DEFAULT 0",
+        [],
+    )
+    .ok(); // Ignore error if column already exists
+
     // Add agent_session_id column for session/load support (migration)
     conn.execute("ALTER TABLE tasks ADD COLUMN agent_session_id TEXT", [])
         .ok(); // Ignore error if column already exists
@@ -627,6 +1014,134 @@ pub fn init_db(path: &PathBuf) -> Result<Connection> {
         .ok();
     conn.execute("ALTER TABLE tasks ADD COLUMN claude_agent_name TEXT", [])
         .ok();
+    // Add codex_mode column so collaboration mode survives reconnects (migration)
+    conn.execute("ALTER TABLE tasks ADD COLUMN codex_mode TEXT", [])
+        .ok();
+    // Add auth_method column recording how the agent was authenticated at spawn (migration)
+    conn.execute("ALTER TABLE tasks ADD COLUMN auth_method TEXT", [])
+        .ok();
+    // Launch options the task was created with, so it can be duplicated (migration)
+    conn.execute("ALTER TABLE tasks ADD COLUMN permission_mode TEXT", [])
+        .ok();
+    conn.execute("ALTER TABLE tasks ADD COLUMN reasoning_effort TEXT", [])
+        .ok();
+    conn.execute("ALTER TABLE tasks ADD COLUMN agent_mode TEXT", [])
+        .ok();
+    conn.execute(
+        "ALTER TABLE tasks ADD COLUMN plan_mode INTEGER NOT NULL DEFAULT 0",
+        [],
+    )
+    .ok();
+    conn.execute("ALTER TABLE tasks ADD COLUMN use_worktree INTEGER", [])
+        .ok();
+    // Add model_applied column recording whether the agent accepted the requested model (migration)
+    conn.execute("ALTER TABLE tasks ADD COLUMN model_applied INTEGER", [])
+        .ok();
+    // Add base_branch and review_compare columns for Review Center comparisons (migration)
+    conn.execute("ALTER TABLE tasks ADD COLUMN base_branch TEXT", [])
+        .ok();
+    conn.execute("ALTER TABLE tasks ADD COLUMN review_compare TEXT", [])
+        .ok();
+    // Add ahead/behind counts against the base branch, refreshed in the background (migration)
+    conn.execute("ALTER TABLE tasks ADD COLUMN branch_ahead INTEGER", [])
+        .ok();
+    conn.execute("ALTER TABLE tasks ADD COLUMN branch_behind INTEGER", [])
+        .ok();
+    // Add git_hooks column recording how the worktree treats repository hooks (migration)
+    conn.execute("ALTER TABLE tasks ADD COLUMN git_hooks TEXT", [])
+        .ok();
+    // Add pull request number/url for PRs created from the task (migration)
+    conn.execute("ALTER TABLE tasks ADD COLUMN pr_number INTEGER", [])
+        .ok();
+    conn.execute("ALTER TABLE tasks ADD COLUMN pr_url TEXT", [])
+        .ok();
+    // Add polled PR state and checks rollup (migration)
+    conn.execute("ALTER TABLE tasks ADD COLUMN pr_state TEXT", [])
+        .ok();
+    conn.execute("ALTER TABLE tasks ADD COLUMN pr_checks TEXT", [])
+        .ok();
+    // Add archived flag for tasks cleared from the task list (migration)
+    conn.execute(
+        "ALTER TABLE tasks ADD COLUMN archived INTEGER NOT NULL DEFAULT 0",
+        [],
+    )
+    .ok();
+    // Add pinned flag exempting a task from retention pruning (migration)
+    conn.execute(
+        "ALTER TABLE tasks ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0",
+        [],
+    )
+    .ok();
+    // Add sparse_worktree flag so duplicates and forks keep the checkout mode (migration)
+    conn.execute(
+        "ALTER TABLE tasks ADD COLUMN sparse_worktree INTEGER NOT NULL DEFAULT 0",
+        [],
+    )
+    .ok();
+    // Add project_key column grouping tasks by normalized project (migration)
+    conn.execute("ALTER TABLE tasks ADD COLUMN project_key TEXT", [])
+        .ok();
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_tasks_project_key ON tasks(project_key)",
+        [],
+    )?;
+    // Add cache_cost column: the part of `cost` spent on cached prompt tokens (migration)
+    conn.execute("ALTER TABLE tasks ADD COLUMN cache_cost REAL DEFAULT 0", [])
+        .ok();
+    // Add equivalent_cost column: API-rate cost including unbilled subscription usage (migration)
+    conn.execute(
+        "ALTER TABLE tasks ADD COLUMN equivalent_cost REAL DEFAULT 0",
+        [],
+    )
+    .ok();
+    // Add billing columns to per-turn usage (migration)
+    conn.execute(
+        "ALTER TABLE task_turn_usage ADD COLUMN equivalent_cost REAL NOT NULL DEFAULT 0",
+        [],
+    )
+    .ok();
+    conn.execute(
+        "ALTER TABLE task_turn_usage ADD COLUMN auth_method TEXT",
+        [],
+    )
+    .ok();
+    // Add per-task message sequence numbers; backfill existing rows in id order (migration)
+    if conn
+        .execute("ALTER TABLE messages ADD COLUMN seq INTEGER", [])
+        .is_ok()
+    {
+        conn.execute(
+            "UPDATE messages SET seq = (
+                SELECT rn FROM (
+                    SELECT id, ROW_NUMBER() OVER (PARTITION BY task_id ORDER BY id) AS rn
+                    FROM messages
+                ) ordered WHERE ordered.id = messages.id
+            )",
+            [],
+        )?;
+    }
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_messages_task_id_seq ON messages(task_id, seq)",
+        [],
+    )?;
+    // Full text of a truncated tool return, relative to the attachments dir (migration)
+    conn.execute("ALTER TABLE messages ADD COLUMN tool_return_path TEXT", [])
+        .ok();
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_messages_tool_return_path
+         ON messages(tool_return_path) WHERE tool_return_path IS NOT NULL",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_review_comments_task_file
+         ON review_comments(task_id, file_path, side, line_number)",
+        [],
+    )?;
+
+    // Full-text message search. Best-effort like `semantic_fts`: without FTS5 the app still starts.
+    if let Err(e) = create_message_search_index(&conn) {
+        eprintln!("[Harness] Message search index unavailable: {}", e);
+    }
 
     // Backfill next_run_at for enabled automations that predate the column (migration).
     // Older schemas added next_run_at without populating it, which would cause enabled
@@ -923,14 +1438,281 @@ pub fn list_automation_runs(conn: &Connection, limit: usize) -> Result<Vec<Autom
     rows.collect()
 }
 
-pub fn save_discord_thread(
-    conn: &Connection,
-    task_id: &str,
-    thread_id: u64,
-    channel_id: u64,
-) -> Result<()> {
-    let now = chrono::Utc::now().timestamp();
-    conn.execute(
+fn scheduled_task_from_row(row: &rusqlite::Row) -> Result<ScheduledTaskRecord> {
+    Ok(ScheduledTaskRecord {
+        id: row.get(0)?,
+        payload: row.get(1)?,
+        cron: row.get(2)?,
+        run_missed: row.get::<_, i64>(3)? != 0,
+        next_run_at: row.get(4)?,
+        last_run_at: row.get(5)?,
+        last_task_id: row.get(6)?,
+        last_error: row.get(7)?,
+        created_at: row.get(8)?,
+    })
+}
+
+pub fn insert_scheduled_task(conn: &Connection, schedule: &ScheduledTaskRecord) -> Result<()> {
+    conn.execute(
+        "INSERT INTO scheduled_tasks (id, payload, cron, run_missed, next_run_at, last_run_at, last_task_id, last_error, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            schedule.id,
+            schedule.payload,
+            schedule.cron,
+            schedule.run_missed as i64,
+            schedule.next_run_at,
+            schedule.last_run_at,
+            schedule.last_task_id,
+            schedule.last_error,
+            schedule.created_at
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn list_scheduled_tasks(conn: &Connection) -> Result<Vec<ScheduledTaskRecord>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, payload, cron, run_missed, next_run_at, last_run_at, last_task_id, last_error, created_at
+         FROM scheduled_tasks
+         ORDER BY next_run_at IS NULL, next_run_at ASC, created_at ASC",
+    )?;
+    let rows = stmt.query_map([], scheduled_task_from_row)?;
+    rows.collect()
+}
+
+pub fn list_due_scheduled_tasks(conn: &Connection, now: i64) -> Result<Vec<ScheduledTaskRecord>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, payload, cron, run_missed, next_run_at, last_run_at, last_task_id, last_error, created_at
+         FROM scheduled_tasks
+         WHERE next_run_at IS NOT NULL AND next_run_at <= ?1
+         ORDER BY next_run_at ASC",
+    )?;
+    let rows = stmt.query_map(params![now], scheduled_task_from_row)?;
+    rows.collect()
+}
+
+pub fn delete_scheduled_task(conn: &Connection, id: &str) -> Result<usize> {
+    conn.execute("DELETE FROM scheduled_tasks WHERE id = ?1", params![id])
+}
+
+/// Move a schedule to `next_run_at` and record the outcome of the run that just happened
+/// (`last_run_at` is left alone when the run was skipped).
+pub fn record_scheduled_task_run(
+    conn: &Connection,
+    id: &str,
+    next_run_at: Option<i64>,
+    last_run_at: Option<i64>,
+    last_task_id: Option<&str>,
+    last_error: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE scheduled_tasks SET
+            next_run_at = ?2,
+            last_run_at = COALESCE(?3, last_run_at),
+            last_task_id = COALESCE(?4, last_task_id),
+            last_error = ?5
+         WHERE id = ?1",
+        params![id, next_run_at, last_run_at, last_task_id, last_error],
+    )?;
+    Ok(())
+}
+
+/// FTS5 indexes over message text and tool arguments (`messages_fts`, external content) and
+/// task prompts and titles (`tasks_fts`), kept current by triggers. Existing rows are indexed
+/// once, when the tables are first created.
+fn create_message_search_index(conn: &Connection) -> Result<()> {
+    let existed: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'messages_fts')",
+        [],
+        |row| row.get(0),
+    )?;
+    conn.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts
+         USING fts5(content, tool_arguments, content = 'messages', content_rowid = 'id');
+         CREATE TRIGGER IF NOT EXISTS messages_fts_ai AFTER INSERT ON messages BEGIN
+            INSERT INTO messages_fts(rowid, content, tool_arguments)
+            VALUES (new.id, new.content, new.tool_arguments);
+         END;
+         CREATE TRIGGER IF NOT EXISTS messages_fts_ad AFTER DELETE ON messages BEGIN
+            INSERT INTO messages_fts(messages_fts, rowid, content, tool_arguments)
+            VALUES ('delete', old.id, old.content, old.tool_arguments);
+         END;
+         CREATE TRIGGER IF NOT EXISTS messages_fts_au AFTER UPDATE OF content, tool_arguments ON messages BEGIN
+            INSERT INTO messages_fts(messages_fts, rowid, content, tool_arguments)
+            VALUES ('delete', old.id, old.content, old.tool_arguments);
+            INSERT INTO messages_fts(rowid, content, tool_arguments)
+            VALUES (new.id, new.content, new.tool_arguments);
+         END;
+
+         CREATE VIRTUAL TABLE IF NOT EXISTS tasks_fts
+         USING fts5(task_id UNINDEXED, prompt, title_summary);
+         CREATE TRIGGER IF NOT EXISTS tasks_fts_ai AFTER INSERT ON tasks BEGIN
+            DELETE FROM tasks_fts WHERE task_id = new.id;
+            INSERT INTO tasks_fts(task_id, prompt, title_summary)
+            VALUES (new.id, new.prompt, new.title_summary);
+         END;
+         CREATE TRIGGER IF NOT EXISTS tasks_fts_ad AFTER DELETE ON tasks BEGIN
+            DELETE FROM tasks_fts WHERE task_id = old.id;
+         END;
+         CREATE TRIGGER IF NOT EXISTS tasks_fts_au AFTER UPDATE OF prompt, title_summary ON tasks BEGIN
+            DELETE FROM tasks_fts WHERE task_id = old.id;
+            INSERT INTO tasks_fts(task_id, prompt, title_summary)
+            VALUES (new.id, new.prompt, new.title_summary);
+         END;",
+    )?;
+    if !existed {
+        conn.execute_batch(
+            "INSERT INTO messages_fts(messages_fts) VALUES ('rebuild');
+             DELETE FROM tasks_fts;
+             INSERT INTO tasks_fts(task_id, prompt, title_summary)
+             SELECT id, prompt, title_summary FROM tasks;",
+        )?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageSearchHit {
+    pub task_id: String,
+    /// None when the task's prompt or title matched
+    pub message_id: Option<i64>,
+    /// Message type, or "task" for prompt/title matches
+    pub message_type: String,
+    /// HTML-escaped excerpt with matches wrapped in `<mark>`
+    pub snippet: String,
+    pub timestamp: Option<String>,
+    pub task_title: Option<String>,
+}
+
+/// Best matches for `query` across message history and task prompts/titles. Every word is
+/// matched as a prefix, and quotes or operators in the query are taken literally.
+pub fn search_messages(
+    conn: &Connection,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<MessageSearchHit>> {
+    let Some(fts_query) = fts_prefix_query(query) else {
+        return Ok(Vec::new());
+    };
+    let limit = limit.clamp(1, 200) as i64;
+    let mut ranked: Vec<(f64, MessageSearchHit)> = Vec::new();
+
+    let mut stmt = conn.prepare_cached(
+        "SELECT m.id, m.task_id, m.message_type, m.timestamp, f.snip, f.rank,
+                COALESCE(t.title_summary, substr(t.prompt, 1, 80))
+         FROM (
+            SELECT rowid, snippet(messages_fts, -1, char(1), char(2), '…', 16) AS snip, rank
+            FROM messages_fts WHERE messages_fts MATCH ?1 ORDER BY rank LIMIT ?2
+         ) f
+         JOIN messages m ON m.id = f.rowid
+         LEFT JOIN tasks t ON t.id = m.task_id",
+    )?;
+    let rows = stmt.query_map(params![fts_query, limit], |row| {
+        let snippet: String = row.get(4)?;
+        Ok((
+            row.get::<_, f64>(5)?,
+            MessageSearchHit {
+                message_id: Some(row.get(0)?),
+                task_id: row.get(1)?,
+                message_type: row.get(2)?,
+                timestamp: row.get(3)?,
+                snippet: highlighted_snippet(&snippet),
+                task_title: row.get(6)?,
+            },
+        ))
+    })?;
+    for row in rows {
+        ranked.push(row?);
+    }
+
+    let mut stmt = conn.prepare_cached(
+        "SELECT task_id, snippet(tasks_fts, -1, char(1), char(2), '…', 16), rank
+         FROM tasks_fts WHERE tasks_fts MATCH ?1 ORDER BY rank LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(params![fts_query, limit], |row| {
+        let snippet: String = row.get(1)?;
+        Ok((
+            row.get::<_, f64>(2)?,
+            MessageSearchHit {
+                task_id: row.get(0)?,
+                message_id: None,
+                message_type: "task".to_string(),
+                snippet: highlighted_snippet(&snippet),
+                timestamp: None,
+                task_title: None,
+            },
+        ))
+    })?;
+    for row in rows {
+        let (rank, mut hit) = row?;
+        let (title, created_at): (Option<String>, Option<i64>) = conn
+            .query_row(
+                "SELECT COALESCE(title_summary, substr(prompt, 1, 80)), created_at FROM tasks WHERE id = ?1",
+                params![hit.task_id],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .optional()?
+            .unwrap_or((None, None));
+        hit.task_title = title;
+        hit.timestamp = created_at
+            .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+            .map(|dt| dt.to_rfc3339());
+        ranked.push((rank, hit));
+    }
+
+    // bm25 ranks are negative; lower is better.
+    ranked.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Ok(ranked
+        .into_iter()
+        .take(limit as usize)
+        .map(|(_, hit)| hit)
+        .collect())
+}
+
+/// FTS5 query matching every word as a quoted prefix, so user input can't form operators.
+fn fts_prefix_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| term.trim_matches('"'))
+        .filter(|term| term.chars().any(char::is_alphanumeric))
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// Escape a snippet for HTML and turn the \x01/\x02 match markers into `<mark>` tags.
+fn highlighted_snippet(snippet: &str) -> String {
+    let mut out = String::with_capacity(snippet.len() + 16);
+    for ch in snippet.chars() {
+        match ch {
+            '\u{1}' => out.push_str("<mark>"),
+            '\u{2}' => out.push_str("</mark>"),
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            '\n' | '\r' | '\t' => out.push(' '),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+pub fn save_discord_thread(
+    conn: &Connection,
+    task_id: &str,
+    thread_id: u64,
+    channel_id: u64,
+) -> Result<()> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
         "INSERT OR REPLACE INTO discord_threads (task_id, thread_id, channel_id, created_at)
          VALUES (?1, ?2, ?3, ?4)",
         params![task_id, thread_id as i64, channel_id as i64, now],
@@ -958,6 +1740,280 @@ pub fn get_task_id_for_discord_thread(conn: &Connection, thread_id: u64) -> Resu
     Ok(None)
 }
 
+pub fn save_discord_requester(conn: &Connection, task_id: &str, user_id: u64) -> Result<()> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "INSERT OR REPLACE INTO discord_task_requesters (task_id, user_id, created_at)
+         VALUES (?1, ?2, ?3)",
+        params![task_id, user_id as i64, now],
+    )?;
+    Ok(())
+}
+
+/// The Discord user who created the task, if it was created from Discord.
+pub fn get_discord_requester(conn: &Connection, task_id: &str) -> Result<Option<u64>> {
+    conn.query_row(
+        "SELECT user_id FROM discord_task_requesters WHERE task_id = ?1",
+        params![task_id],
+        |row| row.get::<_, i64>(0),
+    )
+    .optional()
+    .map(|user_id| user_id.map(|id| id as u64))
+}
+
+/// Persisted outer position and inner size of a chat window, in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChatWindowState {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub fn save_chat_window_state(
+    conn: &Connection,
+    window_label: &str,
+    state: &ChatWindowState,
+) -> Result<()> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "INSERT OR REPLACE INTO chat_window_state (window_label, x, y, width, height, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            window_label,
+            state.x,
+            state.y,
+            state.width as i64,
+            state.height as i64,
+            now
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn get_chat_window_state(
+    conn: &Connection,
+    window_label: &str,
+) -> Result<Option<ChatWindowState>> {
+    let result = conn.query_row(
+        "SELECT x, y, width, height FROM chat_window_state WHERE window_label = ?1",
+        params![window_label],
+        |row| {
+            Ok(ChatWindowState {
+                x: row.get(0)?,
+                y: row.get(1)?,
+                width: row.get::<_, i64>(2)?.max(0) as u32,
+                height: row.get::<_, i64>(3)?.max(0) as u32,
+            })
+        },
+    );
+    match result {
+        Ok(state) => Ok(Some(state)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanVersionRecord {
+    pub version: i64,
+    pub content_hash: String,
+    pub content: String,
+}
+
+pub fn get_latest_plan_version(
+    conn: &Connection,
+    task_id: &str,
+    file_path: &str,
+) -> Result<Option<PlanVersionRecord>> {
+    let result = conn.query_row(
+        "SELECT version, content_hash, content FROM plan_versions
+         WHERE task_id = ?1 AND file_path = ?2
+         ORDER BY version DESC LIMIT 1",
+        params![task_id, file_path],
+        |row| {
+            Ok(PlanVersionRecord {
+                version: row.get(0)?,
+                content_hash: row.get(1)?,
+                content: row.get(2)?,
+            })
+        },
+    );
+    match result {
+        Ok(record) => Ok(Some(record)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn insert_plan_version(
+    conn: &Connection,
+    task_id: &str,
+    file_path: &str,
+    version: i64,
+    content_hash: &str,
+    content: &str,
+) -> Result<()> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "INSERT INTO plan_versions (task_id, file_path, version, content_hash, content, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![task_id, file_path, version, content_hash, content, now],
+    )?;
+    Ok(())
+}
+
+const REVIEW_COMMENT_COLUMNS: &str =
+    "id, task_id, file_path, side, line_number, body, resolved, resolved_at, created_at, updated_at";
+
+fn review_comment_from_row(row: &rusqlite::Row) -> Result<ReviewCommentRecord> {
+    Ok(ReviewCommentRecord {
+        id: row.get(0)?,
+        task_id: row.get(1)?,
+        file_path: row.get(2)?,
+        side: row.get(3)?,
+        line_number: row.get(4)?,
+        body: row.get(5)?,
+        resolved: row.get::<_, i64>(6)? != 0,
+        resolved_at: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+    })
+}
+
+pub fn insert_review_comment(
+    conn: &Connection,
+    task_id: &str,
+    file_path: &str,
+    side: &str,
+    line_number: i64,
+    body: &str,
+) -> Result<ReviewCommentRecord> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "INSERT INTO review_comments (task_id, file_path, side, line_number, body, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+        params![task_id, file_path, side, line_number, body, now],
+    )?;
+    let id = conn.last_insert_rowid();
+    get_review_comment(conn, id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)
+}
+
+pub fn get_review_comment(conn: &Connection, id: i64) -> Result<Option<ReviewCommentRecord>> {
+    let sql = format!(
+        "SELECT {} FROM review_comments WHERE id = ?1",
+        REVIEW_COMMENT_COLUMNS
+    );
+    match conn.query_row(&sql, params![id], review_comment_from_row) {
+        Ok(record) => Ok(Some(record)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Comments for a task, optionally limited to one file, in file/line order.
+pub fn list_review_comments(
+    conn: &Connection,
+    task_id: &str,
+    file_path: Option<&str>,
+) -> Result<Vec<ReviewCommentRecord>> {
+    let sql = format!(
+        "SELECT {} FROM review_comments
+         WHERE task_id = ?1 AND (?2 IS NULL OR file_path = ?2)
+         ORDER BY file_path ASC, line_number ASC, side ASC, id ASC",
+        REVIEW_COMMENT_COLUMNS
+    );
+    let mut stmt = conn.prepare_cached(&sql)?;
+    let rows = stmt.query_map(params![task_id, file_path], review_comment_from_row)?;
+    rows.collect()
+}
+
+/// Per-file (total, unresolved) comment counts for a task.
+pub fn count_review_comments_by_file(
+    conn: &Connection,
+    task_id: &str,
+) -> Result<HashMap<String, (i64, i64)>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT file_path, COUNT(*), SUM(CASE WHEN resolved = 0 THEN 1 ELSE 0 END)
+         FROM review_comments WHERE task_id = ?1 GROUP BY file_path",
+    )?;
+    let rows = stmt.query_map(params![task_id], |row| {
+        Ok((row.get::<_, String>(0)?, (row.get(1)?, row.get(2)?)))
+    })?;
+    rows.collect()
+}
+
+/// Mark a comment resolved (or reopen it). The body is kept either way.
+pub fn set_review_comment_resolved(
+    conn: &Connection,
+    id: i64,
+    resolved: bool,
+) -> Result<Option<ReviewCommentRecord>> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "UPDATE review_comments
+         SET resolved = ?2, resolved_at = CASE WHEN ?2 THEN COALESCE(resolved_at, ?3) ELSE NULL END, updated_at = ?3
+         WHERE id = ?1",
+        params![id, resolved, now],
+    )?;
+    get_review_comment(conn, id)
+}
+
+pub fn delete_review_comment(conn: &Connection, id: i64) -> Result<bool> {
+    let deleted = conn.execute("DELETE FROM review_comments WHERE id = ?1", params![id])?;
+    Ok(deleted > 0)
+}
+
+pub fn set_review_file_viewed(
+    conn: &Connection,
+    task_id: &str,
+    file_path: &str,
+    content_hash: &str,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO review_viewed_files (task_id, file_path, content_hash, viewed_at)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(task_id, file_path) DO UPDATE SET
+            content_hash = excluded.content_hash,
+            viewed_at = excluded.viewed_at",
+        params![
+            task_id,
+            file_path,
+            content_hash,
+            chrono::Utc::now().timestamp()
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn clear_review_file_viewed(conn: &Connection, task_id: &str, file_path: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM review_viewed_files WHERE task_id = ?1 AND file_path = ?2",
+        params![task_id, file_path],
+    )?;
+    Ok(())
+}
+
+/// Viewed files for a task: path -> content hash at the time it was marked.
+pub fn list_review_viewed_files(
+    conn: &Connection,
+    task_id: &str,
+) -> Result<HashMap<String, String>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT file_path, content_hash FROM review_viewed_files WHERE task_id = ?1",
+    )?;
+    let rows = stmt.query_map(params![task_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+pub fn delete_chat_window_state(conn: &Connection, window_label: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM chat_window_state WHERE window_label = ?1",
+        params![window_label],
+    )?;
+    Ok(())
+}
+
 /// Save analytics snapshot to cache
 pub fn save_analytics_cache(
     conn: &Connection,
@@ -1063,6 +2119,34 @@ pub fn get_all_cached_models(conn: &Connection) -> Result<Vec<(String, Vec<Cache
 }
 
 /// Get cached modes for an agent (returns empty vec if none cached)
+/// Record whether the agent advertised session/load the last time it was initialized.
+pub fn save_agent_load_session_support(
+    conn: &Connection,
+    agent_id: &str,
+    supported: bool,
+) -> Result<()> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "INSERT INTO agent_capabilities (agent_id, supports_load_session, updated_at)
+         VALUES (?1, ?2, ?3)
+         ON CONFLICT(agent_id) DO UPDATE SET
+            supports_load_session = excluded.supports_load_session,
+            updated_at = excluded.updated_at",
+        params![agent_id, supported, now],
+    )?;
+    Ok(())
+}
+
+/// `None` until the agent has been initialized at least once.
+pub fn agent_supports_load_session(conn: &Connection, agent_id: &str) -> Result<Option<bool>> {
+    conn.query_row(
+        "SELECT supports_load_session FROM agent_capabilities WHERE agent_id = ?1",
+        params![agent_id],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
 pub fn get_cached_modes(conn: &Connection, agent_id: &str) -> Result<Vec<CachedMode>> {
     let mut stmt = conn.prepare_cached(
         "SELECT value, name, description FROM cached_modes WHERE agent_id = ?1 ORDER BY rowid",
@@ -1125,8 +2209,8 @@ pub fn get_all_cached_modes(conn: &Connection) -> Result<Vec<(String, Vec<Cached
 
 pub fn insert_task(conn: &Connection, task: &TaskRecord) -> Result<()> {
     conn.execute(
-        "INSERT INTO tasks (id, agent_id, codex_account_id, model, prompt, project_path, worktree_path, branch, context_id, status, status_state, cost, created_at, updated_at, title_summary, agent_session_id, total_tokens, context_window, claude_runtime, claude_team_name, claude_agent_name)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21)",
+        "INSERT INTO tasks (id, agent_id, codex_account_id, model, prompt, project_path, worktree_path, branch, context_id, status, status_state, cost, created_at, updated_at, title_summary, agent_session_id, total_tokens, context_window, claude_runtime, claude_team_name, claude_agent_name, codex_mode, model_applied, base_branch, git_hooks, project_key, sparse_worktree)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27)",
         params![
             task.id,
             task.agent_id,
@@ -1149,6 +2233,12 @@ pub fn insert_task(conn: &Connection, task: &TaskRecord) -> Result<()> {
             task.claude_runtime,
             task.claude_team_name,
             task.claude_agent_name,
+            task.codex_mode,
+            task.model_applied,
+            task.base_branch,
+            task.git_hooks,
+            task.project_key,
+            task.sparse_worktree,
         ],
     )?;
     Ok(())
@@ -1172,15 +2262,74 @@ pub fn update_task_branch(conn: &Connection, id: &str, branch: &str) -> Result<(
     Ok(())
 }
 
+pub fn set_task_archived(conn: &Connection, id: &str, archived: bool) -> Result<()> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "UPDATE tasks SET archived = ?1, updated_at = ?2 WHERE id = ?3",
+        params![archived, now, id],
+    )?;
+    Ok(())
+}
+
+pub fn set_task_pinned(conn: &Connection, id: &str, pinned: bool) -> Result<()> {
+    conn.execute(
+        "UPDATE tasks SET pinned = ?1 WHERE id = ?2",
+        params![pinned, id],
+    )?;
+    Ok(())
+}
+
 pub fn update_task_cost(conn: &Connection, id: &str, cost: f64) -> Result<()> {
     let now = chrono::Utc::now().timestamp();
+    let previous = get_task_cost(conn, id).optional()?.unwrap_or(0.0);
     conn.execute(
         "UPDATE tasks SET cost = ?1, updated_at = ?2 WHERE id = ?3",
         params![cost, now, id],
     )?;
+    record_cost_change(conn, id, cost - previous, now)
+}
+
+/// Add `delta` to the task's cost and return the new total.
+pub fn add_task_cost(conn: &Connection, id: &str, delta: f64) -> Result<f64> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "UPDATE tasks SET cost = cost + ?1, updated_at = ?2 WHERE id = ?3",
+        params![delta, now, id],
+    )?;
+    record_cost_change(conn, id, delta, now)?;
+    get_task_cost(conn, id)
+}
+
+fn record_cost_change(conn: &Connection, id: &str, delta: f64, now: i64) -> Result<()> {
+    if delta == 0.0 {
+        return Ok(());
+    }
+    conn.execute(
+        "INSERT INTO cost_ledger (task_id, agent_id, model, delta, created_at)
+         SELECT id, agent_id, model, ?2, ?3 FROM tasks WHERE id = ?1",
+        params![id, delta, now],
+    )?;
     Ok(())
 }
 
+/// Ledger rows recorded at or after `since` (unix seconds), oldest first.
+pub fn list_cost_ledger_since(conn: &Connection, since: i64) -> Result<Vec<CostLedgerEntry>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT task_id, agent_id, model, delta, created_at FROM cost_ledger
+         WHERE created_at >= ?1 ORDER BY created_at, id",
+    )?;
+    let rows = stmt.query_map(params![since], |row| {
+        Ok(CostLedgerEntry {
+            task_id: row.get(0)?,
+            agent_id: row.get(1)?,
+            model: row.get(2)?,
+            delta: row.get(3)?,
+            created_at: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
 pub fn update_task_token_usage(
     conn: &Connection,
     id: &str,
@@ -1195,15 +2344,123 @@ pub fn update_task_token_usage(
     Ok(())
 }
 
-pub fn update_task_title_summary(conn: &Connection, id: &str, title: &str) -> Result<()> {
+/// Record one turn's usage; the turn is tied to the task's latest message.
+#[allow(clippy::too_many_arguments)]
+pub fn insert_task_turn_usage(
+    conn: &Connection,
+    task_id: &str,
+    model: &str,
+    input_tokens: i64,
+    cached_input_tokens: i64,
+    cache_write_tokens: i64,
+    output_tokens: i64,
+    cost: f64,
+    equivalent_cost: f64,
+    auth_method: Option<&str>,
+) -> Result<()> {
     let now = chrono::Utc::now().timestamp();
     conn.execute(
-        "UPDATE tasks SET title_summary = ?1, updated_at = ?2 WHERE id = ?3",
-        params![title, now, id],
+        "INSERT INTO task_turn_usage (task_id, model, input_tokens, cached_input_tokens, cache_write_tokens, output_tokens, cost, equivalent_cost, auth_method, message_seq, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9,
+                 (SELECT COALESCE(MAX(seq), 0) FROM messages WHERE task_id = ?1), ?10)",
+        params![
+            task_id,
+            model,
+            input_tokens,
+            cached_input_tokens,
+            cache_write_tokens,
+            output_tokens,
+            cost,
+            equivalent_cost,
+            auth_method,
+            now
+        ],
     )?;
     Ok(())
 }
 
+fn turn_usage_from_row(row: &rusqlite::Row) -> Result<TurnUsageRecord> {
+    Ok(TurnUsageRecord {
+        id: row.get(0)?,
+        task_id: row.get(1)?,
+        model: row.get(2)?,
+        input_tokens: row.get(3)?,
+        cached_input_tokens: row.get(4)?,
+        cache_write_tokens: row.get(5)?,
+        output_tokens: row.get(6)?,
+        cost: row.get(7)?,
+        equivalent_cost: row.get(8)?,
+        auth_method: row.get(9)?,
+        message_seq: row.get(10)?,
+        created_at: row.get(11)?,
+    })
+}
+
+pub fn list_task_turn_usage(conn: &Connection, task_id: &str) -> Result<Vec<TurnUsageRecord>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, task_id, model, input_tokens, cached_input_tokens, cache_write_tokens, output_tokens, cost, equivalent_cost, auth_method, message_seq, created_at
+         FROM task_turn_usage WHERE task_id = ?1 ORDER BY id",
+    )?;
+    let rows = stmt.query_map(params![task_id], turn_usage_from_row)?;
+    rows.collect()
+}
+
+/// Turn usage across all tasks recorded at or after `since` (unix seconds).
+pub fn list_turn_usage_since(conn: &Connection, since: i64) -> Result<Vec<TurnUsageRecord>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, task_id, model, input_tokens, cached_input_tokens, cache_write_tokens, output_tokens, cost, equivalent_cost, auth_method, message_seq, created_at
+         FROM task_turn_usage WHERE created_at >= ?1 ORDER BY id",
+    )?;
+    let rows = stmt.query_map(params![since], turn_usage_from_row)?;
+    rows.collect()
+}
+
+/// Latest user prompt at or before `seq` and the number of messages in `(after_seq, seq]`.
+pub fn get_turn_message_summary(
+    conn: &Connection,
+    task_id: &str,
+    after_seq: i64,
+    seq: i64,
+) -> Result<(Option<String>, i64)> {
+    let prompt = conn
+        .query_row(
+            "SELECT content FROM messages
+             WHERE task_id = ?1 AND seq <= ?2 AND message_type = 'user_message'
+             ORDER BY seq DESC LIMIT 1",
+            params![task_id, seq],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .optional()?
+        .flatten();
+    let count = conn.query_row(
+        "SELECT COUNT(*) FROM messages WHERE task_id = ?1 AND seq > ?2 AND seq <= ?3",
+        params![task_id, after_seq, seq],
+        |row| row.get(0),
+    )?;
+    Ok((prompt, count))
+}
+
+/// Store a generated title. Returns false (and changes nothing) once the user has renamed
+/// the task.
+pub fn update_task_title_summary(conn: &Connection, id: &str, title: &str) -> Result<bool> {
+    let now = chrono::Utc::now().timestamp();
+    let updated = conn.execute(
+        "UPDATE tasks SET title_summary = ?1, updated_at = ?2 WHERE id = ?3 AND title_user_set = 0",
+        params![title, now, id],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Store a user-chosen title. Returns false if the task doesn't exist.
+pub fn rename_task(conn: &Connection, id: &str, title: &str) -> Result<bool> {
+    let now = chrono::Utc::now().timestamp();
+    let updated = conn.execute(
+        "UPDATE tasks SET title_summary = ?1, title_user_set = 1, updated_at = ?2 WHERE id = ?3",
+        params![title, now, id],
+    )?;
+    Ok(updated > 0)
+}
+
 /// Save the Agent session ID for session/load context restoration
 pub fn update_task_agent_session_id(
     conn: &Connection,
@@ -1235,6 +2492,169 @@ pub fn update_task_claude_team_agent(
     Ok(())
 }
 
+pub fn update_task_codex_mode(conn: &Connection, id: &str, mode: Option<&str>) -> Result<()> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "UPDATE tasks SET codex_mode = ?1, updated_at = ?2 WHERE id = ?3",
+        params![mode, now, id],
+    )?;
+    Ok(())
+}
+
+pub fn update_task_auth_method(conn: &Connection, id: &str, method: Option<&str>) -> Result<()> {
+    conn.execute(
+        "UPDATE tasks SET auth_method = ?1 WHERE id = ?2",
+        params![method, id],
+    )?;
+    Ok(())
+}
+
+pub fn update_task_launch_options(
+    conn: &Connection,
+    id: &str,
+    options: &TaskLaunchOptions,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE tasks SET permission_mode = ?1, reasoning_effort = ?2, agent_mode = ?3, plan_mode = ?4, use_worktree = ?5 WHERE id = ?6",
+        params![
+            options.permission_mode,
+            options.reasoning_effort,
+            options.agent_mode,
+            options.plan_mode,
+            options.use_worktree,
+            id
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn get_task_launch_options(conn: &Connection, id: &str) -> Result<Option<TaskLaunchOptions>> {
+    conn.query_row(
+        "SELECT permission_mode, reasoning_effort, agent_mode, plan_mode, use_worktree FROM tasks WHERE id = ?1",
+        params![id],
+        |row| {
+            Ok(TaskLaunchOptions {
+                permission_mode: row.get(0)?,
+                reasoning_effort: row.get(1)?,
+                agent_mode: row.get(2)?,
+                plan_mode: row.get(3)?,
+                use_worktree: row.get(4)?,
+            })
+        },
+    )
+    .optional()
+}
+
+pub fn update_task_model(conn: &Connection, id: &str, model: &str) -> Result<()> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "UPDATE tasks SET model = ?1, updated_at = ?2 WHERE id = ?3",
+        params![model, now, id],
+    )?;
+    Ok(())
+}
+
+pub fn update_task_model_applied(conn: &Connection, id: &str, applied: Option<bool>) -> Result<()> {
+    conn.execute(
+        "UPDATE tasks SET model_applied = ?1 WHERE id = ?2",
+        params![applied, id],
+    )?;
+    Ok(())
+}
+
+/// Last Review Center compare mode used for a task.
+pub fn update_task_review_compare(conn: &Connection, id: &str, compare: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE tasks SET review_compare = ?1 WHERE id = ?2",
+        params![compare, id],
+    )?;
+    Ok(())
+}
+
+pub fn update_task_base_branch(
+    conn: &Connection,
+    id: &str,
+    base_branch: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE tasks SET base_branch = ?1 WHERE id = ?2",
+        params![base_branch, id],
+    )?;
+    Ok(())
+}
+
+/// Store ahead/behind counts; returns false when they were already current.
+pub fn update_task_branch_divergence(
+    conn: &Connection,
+    id: &str,
+    ahead: i64,
+    behind: i64,
+) -> Result<bool> {
+    let changed = conn.execute(
+        "UPDATE tasks SET branch_ahead = ?1, branch_behind = ?2
+         WHERE id = ?3 AND (branch_ahead IS NOT ?1 OR branch_behind IS NOT ?2)",
+        params![ahead, behind, id],
+    )?;
+    Ok(changed > 0)
+}
+
+/// Pull request opened for the task's branch; polling starts over from `state`.
+pub fn update_task_pull_request(
+    conn: &Connection,
+    id: &str,
+    number: i64,
+    url: &str,
+    state: &str,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE tasks SET pr_number = ?1, pr_url = ?2, pr_state = ?3, pr_checks = NULL
+         WHERE id = ?4",
+        params![number, url, state, id],
+    )?;
+    Ok(())
+}
+
+/// Store polled PR state and checks rollup; returns false when they were already current.
+pub fn update_task_pr_status(
+    conn: &Connection,
+    id: &str,
+    state: &str,
+    checks: Option<&str>,
+) -> Result<bool> {
+    let changed = conn.execute(
+        "UPDATE tasks SET pr_state = ?1, pr_checks = ?2
+         WHERE id = ?3 AND (pr_state IS NOT ?1 OR pr_checks IS NOT ?2)",
+        params![state, checks, id],
+    )?;
+    Ok(changed > 0)
+}
+
+pub fn get_task_review_compare(conn: &Connection, id: &str) -> Result<Option<String>> {
+    let result = conn.query_row(
+        "SELECT review_compare FROM tasks WHERE id = ?1",
+        params![id],
+        |row| row.get::<_, Option<String>>(0),
+    );
+    match result {
+        Ok(compare) => Ok(compare),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn get_task_auth_method(conn: &Connection, id: &str) -> Result<Option<String>> {
+    let result = conn.query_row(
+        "SELECT auth_method FROM tasks WHERE id = ?1",
+        params![id],
+        |row| row.get::<_, Option<String>>(0),
+    );
+    match result {
+        Ok(method) => Ok(method),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
 pub fn update_task_codex_account_id(
     conn: &Connection,
     id: &str,
@@ -1410,989 +2830,2970 @@ pub fn get_task_cost(conn: &Connection, id: &str) -> Result<f64> {
     Ok(cost)
 }
 
-pub fn delete_task(conn: &Connection, id: &str) -> Result<()> {
-    // Messages are auto-deleted via CASCADE
-    conn.execute("DELETE FROM tasks WHERE id = ?1", params![id])?;
-
-    // Best-effort cleanup of semantic search rows (no FK constraints).
-    conn.execute(
-        "DELETE FROM semantic_chunks WHERE entity_type = 'task' AND entity_id = ?1",
+pub fn get_task_cache_cost(conn: &Connection, id: &str) -> Result<f64> {
+    conn.query_row(
+        "SELECT COALESCE(cache_cost, 0) FROM tasks WHERE id = ?1",
         params![id],
+        |row| row.get(0),
     )
-    .ok();
-    conn.execute(
-        "DELETE FROM semantic_fts WHERE entity_type = 'task' AND entity_id = ?1",
+}
+
+pub fn get_task_equivalent_cost(conn: &Connection, id: &str) -> Result<f64> {
+    conn.query_row(
+        "SELECT COALESCE(equivalent_cost, 0) FROM tasks WHERE id = ?1",
         params![id],
+        |row| row.get(0),
     )
-    .ok();
+}
 
-    Ok(())
+/// Add `delta` to the task's API-rate equivalent cost and return the new total.
+pub fn add_task_equivalent_cost(conn: &Connection, id: &str, delta: f64) -> Result<f64> {
+    conn.execute(
+        "UPDATE tasks SET equivalent_cost = COALESCE(equivalent_cost, 0) + ?1 WHERE id = ?2",
+        params![delta, id],
+    )?;
+    get_task_equivalent_cost(conn, id)
 }
 
-/// Save a chat message to the database
-pub fn save_message(
-    conn: &Connection,
-    task_id: &str,
-    message_type: &str,
-    content: Option<&str>,
-    reasoning: Option<&str>,
-    tool_name: Option<&str>,
-    tool_arguments: Option<&str>,
-    tool_return: Option<&str>,
-    timestamp: &str,
-) -> Result<i64> {
+/// Add `delta` to the task's cache cost and return the new total.
+pub fn add_task_cache_cost(conn: &Connection, id: &str, delta: f64) -> Result<f64> {
     conn.execute(
-        "INSERT INTO messages (task_id, message_type, content, reasoning, tool_name, tool_arguments, tool_return, timestamp)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-        params![task_id, message_type, content, reasoning, tool_name, tool_arguments, tool_return, timestamp],
+        "UPDATE tasks SET cache_cost = COALESCE(cache_cost, 0) + ?1 WHERE id = ?2",
+        params![delta, id],
     )?;
-    Ok(conn.last_insert_rowid())
+    get_task_cache_cost(conn, id)
 }
 
-#[allow(dead_code)]
-pub fn save_message_attachments(
-    conn: &Connection,
-    task_id: &str,
-    message_id: i64,
-    attachments: &[AttachmentRecord],
-) -> Result<()> {
-    if attachments.is_empty() {
-        return Ok(());
-    }
+/// Record that a cost alert at `amount` was sent for the task. Returns false when an
+/// alert at this level (or higher) was already sent.
+pub fn claim_cost_alert(conn: &Connection, task_id: &str, amount: f64) -> Result<bool> {
     let now = chrono::Utc::now().timestamp();
-    for attachment in attachments {
-        conn.execute(
-            "INSERT OR REPLACE INTO message_attachments
-             (id, task_id, message_id, file_name, mime_type, relative_path, byte_size, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-            params![
-                attachment.id,
-                task_id,
-                message_id,
-                attachment.file_name,
-                attachment.mime_type,
-                attachment.relative_path,
-                attachment.byte_size,
-                now
-            ],
+    let changed = conn.execute(
+        "INSERT INTO cost_alerts (task_id, alerted_amount, alerted_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(task_id) DO UPDATE SET
+            alerted_amount = excluded.alerted_amount,
+            alerted_at = excluded.alerted_at
+         WHERE excluded.alerted_amount > cost_alerts.alerted_amount + 1e-9",
+        params![task_id, amount, now],
+    )?;
+    Ok(changed > 0)
+}
+
+pub fn insert_task_chain(conn: &Connection, parent_task_id: &str, payload: &str) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO task_chains (parent_task_id, payload, created_at) VALUES (?1, ?2, ?3)",
+        params![parent_task_id, payload, chrono::Utc::now().timestamp()],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Tasks that still have chains waiting on them.
+pub fn list_task_chain_parents(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT DISTINCT parent_task_id FROM task_chains")?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// Remove and return the chains waiting on `parent_task_id`, oldest first.
+pub fn take_task_chains(conn: &Connection, parent_task_id: &str) -> Result<Vec<TaskChainRecord>> {
+    let chains = {
+        let mut stmt = conn.prepare(
+            "SELECT id, parent_task_id, payload, created_at FROM task_chains
+             WHERE parent_task_id = ?1 ORDER BY id ASC",
         )?;
+        let rows = stmt.query_map(params![parent_task_id], |row| {
+            Ok(TaskChainRecord {
+                id: row.get(0)?,
+                parent_task_id: row.get(1)?,
+                payload: row.get(2)?,
+                created_at: row.get(3)?,
+            })
+        })?;
+        rows.collect::<Result<Vec<_>>>()?
+    };
+    for chain in &chains {
+        conn.execute("DELETE FROM task_chains WHERE id = ?1", params![chain.id])?;
     }
-    Ok(())
+    Ok(chains)
 }
 
-pub fn save_pending_attachments(
-    conn: &mut Connection,
-    task_id: &str,
-    attachments: &[AttachmentRecord],
+pub fn insert_generation_metrics(
+    conn: &Connection,
+    metrics: &GenerationMetricsRecord,
 ) -> Result<()> {
-    if attachments.is_empty() {
-        return Ok(());
-    }
-    let now = chrono::Utc::now().timestamp();
-    let tx = conn.transaction()?;
-    for attachment in attachments {
-        tx.execute(
-            "INSERT OR REPLACE INTO pending_attachments
-             (id, task_id, file_name, mime_type, relative_path, byte_size, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            params![
-                attachment.id,
-                task_id,
-                attachment.file_name,
-                attachment.mime_type,
-                attachment.relative_path,
-                attachment.byte_size,
-                now
-            ],
-        )?;
-    }
-    tx.commit()?;
+    conn.execute(
+        "INSERT INTO generation_metrics
+            (task_id, agent_id, model, time_to_first_token_ms, wall_time_ms, tool_calls, output_tokens, tokens_per_sec, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            metrics.task_id,
+            metrics.agent_id,
+            metrics.model,
+            metrics.time_to_first_token_ms,
+            metrics.wall_time_ms,
+            metrics.tool_calls,
+            metrics.output_tokens,
+            metrics.tokens_per_sec,
+            metrics.created_at
+        ],
+    )?;
     Ok(())
 }
 
-pub fn get_pending_attachments(conn: &Connection, task_id: &str) -> Result<Vec<AttachmentRecord>> {
+/// A task's generation metrics, oldest first.
+pub fn list_generation_metrics(
+    conn: &Connection,
+    task_id: &str,
+) -> Result<Vec<GenerationMetricsRecord>> {
     let mut stmt = conn.prepare(
-        "SELECT id, file_name, mime_type, relative_path, byte_size
-         FROM pending_attachments WHERE task_id = ?1 ORDER BY created_at ASC",
+        "SELECT task_id, agent_id, model, time_to_first_token_ms, wall_time_ms, tool_calls, output_tokens, tokens_per_sec, created_at
+         FROM generation_metrics WHERE task_id = ?1 ORDER BY created_at ASC, id ASC",
     )?;
     let rows = stmt.query_map(params![task_id], |row| {
-        Ok(AttachmentRecord {
-            id: row.get(0)?,
-            file_name: row.get(1)?,
-            mime_type: row.get(2)?,
-            relative_path: row.get(3)?,
-            byte_size: row.get(4)?,
+        Ok(GenerationMetricsRecord {
+            task_id: row.get(0)?,
+            agent_id: row.get(1)?,
+            model: row.get(2)?,
+            time_to_first_token_ms: row.get(3)?,
+            wall_time_ms: row.get(4)?,
+            tool_calls: row.get(5)?,
+            output_tokens: row.get(6)?,
+            tokens_per_sec: row.get(7)?,
+            created_at: row.get(8)?,
         })
     })?;
     rows.collect()
 }
 
-#[allow(dead_code)]
-pub fn clear_pending_attachments(conn: &Connection, task_id: &str) -> Result<()> {
+/// Record (or update the kind of) a file changed by the generation started at
+/// `generation_started_at`.
+pub fn upsert_file_change(
+    conn: &Connection,
+    task_id: &str,
+    generation_started_at: i64,
+    path: &str,
+    kind: &str,
+) -> Result<()> {
     conn.execute(
-        "DELETE FROM pending_attachments WHERE task_id = ?1",
-        params![task_id],
+        "INSERT INTO task_file_changes (task_id, generation_started_at, path, kind)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(task_id, generation_started_at, path) DO UPDATE SET kind = excluded.kind",
+        params![task_id, generation_started_at, path, kind],
     )?;
     Ok(())
 }
 
-pub fn get_message_attachments(
-    conn: &Connection,
-    task_id: &str,
-) -> Result<HashMap<i64, Vec<AttachmentRecord>>> {
-    let mut stmt = conn.prepare_cached(
-        "SELECT message_id, id, file_name, mime_type, relative_path, byte_size
-         FROM message_attachments WHERE task_id = ?1 ORDER BY message_id ASC, rowid ASC",
+/// Files changed by the task's latest generation that changed any, by path.
+pub fn latest_file_changes(conn: &Connection, task_id: &str) -> Result<Vec<FileChangeRecord>> {
+    let mut stmt = conn.prepare(
+        "SELECT path, kind, generation_started_at FROM task_file_changes
+         WHERE task_id = ?1 AND generation_started_at = (
+             SELECT MAX(generation_started_at) FROM task_file_changes WHERE task_id = ?1
+         )
+         ORDER BY path",
     )?;
-    let mut map: HashMap<i64, Vec<AttachmentRecord>> = HashMap::new();
     let rows = stmt.query_map(params![task_id], |row| {
-        let message_id: i64 = row.get(0)?;
-        let attachment = AttachmentRecord {
-            id: row.get(1)?,
-            file_name: row.get(2)?,
-            mime_type: row.get(3)?,
-            relative_path: row.get(4)?,
-            byte_size: row.get(5)?,
-        };
-        Ok((message_id, attachment))
+        Ok(FileChangeRecord {
+            path: row.get(0)?,
+            kind: row.get(1)?,
+            generation_started_at: row.get(2)?,
+        })
     })?;
-    for row in rows {
-        let (message_id, attachment) = row?;
-        map.entry(message_id).or_default().push(attachment);
-    }
-    Ok(map)
-}
-
-#[allow(dead_code)]
-pub fn list_attachment_relative_paths(conn: &Connection, task_id: &str) -> Result<Vec<String>> {
-    let mut stmt = conn.prepare(
-        "SELECT relative_path FROM pending_attachments WHERE task_id = ?1
-         UNION ALL
-         SELECT relative_path FROM message_attachments WHERE task_id = ?1",
-    )?;
-    let rows = stmt.query_map(params![task_id], |row| row.get(0))?;
     rows.collect()
 }
 
-#[allow(dead_code)]
-pub fn attachment_ref_count(
+/// Number of tasks other than `exclude_id` whose worktree is `worktree_path`.
+pub fn count_tasks_sharing_worktree(
     conn: &Connection,
-    relative_path: &str,
-    exclude_task_id: Option<&str>,
+    worktree_path: &str,
+    exclude_id: &str,
 ) -> Result<i64> {
-    if let Some(exclude) = exclude_task_id {
-        conn.query_row(
-            "SELECT COUNT(*) FROM (
-                SELECT task_id FROM pending_attachments WHERE relative_path = ?1
-                UNION ALL
-                SELECT task_id FROM message_attachments WHERE relative_path = ?1
-            ) WHERE task_id != ?2",
-            params![relative_path, exclude],
-            |row| row.get(0),
-        )
-    } else {
-        conn.query_row(
-            "SELECT COUNT(*) FROM (
-                SELECT task_id FROM pending_attachments WHERE relative_path = ?1
-                UNION ALL
-                SELECT task_id FROM message_attachments WHERE relative_path = ?1
-            )",
-            params![relative_path],
-            |row| row.get(0),
-        )
-    }
+    conn.query_row(
+        "SELECT COUNT(*) FROM tasks WHERE worktree_path = ?1 AND id != ?2",
+        params![worktree_path, exclude_id],
+        |row| row.get(0),
+    )
 }
 
-/// Load all messages for a task
-pub fn get_messages(conn: &Connection, task_id: &str) -> Result<Vec<serde_json::Value>> {
-    let attachments_by_message = get_message_attachments(conn, task_id).unwrap_or_default();
-    let mut stmt = conn.prepare_cached(
-        "SELECT id, message_type, content, reasoning, tool_name, tool_arguments, tool_return, timestamp
-         FROM messages WHERE task_id = ?1 ORDER BY id ASC",
-    )?;
-    let rows = stmt.query_map(params![task_id], |row| {
-        let message_id: i64 = row.get(0)?;
-        let message_type: String = row.get(1)?;
-        let content: Option<String> = row.get(2)?;
-        let reasoning: Option<String> = row.get(3)?;
-        let tool_name: Option<String> = row.get(4)?;
-        let tool_arguments: Option<String> = row.get(5)?;
-        let tool_return: Option<String> = row.get(6)?;
-        let timestamp: String = row.get(7)?;
-
-        // Build tool_call object if tool_name exists
-        let tool_call = tool_name.as_ref().map(|name| {
-            serde_json::json!({
-                "name": name,
-                "arguments": tool_arguments
-            })
-        });
+pub fn delete_task(conn: &Connection, id: &str) -> Result<()> {
+    // Messages are auto-deleted via CASCADE
+    conn.execute("DELETE FROM tasks WHERE id = ?1", params![id])?;
 
-        Ok((
-            message_id,
-            serde_json::json!({
-                "message_type": message_type,
-                "content": content,
-                "reasoning": reasoning,
-                "tool_call": tool_call,
-                "tool_return": tool_return,
-                "timestamp": timestamp
-            }),
-        ))
-    })?;
+    conn.execute("DELETE FROM plan_versions WHERE task_id = ?1", params![id])
+        .ok();
+    conn.execute(
+        "DELETE FROM review_comments WHERE task_id = ?1",
+        params![id],
+    )
+    .ok();
+    conn.execute(
+        "DELETE FROM review_viewed_files WHERE task_id = ?1",
+        params![id],
+    )
+    .ok();
+    conn.execute(
+        "DELETE FROM task_turn_usage WHERE task_id = ?1",
+        params![id],
+    )
+    .ok();
+    conn.execute("DELETE FROM cost_alerts WHERE task_id = ?1", params![id])
+        .ok();
+    conn.execute(
+        "DELETE FROM history_summaries WHERE task_id = ?1",
+        params![id],
+    )
+    .ok();
+    conn.execute(
+        "DELETE FROM task_chains WHERE parent_task_id = ?1",
+        params![id],
+    )
+    .ok();
+    conn.execute(
+        "DELETE FROM generation_metrics WHERE task_id = ?1",
+        params![id],
+    )
+    .ok();
+    conn.execute(
+        "DELETE FROM task_file_changes WHERE task_id = ?1",
+        params![id],
+    )
+    .ok();
+    conn.execute(
+        "DELETE FROM discord_task_requesters WHERE task_id = ?1",
+        params![id],
+    )
+    .ok();
 
-    let mut messages = Vec::new();
-    for row in rows {
-        let (message_id, mut message) = row?;
-        if let Some(attachments) = attachments_by_message.get(&message_id) {
-            message["attachments"] = serde_json::json!(attachments);
-        }
-        messages.push(message);
-    }
-    Ok(messages)
-}
+    // Best-effort cleanup of semantic search rows (no FK constraints).
+    conn.execute(
+        "DELETE FROM semantic_chunks WHERE entity_type = 'task' AND entity_id = ?1",
+        params![id],
+    )
+    .ok();
+    conn.execute(
+        "DELETE FROM semantic_fts WHERE entity_type = 'task' AND entity_id = ?1",
+        params![id],
+    )
+    .ok();
 
-pub fn list_tasks(conn: &Connection) -> Result<Vec<TaskRecord>> {
-    let mut stmt = conn.prepare_cached(
-        "SELECT id, agent_id, codex_account_id, model, prompt, project_path, worktree_path, branch, context_id, status, status_state, cost, created_at, updated_at, title_summary, agent_session_id, total_tokens, context_window, claude_runtime, claude_team_name, claude_agent_name
-         FROM tasks ORDER BY created_at ASC"
-    )?;
-    let tasks = stmt.query_map([], |row| {
-        Ok(TaskRecord {
-            id: row.get(0)?,
-            agent_id: row.get(1)?,
-            codex_account_id: row.get(2)?,
-            model: row.get(3)?,
-            prompt: row.get(4)?,
-            project_path: row.get(5)?,
-            worktree_path: row.get(6)?,
-            branch: row.get(7)?,
-            context_id: row.get(8)?,
-            status: row.get(9)?,
-            status_state: row.get(10)?,
-            cost: row.get(11)?,
-            created_at: row.get(12)?,
-            updated_at: row.get(13)?,
-            title_summary: row.get(14)?,
-            agent_session_id: row.get(15)?,
-            total_tokens: row.get(16)?,
-            context_window: row.get(17)?,
-            claude_runtime: row.get(18)?,
-            claude_team_name: row.get(19)?,
-            claude_agent_name: row.get(20)?,
-        })
-    })?;
-    tasks.collect()
+    Ok(())
 }
 
-pub fn list_contexts(conn: &Connection) -> Result<Vec<ContextRecord>> {
-    let mut stmt = conn.prepare_cached(
-        "SELECT id, name, description, created_at, updated_at
-         FROM contexts
-         ORDER BY updated_at DESC, created_at DESC",
-    )?;
-    let rows = stmt.query_map([], |row| {
-        Ok(ContextRecord {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            description: row.get(2)?,
-            created_at: row.get(3)?,
-            updated_at: row.get(4)?,
-        })
-    })?;
-    rows.collect()
+/// Delete up to `limit` of a task's messages older than `cutoff` (RFC 3339) and return
+/// how many went. The first user message (the prompt), the last assistant message, plan
+/// updates and messages with attachments are always kept.
+pub fn prune_task_messages(
+    conn: &Connection,
+    task_id: &str,
+    cutoff: &str,
+    limit: usize,
+) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM messages WHERE id IN (
+            SELECT id FROM messages
+            WHERE task_id = ?1
+              AND julianday(timestamp) < julianday(?2)
+              AND message_type NOT IN ('plan_update', 'plan_content')
+              AND id != COALESCE((SELECT id FROM messages WHERE task_id = ?1
+                                  AND message_type = 'user_message'
+                                  ORDER BY seq ASC, id ASC LIMIT 1), -1)
+              AND id != COALESCE((SELECT id FROM messages WHERE task_id = ?1
+                                  AND message_type = 'assistant_message'
+                                  ORDER BY seq DESC, id DESC LIMIT 1), -1)
+              AND id NOT IN (SELECT message_id FROM message_attachments WHERE task_id = ?1)
+            LIMIT ?3)",
+        params![task_id, cutoff, limit as i64],
+    )
 }
 
-pub fn create_context(
-    conn: &Connection,
-    name: &str,
-    description: Option<&str>,
-) -> Result<ContextRecord> {
-    let id = Uuid::new_v4().to_string();
-    let now = chrono::Utc::now().timestamp();
+/// Delete up to `limit` of a task's messages, so a large task can be removed in steps.
+pub fn delete_task_messages_batch(conn: &Connection, task_id: &str, limit: usize) -> Result<usize> {
     conn.execute(
-        "INSERT INTO contexts (id, name, description, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5)",
-        params![id, name, description, now, now],
-    )?;
-    Ok(ContextRecord {
-        id,
-        name: name.to_string(),
-        description: description.map(|s| s.to_string()),
-        created_at: now,
-        updated_at: now,
-    })
+        "DELETE FROM messages WHERE id IN (SELECT id FROM messages WHERE task_id = ?1 LIMIT ?2)",
+        params![task_id, limit as i64],
+    )
 }
 
-pub fn list_codex_accounts(conn: &Connection) -> Result<Vec<CodexAccountRecord>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, label, codex_home, email, plan_type, created_at, updated_at
-         FROM codex_accounts ORDER BY created_at ASC",
-    )?;
-    let rows = stmt.query_map([], |row| {
-        Ok(CodexAccountRecord {
-            id: row.get(0)?,
-            label: row.get(1)?,
-            codex_home: row.get(2)?,
-            email: row.get(3)?,
-            plan_type: row.get(4)?,
-            created_at: row.get(5)?,
-            updated_at: row.get(6)?,
-        })
-    })?;
-    rows.collect()
+/// Delete one message (e.g. a queued prompt that was withdrawn before it was sent).
+pub fn delete_message(conn: &Connection, task_id: &str, message_id: i64) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM messages WHERE id = ?1 AND task_id = ?2",
+        params![message_id, task_id],
+    )
 }
 
-pub fn get_codex_account(conn: &Connection, id: &str) -> Result<Option<CodexAccountRecord>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, label, codex_home, email, plan_type, created_at, updated_at
-         FROM codex_accounts WHERE id = ?1",
-    )?;
-    let mut rows = stmt.query(params![id])?;
-    if let Some(row) = rows.next()? {
-        Ok(Some(CodexAccountRecord {
-            id: row.get(0)?,
-            label: row.get(1)?,
-            codex_home: row.get(2)?,
-            email: row.get(3)?,
-            plan_type: row.get(4)?,
-            created_at: row.get(5)?,
-            updated_at: row.get(6)?,
-        }))
-    } else {
-        Ok(None)
+/// Message type of the checkpoint of assistant output still being streamed.
+pub const PARTIAL_MESSAGE_TYPE: &str = "partial_assistant_message";
+
+/// Create or refresh a task's streaming checkpoint. Returns the row id to pass next time.
+pub fn upsert_partial_message(
+    conn: &Connection,
+    task_id: &str,
+    message_id: Option<i64>,
+    content: &str,
+    reasoning: Option<&str>,
+    timestamp: &str,
+) -> Result<i64> {
+    if let Some(message_id) = message_id {
+        let updated = conn.execute(
+            "UPDATE messages SET content = ?1, reasoning = ?2, timestamp = ?3
+             WHERE id = ?4 AND task_id = ?5 AND message_type = ?6",
+            params![
+                content,
+                reasoning,
+                timestamp,
+                message_id,
+                task_id,
+                PARTIAL_MESSAGE_TYPE
+            ],
+        )?;
+        if updated > 0 {
+            return Ok(message_id);
+        }
     }
+    save_message(
+        conn,
+        task_id,
+        PARTIAL_MESSAGE_TYPE,
+        Some(content),
+        reasoning,
+        None,
+        None,
+        None,
+        timestamp,
+    )
 }
 
-pub fn insert_codex_account(conn: &Connection, account: &CodexAccountRecord) -> Result<()> {
+/// Drop a task's streaming checkpoints once the final messages are saved.
+pub fn delete_partial_messages(conn: &Connection, task_id: &str) -> Result<usize> {
     conn.execute(
-        "INSERT INTO codex_accounts (id, label, codex_home, email, plan_type, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-        params![
-            account.id,
-            account.label,
-            account.codex_home,
-            account.email,
-            account.plan_type,
-            account.created_at,
-            account.updated_at
-        ],
-    )?;
-    Ok(())
+        "DELETE FROM messages WHERE task_id = ?1 AND message_type = ?2",
+        params![task_id, PARTIAL_MESSAGE_TYPE],
+    )
 }
 
-pub fn update_codex_account_meta(
+/// Give a message the next `seq` and a new timestamp, so it sorts after everything saved
+/// so far. Used when a prompt saved at queue time is finally sent.
+pub fn move_message_to_end(
     conn: &Connection,
-    id: &str,
-    email: Option<&str>,
-    plan_type: Option<&str>,
-    label: Option<&str>,
-) -> Result<()> {
-    let now = chrono::Utc::now().timestamp();
+    task_id: &str,
+    message_id: i64,
+    timestamp: &str,
+) -> Result<usize> {
     conn.execute(
-        "UPDATE codex_accounts SET email = COALESCE(?1, email), plan_type = COALESCE(?2, plan_type), label = COALESCE(?3, label), updated_at = ?4 WHERE id = ?5",
-        params![email, plan_type, label, now, id],
-    )?;
-    Ok(())
+        "UPDATE messages
+         SET seq = (SELECT COALESCE(MAX(seq), 0) + 1 FROM messages WHERE task_id = ?1),
+             timestamp = ?3
+         WHERE id = ?2 AND task_id = ?1",
+        params![task_id, message_id, timestamp],
+    )
 }
 
-pub fn delete_codex_account(conn: &Connection, id: &str) -> Result<()> {
-    conn.execute("DELETE FROM codex_accounts WHERE id = ?1", params![id])?;
-    Ok(())
+/// Bytes held by free pages, i.e. what a VACUUM would give back.
+pub fn reclaimable_bytes(conn: &Connection) -> Result<i64> {
+    let free_pages: i64 = conn.query_row("PRAGMA freelist_count", [], |row| row.get(0))?;
+    let page_size: i64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+    Ok(free_pages * page_size)
 }
 
-/// Get messages as structured MessageRecord for history formatting
-pub fn get_message_records(conn: &Connection, task_id: &str) -> Result<Vec<MessageRecord>> {
-    let mut stmt = conn.prepare_cached(
-        "SELECT id, message_type, content, reasoning, tool_name, tool_arguments, tool_return, timestamp
-         FROM messages WHERE task_id = ?1 ORDER BY id ASC",
-    )?;
-    let rows = stmt.query_map(params![task_id], |row| {
-        Ok(MessageRecord {
-            id: row.get(0)?,
-            message_type: row.get(1)?,
-            content: row.get(2)?,
-            reasoning: row.get(3)?,
-            tool_name: row.get(4)?,
-            tool_arguments: row.get(5)?,
-            tool_return: row.get(6)?,
-            timestamp: row.get(7)?,
-        })
-    })?;
-    rows.collect()
+/// Save a chat message to the database
+pub fn save_message(
+    conn: &Connection,
+    task_id: &str,
+    message_type: &str,
+    content: Option<&str>,
+    reasoning: Option<&str>,
+    tool_name: Option<&str>,
+    tool_arguments: Option<&str>,
+    tool_return: Option<&str>,
+    timestamp: &str,
+) -> Result<i64> {
+    save_message_with_seq(
+        conn,
+        task_id,
+        message_type,
+        content,
+        reasoning,
+        tool_name,
+        tool_arguments,
+        tool_return,
+        timestamp,
+    )
+    .map(|(id, _)| id)
 }
 
-/// Format conversation history as a context string for agent injection
-/// Includes full tool call details with arguments and returns
-pub fn format_conversation_history(
-    messages: &[MessageRecord],
-    original_prompt: Option<&str>,
-) -> String {
-    let mut output = String::new();
-
-    // Include original task prompt if available
-    if let Some(prompt) = original_prompt {
-        output.push_str("[Original Task]\n");
-        output.push_str(prompt);
-        output.push_str("\n\n");
-    }
-
-    output.push_str("[Previous Conversation]\n\n");
-
-    for msg in messages {
-        match msg.message_type.as_str() {
-            "user_message" => {
-                if let Some(ref content) = msg.content {
-                    output.push_str("User: ");
-                    output.push_str(content);
-                    output.push_str("\n\n");
-                }
-            }
-            "assistant_message" => {
-                if let Some(ref content) = msg.content {
-                    output.push_str("Assistant: ");
-                    output.push_str(content);
-                    output.push_str("\n\n");
-                }
-            }
-            "reasoning_message" => {
-                if let Some(ref reasoning) = msg.reasoning {
-                    output.push_str("[Reasoning] ");
-                    // Truncate long reasoning to save context (safe for UTF-8)
-                    let prefix = safe_prefix(reasoning, 500);
-                    output.push_str(prefix);
-                    if reasoning.chars().count() > 500 {
-                        output.push_str("...");
-                    }
-                    output.push_str("\n\n");
-                }
-            }
-            "tool_call_message" => {
-                if let Some(ref name) = msg.tool_name {
-                    output.push_str(&format!("[Tool Call: {}]\n", name));
-                    if let Some(ref args) = msg.tool_arguments {
-                        // Truncate very long arguments (safe for UTF-8)
-                        output.push_str("Arguments: ");
-                        output.push_str(safe_prefix(args, 1000));
-                        if args.chars().count() > 1000 {
-                            output.push_str("...");
-                        }
-                        output.push('\n');
-                    }
-                    output.push('\n');
-                }
-            }
-            "tool_return_message" => {
-                if let Some(ref result) = msg.tool_return {
-                    output.push_str("[Tool Result]\n");
-                    // Truncate very long tool returns (safe for UTF-8)
-                    output.push_str(safe_prefix(result, 2000));
-                    if result.chars().count() > 2000 {
-                        output.push_str("...");
-                    }
-                    output.push('\n');
-                    output.push('\n');
-                }
-            }
-            _ => {
-                // Skip unknown message types
-            }
-        }
-    }
-
-    output.push_str("---\n\n");
-    output
+/// Save a chat message and return `(id, seq)`. The per-task sequence number is allocated
+/// here, in the same statement as the insert, so read-back order matches write order.
+#[allow(clippy::too_many_arguments)]
+pub fn save_message_with_seq(
+    conn: &Connection,
+    task_id: &str,
+    message_type: &str,
+    content: Option<&str>,
+    reasoning: Option<&str>,
+    tool_name: Option<&str>,
+    tool_arguments: Option<&str>,
+    tool_return: Option<&str>,
+    timestamp: &str,
+) -> Result<(i64, i64)> {
+    conn.query_row(
+        "INSERT INTO messages (task_id, message_type, content, reasoning, tool_name, tool_arguments, tool_return, timestamp, seq)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8,
+                 (SELECT COALESCE(MAX(seq), 0) + 1 FROM messages WHERE task_id = ?1))
+         RETURNING id, seq",
+        params![task_id, message_type, content, reasoning, tool_name, tool_arguments, tool_return, timestamp],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
 }
 
-/// Compact history to fit within a token limit
-/// Keeps recent messages intact and progressively summarizes older messages
-/// Returns (compacted_history, was_truncated)
-pub fn compact_history(
-    messages: &[MessageRecord],
-    original_prompt: Option<&str>,
-    max_chars: usize,
-) -> (String, bool) {
-    // First, try full history
-    let full_history = format_conversation_history(messages, original_prompt);
-    if full_history.len() <= max_chars {
-        return (full_history, false);
-    }
-
-    // Need to compact - keep most recent messages, summarize older ones
-    let mut output = String::new();
-
-    // Always include original prompt
-    if let Some(prompt) = original_prompt {
-        output.push_str("[Original Task]\n");
-        output.push_str(prompt);
-        output.push_str("\n\n");
-    }
-
-    // Calculate how many chars we have for messages
-    let available_chars = max_chars.saturating_sub(output.len() + 100); // Reserve 100 for summary header
-
-    // Split messages: older half gets summarized, newer half kept intact
-    let split_point = messages.len() / 2;
-    let (older, newer) = messages.split_at(split_point);
-
-    // Summarize older messages
-    if !older.is_empty() {
-        let mut tool_calls = 0;
-        let mut assistant_msgs = 0;
-        let mut user_msgs = 0;
-
-        for msg in older {
-            match msg.message_type.as_str() {
-                "tool_call_message" => tool_calls += 1,
-                "assistant_message" => assistant_msgs += 1,
-                "user_message" => user_msgs += 1,
-                _ => {}
-            }
-        }
-
-        output.push_str(&format!(
-            "[Earlier conversation summary: {} user messages, {} assistant responses, {} tool calls]\n\n",
-            user_msgs, assistant_msgs, tool_calls
-        ));
-    }
-
-    // Format newer messages
-    output.push_str("[Recent Conversation]\n\n");
-    let newer_history = format_conversation_history(newer, None);
-
-    // If still too long, truncate from the beginning of newer messages
-    if output.len() + newer_history.len() > available_chars {
-        let remaining = available_chars.saturating_sub(output.len());
-        if remaining > 0 && newer_history.len() > remaining {
-            // Take the last 'remaining' chars
-            let start = newer_history.len() - remaining;
-            output.push_str("...[truncated]...\n\n");
-            output.push_str(&newer_history[start..]);
-        } else if remaining > 0 {
-            output.push_str(&newer_history);
-        }
-    } else {
-        output.push_str(&newer_history);
-    }
-
-    output.push_str("---\n\n");
-    (output, true)
+/// Copy every chat message of `from_task_id` to `to_task_id`, keeping their order.
+/// Returns how many were copied.
+pub fn copy_messages(conn: &Connection, from_task_id: &str, to_task_id: &str) -> Result<usize> {
+    conn.execute(
+        "INSERT INTO messages (task_id, message_type, content, reasoning, tool_name, tool_arguments, tool_return, tool_return_path, timestamp, seq)
+         SELECT ?2, message_type, content, reasoning, tool_name, tool_arguments, tool_return, tool_return_path, timestamp, seq
+         FROM messages WHERE task_id = ?1 ORDER BY seq, id",
+        params![from_task_id, to_task_id],
+    )
 }
 
-/// Insert a new meeting session
-pub fn insert_meeting_session(conn: &Connection, session: &MeetingSessionRecord) -> Result<()> {
+/// Point a truncated tool return at the file holding its full text.
+pub fn set_message_tool_return_path(conn: &Connection, message_id: i64, path: &str) -> Result<()> {
     conn.execute(
-        "INSERT INTO meeting_sessions (id, title, status, capture_mic, capture_system, started_at, stopped_at, duration_ms, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-        params![
-            session.id,
-            session.title,
-            session.status,
-            session.capture_mic as i64,
-            session.capture_system as i64,
-            session.started_at,
-            session.stopped_at,
-            session.duration_ms,
-            session.created_at,
-            session.updated_at,
-        ],
+        "UPDATE messages SET tool_return_path = ?2 WHERE id = ?1",
+        params![message_id, path],
     )?;
     Ok(())
 }
 
-/// Update meeting session status, stopped_at, duration_ms, and updated_at
-pub fn update_meeting_session_status(
+/// `(tool_return, tool_return_path)` of a message, None if it doesn't exist.
+pub fn get_message_tool_return(
     conn: &Connection,
-    id: &str,
-    status: &str,
-    stopped_at: Option<i64>,
-    duration_ms: Option<i64>,
-) -> Result<()> {
-    let now = chrono::Utc::now().timestamp();
-    conn.execute(
-        "UPDATE meeting_sessions SET status = ?1, stopped_at = COALESCE(?2, stopped_at), duration_ms = COALESCE(?3, duration_ms), updated_at = ?4 WHERE id = ?5",
-        params![status, stopped_at, duration_ms, now, id],
+    message_id: i64,
+) -> Result<Option<(Option<String>, Option<String>)>> {
+    conn.query_row(
+        "SELECT tool_return, tool_return_path FROM messages WHERE id = ?1",
+        params![message_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+/// Saved full tool output paths of a task's messages.
+pub fn list_tool_return_paths(conn: &Connection, task_id: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT tool_return_path FROM messages
+         WHERE task_id = ?1 AND tool_return_path IS NOT NULL",
     )?;
-    Ok(())
+    let rows = stmt.query_map(params![task_id], |row| row.get(0))?;
+    rows.collect()
 }
 
-/// Update meeting session title and updated_at.
-pub fn update_meeting_session_title(
+/// How many messages point at a saved tool output (`copy_messages` shares them).
+pub fn tool_return_path_ref_count(conn: &Connection, path: &str) -> Result<i64> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM messages WHERE tool_return_path = ?1",
+        params![path],
+        |row| row.get(0),
+    )
+}
+
+#[allow(dead_code)]
+pub fn save_message_attachments(
     conn: &Connection,
-    id: &str,
-    title: Option<&str>,
+    task_id: &str,
+    message_id: i64,
+    attachments: &[AttachmentRecord],
 ) -> Result<()> {
+    if attachments.is_empty() {
+        return Ok(());
+    }
     let now = chrono::Utc::now().timestamp();
-    conn.execute(
-        "UPDATE meeting_sessions SET title = ?1, updated_at = ?2 WHERE id = ?3",
-        params![title, now, id],
-    )?;
+    for attachment in attachments {
+        conn.execute(
+            "INSERT OR REPLACE INTO message_attachments
+             (id, task_id, message_id, file_name, mime_type, relative_path, byte_size, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                attachment.id,
+                task_id,
+                message_id,
+                attachment.file_name,
+                attachment.mime_type,
+                attachment.relative_path,
+                attachment.byte_size,
+                now
+            ],
+        )?;
+    }
     Ok(())
 }
 
-/// List all meeting sessions ordered by created_at DESC
-pub fn list_meeting_sessions(conn: &Connection) -> Result<Vec<MeetingSessionRecord>> {
-    let mut stmt = conn.prepare_cached(
-        "SELECT id, title, status, capture_mic, capture_system, started_at, stopped_at, duration_ms, created_at, updated_at
-         FROM meeting_sessions ORDER BY created_at DESC",
+pub fn save_pending_attachments(
+    conn: &mut Connection,
+    task_id: &str,
+    attachments: &[AttachmentRecord],
+) -> Result<()> {
+    if attachments.is_empty() {
+        return Ok(());
+    }
+    let now = chrono::Utc::now().timestamp();
+    let tx = conn.transaction()?;
+    for attachment in attachments {
+        tx.execute(
+            "INSERT OR REPLACE INTO pending_attachments
+             (id, task_id, file_name, mime_type, relative_path, byte_size, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                attachment.id,
+                task_id,
+                attachment.file_name,
+                attachment.mime_type,
+                attachment.relative_path,
+                attachment.byte_size,
+                now
+            ],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+pub fn get_pending_attachments(conn: &Connection, task_id: &str) -> Result<Vec<AttachmentRecord>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, file_name, mime_type, relative_path, byte_size
+         FROM pending_attachments WHERE task_id = ?1 ORDER BY created_at ASC",
     )?;
-    let rows = stmt.query_map([], |row| {
-        Ok(MeetingSessionRecord {
+    let rows = stmt.query_map(params![task_id], |row| {
+        Ok(AttachmentRecord {
             id: row.get(0)?,
-            title: row.get(1)?,
-            status: row.get(2)?,
-            capture_mic: row.get::<_, i64>(3)? != 0,
-            capture_system: row.get::<_, i64>(4)? != 0,
-            started_at: row.get(5)?,
-            stopped_at: row.get(6)?,
-            duration_ms: row.get(7)?,
-            created_at: row.get(8)?,
-            updated_at: row.get(9)?,
+            file_name: row.get(1)?,
+            mime_type: row.get(2)?,
+            relative_path: row.get(3)?,
+            byte_size: row.get(4)?,
         })
     })?;
     rows.collect()
 }
 
-/// Fetch a single meeting session record by id.
-pub fn get_meeting_session(conn: &Connection, id: &str) -> Result<Option<MeetingSessionRecord>> {
-    let mut stmt = conn.prepare_cached(
-        "SELECT id, title, status, capture_mic, capture_system, started_at, stopped_at, duration_ms, created_at, updated_at
-         FROM meeting_sessions WHERE id = ?1",
+#[allow(dead_code)]
+pub fn clear_pending_attachments(conn: &Connection, task_id: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM pending_attachments WHERE task_id = ?1",
+        params![task_id],
     )?;
-    let mut rows = stmt.query(params![id])?;
-    if let Some(row) = rows.next()? {
-        Ok(Some(MeetingSessionRecord {
-            id: row.get(0)?,
-            title: row.get(1)?,
-            status: row.get(2)?,
-            capture_mic: row.get::<_, i64>(3)? != 0,
-            capture_system: row.get::<_, i64>(4)? != 0,
-            started_at: row.get(5)?,
-            stopped_at: row.get(6)?,
-            duration_ms: row.get(7)?,
-            created_at: row.get(8)?,
-            updated_at: row.get(9)?,
-        }))
-    } else {
-        Ok(None)
+    Ok(())
+}
+
+/// Drop pending uploads that were claimed by a specific message.
+pub fn remove_pending_attachments(
+    conn: &Connection,
+    task_id: &str,
+    ids: &[String],
+) -> Result<usize> {
+    let mut removed = 0;
+    for id in ids {
+        removed += conn.execute(
+            "DELETE FROM pending_attachments WHERE task_id = ?1 AND id = ?2",
+            params![task_id, id],
+        )?;
     }
+    Ok(removed)
 }
 
-/// Get all segments for a meeting session ordered by start_ms ASC
-pub fn get_meeting_segments(
+pub fn get_message_attachments(
     conn: &Connection,
-    session_id: &str,
-) -> Result<Vec<MeetingSegmentRecord>> {
+    task_id: &str,
+) -> Result<HashMap<i64, Vec<AttachmentRecord>>> {
     let mut stmt = conn.prepare_cached(
-        "SELECT id, session_id, text, start_ms, end_ms, speaker, created_at
-         FROM meeting_segments WHERE session_id = ?1 ORDER BY start_ms ASC",
+        "SELECT message_id, id, file_name, mime_type, relative_path, byte_size
+         FROM message_attachments WHERE task_id = ?1 ORDER BY message_id ASC, rowid ASC",
     )?;
-    let rows = stmt.query_map(params![session_id], |row| {
-        Ok(MeetingSegmentRecord {
-            id: row.get(0)?,
-            session_id: row.get(1)?,
-            text: row.get(2)?,
-            start_ms: row.get(3)?,
-            end_ms: row.get(4)?,
-            speaker: row.get(5)?,
-            created_at: row.get(6)?,
-        })
+    let mut map: HashMap<i64, Vec<AttachmentRecord>> = HashMap::new();
+    let rows = stmt.query_map(params![task_id], |row| {
+        let message_id: i64 = row.get(0)?;
+        let attachment = AttachmentRecord {
+            id: row.get(1)?,
+            file_name: row.get(2)?,
+            mime_type: row.get(3)?,
+            relative_path: row.get(4)?,
+            byte_size: row.get(5)?,
+        };
+        Ok((message_id, attachment))
     })?;
-    rows.collect()
+    for row in rows {
+        let (message_id, attachment) = row?;
+        map.entry(message_id).or_default().push(attachment);
+    }
+    Ok(map)
 }
 
-/// Delete all segments for a meeting session.
-pub fn delete_meeting_segments_for_session(conn: &Connection, session_id: &str) -> Result<()> {
-    conn.execute(
-        "DELETE FROM meeting_segments WHERE session_id = ?1",
-        params![session_id],
+#[allow(dead_code)]
+pub fn list_attachment_relative_paths(conn: &Connection, task_id: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT relative_path FROM pending_attachments WHERE task_id = ?1
+         UNION ALL
+         SELECT relative_path FROM message_attachments WHERE task_id = ?1",
     )?;
-    Ok(())
+    let rows = stmt.query_map(params![task_id], |row| row.get(0))?;
+    rows.collect()
 }
 
-/// Save a transcription segment and return the inserted row ID
-pub fn save_meeting_segment(
+#[allow(dead_code)]
+pub fn attachment_ref_count(
     conn: &Connection,
-    session_id: &str,
-    text: &str,
-    start_ms: i64,
-    end_ms: i64,
-    speaker: Option<&str>,
+    relative_path: &str,
+    exclude_task_id: Option<&str>,
 ) -> Result<i64> {
-    let now = chrono::Utc::now().timestamp();
-    conn.execute(
-        "INSERT INTO meeting_segments (session_id, text, start_ms, end_ms, speaker, created_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-        params![session_id, text, start_ms, end_ms, speaker, now],
+    if let Some(exclude) = exclude_task_id {
+        conn.query_row(
+            "SELECT COUNT(*) FROM (
+                SELECT task_id FROM pending_attachments WHERE relative_path = ?1
+                UNION ALL
+                SELECT task_id FROM message_attachments WHERE relative_path = ?1
+            ) WHERE task_id != ?2",
+            params![relative_path, exclude],
+            |row| row.get(0),
+        )
+    } else {
+        conn.query_row(
+            "SELECT COUNT(*) FROM (
+                SELECT task_id FROM pending_attachments WHERE relative_path = ?1
+                UNION ALL
+                SELECT task_id FROM message_attachments WHERE relative_path = ?1
+            )",
+            params![relative_path],
+            |row| row.get(0),
+        )
+    }
+}
+
+/// Load all messages for a task
+pub fn get_messages(conn: &Connection, task_id: &str) -> Result<Vec<serde_json::Value>> {
+    let attachments_by_message = get_message_attachments(conn, task_id).unwrap_or_default();
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, message_type, content, reasoning, tool_name, tool_arguments, tool_return, timestamp, seq,
+                tool_return_path IS NOT NULL
+         FROM messages WHERE task_id = ?1 ORDER BY seq ASC, id ASC",
     )?;
-    Ok(conn.last_insert_rowid())
+    let rows = stmt.query_map(params![task_id], message_from_row)?;
+
+    let mut messages = Vec::new();
+    for row in rows {
+        let (message_id, mut message) = row?;
+        if let Some(attachments) = attachments_by_message.get(&message_id) {
+            message["attachments"] = serde_json::json!(attachments);
+        }
+        messages.push(message);
+    }
+    Ok(messages)
 }
 
-/// Delete a meeting session (segments auto-deleted via CASCADE)
-pub fn delete_meeting_session(conn: &Connection, id: &str) -> Result<()> {
-    conn.execute("DELETE FROM meeting_sessions WHERE id = ?1", params![id])?;
+/// A window of a task's history, oldest message first.
+#[derive(Debug, Clone)]
+pub struct MessagePage {
+    pub messages: Vec<serde_json::Value>,
+    /// Older messages exist before this page
+    pub has_more: bool,
+    /// Id of the oldest message in the page, the `before_id` for the next page
+    pub earliest_id: Option<i64>,
+}
 
-    // Best-effort cleanup of semantic search rows (no FK constraints).
-    // Notes/meetings are indexed under entity_type = 'note'.
-    conn.execute(
-        "DELETE FROM semantic_chunks WHERE entity_type = 'note' AND entity_id = ?1",
-        params![id],
-    )
-    .ok();
-    conn.execute(
-        "DELETE FROM semantic_fts WHERE entity_type = 'note' AND entity_id = ?1",
-        params![id],
+/// The `limit` most recent messages older than `before_id` (or the latest when None).
+pub fn get_messages_page(
+    conn: &Connection,
+    task_id: &str,
+    limit: usize,
+    before_id: Option<i64>,
+) -> Result<MessagePage> {
+    let attachments_by_message = get_message_attachments(conn, task_id).unwrap_or_default();
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, message_type, content, reasoning, tool_name, tool_arguments, tool_return, timestamp, seq,
+                tool_return_path IS NOT NULL
+         FROM messages
+         WHERE task_id = ?1
+           AND (?2 IS NULL OR (seq, id) < (SELECT seq, id FROM messages WHERE id = ?2))
+         ORDER BY seq DESC, id DESC
+         LIMIT ?3",
+    )?;
+    // One extra row tells whether anything older remains.
+    let rows = stmt.query_map(
+        params![task_id, before_id, limit as i64 + 1],
+        message_from_row,
+    )?;
+
+    let mut page = Vec::new();
+    for row in rows {
+        page.push(row?);
+    }
+    let has_more = page.len() > limit;
+    page.truncate(limit);
+    page.reverse();
+    let earliest_id = page.first().map(|(id, _)| *id);
+    let messages = page
+        .into_iter()
+        .map(|(message_id, mut message)| {
+            if let Some(attachments) = attachments_by_message.get(&message_id) {
+                message["attachments"] = serde_json::json!(attachments);
+            }
+            message
+        })
+        .collect();
+    Ok(MessagePage {
+        messages,
+        has_more,
+        earliest_id,
+    })
+}
+
+pub fn has_user_message(conn: &Connection, task_id: &str, content: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM messages WHERE task_id = ?1 AND message_type = 'user_message' AND content = ?2)",
+        params![task_id, content],
+        |row| row.get(0),
     )
-    .ok();
+}
 
-    Ok(())
+fn message_from_row(row: &rusqlite::Row<'_>) -> Result<(i64, serde_json::Value)> {
+    let message_id: i64 = row.get(0)?;
+    let message_type: String = row.get(1)?;
+    let content: Option<String> = row.get(2)?;
+    let reasoning: Option<String> = row.get(3)?;
+    let tool_name: Option<String> = row.get(4)?;
+    let tool_arguments: Option<String> = row.get(5)?;
+    let tool_return: Option<String> = row.get(6)?;
+    let timestamp: String = row.get(7)?;
+    let seq: Option<i64> = row.get(8)?;
+    let tool_return_truncated: bool = row.get(9)?;
+
+    // Build tool_call object if tool_name exists
+    let tool_call = tool_name.as_ref().map(|name| {
+        serde_json::json!({
+            "name": name,
+            "arguments": tool_arguments
+        })
+    });
+
+    Ok((
+        message_id,
+        serde_json::json!({
+            "id": message_id,
+            "message_type": message_type,
+            "content": content,
+            "reasoning": reasoning,
+            "tool_call": tool_call,
+            "tool_return": tool_return,
+            "tool_return_truncated": tool_return_truncated,
+            "timestamp": timestamp,
+            "seq": seq
+        }),
+    ))
 }
 
-/// Update a session's updated_at field to "now".
-pub fn touch_meeting_session_updated_at(conn: &Connection, id: &str) -> Result<()> {
-    let now = chrono::Utc::now().timestamp();
-    conn.execute(
-        "UPDATE meeting_sessions SET updated_at = ?1 WHERE id = ?2",
-        params![now, id],
-    )?;
-    Ok(())
+const TASK_COLUMNS: &str = "id, agent_id, codex_account_id, model, prompt, project_path, worktree_path, branch, context_id, status, status_state, cost, created_at, updated_at, title_summary, agent_session_id, total_tokens, context_window, claude_runtime, claude_team_name, claude_agent_name, codex_mode, model_applied, base_branch, branch_ahead, branch_behind, git_hooks, pr_number, pr_url, pr_state, pr_checks, archived, pinned, project_key, sparse_worktree";
+
+fn task_from_row(row: &rusqlite::Row<'_>) -> Result<TaskRecord> {
+    Ok(TaskRecord {
+        id: row.get(0)?,
+        agent_id: row.get(1)?,
+        codex_account_id: row.get(2)?,
+        model: row.get(3)?,
+        prompt: row.get(4)?,
+        project_path: row.get(5)?,
+        project_key: row.get(33)?,
+        worktree_path: row.get(6)?,
+        branch: row.get(7)?,
+        context_id: row.get(8)?,
+        status: row.get(9)?,
+        status_state: row.get(10)?,
+        cost: row.get(11)?,
+        created_at: row.get(12)?,
+        updated_at: row.get(13)?,
+        title_summary: row.get(14)?,
+        agent_session_id: row.get(15)?,
+        total_tokens: row.get(16)?,
+        context_window: row.get(17)?,
+        claude_runtime: row.get(18)?,
+        claude_team_name: row.get(19)?,
+        claude_agent_name: row.get(20)?,
+        codex_mode: row.get(21)?,
+        model_applied: row.get(22)?,
+        base_branch: row.get(23)?,
+        branch_ahead: row.get(24)?,
+        branch_behind: row.get(25)?,
+        git_hooks: row.get(26)?,
+        pr_number: row.get(27)?,
+        pr_url: row.get(28)?,
+        pr_state: row.get(29)?,
+        pr_checks: row.get(30)?,
+        archived: row.get(31)?,
+        pinned: row.get(32)?,
+        sparse_worktree: row.get(34)?,
+    })
 }
 
-/// Optimize database and checkpoint WAL on shutdown
-pub fn optimize_and_shutdown(conn: &Connection) -> Result<()> {
-    // Let SQLite analyze and optimize based on usage patterns
-    conn.execute("PRAGMA optimize", [])?;
-    // Merge WAL back into main database file
-    conn.execute("PRAGMA wal_checkpoint(PASSIVE)", [])?;
-    Ok(())
+/// All tasks, pinned first, then most recently updated.
+pub fn list_tasks(conn: &Connection) -> Result<Vec<TaskRecord>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM tasks ORDER BY pinned DESC, updated_at DESC, created_at DESC",
+        TASK_COLUMNS
+    ))?;
+    let tasks = stmt.query_map([], task_from_row)?;
+    tasks.collect()
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::time::{SystemTime, UNIX_EPOCH};
+/// Tasks whose `project_key` is `project_key`, in `list_tasks` order.
+pub fn list_tasks_by_project(conn: &Connection, project_key: &str) -> Result<Vec<TaskRecord>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM tasks WHERE project_key = ?1 ORDER BY pinned DESC, updated_at DESC, created_at DESC",
+        TASK_COLUMNS
+    ))?;
+    let tasks = stmt.query_map(params![project_key], task_from_row)?;
+    tasks.collect()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownProject {
+    pub project_key: String,
+    pub name: String,
+    pub task_count: i64,
+    pub last_activity: i64,
+}
+
+/// Distinct projects of unarchived tasks, most recently active first.
+pub fn list_known_projects(conn: &Connection) -> Result<Vec<KnownProject>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT project_key, COUNT(*), MAX(updated_at) FROM tasks
+         WHERE project_key IS NOT NULL AND archived = 0
+         GROUP BY project_key
+         ORDER BY MAX(updated_at) DESC",
+    )?;
+    let projects = stmt.query_map([], |row| {
+        let project_key: String = row.get(0)?;
+        let name = std::path::Path::new(&project_key)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(&project_key)
+            .to_string();
+        Ok(KnownProject {
+            project_key,
+            name,
+            task_count: row.get(1)?,
+            last_activity: row.get(2)?,
+        })
+    })?;
+    projects.collect()
+}
+
+/// `(id, project_path)` of tasks that have a project path but no `project_key` yet.
+pub fn list_tasks_missing_project_key(conn: &Connection) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, project_path FROM tasks
+         WHERE project_key IS NULL AND project_path IS NOT NULL AND TRIM(project_path) != ''",
+    )?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+pub fn update_task_project_key(conn: &Connection, id: &str, project_key: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE tasks SET project_key = ?1 WHERE id = ?2",
+        params![project_key, id],
+    )?;
+    Ok(())
+}
+
+pub fn get_task(conn: &Connection, id: &str) -> Result<Option<TaskRecord>> {
+    let mut stmt =
+        conn.prepare_cached(&format!("SELECT {} FROM tasks WHERE id = ?1", TASK_COLUMNS))?;
+    stmt.query_row(params![id], task_from_row).optional()
+}
+
+pub fn list_contexts(conn: &Connection) -> Result<Vec<ContextRecord>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, name, description, created_at, updated_at
+         FROM contexts
+         ORDER BY updated_at DESC, created_at DESC",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(ContextRecord {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            description: row.get(2)?,
+            created_at: row.get(3)?,
+            updated_at: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn create_context(
+    conn: &Connection,
+    name: &str,
+    description: Option<&str>,
+) -> Result<ContextRecord> {
+    let id = Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "INSERT INTO contexts (id, name, description, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![id, name, description, now, now],
+    )?;
+    Ok(ContextRecord {
+        id,
+        name: name.to_string(),
+        description: description.map(|s| s.to_string()),
+        created_at: now,
+        updated_at: now,
+    })
+}
+
+pub fn list_codex_accounts(conn: &Connection) -> Result<Vec<CodexAccountRecord>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, label, codex_home, email, plan_type, created_at, updated_at
+         FROM codex_accounts ORDER BY created_at ASC",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(CodexAccountRecord {
+            id: row.get(0)?,
+            label: row.get(1)?,
+            codex_home: row.get(2)?,
+            email: row.get(3)?,
+            plan_type: row.get(4)?,
+            created_at: row.get(5)?,
+            updated_at: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn get_codex_account(conn: &Connection, id: &str) -> Result<Option<CodexAccountRecord>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, label, codex_home, email, plan_type, created_at, updated_at
+         FROM codex_accounts WHERE id = ?1",
+    )?;
+    let mut rows = stmt.query(params![id])?;
+    if let Some(row) = rows.next()? {
+        Ok(Some(CodexAccountRecord {
+            id: row.get(0)?,
+            label: row.get(1)?,
+            codex_home: row.get(2)?,
+            email: row.get(3)?,
+            plan_type: row.get(4)?,
+            created_at: row.get(5)?,
+            updated_at: row.get(6)?,
+        }))
+    } else {
+        Ok(None)
+    }
+}
+
+pub fn insert_codex_account(conn: &Connection, account: &CodexAccountRecord) -> Result<()> {
+    conn.execute(
+        "INSERT INTO codex_accounts (id, label, codex_home, email, plan_type, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            account.id,
+            account.label,
+            account.codex_home,
+            account.email,
+            account.plan_type,
+            account.created_at,
+            account.updated_at
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn update_codex_account_meta(
+    conn: &Connection,
+    id: &str,
+    email: Option<&str>,
+    plan_type: Option<&str>,
+    label: Option<&str>,
+) -> Result<()> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "UPDATE codex_accounts SET email = COALESCE(?1, email), plan_type = COALESCE(?2, plan_type), label = COALESCE(?3, label), updated_at = ?4 WHERE id = ?5",
+        params![email, plan_type, label, now, id],
+    )?;
+    Ok(())
+}
+
+pub fn delete_codex_account(conn: &Connection, id: &str) -> Result<()> {
+    conn.execute("DELETE FROM codex_accounts WHERE id = ?1", params![id])?;
+    Ok(())
+}
+
+/// Get messages as structured MessageRecord for history formatting
+pub fn get_message_records(conn: &Connection, task_id: &str) -> Result<Vec<MessageRecord>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, message_type, content, reasoning, tool_name, tool_arguments, tool_return, timestamp
+         FROM messages WHERE task_id = ?1 ORDER BY seq ASC, id ASC",
+    )?;
+    let rows = stmt.query_map(params![task_id], |row| {
+        Ok(MessageRecord {
+            id: row.get(0)?,
+            message_type: row.get(1)?,
+            content: row.get(2)?,
+            reasoning: row.get(3)?,
+            tool_name: row.get(4)?,
+            tool_arguments: row.get(5)?,
+            tool_return: row.get(6)?,
+            timestamp: row.get(7)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Format conversation history as a context string for agent injection
+/// Includes full tool call details with arguments and returns
+pub fn format_conversation_history(
+    messages: &[MessageRecord],
+    original_prompt: Option<&str>,
+) -> String {
+    let mut output = String::new();
+
+    // Include original task prompt if available
+    if let Some(prompt) = original_prompt {
+        output.push_str("[Original Task]\n");
+        output.push_str(prompt);
+        output.push_str("\n\n");
+    }
+
+    output.push_str("[Previous Conversation]\n\n");
+
+    for msg in messages {
+        match msg.message_type.as_str() {
+            "user_message" => {
+                if let Some(ref content) = msg.content {
+                    output.push_str("User: ");
+                    output.push_str(content);
+                    output.push_str("\n\n");
+                }
+            }
+            "assistant_message" => {
+                if let Some(ref content) = msg.content {
+                    output.push_str("Assistant: ");
+                    output.push_str(content);
+                    output.push_str("\n\n");
+                }
+            }
+            PARTIAL_MESSAGE_TYPE => {
+                if let Some(ref content) = msg.content {
+                    output.push_str("Assistant (incomplete, interrupted): ");
+                    output.push_str(content);
+                    output.push_str("\n\n");
+                }
+            }
+            "reasoning_message" => {
+                if let Some(ref reasoning) = msg.reasoning {
+                    output.push_str("[Reasoning] ");
+                    // Truncate long reasoning to save context (safe for UTF-8)
+                    let prefix = safe_prefix(reasoning, 500);
+                    output.push_str(prefix);
+                    if reasoning.chars().count() > 500 {
+                        output.push_str("...");
+                    }
+                    output.push_str("\n\n");
+                }
+            }
+            "tool_call_message" => {
+                if let Some(ref name) = msg.tool_name {
+                    output.push_str(&format!("[Tool Call: {}]\n", name));
+                    if let Some(ref args) = msg.tool_arguments {
+                        // Truncate very long arguments (safe for UTF-8)
+                        output.push_str("Arguments: ");
+                        output.push_str(safe_prefix(args, 1000));
+                        if args.chars().count() > 1000 {
+                            output.push_str("...");
+                        }
+                        output.push('\n');
+                    }
+                    output.push('\n');
+                }
+            }
+            "tool_return_message" => {
+                if let Some(ref result) = msg.tool_return {
+                    output.push_str("[Tool Result]\n");
+                    // Truncate very long tool returns (safe for UTF-8)
+                    output.push_str(safe_prefix(result, 2000));
+                    if result.chars().count() > 2000 {
+                        output.push_str("...");
+                    }
+                    output.push('\n');
+                    output.push('\n');
+                }
+            }
+            _ => {
+                // Skip unknown message types
+            }
+        }
+    }
+
+    output.push_str("---\n\n");
+    output
+}
+
+/// Compact history to fit within a token limit
+/// Keeps recent messages intact and progressively summarizes older messages
+/// Returns (compacted_history, was_truncated)
+pub fn compact_history(
+    messages: &[MessageRecord],
+    original_prompt: Option<&str>,
+    max_chars: usize,
+) -> (String, bool) {
+    // First, try full history
+    let full_history = format_conversation_history(messages, original_prompt);
+    if full_history.len() <= max_chars {
+        return (full_history, false);
+    }
+
+    // Need to compact - keep most recent messages, summarize older ones
+    let mut output = String::new();
+
+    // Always include original prompt
+    if let Some(prompt) = original_prompt {
+        output.push_str("[Original Task]\n");
+        output.push_str(prompt);
+        output.push_str("\n\n");
+    }
+
+    // Calculate how many chars we have for messages
+    let available_chars = max_chars.saturating_sub(output.len() + 100); // Reserve 100 for summary header
+
+    // Split messages: older half gets summarized, newer half kept intact
+    let split_point = messages.len() / 2;
+    let (older, newer) = messages.split_at(split_point);
+
+    // Summarize older messages
+    if !older.is_empty() {
+        let mut tool_calls = 0;
+        let mut assistant_msgs = 0;
+        let mut user_msgs = 0;
+
+        for msg in older {
+            match msg.message_type.as_str() {
+                "tool_call_message" => tool_calls += 1,
+                "assistant_message" => assistant_msgs += 1,
+                "user_message" => user_msgs += 1,
+                _ => {}
+            }
+        }
+
+        output.push_str(&format!(
+            "[Earlier conversation summary: {} user messages, {} assistant responses, {} tool calls]\n\n",
+            user_msgs, assistant_msgs, tool_calls
+        ));
+    }
+
+    // Format newer messages
+    output.push_str("[Recent Conversation]\n\n");
+    let newer_history = format_conversation_history(newer, None);
+
+    // If still too long, truncate from the beginning of newer messages
+    if output.len() + newer_history.len() > available_chars {
+        let remaining = available_chars.saturating_sub(output.len());
+        if remaining > 0 && newer_history.len() > remaining {
+            // Take the last 'remaining' chars
+            let start = newer_history.len() - remaining;
+            output.push_str("...[truncated]...\n\n");
+            output.push_str(&newer_history[start..]);
+        } else if remaining > 0 {
+            output.push_str(&newer_history);
+        }
+    } else {
+        output.push_str(&newer_history);
+    }
+
+    output.push_str("---\n\n");
+    (output, true)
+}
+
+/// Index where the last `keep_turns` turns begin (a turn starts at a user message).
+/// Everything before it is older history that can be summarized.
+pub fn recent_turns_start(messages: &[MessageRecord], keep_turns: usize) -> usize {
+    if keep_turns == 0 {
+        return messages.len();
+    }
+    let mut seen = 0;
+    for (i, msg) in messages.iter().enumerate().rev() {
+        if msg.message_type == "user_message" {
+            seen += 1;
+            if seen == keep_turns {
+                return i;
+            }
+        }
+    }
+    0
+}
+
+/// Split `messages` into consecutive chunks of roughly `max_chars` of formatted history.
+/// A single oversized message gets a chunk of its own.
+pub fn history_chunks(messages: &[MessageRecord], max_chars: usize) -> Vec<&[MessageRecord]> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut size = 0;
+    for (i, msg) in messages.iter().enumerate() {
+        let len = format_conversation_history(std::slice::from_ref(msg), None).len();
+        if size > 0 && size + len > max_chars {
+            chunks.push(&messages[start..i]);
+            start = i;
+            size = 0;
+        }
+        size += len;
+    }
+    if start < messages.len() {
+        chunks.push(&messages[start..]);
+    }
+    chunks
+}
+
+/// Format history with `summary` standing in for everything before `recent`.
+/// Like `compact_history`, the recent part is cut from the front if it exceeds `max_chars`.
+pub fn format_summarized_history(
+    summary: &str,
+    recent: &[MessageRecord],
+    original_prompt: Option<&str>,
+    max_chars: usize,
+) -> String {
+    let mut output = String::new();
+    if let Some(prompt) = original_prompt {
+        output.push_str("[Original Task]\n");
+        output.push_str(prompt);
+        output.push_str("\n\n");
+    }
+    output.push_str("[Earlier conversation summary]\n");
+    output.push_str(summary.trim());
+    output.push_str("\n\n[Recent Conversation]\n\n");
+
+    let recent_history = format_conversation_history(recent, None);
+    let remaining = max_chars.saturating_sub(output.len());
+    if recent_history.len() > remaining {
+        let mut start = recent_history.len() - remaining;
+        while !recent_history.is_char_boundary(start) {
+            start += 1;
+        }
+        output.push_str("...[truncated]...\n\n");
+        output.push_str(&recent_history[start..]);
+    } else {
+        output.push_str(&recent_history);
+    }
+    output.push_str("---\n\n");
+    output
+}
+
+/// Latest cached history summary for a task, as `(last_message_id, summary)`, covering
+/// messages up to and including `up_to_message_id`.
+pub fn get_latest_history_summary(
+    conn: &Connection,
+    task_id: &str,
+    up_to_message_id: i64,
+) -> Result<Option<(i64, String)>> {
+    conn.query_row(
+        "SELECT last_message_id, summary FROM history_summaries
+         WHERE task_id = ?1 AND last_message_id <= ?2
+         ORDER BY last_message_id DESC LIMIT 1",
+        params![task_id, up_to_message_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+/// Cache the rolling summary of a task's history through `last_message_id`.
+pub fn save_history_summary(
+    conn: &Connection,
+    task_id: &str,
+    last_message_id: i64,
+    summary: &str,
+) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO history_summaries (task_id, last_message_id, summary, created_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![
+            task_id,
+            last_message_id,
+            summary,
+            chrono::Utc::now().timestamp()
+        ],
+    )?;
+    Ok(())
+}
+
+/// Insert a new meeting session
+pub fn insert_meeting_session(conn: &Connection, session: &MeetingSessionRecord) -> Result<()> {
+    conn.execute(
+        "INSERT INTO meeting_sessions (id, title, status, capture_mic, capture_system, started_at, stopped_at, duration_ms, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            session.id,
+            session.title,
+            session.status,
+            session.capture_mic as i64,
+            session.capture_system as i64,
+            session.started_at,
+            session.stopped_at,
+            session.duration_ms,
+            session.created_at,
+            session.updated_at,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Update meeting session status, stopped_at, duration_ms, and updated_at
+pub fn update_meeting_session_status(
+    conn: &Connection,
+    id: &str,
+    status: &str,
+    stopped_at: Option<i64>,
+    duration_ms: Option<i64>,
+) -> Result<()> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "UPDATE meeting_sessions SET status = ?1, stopped_at = COALESCE(?2, stopped_at), duration_ms = COALESCE(?3, duration_ms), updated_at = ?4 WHERE id = ?5",
+        params![status, stopped_at, duration_ms, now, id],
+    )?;
+    Ok(())
+}
+
+/// Update meeting session title and updated_at.
+pub fn update_meeting_session_title(
+    conn: &Connection,
+    id: &str,
+    title: Option<&str>,
+) -> Result<()> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "UPDATE meeting_sessions SET title = ?1, updated_at = ?2 WHERE id = ?3",
+        params![title, now, id],
+    )?;
+    Ok(())
+}
+
+/// List all meeting sessions ordered by created_at DESC
+pub fn list_meeting_sessions(conn: &Connection) -> Result<Vec<MeetingSessionRecord>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, title, status, capture_mic, capture_system, started_at, stopped_at, duration_ms, created_at, updated_at
+         FROM meeting_sessions ORDER BY created_at DESC",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(MeetingSessionRecord {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            status: row.get(2)?,
+            capture_mic: row.get::<_, i64>(3)? != 0,
+            capture_system: row.get::<_, i64>(4)? != 0,
+            started_at: row.get(5)?,
+            stopped_at: row.get(6)?,
+            duration_ms: row.get(7)?,
+            created_at: row.get(8)?,
+            updated_at: row.get(9)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Fetch a single meeting session record by id.
+pub fn get_meeting_session(conn: &Connection, id: &str) -> Result<Option<MeetingSessionRecord>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, title, status, capture_mic, capture_system, started_at, stopped_at, duration_ms, created_at, updated_at
+         FROM meeting_sessions WHERE id = ?1",
+    )?;
+    let mut rows = stmt.query(params![id])?;
+    if let Some(row) = rows.next()? {
+        Ok(Some(MeetingSessionRecord {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            status: row.get(2)?,
+            capture_mic: row.get::<_, i64>(3)? != 0,
+            capture_system: row.get::<_, i64>(4)? != 0,
+            started_at: row.get(5)?,
+            stopped_at: row.get(6)?,
+            duration_ms: row.get(7)?,
+            created_at: row.get(8)?,
+            updated_at: row.get(9)?,
+        }))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Get all segments for a meeting session ordered by start_ms ASC
+pub fn get_meeting_segments(
+    conn: &Connection,
+    session_id: &str,
+) -> Result<Vec<MeetingSegmentRecord>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, session_id, text, start_ms, end_ms, speaker, created_at
+         FROM meeting_segments WHERE session_id = ?1 ORDER BY start_ms ASC",
+    )?;
+    let rows = stmt.query_map(params![session_id], |row| {
+        Ok(MeetingSegmentRecord {
+            id: row.get(0)?,
+            session_id: row.get(1)?,
+            text: row.get(2)?,
+            start_ms: row.get(3)?,
+            end_ms: row.get(4)?,
+            speaker: row.get(5)?,
+            created_at: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Delete all segments for a meeting session.
+pub fn delete_meeting_segments_for_session(conn: &Connection, session_id: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM meeting_segments WHERE session_id = ?1",
+        params![session_id],
+    )?;
+    Ok(())
+}
+
+/// Save a transcription segment and return the inserted row ID
+pub fn save_meeting_segment(
+    conn: &Connection,
+    session_id: &str,
+    text: &str,
+    start_ms: i64,
+    end_ms: i64,
+    speaker: Option<&str>,
+) -> Result<i64> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "INSERT INTO meeting_segments (session_id, text, start_ms, end_ms, speaker, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![session_id, text, start_ms, end_ms, speaker, now],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Delete a meeting session (segments auto-deleted via CASCADE)
+pub fn delete_meeting_session(conn: &Connection, id: &str) -> Result<()> {
+    conn.execute("DELETE FROM meeting_sessions WHERE id = ?1", params![id])?;
+
+    // Best-effort cleanup of semantic search rows (no FK constraints).
+    // Notes/meetings are indexed under entity_type = 'note'.
+    conn.execute(
+        "DELETE FROM semantic_chunks WHERE entity_type = 'note' AND entity_id = ?1",
+        params![id],
+    )
+    .ok();
+    conn.execute(
+        "DELETE FROM semantic_fts WHERE entity_type = 'note' AND entity_id = ?1",
+        params![id],
+    )
+    .ok();
+
+    Ok(())
+}
+
+/// Update a session's updated_at field to "now".
+pub fn touch_meeting_session_updated_at(conn: &Connection, id: &str) -> Result<()> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "UPDATE meeting_sessions SET updated_at = ?1 WHERE id = ?2",
+        params![now, id],
+    )?;
+    Ok(())
+}
+
+/// Optimize database and checkpoint WAL on shutdown
+pub fn optimize_and_shutdown(conn: &Connection) -> Result<()> {
+    // Let SQLite analyze and optimize based on usage patterns
+    conn.execute("PRAGMA optimize", [])?;
+    // Merge WAL back into main database file
+    conn.execute("PRAGMA wal_checkpoint(PASSIVE)", [])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn test_init_db_migrates_automations_schema() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "phantom-harness-automations-migration-{suffix}.sqlite"
+        ));
+
+        // Create an older automations schema (missing next_run_at and other newer fields).
+        {
+            let conn = Connection::open(&path).expect("open temp db");
+            conn.execute(
+                "CREATE TABLE automations (
+                    id TEXT PRIMARY KEY,
+                    name TEXT NOT NULL,
+                    enabled INTEGER NOT NULL DEFAULT 1,
+                    agent_id TEXT NOT NULL,
+                    exec_model TEXT NOT NULL,
+                    prompt TEXT NOT NULL,
+                    project_path TEXT,
+                    cron TEXT NOT NULL,
+                    created_at INTEGER NOT NULL,
+                    updated_at INTEGER NOT NULL
+                )",
+                [],
+            )
+            .expect("create old automations table");
+
+            let now = chrono::Utc::now().timestamp();
+            conn.execute(
+                "INSERT INTO automations
+                 (id, name, enabled, agent_id, exec_model, prompt, project_path, cron, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    "auto-1",
+                    "Test automation",
+                    1,
+                    "codex",
+                    "default",
+                    "hello",
+                    Option::<String>::None,
+                    "0 9 * * *",
+                    now,
+                    now
+                ],
+            )
+            .expect("insert old automation row");
+        }
+
+        // init_db should migrate the schema so list_automations can select all expected columns.
+        let conn = init_db(&path).expect("init_db should migrate");
+        let automations = list_automations(&conn).expect("list_automations should work");
+        assert_eq!(automations.len(), 1);
+        assert_eq!(automations[0].id, "auto-1");
+        assert_eq!(automations[0].cron, "0 9 * * *");
+        assert_eq!(automations[0].permission_mode, "default");
+        assert!(
+            automations[0].next_run_at.is_some(),
+            "init_db should backfill next_run_at for enabled automations"
+        );
+
+        // Best-effort cleanup.
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
+
+    #[test]
+    fn test_init_db_migrates_automation_runs_schema() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "phantom-harness-automation-runs-migration-{suffix}.sqlite"
+        ));
+
+        // Create an older automation_runs schema (missing created_at).
+        {
+            let conn = Connection::open(&path).expect("open temp db");
+            conn.execute(
+                "CREATE TABLE automation_runs (
+                    id TEXT PRIMARY KEY,
+                    automation_id TEXT NOT NULL,
+                    task_id TEXT,
+                    scheduled_for INTEGER NOT NULL,
+                    error TEXT
+                )",
+                [],
+            )
+            .expect("create old automation_runs table");
+
+            let now = chrono::Utc::now().timestamp();
+            conn.execute(
+                "INSERT INTO automation_runs
+                 (id, automation_id, task_id, scheduled_for, error)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    "run-1",
+                    "auto-1",
+                    Option::<String>::None,
+                    now,
+                    Option::<String>::None
+                ],
+            )
+            .expect("insert old automation_runs row");
+        }
+
+        // init_db should migrate the schema so list_automation_runs can select created_at.
+        let conn = init_db(&path).expect("init_db should migrate");
+        let runs = list_automation_runs(&conn, 10).expect("list_automation_runs should work");
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].id, "run-1");
+        assert_eq!(runs[0].automation_id, "auto-1");
+
+        // Best-effort cleanup.
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
+
+    #[test]
+    fn test_set_automation_last_error_does_not_clobber_next_run_at() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "phantom-harness-automation-last-error-{suffix}.sqlite"
+        ));
+
+        let conn = init_db(&path).expect("init_db should create schema");
+
+        let now = chrono::Utc::now().timestamp();
+        let automation = AutomationRecord {
+            id: "auto-err-1".to_string(),
+            name: "Test".to_string(),
+            enabled: true,
+            agent_id: "codex".to_string(),
+            exec_model: "default".to_string(),
+            prompt: "hello".to_string(),
+            project_path: None,
+            base_branch: None,
+            plan_mode: false,
+            thinking: true,
+            use_worktree: true,
+            permission_mode: "default".to_string(),
+            reasoning_effort: None,
+            agent_mode: None,
+            codex_mode: None,
+            claude_runtime: None,
+            cron: "0 9 * * *".to_string(),
+            next_run_at: Some(now + 3600),
+            last_run_at: Some(now),
+            last_error: None,
+            created_at: now,
+            updated_at: now,
+        };
+        insert_automation(&conn, &automation).expect("insert automation");
+
+        set_automation_last_error(&conn, "auto-err-1", Some("boom".to_string()), now + 1)
+            .expect("set_automation_last_error should work");
+
+        let updated = get_automation(&conn, "auto-err-1")
+            .expect("get automation")
+            .expect("automation exists");
+        assert_eq!(updated.last_error.as_deref(), Some("boom"));
+        assert_eq!(updated.next_run_at, automation.next_run_at);
+
+        // Best-effort cleanup.
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
+
+    #[test]
+    fn test_text_note_segments_roundtrip() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!("phantom-harness-text-note-{suffix}.sqlite"));
+
+        let conn = init_db(&path).expect("init db");
+        let now = chrono::Utc::now().timestamp();
+
+        let session = MeetingSessionRecord {
+            id: "note-test-1".to_string(),
+            title: Some("Scratch".to_string()),
+            status: "text".to_string(),
+            capture_mic: false,
+            capture_system: false,
+            started_at: None,
+            stopped_at: None,
+            duration_ms: 0,
+            created_at: now,
+            updated_at: now,
+        };
+        insert_meeting_session(&conn, &session).expect("insert meeting session");
+
+        // Write text note content as a single segment.
+        delete_meeting_segments_for_session(&conn, &session.id).expect("clear segments");
+        save_meeting_segment(&conn, &session.id, "hello\nworld", 0, 0, None).expect("save segment");
+        touch_meeting_session_updated_at(&conn, &session.id).expect("touch updated_at");
+
+        let fetched = get_meeting_session(&conn, &session.id)
+            .expect("get session")
+            .expect("session exists");
+        assert_eq!(fetched.status, "text");
+
+        let segs = get_meeting_segments(&conn, &session.id).expect("get segments");
+        assert_eq!(segs.len(), 1);
+        assert_eq!(segs[0].text, "hello\nworld");
+
+        // Best-effort cleanup.
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
+
+    #[test]
+    fn test_chat_window_state_roundtrip() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!("phantom-harness-chat-window-state-{suffix}.sqlite"));
+
+        let conn = init_db(&path).expect("init db");
+        assert_eq!(
+            get_chat_window_state(&conn, "chat-task_1").expect("get state"),
+            None
+        );
+
+        let first = ChatWindowState {
+            x: 10,
+            y: -20,
+            width: 650,
+            height: 750,
+        };
+        save_chat_window_state(&conn, "chat-task_1", &first).expect("save state");
+        let moved = ChatWindowState { x: 400, ..first };
+        save_chat_window_state(&conn, "chat-task_1", &moved).expect("overwrite state");
+        assert_eq!(
+            get_chat_window_state(&conn, "chat-task_1").expect("get state"),
+            Some(moved)
+        );
+
+        delete_chat_window_state(&conn, "chat-task_1").expect("delete state");
+        assert_eq!(
+            get_chat_window_state(&conn, "chat-task_1").expect("get state"),
+            None
+        );
+
+        // Best-effort cleanup.
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
+
+    #[test]
+    fn test_message_seq_orders_interleaved_bursts() {
+        use std::collections::HashSet;
+        use std::sync::{Arc, Barrier};
+
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!("phantom-harness-message-seq-{suffix}.sqlite"));
+
+        let conn = init_db(&path).expect("init db");
+        for task_id in ["task-a", "task-b"] {
+            conn.execute(
+                "INSERT INTO tasks (id, agent_id, model, created_at, updated_at) VALUES (?1, 'codex', 'gpt', 0, 0)",
+                params![task_id],
+            )
+            .expect("insert task");
+        }
+
+        // Writers on their own connections emit call/return pairs with identical
+        // timestamps, as a tool-heavy turn does when streamed and response persistence
+        // overlap, while another task's history grows alongside.
+        const WRITERS: usize = 4;
+        let barrier = Arc::new(Barrier::new(WRITERS));
+        let writers: Vec<_> = (0..WRITERS)
+            .map(|writer| {
+                let path = path.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    let conn = init_db(&path).expect("open db");
+                    barrier.wait();
+                    let mut seqs = Vec::new();
+                    for i in 0..25 {
+                        let name = format!("tool-{writer}-{i}");
+                        for (message_type, tool_return) in
+                            [("tool_call", None), ("tool_return", Some("ok"))]
+                        {
+                            let (_, seq) = save_message_with_seq(
+                                &conn,
+                                "task-a",
+                                message_type,
+                                None,
+                                None,
+                                Some(&name),
+                                None,
+                                tool_return,
+                                "2026-01-01T00:00:00Z",
+                            )
+                            .expect("save message");
+                            seqs.push(seq);
+                        }
+                        if i % 5 == 0 {
+                            save_message_with_seq(
+                                &conn,
+                                "task-b",
+                                "user_message",
+                                Some("hi"),
+                                None,
+                                None,
+                                None,
+                                None,
+                                "2026-01-01T00:00:00Z",
+                            )
+                            .expect("save other task");
+                        }
+                    }
+                    seqs
+                })
+            })
+            .collect();
+        let mut all_seqs = Vec::new();
+        for writer in writers {
+            let seqs = writer.join().expect("writer thread");
+            assert!(
+                seqs.windows(2).all(|pair| pair[0] < pair[1]),
+                "a writer's messages keep their order: {seqs:?}"
+            );
+            all_seqs.extend(seqs);
+        }
+        let unique: HashSet<i64> = all_seqs.iter().copied().collect();
+        assert_eq!(unique.len(), all_seqs.len(), "seqs are unique");
+        all_seqs.sort_unstable();
+        assert_eq!(
+            all_seqs,
+            (1..=200).collect::<Vec<i64>>(),
+            "seqs have no gaps"
+        );
+
+        let seqs = |task_id: &str| -> Vec<i64> {
+            get_messages(&conn, task_id)
+                .expect("get messages")
+                .iter()
+                .map(|message| message["seq"].as_i64().unwrap())
+                .collect()
+        };
+        assert_eq!(seqs("task-a"), (1..=200).collect::<Vec<i64>>());
+        assert_eq!(
+            seqs("task-b"),
+            (1..=WRITERS as i64 * 5).collect::<Vec<i64>>()
+        );
+
+        // Every return comes after its call.
+        let messages = get_messages(&conn, "task-a").expect("get messages");
+        for call in messages.iter().filter(|m| m["message_type"] == "tool_call") {
+            let ret = messages
+                .iter()
+                .find(|m| {
+                    m["message_type"] == "tool_return"
+                        && m["tool_call"]["name"] == call["tool_call"]["name"]
+                })
+                .expect("matching return");
+            assert!(ret["seq"].as_i64() > call["seq"].as_i64());
+        }
+
+        let latest = get_messages_page(&conn, "task-a", 30, None).expect("latest page");
+        assert!(latest.has_more);
+        assert_eq!(latest.messages.len(), 30);
+        assert_eq!(latest.messages[0]["seq"], serde_json::json!(171));
+        assert_eq!(latest.messages[29]["seq"], serde_json::json!(200));
+        let older =
+            get_messages_page(&conn, "task-a", 200, latest.earliest_id).expect("older page");
+        assert!(!older.has_more);
+        assert_eq!(older.messages.len(), 170);
+        assert_eq!(older.messages[0]["seq"], serde_json::json!(1));
+        assert_eq!(older.messages[169]["seq"], serde_json::json!(170));
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
+
+    #[test]
+    fn test_review_comments_resolve_and_task_cleanup() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!("phantom-harness-review-comments-{suffix}.sqlite"));
+
+        let conn = init_db(&path).expect("init db");
+        conn.execute(
+            "INSERT INTO tasks (id, agent_id, model, created_at, updated_at) VALUES ('task-1', 'codex', 'gpt', 0, 0)",
+            [],
+        )
+        .expect("insert task");
+
+        let first = insert_review_comment(&conn, "task-1", "src/lib.rs", "new", 12, "rename this")
+            .expect("add comment");
+        insert_review_comment(&conn, "task-1", "src/lib.rs", "old", 3, "why removed?")
+            .expect("add comment");
+        insert_review_comment(&conn, "task-1", "README.md", "new", 1, "typo").expect("add comment");
+
+        let resolved = set_review_comment_resolved(&conn, first.id, true)
+            .expect("resolve")
+            .expect("comment exists");
+        assert!(resolved.resolved);
+        assert!(resolved.resolved_at.is_some());
+        assert_eq!(resolved.body, "rename this");
+
+        let counts = count_review_comments_by_file(&conn, "task-1").expect("counts");
+        assert_eq!(counts.get("src/lib.rs"), Some(&(2, 1)));
+        assert_eq!(counts.get("README.md"), Some(&(1, 1)));
+
+        let lib_comments =
+            list_review_comments(&conn, "task-1", Some("src/lib.rs")).expect("list comments");
+        let lines: Vec<i64> = lib_comments.iter().map(|c| c.line_number).collect();
+        assert_eq!(lines, vec![3, 12]);
+
+        set_review_file_viewed(&conn, "task-1", "src/lib.rs", "hash-1").expect("mark viewed");
+        set_review_file_viewed(&conn, "task-1", "src/lib.rs", "hash-2").expect("re-mark viewed");
+        set_review_file_viewed(&conn, "task-1", "README.md", "hash-3").expect("mark viewed");
+        clear_review_file_viewed(&conn, "task-1", "README.md").expect("unmark viewed");
+        let viewed = list_review_viewed_files(&conn, "task-1").expect("viewed files");
+        assert_eq!(viewed.len(), 1);
+        assert_eq!(viewed.get("src/lib.rs").map(String::as_str), Some("hash-2"));
+
+        delete_task(&conn, "task-1").expect("delete task");
+        assert!(list_review_comments(&conn, "task-1", None)
+            .expect("list comments")
+            .is_empty());
+        assert!(list_review_viewed_files(&conn, "task-1")
+            .expect("viewed files")
+            .is_empty());
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
+
+    #[test]
+    fn test_task_turn_usage_links_messages() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!("phantom-harness-turn-usage-{suffix}.sqlite"));
+
+        let conn = init_db(&path).expect("init db");
+        conn.execute(
+            "INSERT INTO tasks (id, agent_id, model, created_at, updated_at) VALUES ('task-1', 'codex', 'gpt', 0, 0)",
+            [],
+        )
+        .expect("insert task");
+
+        let ts = "2024-01-01T00:00:00Z";
+        save_message(
+            &conn,
+            "task-1",
+            "user_message",
+            Some("first"),
+            None,
+            None,
+            None,
+            None,
+            ts,
+        )
+        .expect("save message");
+        save_message(
+            &conn,
+            "task-1",
+            "assistant_message",
+            Some("ok"),
+            None,
+            None,
+            None,
+            None,
+            ts,
+        )
+        .expect("save message");
+        insert_task_turn_usage(
+            &conn,
+            "task-1",
+            "gpt-5",
+            100,
+            20,
+            0,
+            10,
+            0.5,
+            0.5,
+            Some("api-key"),
+        )
+        .expect("turn 1");
+        save_message(
+            &conn,
+            "task-1",
+            "user_message",
+            Some("second"),
+            None,
+            None,
+            None,
+            None,
+            ts,
+        )
+        .expect("save message");
+        for _ in 0..3 {
+            save_message(
+                &conn,
+                "task-1",
+                "tool_call_message",
+                None,
+                None,
+                None,
+                None,
+                None,
+                ts,
+            )
+            .expect("save message");
+        }
+        insert_task_turn_usage(
+            &conn,
+            "task-1",
+            "o3",
+            50,
+            0,
+            0,
+            5,
+            0.0,
+            0.25,
+            Some("chatgpt"),
+        )
+        .expect("turn 2");
+
+        let turns = list_task_turn_usage(&conn, "task-1").expect("list turns");
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[0].message_seq, 2);
+        assert_eq!(turns[1].message_seq, 6);
+        assert_eq!(turns[1].model, "o3");
+        assert_eq!(turns[1].cost, 0.0);
+        assert_eq!(turns[1].equivalent_cost, 0.25);
+        assert_eq!(turns[1].auth_method.as_deref(), Some("chatgpt"));
+
+        let (prompt, count) = get_turn_message_summary(&conn, "task-1", 0, 2).expect("summary");
+        assert_eq!((prompt.as_deref(), count), (Some("first"), 2));
+        let (prompt, count) = get_turn_message_summary(&conn, "task-1", 2, 6).expect("summary");
+        assert_eq!((prompt.as_deref(), count), (Some("second"), 4));
+
+        delete_task(&conn, "task-1").expect("delete task");
+        assert!(list_task_turn_usage(&conn, "task-1")
+            .expect("list turns")
+            .is_empty());
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
+
+    #[test]
+    fn test_shared_worktree_refcount() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!("phantom-harness-shared-worktree-{suffix}.sqlite"));
+
+        let conn = init_db(&path).expect("init db");
+        for id in ["codex-task", "claude-task"] {
+            conn.execute(
+                "INSERT INTO tasks (id, agent_id, model, worktree_path, created_at, updated_at) VALUES (?1, 'codex', 'gpt-5', '/ws/repo/otter', 0, 0)",
+                params![id],
+            )
+            .expect("insert task");
+        }
+        assert_eq!(
+            count_tasks_sharing_worktree(&conn, "/ws/repo/otter", "codex-task").unwrap(),
+            1
+        );
+        delete_task(&conn, "claude-task").expect("delete task");
+        assert_eq!(
+            count_tasks_sharing_worktree(&conn, "/ws/repo/otter", "codex-task").unwrap(),
+            0
+        );
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
+
+    #[test]
+    fn test_branch_divergence_reports_changes_only() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!("phantom-harness-divergence-{suffix}.sqlite"));
+
+        let conn = init_db(&path).expect("init db");
+        conn.execute(
+            "INSERT INTO tasks (id, agent_id, model, created_at, updated_at) VALUES ('task-1', 'codex', 'gpt-5', 0, 0)",
+            [],
+        )
+        .expect("insert task");
+        assert!(update_task_branch_divergence(&conn, "task-1", 2, 5).unwrap());
+        assert!(!update_task_branch_divergence(&conn, "task-1", 2, 5).unwrap());
+        assert!(update_task_branch_divergence(&conn, "task-1", 2, 0).unwrap());
+        let task = list_tasks(&conn).unwrap().remove(0);
+        assert_eq!((task.branch_ahead, task.branch_behind), (Some(2), Some(0)));
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
+
+    #[test]
+    fn test_search_messages() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!("phantom-harness-search-{suffix}.sqlite"));
+
+        let conn = init_db(&path).expect("init db");
+        conn.execute(
+            "INSERT INTO tasks (id, agent_id, model, prompt, created_at, updated_at) VALUES ('task-1', 'codex', 'gpt-5', 'Fix the webhook retry logic', 0, 0)",
+            [],
+        )
+        .expect("insert task");
+        let message_id = save_message(
+            &conn,
+            "task-1",
+            "tool_call",
+            Some("Editing <retry> loop"),
+            None,
+            Some("edit"),
+            Some(r#"{"file_path":"src/webhooks/retry.rs"}"#),
+            None,
+            "2026-01-01T00:00:00Z",
+        )
+        .unwrap();
+
+        let hits = search_messages(&conn, "webhook retr", 10).unwrap();
+        assert_eq!(hits.len(), 2);
+        let message_hit = hits.iter().find(|h| h.message_id.is_some()).unwrap();
+        assert_eq!(message_hit.message_type, "tool_call");
+        assert_eq!(
+            message_hit.task_title.as_deref(),
+            Some("Fix the webhook retry logic")
+        );
+        assert!(message_hit.snippet.contains("<mark>webhooks</mark>"));
+        let task_hit = hits.iter().find(|h| h.message_id.is_none()).unwrap();
+        assert_eq!(task_hit.message_type, "task");
+
+        // Quotes and FTS operators are treated as text.
+        assert_eq!(
+            search_messages(&conn, "\"<retry>\" (webhook* -", 10)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(search_messages(&conn, "\" ( *", 10).unwrap().is_empty());
+
+        conn.execute("DELETE FROM messages WHERE id = ?1", params![message_id])
+            .unwrap();
+        conn.execute(
+            "UPDATE tasks SET prompt = 'Something else' WHERE id = 'task-1'",
+            [],
+        )
+        .unwrap();
+        assert!(search_messages(&conn, "retry", 10).unwrap().is_empty());
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_archived_task_keeps_messages() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!("phantom-harness-archive-{suffix}.sqlite"));
+
+        let conn = init_db(&path).expect("init db");
+        conn.execute(
+            "INSERT INTO tasks (id, agent_id, model, created_at, updated_at) VALUES ('task-1', 'codex', 'gpt-5', 0, 0)",
+            [],
+        )
+        .expect("insert task");
+        save_message(
+            &conn,
+            "task-1",
+            "user_message",
+            Some("hello"),
+            None,
+            None,
+            None,
+            None,
+            "2026-01-01T00:00:00Z",
+        )
+        .unwrap();
+        assert!(!list_tasks(&conn).unwrap()[0].archived);
+
+        set_task_archived(&conn, "task-1", true).unwrap();
+        assert!(list_tasks(&conn).unwrap()[0].archived);
+        assert_eq!(get_messages(&conn, "task-1").unwrap().len(), 1);
+
+        set_task_archived(&conn, "task-1", false).unwrap();
+        assert!(!list_tasks(&conn).unwrap()[0].archived);
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_prune_task_messages_keeps_key_messages() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!("phantom-harness-prune-{suffix}.sqlite"));
+
+        let conn = init_db(&path).expect("init db");
+        conn.execute(
+            "INSERT INTO tasks (id, agent_id, model, created_at, updated_at) VALUES ('task-1', 'codex', 'gpt-5', 0, 0)",
+            [],
+        )
+        .expect("insert task");
+        let old = "2025-01-01T00:00:00+00:00";
+        for (kind, content) in [
+            ("user_message", "the prompt"),
+            ("tool_call", "a"),
+            ("tool_return", "b"),
+            ("plan_update", "{}"),
+            ("assistant_message", "interim"),
+            ("user_message", "follow-up"),
+            ("tool_return", "c"),
+            ("assistant_message", "final"),
+        ] {
+            save_message(
+                &conn,
+                "task-1",
+                kind,
+                Some(content),
+                None,
+                None,
+                None,
+                None,
+                old,
+            )
+            .unwrap();
+        }
+        save_message(
+            &conn,
+            "task-1",
+            "tool_return",
+            Some("recent"),
+            None,
+            None,
+            None,
+            None,
+            "2026-06-01T00:00:00Z",
+        )
+        .unwrap();
+
+        let cutoff = "2026-01-01T00:00:00+00:00";
+        assert_eq!(prune_task_messages(&conn, "task-1", cutoff, 2).unwrap(), 2);
+        assert_eq!(prune_task_messages(&conn, "task-1", cutoff, 10).unwrap(), 3);
+        assert_eq!(prune_task_messages(&conn, "task-1", cutoff, 10).unwrap(), 0);
+        let kept: Vec<_> = get_message_records(&conn, "task-1")
+            .unwrap()
+            .into_iter()
+            .map(|m| m.content.unwrap_or_default())
+            .collect();
+        assert_eq!(kept, vec!["the prompt", "{}", "final", "recent"]);
+
+        assert_eq!(delete_task_messages_batch(&conn, "task-1", 3).unwrap(), 3);
+        assert_eq!(delete_task_messages_batch(&conn, "task-1", 3).unwrap(), 1);
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_pr_status_reports_changes_only() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!("phantom-harness-pr-status-{suffix}.sqlite"));
+
+        let conn = init_db(&path).expect("init db");
+        conn.execute(
+            "INSERT INTO tasks (id, agent_id, model, created_at, updated_at) VALUES ('task-1', 'codex', 'gpt-5', 0, 0)",
+            [],
+        )
+        .expect("insert task");
+        update_task_pull_request(
+            &conn,
+            "task-1",
+            12,
+            "https://github.com/o/r/pull/12",
+            "OPEN",
+        )
+        .unwrap();
+        assert!(!update_task_pr_status(&conn, "task-1", "OPEN", None).unwrap());
+        assert!(update_task_pr_status(&conn, "task-1", "OPEN", Some("pending")).unwrap());
+        assert!(!update_task_pr_status(&conn, "task-1", "OPEN", Some("pending")).unwrap());
+        assert!(update_task_pr_status(&conn, "task-1", "MERGED", Some("passing")).unwrap());
+        let task = list_tasks(&conn).unwrap().remove(0);
+        assert_eq!(task.pr_number, Some(12));
+        assert_eq!(task.pr_state.as_deref(), Some("MERGED"));
+        assert_eq!(task.pr_checks.as_deref(), Some("passing"));
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
+
+    #[test]
+    fn test_cost_alerts_fire_once_per_level() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!("phantom-harness-cost-alerts-{suffix}.sqlite"));
+
+        let conn = init_db(&path).expect("init db");
+        assert!(claim_cost_alert(&conn, "task-1", 5.0).expect("first"));
+        assert!(!claim_cost_alert(&conn, "task-1", 5.0).expect("repeat"));
+        assert!(claim_cost_alert(&conn, "task-1", 10.0).expect("next level"));
+        assert!(!claim_cost_alert(&conn, "task-1", 5.0).expect("lower level"));
+        drop(conn);
+
+        // Reopening the database keeps the alert state.
+        let conn = init_db(&path).expect("reopen db");
+        assert!(!claim_cost_alert(&conn, "task-1", 10.0).expect("after restart"));
+        assert!(claim_cost_alert(&conn, "task-2", 5.0).expect("other task"));
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
+
+    #[test]
+    fn test_sparse_worktree_flag_round_trips() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!("phantom-harness-sparse-{suffix}.sqlite"));
+
+        let conn = init_db(&path).expect("init db");
+        conn.execute(
+            "INSERT INTO tasks (id, agent_id, model, created_at, updated_at) VALUES ('full', 'codex', 'gpt-5', 0, 0)",
+            [],
+        )
+        .expect("insert task");
+        let full = get_task(&conn, "full").expect("get").expect("task");
+        assert!(!full.sparse_worktree);
+
+        let sparse = TaskRecord {
+            id: "sparse".to_string(),
+            sparse_worktree: true,
+            ..full
+        };
+        insert_task(&conn, &sparse).expect("insert sparse");
+        drop(conn);
+
+        let conn = init_db(&path).expect("reopen db");
+        let task = get_task(&conn, "sparse").expect("get").expect("task");
+        assert!(task.sparse_worktree);
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_list_tasks_puts_pinned_first() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!("phantom-harness-pinned-{suffix}.sqlite"));
+
+        let conn = init_db(&path).expect("init db");
+        for (id, updated_at) in [("old", 100), ("new", 300), ("mid", 200)] {
+            conn.execute(
+                "INSERT INTO tasks (id, agent_id, model, created_at, updated_at) VALUES (?1, 'codex', 'gpt-5', 0, ?2)",
+                params![id, updated_at],
+            )
+            .expect("insert task");
+        }
+        set_task_pinned(&conn, "old", true).expect("pin");
+        drop(conn);
+
+        // Pin state and ordering survive a restart.
+        let conn = init_db(&path).expect("reopen db");
+        let tasks = list_tasks(&conn).expect("list");
+        let ids: Vec<&str> = tasks.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["old", "new", "mid"]);
+        assert!(tasks[0].pinned && !tasks[1].pinned);
+
+        set_task_pinned(&conn, "old", false).expect("unpin");
+        let ids: Vec<String> = list_tasks(&conn)
+            .expect("list")
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(ids, vec!["new", "mid", "old"]);
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
+
+    #[test]
+    fn test_task_launch_options_round_trip() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!("phantom-harness-launch-options-{suffix}.sqlite"));
+
+        let conn = init_db(&path).expect("init db");
+        conn.execute(
+            "INSERT INTO tasks (id, agent_id, model, created_at, updated_at) VALUES ('task-1', 'codex', 'gpt-5', 0, 0)",
+            [],
+        )
+        .expect("insert task");
+
+        // Tasks created before launch options were recorded read back as unknown.
+        let legacy = get_task_launch_options(&conn, "task-1")
+            .expect("get")
+            .expect("task");
+        assert_eq!(legacy, TaskLaunchOptions::default());
+
+        let options = TaskLaunchOptions {
+            permission_mode: Some("acceptEdits".to_string()),
+            reasoning_effort: Some("high".to_string()),
+            agent_mode: None,
+            plan_mode: true,
+            use_worktree: Some(true),
+        };
+        update_task_launch_options(&conn, "task-1", &options).expect("save");
+        assert_eq!(
+            get_task_launch_options(&conn, "task-1").expect("get"),
+            Some(options)
+        );
+        assert_eq!(
+            get_task_launch_options(&conn, "missing").expect("get"),
+            None
+        );
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
 
     #[test]
-    fn test_init_db_migrates_automations_schema() {
+    fn test_history_chunks_and_summary_cache() {
+        let message = |id: i64, message_type: &str, content: &str| MessageRecord {
+            id,
+            message_type: message_type.to_string(),
+            content: Some(content.to_string()),
+            reasoning: None,
+            tool_name: None,
+            tool_arguments: None,
+            tool_return: None,
+            timestamp: "now".to_string(),
+        };
+        let messages = vec![
+            message(1, "user_message", "first"),
+            message(2, "assistant_message", &"a".repeat(300)),
+            message(3, "user_message", "second"),
+            message(4, "assistant_message", "ok"),
+            message(5, "user_message", "third"),
+        ];
+        assert_eq!(recent_turns_start(&messages, 2), 2);
+        assert_eq!(recent_turns_start(&messages, 5), 0);
+        assert_eq!(recent_turns_start(&messages, 0), 5);
+
+        let chunks = history_chunks(&messages, 200);
+        let ids: Vec<Vec<i64>> = chunks
+            .iter()
+            .map(|chunk| chunk.iter().map(|m| m.id).collect())
+            .collect();
+        assert_eq!(ids, vec![vec![1], vec![2], vec![3, 4, 5]]);
+
         let suffix = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("time went backwards")
             .as_nanos();
         let mut path = std::env::temp_dir();
-        path.push(format!(
-            "phantom-harness-automations-migration-{suffix}.sqlite"
-        ));
+        path.push(format!("phantom-harness-history-summary-{suffix}.sqlite"));
+        let conn = init_db(&path).expect("init db");
+        save_history_summary(&conn, "task-1", 2, "early").expect("save");
+        save_history_summary(&conn, "task-1", 4, "later").expect("save");
+        assert_eq!(
+            get_latest_history_summary(&conn, "task-1", 3).expect("get"),
+            Some((2, "early".to_string()))
+        );
+        assert_eq!(
+            get_latest_history_summary(&conn, "task-1", 10).expect("get"),
+            Some((4, "later".to_string()))
+        );
+        assert_eq!(
+            get_latest_history_summary(&conn, "task-1", 1).expect("get"),
+            None
+        );
+        delete_task(&conn, "task-1").expect("delete");
+        assert_eq!(
+            get_latest_history_summary(&conn, "task-1", 10).expect("get"),
+            None
+        );
 
-        // Create an older automations schema (missing next_run_at and other newer fields).
-        {
-            let conn = Connection::open(&path).expect("open temp db");
-            conn.execute(
-                "CREATE TABLE automations (
-                    id TEXT PRIMARY KEY,
-                    name TEXT NOT NULL,
-                    enabled INTEGER NOT NULL DEFAULT 1,
-                    agent_id TEXT NOT NULL,
-                    exec_model TEXT NOT NULL,
-                    prompt TEXT NOT NULL,
-                    project_path TEXT,
-                    cron TEXT NOT NULL,
-                    created_at INTEGER NOT NULL,
-                    updated_at INTEGER NOT NULL
-                )",
-                [],
-            )
-            .expect("create old automations table");
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
 
-            let now = chrono::Utc::now().timestamp();
+    #[test]
+    fn test_tasks_grouped_by_project_key() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!("phantom-harness-project-key-{suffix}.sqlite"));
+
+        let conn = init_db(&path).expect("init db");
+        for (id, key, updated_at, archived) in [
+            ("a1", Some("/src/app"), 10, 0),
+            ("a2", Some("/src/app"), 30, 0),
+            ("a3", Some("/src/app"), 50, 1),
+            ("b1", Some("/src/lib"), 20, 0),
+            ("legacy", None, 40, 0),
+        ] {
             conn.execute(
-                "INSERT INTO automations
-                 (id, name, enabled, agent_id, exec_model, prompt, project_path, cron, created_at, updated_at)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-                params![
-                    "auto-1",
-                    "Test automation",
-                    1,
-                    "codex",
-                    "default",
-                    "hello",
-                    Option::<String>::None,
-                    "0 9 * * *",
-                    now,
-                    now
-                ],
+                "INSERT INTO tasks (id, agent_id, model, project_path, project_key, archived, created_at, updated_at)
+                 VALUES (?1, 'codex', 'gpt-5', '/src/app', ?2, ?3, 0, ?4)",
+                params![id, key, archived, updated_at],
             )
-            .expect("insert old automation row");
+            .expect("insert task");
         }
 
-        // init_db should migrate the schema so list_automations can select all expected columns.
-        let conn = init_db(&path).expect("init_db should migrate");
-        let automations = list_automations(&conn).expect("list_automations should work");
-        assert_eq!(automations.len(), 1);
-        assert_eq!(automations[0].id, "auto-1");
-        assert_eq!(automations[0].cron, "0 9 * * *");
-        assert_eq!(automations[0].permission_mode, "default");
-        assert!(
-            automations[0].next_run_at.is_some(),
-            "init_db should backfill next_run_at for enabled automations"
+        let ids: Vec<String> = list_tasks_by_project(&conn, "/src/app")
+            .expect("list")
+            .into_iter()
+            .map(|task| task.id)
+            .collect();
+        assert_eq!(ids, vec!["a3", "a2", "a1"]);
+
+        let projects = list_known_projects(&conn).expect("projects");
+        assert_eq!(
+            projects,
+            vec![
+                KnownProject {
+                    project_key: "/src/app".to_string(),
+                    name: "app".to_string(),
+                    task_count: 2,
+                    last_activity: 30,
+                },
+                KnownProject {
+                    project_key: "/src/lib".to_string(),
+                    name: "lib".to_string(),
+                    task_count: 1,
+                    last_activity: 20,
+                },
+            ]
         );
 
-        // Best-effort cleanup.
+        assert_eq!(
+            list_tasks_missing_project_key(&conn).expect("missing"),
+            vec![("legacy".to_string(), "/src/app".to_string())]
+        );
+        update_task_project_key(&conn, "legacy", "/src/app").expect("update");
+        assert!(list_tasks_missing_project_key(&conn)
+            .expect("missing")
+            .is_empty());
+        assert_eq!(
+            get_task(&conn, "legacy")
+                .expect("get")
+                .expect("task")
+                .project_key
+                .as_deref(),
+            Some("/src/app")
+        );
+
+        drop(conn);
         let _ = std::fs::remove_file(&path);
         let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
         let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
     }
 
     #[test]
-    fn test_init_db_migrates_automation_runs_schema() {
+    fn test_queued_prompt_moves_after_previous_turn() {
         let suffix = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("time went backwards")
             .as_nanos();
         let mut path = std::env::temp_dir();
-        path.push(format!(
-            "phantom-harness-automation-runs-migration-{suffix}.sqlite"
-        ));
-
-        // Create an older automation_runs schema (missing created_at).
-        {
-            let conn = Connection::open(&path).expect("open temp db");
-            conn.execute(
-                "CREATE TABLE automation_runs (
-                    id TEXT PRIMARY KEY,
-                    automation_id TEXT NOT NULL,
-                    task_id TEXT,
-                    scheduled_for INTEGER NOT NULL,
-                    error TEXT
-                )",
-                [],
+        path.push(format!("phantom-harness-queued-prompt-{suffix}.sqlite"));
+        let conn = init_db(&path).expect("init db");
+        conn.execute(
+            "INSERT INTO tasks (id, agent_id, model, created_at, updated_at) VALUES ('t', 'codex', 'gpt-5', 0, 0)",
+            [],
+        )
+        .unwrap();
+
+        let save = |message_type: &str, content: &str| {
+            save_message(
+                &conn,
+                "t",
+                message_type,
+                Some(content),
+                None,
+                None,
+                None,
+                None,
+                "now",
             )
-            .expect("create old automation_runs table");
+            .expect("save")
+        };
+        save("user_message", "first");
+        let queued = save("user_message", "follow-up");
+        let withdrawn = save("user_message", "never mind");
+        save("assistant_message", "answer");
+
+        assert_eq!(delete_message(&conn, "t", withdrawn).expect("delete"), 1);
+        assert_eq!(
+            move_message_to_end(&conn, "t", queued, "later").expect("move"),
+            1
+        );
+        let contents: Vec<String> = get_message_records(&conn, "t")
+            .expect("messages")
+            .into_iter()
+            .filter_map(|m| m.content)
+            .collect();
+        assert_eq!(contents, vec!["first", "answer", "follow-up"]);
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
 
-            let now = chrono::Utc::now().timestamp();
-            conn.execute(
-                "INSERT INTO automation_runs
-                 (id, automation_id, task_id, scheduled_for, error)
-                 VALUES (?1, ?2, ?3, ?4, ?5)",
-                params![
-                    "run-1",
-                    "auto-1",
-                    Option::<String>::None,
-                    now,
-                    Option::<String>::None
-                ],
-            )
-            .expect("insert old automation_runs row");
-        }
+    #[test]
+    fn test_user_title_survives_ai_summary() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let mut path = std::env::temp_dir();
+        path.push(format!("phantom-harness-rename-{suffix}.sqlite"));
 
-        // init_db should migrate the schema so list_automation_runs can select created_at.
-        let conn = init_db(&path).expect("init_db should migrate");
-        let runs = list_automation_runs(&conn, 10).expect("list_automation_runs should work");
-        assert_eq!(runs.len(), 1);
-        assert_eq!(runs[0].id, "run-1");
-        assert_eq!(runs[0].automation_id, "auto-1");
+        let conn = init_db(&path).expect("init db");
+        conn.execute(
+            "INSERT INTO tasks (id, agent_id, model, created_at, updated_at) VALUES ('task-1', 'codex', 'gpt-5', 0, 0)",
+            [],
+        )
+        .expect("insert task");
 
-        // Best-effort cleanup.
+        assert!(update_task_title_summary(&conn, "task-1", "Fix login bug").expect("summary"));
+        assert!(rename_task(&conn, "task-1", "Auth cleanup").expect("rename"));
+        // A summary that finishes after the rename must not clobber it.
+        assert!(!update_task_title_summary(&conn, "task-1", "Fix the login bug").expect("late"));
+        let task = get_task(&conn, "task-1").expect("get").expect("task");
+        assert_eq!(task.title_summary.as_deref(), Some("Auth cleanup"));
+        assert!(!rename_task(&conn, "missing", "x").expect("rename missing"));
+
+        drop(conn);
         let _ = std::fs::remove_file(&path);
         let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
         let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
     }
 
     #[test]
-    fn test_set_automation_last_error_does_not_clobber_next_run_at() {
+    fn test_cost_ledger_outlives_task() {
         let suffix = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("time went backwards")
             .as_nanos();
         let mut path = std::env::temp_dir();
-        path.push(format!(
-            "phantom-harness-automation-last-error-{suffix}.sqlite"
-        ));
-
-        let conn = init_db(&path).expect("init_db should create schema");
+        path.push(format!("phantom-harness-cost-ledger-{suffix}.sqlite"));
 
-        let now = chrono::Utc::now().timestamp();
-        let automation = AutomationRecord {
-            id: "auto-err-1".to_string(),
-            name: "Test".to_string(),
-            enabled: true,
-            agent_id: "codex".to_string(),
-            exec_model: "default".to_string(),
-            prompt: "hello".to_string(),
-            project_path: None,
-            base_branch: None,
-            plan_mode: false,
-            thinking: true,
-            use_worktree: true,
-            permission_mode: "default".to_string(),
-            reasoning_effort: None,
-            agent_mode: None,
-            codex_mode: None,
-            claude_runtime: None,
-            cron: "0 9 * * *".to_string(),
-            next_run_at: Some(now + 3600),
-            last_run_at: Some(now),
-            last_error: None,
-            created_at: now,
-            updated_at: now,
-        };
-        insert_automation(&conn, &automation).expect("insert automation");
+        let conn = init_db(&path).expect("init db");
+        conn.execute(
+            "INSERT INTO tasks (id, agent_id, model, created_at, updated_at) VALUES ('task-1', 'codex', 'gpt-5', 0, 0)",
+            [],
+        )
+        .expect("insert task");
 
-        set_automation_last_error(&conn, "auto-err-1", Some("boom".to_string()), now + 1)
-            .expect("set_automation_last_error should work");
+        update_task_cost(&conn, "task-1", 1.5).expect("set cost");
+        add_task_cost(&conn, "task-1", 0.25).expect("add cost");
+        update_task_cost(&conn, "task-1", 1.0).expect("lower cost");
+        delete_task(&conn, "task-1").expect("delete task");
 
-        let updated = get_automation(&conn, "auto-err-1")
-            .expect("get automation")
-            .expect("automation exists");
-        assert_eq!(updated.last_error.as_deref(), Some("boom"));
-        assert_eq!(updated.next_run_at, automation.next_run_at);
+        let entries = list_cost_ledger_since(&conn, 0).expect("ledger");
+        let deltas: Vec<f64> = entries.iter().map(|e| e.delta).collect();
+        assert_eq!(deltas, vec![1.5, 0.25, -0.75]);
+        assert!(entries
+            .iter()
+            .all(|e| e.agent_id == "codex" && e.model == "gpt-5"));
 
-        // Best-effort cleanup.
+        drop(conn);
         let _ = std::fs::remove_file(&path);
         let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
         let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
     }
 
     #[test]
-    fn test_text_note_segments_roundtrip() {
+    fn test_concurrent_writers_survive_contention() {
         let suffix = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("time went backwards")
             .as_nanos();
         let mut path = std::env::temp_dir();
-        path.push(format!("phantom-harness-text-note-{suffix}.sqlite"));
+        path.push(format!("phantom-harness-contention-{suffix}.sqlite"));
 
         let conn = init_db(&path).expect("init db");
-        let now = chrono::Utc::now().timestamp();
+        let mode: String = conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .expect("journal mode");
+        assert_eq!(mode.to_lowercase(), "wal");
+        conn.execute(
+            "INSERT INTO tasks (id, agent_id, model, created_at, updated_at) VALUES ('task-1', 'codex', 'gpt-5', 0, 0)",
+            [],
+        )
+        .expect("insert task");
+
+        // Separate connections, like the streaming thread, usage watcher and UI commands.
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let conn = init_db(&path).expect("open db");
+                    for n in 0..50 {
+                        save_message_with_seq(
+                            &conn,
+                            "task-1",
+                            "tool_call",
+                            None,
+                            None,
+                            Some("shell"),
+                            None,
+                            None,
+                            "2026-01-01T00:00:00Z",
+                        )
+                        .expect("save message");
+                        update_task_status(&conn, "task-1", &format!("step {i}/{n}"), "running")
+                            .expect("update status");
+                        add_task_cost(&conn, "task-1", 0.5).expect("add cost");
+                        update_task_token_usage(&conn, "task-1", n, Some(200_000))
+                            .expect("update tokens");
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().expect("writer panicked");
+        }
 
-        let session = MeetingSessionRecord {
-            id: "note-test-1".to_string(),
-            title: Some("Scratch".to_string()),
-            status: "text".to_string(),
-            capture_mic: false,
-            capture_system: false,
-            started_at: None,
-            stopped_at: None,
-            duration_ms: 0,
-            created_at: now,
-            updated_at: now,
-        };
-        insert_meeting_session(&conn, &session).expect("insert meeting session");
+        let seqs: Vec<i64> = get_messages(&conn, "task-1")
+            .expect("messages")
+            .iter()
+            .map(|m| m["seq"].as_i64().unwrap())
+            .collect();
+        assert_eq!(seqs, (1..=400).collect::<Vec<_>>());
+        assert_eq!(get_task_cost(&conn, "task-1").expect("cost"), 200.0);
 
-        // Write text note content as a single segment.
-        delete_meeting_segments_for_session(&conn, &session.id).expect("clear segments");
-        save_meeting_segment(&conn, &session.id, "hello\nworld", 0, 0, None).expect("save segment");
-        touch_meeting_session_updated_at(&conn, &session.id).expect("touch updated_at");
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
 
-        let fetched = get_meeting_session(&conn, &session.id)
-            .expect("get session")
-            .expect("session exists");
-        assert_eq!(fetched.status, "text");
+    #[test]
+    fn test_task_chains_are_taken_once_and_dropped_with_parent() {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "phantom-harness-task-chains-{}.sqlite",
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let conn = init_db(&path).expect("init db");
+        for id in ["parent", "other"] {
+            conn.execute(
+                "INSERT INTO tasks (id, agent_id, model, created_at, updated_at) VALUES (?1, 'codex', 'gpt-5', 0, 0)",
+                params![id],
+            )
+            .unwrap();
+        }
+        insert_task_chain(&conn, "parent", r#"{"prompt":"review"}"#).unwrap();
+        insert_task_chain(&conn, "parent", r#"{"prompt":"docs"}"#).unwrap();
+        insert_task_chain(&conn, "other", r#"{"prompt":"other"}"#).unwrap();
+
+        let chains = take_task_chains(&conn, "parent").unwrap();
+        let payloads: Vec<&str> = chains.iter().map(|c| c.payload.as_str()).collect();
+        assert_eq!(
+            payloads,
+            vec![r#"{"prompt":"review"}"#, r#"{"prompt":"docs"}"#]
+        );
+        assert!(take_task_chains(&conn, "parent").unwrap().is_empty());
+        assert_eq!(list_task_chain_parents(&conn).unwrap(), vec!["other"]);
 
-        let segs = get_meeting_segments(&conn, &session.id).expect("get segments");
-        assert_eq!(segs.len(), 1);
-        assert_eq!(segs[0].text, "hello\nworld");
+        delete_task(&conn, "other").unwrap();
+        assert!(take_task_chains(&conn, "other").unwrap().is_empty());
+        assert!(list_task_chain_parents(&conn).unwrap().is_empty());
 
-        // Best-effort cleanup.
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
+
+    #[test]
+    fn test_copied_messages_survive_source_delete() {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "phantom-harness-copy-messages-{}.sqlite",
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let conn = init_db(&path).expect("init db");
+        for id in ["source", "fork"] {
+            conn.execute(
+                "INSERT INTO tasks (id, agent_id, model, created_at, updated_at) VALUES (?1, 'codex', 'gpt-5', 0, 0)",
+                params![id],
+            )
+            .unwrap();
+        }
+        save_message(
+            &conn,
+            "source",
+            "user_message",
+            Some("fix it"),
+            None,
+            None,
+            None,
+            None,
+            "t1",
+        )
+        .unwrap();
+        let done_id = save_message(
+            &conn,
+            "source",
+            "assistant_message",
+            Some("done"),
+            None,
+            None,
+            None,
+            None,
+            "t2",
+        )
+        .unwrap();
+        set_message_tool_return_path(&conn, done_id, "tool-output/full.txt").unwrap();
+
+        assert_eq!(copy_messages(&conn, "source", "fork").unwrap(), 2);
+        assert_eq!(
+            tool_return_path_ref_count(&conn, "tool-output/full.txt").unwrap(),
+            2
+        );
+        delete_task(&conn, "source").unwrap();
+        assert_eq!(
+            list_tool_return_paths(&conn, "fork").unwrap(),
+            vec!["tool-output/full.txt".to_string()]
+        );
+        assert_eq!(
+            tool_return_path_ref_count(&conn, "tool-output/full.txt").unwrap(),
+            1
+        );
+
+        let copied = get_message_records(&conn, "fork").unwrap();
+        let contents: Vec<_> = copied.iter().filter_map(|m| m.content.as_deref()).collect();
+        assert_eq!(contents, vec!["fix it", "done"]);
+        save_message(
+            &conn,
+            "fork",
+            "user_message",
+            Some("next"),
+            None,
+            None,
+            None,
+            None,
+            "t3",
+        )
+        .unwrap();
+        assert_eq!(get_message_records(&conn, "fork").unwrap().len(), 3);
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
+
+    #[test]
+    fn test_scheduled_tasks_advance_and_keep_last_task() {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "phantom-harness-scheduled-tasks-{}.sqlite",
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let conn = init_db(&path).expect("init db");
+        let schedule = |id: &str, cron: Option<&str>, next_run_at: i64| ScheduledTaskRecord {
+            id: id.to_string(),
+            payload: "{}".to_string(),
+            cron: cron.map(str::to_string),
+            run_missed: false,
+            next_run_at: Some(next_run_at),
+            last_run_at: None,
+            last_task_id: None,
+            last_error: None,
+            created_at: 0,
+        };
+        insert_scheduled_task(&conn, &schedule("once", None, 100)).unwrap();
+        insert_scheduled_task(&conn, &schedule("daily", Some("0 9 * * *"), 150)).unwrap();
+        insert_scheduled_task(&conn, &schedule("later", None, 500)).unwrap();
+
+        let due: Vec<String> = list_due_scheduled_tasks(&conn, 200)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(due, vec!["once", "daily"]);
+
+        record_scheduled_task_run(&conn, "once", None, Some(200), Some("task-1"), None).unwrap();
+        // A skipped run keeps the previous run's task.
+        record_scheduled_task_run(&conn, "once", None, None, None, Some("skipped")).unwrap();
+        record_scheduled_task_run(&conn, "daily", Some(1000), Some(200), None, None).unwrap();
+        assert!(list_due_scheduled_tasks(&conn, 200).unwrap().is_empty());
+
+        let once = list_scheduled_tasks(&conn)
+            .unwrap()
+            .into_iter()
+            .find(|s| s.id == "once")
+            .unwrap();
+        assert_eq!(once.next_run_at, None);
+        assert_eq!(once.last_run_at, Some(200));
+        assert_eq!(once.last_task_id.as_deref(), Some("task-1"));
+        assert_eq!(once.last_error.as_deref(), Some("skipped"));
+
+        drop(conn);
         let _ = std::fs::remove_file(&path);
         let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
         let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
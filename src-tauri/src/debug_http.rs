@@ -1,5 +1,6 @@
 use std::io::{Read, Write};
 use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex as StdMutex};
 
 use rusqlite::Connection;
@@ -18,7 +19,11 @@ fn respond_json(mut stream: TcpStream, status: &str, body: serde_json::Value) {
     let _ = stream.write_all(resp.as_bytes());
 }
 
-fn handle_request(stream: TcpStream, db_conn: Arc<StdMutex<Connection>>) {
+fn handle_request(
+    stream: TcpStream,
+    db_conn: Arc<StdMutex<Connection>>,
+    automation_paused: Arc<AtomicBool>,
+) {
     let mut stream = stream;
     let mut buf = [0u8; 8192];
     let Ok(n) = stream.read(&mut buf) else {
@@ -53,7 +58,11 @@ fn handle_request(stream: TcpStream, db_conn: Arc<StdMutex<Connection>>) {
     };
 
     match path_only {
-        "/health" => respond_json(stream, "200 OK", json!({"ok": true})),
+        "/health" => respond_json(
+            stream,
+            "200 OK",
+            json!({"ok": true, "automationPaused": automation_paused.load(Ordering::SeqCst)}),
+        ),
         "/tasks" => {
             let conn = match db_conn.lock() {
                 Ok(c) => c,
@@ -218,7 +227,10 @@ fn handle_request(stream: TcpStream, db_conn: Arc<StdMutex<Connection>>) {
     }
 }
 
-pub fn start_debug_http(db: Arc<StdMutex<Connection>>) -> anyhow::Result<()> {
+pub fn start_debug_http(
+    db: Arc<StdMutex<Connection>>,
+    automation_paused: Arc<AtomicBool>,
+) -> anyhow::Result<()> {
     let port: u16 = std::env::var("PHANTOM_DEBUG_HTTP_PORT")
         .ok()
         .and_then(|s| s.parse().ok())
@@ -230,7 +242,8 @@ pub fn start_debug_http(db: Arc<StdMutex<Connection>>) -> anyhow::Result<()> {
     for stream in listener.incoming() {
         if let Ok(stream) = stream {
             let db_conn = db.clone();
-            std::thread::spawn(move || handle_request(stream, db_conn));
+            let automation_paused = automation_paused.clone();
+            std::thread::spawn(move || handle_request(stream, db_conn, automation_paused));
         }
     }
 
@@ -6,15 +6,15 @@
 //! - Paste into the currently focused text field, or fall back to clipboard
 
 use crate::{
-    audio_capture, local_asr_model, parakeet_model, transcription, whisper_model, AppState,
-    Settings,
+    audio_capture, local_asr_model, parakeet_model, transcription, whisper_model, AppHandle,
+    AppState, Settings,
 };
 
 use serde::Serialize;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{mpsc, Arc, Mutex as StdMutex};
 use std::time::Duration;
-use tauri::{AppHandle, Emitter, Manager};
+use tauri::{Emitter, Manager};
 use transcribe_rs::engines::parakeet::{ParakeetEngine, ParakeetModelParams};
 use transcribe_rs::TranscriptionEngine;
 use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
@@ -248,7 +248,11 @@ impl DictationManager {
         }
     }
 
-    fn emit_status<R: tauri::Runtime>(&self, app: &AppHandle<R>, fn_listener_active: Option<bool>) {
+    fn emit_status<R: tauri::Runtime>(
+        &self,
+        app: &tauri::AppHandle<R>,
+        fn_listener_active: Option<bool>,
+    ) {
         let accessibility_trusted = {
             #[cfg(target_os = "macos")]
             {
@@ -293,7 +297,10 @@ impl DictationManager {
         }
     }
 
-    pub fn start_recording<R: tauri::Runtime>(&mut self, app: &AppHandle<R>) -> Result<(), String> {
+    pub fn start_recording<R: tauri::Runtime>(
+        &mut self,
+        app: &tauri::AppHandle<R>,
+    ) -> Result<(), String> {
         if !matches!(self.state, DictationState::Idle | DictationState::Error) {
             return Err("Dictation is already active".to_string());
         }
@@ -695,7 +702,7 @@ fn pcm_f32_to_wav_i16_le(samples_16k_mono: &[f32], sample_rate: u32) -> Vec<u8>
 }
 
 fn insert_transcript<R: tauri::Runtime>(
-    _app: &AppHandle<R>,
+    _app: &tauri::AppHandle<R>,
     settings: &Settings,
     transcript: &str,
 ) -> Result<DictationOutcome, String> {
@@ -785,7 +792,7 @@ impl DictationService {
 
     pub fn configure<R: tauri::Runtime>(
         &mut self,
-        app: &AppHandle<R>,
+        app: &tauri::AppHandle<R>,
         settings: &Settings,
     ) -> Result<(), String> {
         // UX: default dictation OFF so we don't trigger OS permission prompts on first launch.
@@ -865,7 +872,7 @@ impl DictationService {
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
     pub fn handle_global_shortcut<R: tauri::Runtime>(
         &mut self,
-        app: &AppHandle<R>,
+        app: &tauri::AppHandle<R>,
         shortcut: &GlobalShortcut,
         settings: &Settings,
     ) -> bool {
@@ -889,7 +896,7 @@ impl DictationService {
         true
     }
 
-    pub fn start<R: tauri::Runtime>(&mut self, app: &AppHandle<R>) -> Result<(), String> {
+    pub fn start<R: tauri::Runtime>(&mut self, app: &tauri::AppHandle<R>) -> Result<(), String> {
         if !self.enabled {
             return Err("Dictation is disabled in Notes Settings".to_string());
         }
@@ -906,7 +913,7 @@ impl DictationService {
 
     pub fn stop<R: tauri::Runtime>(
         &mut self,
-        app: &AppHandle<R>,
+        app: &tauri::AppHandle<R>,
         settings: &Settings,
     ) -> Result<(), String> {
         if !matches!(self.mgr.state, DictationState::Listening) {
@@ -1,39 +1,71 @@
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
 
 use chrono::Utc;
-use serenity::all::{ButtonStyle, ComponentInteractionDataKind, Interaction};
+use phantom_harness_backend::cli::PermissionOption;
+use serenity::all::{
+    ButtonStyle, CommandInteraction, ComponentInteraction, ComponentInteractionDataKind,
+    Interaction,
+};
 use serenity::async_trait;
 use serenity::builder::{
-    CreateActionRow, CreateButton, CreateCommand, CreateCommandOption, CreateInteractionResponse,
-    CreateInteractionResponseFollowup, CreateInteractionResponseMessage, CreateMessage,
-    CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, CreateThread,
+    CreateActionRow, CreateAutocompleteResponse, CreateButton, CreateCommand, CreateCommandOption,
+    CreateInteractionResponse, CreateInteractionResponseFollowup, CreateInteractionResponseMessage,
+    CreateMessage, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, CreateThread,
+    EditMessage, EditThread,
 };
-use serenity::http::Http;
+use serenity::http::{Http, HttpError};
 use serenity::model::application::{CommandDataOptionValue, CommandOptionType};
-use serenity::model::channel::{Channel, Message};
-use serenity::model::id::{ChannelId, UserId};
+use serenity::model::channel::{Attachment, Channel, Message};
+use serenity::model::id::{ChannelId, MessageId, RoleId, UserId};
 use serenity::prelude::*;
 use uuid::Uuid;
 
+use crate::async_db::AsyncDb;
 use crate::db;
-use crate::{AppState, PendingDiscordTask, PendingUserInput, Settings};
-use tauri::{AppHandle, Manager};
+use crate::progress::{Phase, ProgressKind};
+use crate::utils::truncate_str;
+use crate::{AppHandle, AppState, PendingDiscordTask, PendingUserInput, Settings};
+use serde::Serialize;
+use tauri::{Emitter, Manager};
 
 use serenity::all::ShardManager;
+use serenity::gateway::{ConnectionStage, GatewayError, ShardStageUpdateEvent};
+
+/// How long a task's questions can be answered from Discord. Later answers are refused and
+/// the question stays pending for the desktop UI.
+pub const DISCORD_INPUT_TIMEOUT: Duration = Duration::from_secs(30 * 60);
 
 #[derive(Clone)]
 pub struct DiscordBotHandle {
     http: Arc<Http>,
     channel_id: ChannelId,
     _bot_user_id: Arc<StdMutex<Option<UserId>>>,
-    shard_manager: Arc<ShardManager>,
+    /// The current client's shards; replaced on each reconnect
+    shard_manager: Arc<Mutex<Arc<ShardManager>>>,
+    health: DiscordHealth,
+    stopped: Arc<AtomicBool>,
+    /// False once the reconnect loop has given up
+    running: Arc<AtomicBool>,
 }
 
 impl DiscordBotHandle {
     pub async fn shutdown(&self) {
-        self.shard_manager.shutdown_all().await;
+        self.stopped.store(true, Ordering::SeqCst);
+        let shard_manager = self.shard_manager.lock().await.clone();
+        shard_manager.shutdown_all().await;
+        self.health.disconnected(None);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub fn status(&self) -> DiscordStatus {
+        self.health.snapshot()
     }
 
     pub fn channel_id(&self) -> ChannelId {
@@ -64,22 +96,137 @@ impl DiscordBotHandle {
         thread_id: ChannelId,
         content: &str,
         components: Vec<CreateActionRow>,
-    ) -> Result<(), String> {
+    ) -> Result<Message, String> {
         thread_id
             .send_message(
                 &self.http,
                 CreateMessage::new().content(content).components(components),
             )
             .await
-            .map_err(|e| format!("Discord thread send_message failed: {e}"))?;
+            .map_err(|e| format!("Discord thread send_message failed: {e}"))
+    }
+
+    /// Disable a permission prompt's buttons after it was answered in Phantom.
+    pub async fn mark_permission_handled(
+        &self,
+        prompt: &DiscordPermissionPrompt,
+    ) -> Result<(), String> {
+        let content = format!("{}\n\n_Already handled in Phantom._", prompt.content);
+        prompt
+            .thread_id
+            .edit_message(
+                &self.http,
+                prompt.message_id,
+                EditMessage::new()
+                    .content(content)
+                    .components(permission_buttons(
+                        &prompt.request_id,
+                        &prompt.options,
+                        true,
+                    )),
+            )
+            .await
+            .map_err(|e| format!("Discord edit_message failed: {e}"))?;
+        Ok(())
+    }
+
+    pub async fn rename_thread(&self, thread_id: ChannelId, name: &str) -> Result<(), String> {
+        thread_id
+            .edit_thread(&self.http, EditThread::new().name(name))
+            .await
+            .map_err(|e| format!("Discord edit_thread failed: {e}"))?;
         Ok(())
     }
 }
 
+/// Delay before the first reconnect after the gateway client stops; doubles per attempt.
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(2);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+/// A connection that stayed up this long starts the backoff over.
+const RECONNECT_RESET_AFTER: Duration = Duration::from_secs(5 * 60);
+/// How often shard runners are checked for heartbeat acks.
+const HEARTBEAT_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Gateway connection health, for the Settings screen and `DiscordStatusChanged`.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscordStatus {
+    pub connected: bool,
+    pub last_error: Option<String>,
+    /// ms since the epoch of the last heartbeat ack
+    pub last_heartbeat: Option<i64>,
+}
+
+#[derive(Clone)]
+struct DiscordHealth {
+    app: AppHandle,
+    status: Arc<StdMutex<DiscordStatus>>,
+}
+
+impl DiscordHealth {
+    fn snapshot(&self) -> DiscordStatus {
+        self.status
+            .lock()
+            .map(|status| status.clone())
+            .unwrap_or_default()
+    }
+
+    /// Apply `f` and emit `DiscordStatusChanged` if the connection state or error changed.
+    fn update(&self, f: impl FnOnce(&mut DiscordStatus)) {
+        let changed = {
+            let Ok(mut status) = self.status.lock() else {
+                return;
+            };
+            let before = (status.connected, status.last_error.clone());
+            f(&mut status);
+            (before != (status.connected, status.last_error.clone())).then(|| status.clone())
+        };
+        if let Some(status) = changed {
+            let _ = self.app.emit("DiscordStatusChanged", &status);
+        }
+    }
+
+    fn connected(&self) {
+        self.update(|status| {
+            status.connected = true;
+            status.last_heartbeat = Some(Utc::now().timestamp_millis());
+        });
+    }
+
+    fn disconnected(&self, error: Option<String>) {
+        self.update(|status| {
+            status.connected = false;
+            if error.is_some() {
+                status.last_error = error;
+            }
+        });
+    }
+}
+
+fn reconnect_delay(attempt: u32) -> Duration {
+    RECONNECT_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(RECONNECT_MAX_DELAY)
+}
+
+/// Errors reconnecting can't fix, like a revoked token or missing intents.
+fn is_fatal_gateway_error(err: &serenity::Error) -> bool {
+    matches!(
+        err,
+        serenity::Error::Gateway(
+            GatewayError::InvalidAuthentication
+                | GatewayError::InvalidGatewayIntents
+                | GatewayError::DisallowedGatewayIntents
+        )
+    )
+}
+
+#[derive(Clone)]
 struct DiscordEventHandler {
     app: AppHandle,
     channel_id: ChannelId,
     bot_user_id: Arc<StdMutex<Option<UserId>>>,
+    health: DiscordHealth,
 }
 
 #[async_trait]
@@ -88,6 +235,7 @@ impl EventHandler for DiscordEventHandler {
         if let Ok(mut guard) = self.bot_user_id.lock() {
             *guard = Some(ready.user.id);
         }
+        self.health.connected();
         println!("[Discord] Bot ready: {}", ready.user.name);
         // Warm cache by fetching channel info
         if let Ok(channel) = self.channel_id.to_channel(&ctx.http).await {
@@ -107,9 +255,9 @@ impl EventHandler for DiscordEventHandler {
                         CreateCommandOption::new(
                             CommandOptionType::String,
                             "project",
-                            "Project name or path",
+                            "Project name or path (defaults to the channel's project)",
                         )
-                        .required(true),
+                        .required(false),
                     )
                     .add_option(
                         CreateCommandOption::new(CommandOptionType::String, "agent", "Agent id")
@@ -118,14 +266,50 @@ impl EventHandler for DiscordEventHandler {
                     .add_option(
                         CreateCommandOption::new(CommandOptionType::String, "model", "Model id")
                             .required(false),
+                    )
+                    .add_option(
+                        CreateCommandOption::new(
+                            CommandOptionType::Attachment,
+                            "image",
+                            "Screenshot or image for the agent",
+                        )
+                        .required(false),
                     );
                 if let Err(err) = guild_id.create_command(&ctx.http, command).await {
                     println!("[Discord] Failed to register /task: {err}");
                 }
+                for kind in [StopKind::Stop, StopKind::Cancel] {
+                    let command = CreateCommand::new(kind.command())
+                        .description(kind.description())
+                        .add_option(
+                            CreateCommandOption::new(
+                                CommandOptionType::String,
+                                "task",
+                                "Task id (defaults to this thread's task)",
+                            )
+                            .required(false)
+                            .set_autocomplete(true),
+                        );
+                    if let Err(err) = guild_id.create_command(&ctx.http, command).await {
+                        println!("[Discord] Failed to register /{}: {err}", kind.command());
+                    }
+                }
             }
         }
     }
 
+    async fn resume(&self, _ctx: Context, _event: serenity::model::event::ResumedEvent) {
+        self.health.connected();
+    }
+
+    async fn shard_stage_update(&self, _ctx: Context, event: ShardStageUpdateEvent) {
+        match event.new {
+            ConnectionStage::Connected => self.health.connected(),
+            ConnectionStage::Disconnected => self.health.disconnected(None),
+            _ => {}
+        }
+    }
+
     async fn message(&self, ctx: Context, msg: Message) {
         if msg.author.bot {
             return;
@@ -134,9 +318,9 @@ impl EventHandler for DiscordEventHandler {
         let state = self.app.state::<crate::AppState>().inner().clone();
         let app = self.app.clone();
 
-        if msg.channel_id == self.channel_id {
-            let settings = state.settings.lock().await.clone();
-            let allowlist = project_allowlist(&settings);
+        let settings = state.settings.lock().await.clone();
+        if self.is_task_channel(&settings, msg.channel_id) {
+            let allowlist = project_choices(&state, &project_allowlist(&settings)).await;
             let bot_user_id = self.bot_user_id.lock().ok().and_then(|g| *g);
             if let Some(bot_user_id) = bot_user_id {
                 let mentioned = msg.mentions.iter().any(|user| user.id == bot_user_id);
@@ -173,26 +357,36 @@ impl EventHandler for DiscordEventHandler {
                         prompt,
                         requester_id: msg.author.id.get(),
                         channel_id: msg.channel_id.get(),
-                        project_path: None,
+                        project_path: channel_project(&settings, msg.channel_id),
                         agent_id: None,
                         model: None,
+                        images: image_attachments(&msg.attachments),
                         created_at: Utc::now().timestamp(),
                         ephemeral: false,
                     };
-                    let (components, truncated) =
-                        build_project_action_rows(&allowlist, &pending_id);
+                    let (content, components) = match &pending.project_path {
+                        Some(path) => (
+                            format!("Pick an agent for this task in `{}`:", project_label(path)),
+                            build_agent_action_rows(&state, &pending_id),
+                        ),
+                        None => {
+                            let (components, truncated) =
+                                build_project_action_rows(&allowlist, &pending_id);
+                            let mut content = "Pick a project for this task:".to_string();
+                            if truncated {
+                                content.push_str(
+                                    " (Too many projects; reply with `project: <name>` to use another.)",
+                                );
+                            }
+                            (content, components)
+                        }
+                    };
                     {
                         let mut pending_guard = state.pending_discord_tasks.lock().await;
                         prune_pending_discord_tasks(&mut pending_guard);
                         pending_guard.insert(pending_id.clone(), pending);
                     }
 
-                    let mut content = "Pick a project for this task:".to_string();
-                    if truncated {
-                        content.push_str(
-                            " (Too many projects; reply with `project: <name>` to use another.)",
-                        );
-                    }
                     let _ = msg
                         .channel_id
                         .send_message(
@@ -296,7 +490,7 @@ impl EventHandler for DiscordEventHandler {
                             .clone()
                             .unwrap_or_else(|| "default".to_string());
                         let (components, truncated) =
-                            build_model_action_rows(&state, &pending_id, &agent_id);
+                            build_model_action_rows(&state, &pending_id, &agent_id).await;
                         let mut content = format!("Select a model for `{}`:", agent_id);
                         if truncated {
                             content.push_str(
@@ -337,6 +531,8 @@ impl EventHandler for DiscordEventHandler {
                         agent_id,
                         project_path,
                         model,
+                        pending_snapshot.requester_id,
+                        pending_snapshot.images,
                     )
                     .await
                     {
@@ -370,15 +566,7 @@ impl EventHandler for DiscordEventHandler {
         }
 
         let thread_id = msg.channel_id.get();
-        let task_id_opt = {
-            let conn = match state.db.lock() {
-                Ok(c) => c,
-                Err(_) => return,
-            };
-            db::get_task_id_for_discord_thread(&conn, thread_id)
-                .ok()
-                .flatten()
-        };
+        let task_id_opt = task_for_thread(&state, thread_id).await;
 
         let Some(task_id) = task_id_opt else {
             return;
@@ -396,17 +584,60 @@ impl EventHandler for DiscordEventHandler {
         };
 
         if let Some(pending_req) = pending {
-            let answers = parse_user_input_answers(&pending_req, content, &pending_req.answers);
-            if let Err(err) = crate::respond_to_user_input_internal(
-                task_id.clone(),
-                pending_req.request_id.clone(),
-                answers,
-                &state,
-                app,
-            )
-            .await
-            {
-                println!("[Discord] Failed to respond to user input: {err}");
+            // Other people's messages neither answer the question nor reach the agent.
+            let roles = msg.member.as_ref().map(|member| member.roles.as_slice());
+            if !user_may_answer(&state, &task_id, msg.author.id, roles.unwrap_or_default()).await {
+                return;
+            }
+            if input_expired(&pending_req) {
+                let _ = msg
+                    .channel_id
+                    .send_message(
+                        &ctx.http,
+                        CreateMessage::new().content(INPUT_EXPIRED_MESSAGE),
+                    )
+                    .await;
+                return;
+            }
+            let reply = parse_user_input_reply(&pending_req, content);
+            let recorded = {
+                let mut guard = state.pending_user_inputs.lock().await;
+                guard
+                    .get_mut(&task_id)
+                    .filter(|pending| pending.request_id == pending_req.request_id)
+                    .map(|pending| {
+                        pending.answers.extend(reply);
+                        (
+                            pending_complete(pending),
+                            build_answers_payload(&pending.answers),
+                        )
+                    })
+            };
+            match recorded {
+                Some((true, answers)) => {
+                    if let Err(err) = crate::respond_to_user_input_internal(
+                        task_id.clone(),
+                        pending_req.request_id.clone(),
+                        answers,
+                        &state,
+                        app,
+                    )
+                    .await
+                    {
+                        println!("[Discord] Failed to respond to user input: {err}");
+                    }
+                }
+                Some((false, _)) => {
+                    let _ = msg
+                        .channel_id
+                        .send_message(
+                            &ctx.http,
+                            CreateMessage::new()
+                                .content("Answer recorded. Please answer the remaining questions."),
+                        )
+                        .await;
+                }
+                None => {}
             }
             return;
         }
@@ -415,6 +646,7 @@ impl EventHandler for DiscordEventHandler {
             task_id,
             content.to_string(),
             None,
+            Vec::new(),
             &state,
             app,
             crate::MessageOrigin::Discord,
@@ -427,12 +659,23 @@ impl EventHandler for DiscordEventHandler {
 
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
         match interaction {
+            Interaction::Autocomplete(command)
+                if StopKind::from_command(&command.data.name).is_some() =>
+            {
+                self.autocomplete_stoppable_tasks(&ctx, &command).await;
+            }
             Interaction::Command(command) => {
+                if let Some(kind) = StopKind::from_command(&command.data.name) {
+                    self.stop_task_command(&ctx, &command, kind).await;
+                    return;
+                }
                 if command.data.name != "task" {
                     return;
                 }
 
-                if command.channel_id != self.channel_id {
+                let state = self.app.state::<crate::AppState>().inner().clone();
+                let settings = state.settings.lock().await.clone();
+                if !self.is_task_channel(&settings, command.channel_id) {
                     let _ = command
                         .create_response(
                             &ctx.http,
@@ -446,23 +689,26 @@ impl EventHandler for DiscordEventHandler {
                     return;
                 }
 
-                let state = self.app.state::<crate::AppState>().inner().clone();
-                let settings = state.settings.lock().await.clone();
-                let allowlist = project_allowlist(&settings);
+                let allowlist = project_choices(&state, &project_allowlist(&settings)).await;
 
                 let mut prompt = None;
                 let mut project = None;
                 let mut agent_id = None;
                 let mut model = None;
+                let mut images = Vec::new();
                 for option in &command.data.options {
-                    if let CommandDataOptionValue::String(value) = &option.value {
-                        match option.name.as_str() {
+                    match &option.value {
+                        CommandDataOptionValue::String(value) => match option.name.as_str() {
                             "prompt" => prompt = Some(value.clone()),
                             "project" => project = Some(value.clone()),
                             "agent" => agent_id = Some(value.clone()),
                             "model" => model = Some(value.clone()),
                             _ => {}
+                        },
+                        CommandDataOptionValue::Attachment(id) => {
+                            images.extend(command.data.resolved.attachments.get(id).cloned());
                         }
+                        _ => {}
                     }
                 }
 
@@ -483,24 +729,13 @@ impl EventHandler for DiscordEventHandler {
                     }
                 };
 
-                let project = match project {
-                    Some(project) if !project.trim().is_empty() => project,
-                    _ => {
-                        let _ = command
-                            .create_response(
-                                &ctx.http,
-                                CreateInteractionResponse::Message(
-                                    CreateInteractionResponseMessage::new()
-                                        .content("Provide a project from the allowlist.")
-                                        .ephemeral(true),
-                                ),
-                            )
-                            .await;
-                        return;
-                    }
+                let project = project.filter(|project| !project.trim().is_empty());
+                let project_path = match project {
+                    Some(project) => resolve_project_match(&allowlist, &project),
+                    None => channel_project(&settings, command.channel_id)
+                        .ok_or_else(|| "Provide a project from the allowlist.".to_string()),
                 };
-
-                let project_path = match resolve_project_match(&allowlist, &project) {
+                let project_path = match project_path {
                     Ok(path) => path,
                     Err(err) => {
                         let _ = command
@@ -541,6 +776,7 @@ impl EventHandler for DiscordEventHandler {
                     project_path: Some(project_path.clone()),
                     agent_id: agent_id.clone(),
                     model: model.clone(),
+                    images: image_attachments(&images),
                     created_at: Utc::now().timestamp(),
                     ephemeral: true,
                 };
@@ -567,6 +803,8 @@ impl EventHandler for DiscordEventHandler {
                             agent_id,
                             project_path,
                             model,
+                            pending.requester_id,
+                            pending.images,
                         )
                         .await;
                         let content = match result {
@@ -612,7 +850,7 @@ impl EventHandler for DiscordEventHandler {
 
                 let agent_id = agent_id.unwrap_or_else(|| "default".to_string());
                 let (components, truncated) =
-                    build_model_action_rows(&state, &pending_id, &agent_id);
+                    build_model_action_rows(&state, &pending_id, &agent_id).await;
                 let mut content = format!("Select a model for `{}`:", agent_id);
                 if truncated {
                     content
@@ -635,7 +873,7 @@ impl EventHandler for DiscordEventHandler {
                 if let Some(action) = parse_task_create_action(custom_id) {
                     let state = self.app.state::<crate::AppState>().inner().clone();
                     let settings = state.settings.lock().await.clone();
-                    let allowlist = project_allowlist(&settings);
+                    let allowlist = project_choices(&state, &project_allowlist(&settings)).await;
                     let selected_value =
                         action.value.clone().or_else(|| match &component.data.kind {
                             ComponentInteractionDataKind::StringSelect { values } => {
@@ -793,7 +1031,7 @@ impl EventHandler for DiscordEventHandler {
 
                     if pending_snapshot.model.is_none() {
                         let (components, truncated) =
-                            build_model_action_rows(&state, &action.pending_id, &agent_id);
+                            build_model_action_rows(&state, &action.pending_id, &agent_id).await;
                         let mut content = format!("Select a model for `{}`:", agent_id);
                         if truncated {
                             content.push_str(
@@ -845,6 +1083,8 @@ impl EventHandler for DiscordEventHandler {
                             agent_id,
                             project_path,
                             model,
+                            pending_snapshot.requester_id,
+                            pending_snapshot.images,
                         )
                         .await;
                         let content = match result {
@@ -866,6 +1106,12 @@ impl EventHandler for DiscordEventHandler {
                     return;
                 }
 
+                if let Some((request_id, option_idx)) = parse_permission_custom_id(custom_id) {
+                    self.permission_button(&ctx, &component, request_id, option_idx)
+                        .await;
+                    return;
+                }
+
                 if !custom_id.starts_with("user_input:") {
                     return;
                 }
@@ -873,15 +1119,7 @@ impl EventHandler for DiscordEventHandler {
                 let thread_id = component.channel_id.get();
 
                 let state = self.app.state::<crate::AppState>().inner().clone();
-                let task_id_opt = {
-                    let conn = match state.db.lock() {
-                        Ok(c) => c,
-                        Err(_) => return,
-                    };
-                    db::get_task_id_for_discord_thread(&conn, thread_id)
-                        .ok()
-                        .flatten()
-                };
+                let task_id_opt = task_for_thread(&state, thread_id).await;
                 let Some(task_id) = task_id_opt else {
                     let _ = component
                         .create_response(
@@ -896,9 +1134,18 @@ impl EventHandler for DiscordEventHandler {
                     return;
                 };
 
-                let Some((request_id, question_id, option_idx)) =
-                    parse_user_input_custom_id(custom_id)
-                else {
+                let parsed = parse_user_input_custom_id(custom_id).and_then(
+                    |(request_id, question_id, option_idx)| {
+                        let option_idx = option_idx.or_else(|| match &component.data.kind {
+                            ComponentInteractionDataKind::StringSelect { values } => {
+                                values.first().and_then(|value| value.parse().ok())
+                            }
+                            _ => None,
+                        })?;
+                        Some((request_id, question_id, option_idx))
+                    },
+                );
+                let Some((request_id, question_id, option_idx)) = parsed else {
                     let _ = component
                         .create_response(
                             &ctx.http,
@@ -912,6 +1159,25 @@ impl EventHandler for DiscordEventHandler {
                     return;
                 };
 
+                let roles = component
+                    .member
+                    .as_ref()
+                    .map(|member| member.roles.as_slice());
+                let user_id = component.user.id;
+                if !user_may_answer(&state, &task_id, user_id, roles.unwrap_or_default()).await {
+                    let _ = component
+                        .create_response(
+                            &ctx.http,
+                            CreateInteractionResponse::Message(
+                                CreateInteractionResponseMessage::new()
+                                    .content(NOT_REQUESTER_MESSAGE)
+                                    .ephemeral(true),
+                            ),
+                        )
+                        .await;
+                    return;
+                }
+
                 let mut pending_guard = state.pending_user_inputs.lock().await;
                 let Some(pending) = pending_guard.get_mut(&task_id) else {
                     let _ = component
@@ -941,6 +1207,20 @@ impl EventHandler for DiscordEventHandler {
                     return;
                 }
 
+                if input_expired(pending) {
+                    let _ = component
+                        .create_response(
+                            &ctx.http,
+                            CreateInteractionResponse::Message(
+                                CreateInteractionResponseMessage::new()
+                                    .content(INPUT_EXPIRED_MESSAGE)
+                                    .ephemeral(true),
+                            ),
+                        )
+                        .await;
+                    return;
+                }
+
                 let Some(question) = pending.questions.iter().find(|q| q.id == question_id) else {
                     let _ = component
                         .create_response(
@@ -1030,145 +1310,634 @@ impl EventHandler for DiscordEventHandler {
     }
 }
 
-/// Extracts a short, friendly identifier from a full task ID.
-/// For IDs like "task-1769976653565-4bddf02d", returns "4bddf02d".
-fn short_task_id(full_id: &str) -> &str {
-    // Split by '-' and get the last part (UUID suffix)
-    full_id.rsplit('-').next().unwrap_or(full_id)
+/// `/stop` ends the task's session; `/cancel` only cancels the current generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StopKind {
+    Stop,
+    Cancel,
 }
 
-fn parse_user_input_answers(
-    pending: &PendingUserInput,
-    content: &str,
-    seed: &HashMap<String, String>,
-) -> serde_json::Value {
-    let mut answers: serde_json::Map<String, serde_json::Value> = serde_json::Map::new();
-    let lines: Vec<&str> = content
-        .lines()
-        .map(|line| line.trim())
-        .filter(|line| !line.is_empty())
-        .collect();
-
-    for (qid, value) in seed {
-        answers.insert(qid.clone(), serde_json::json!({ "answers": [value] }));
-    }
-
-    if pending.questions.len() == 1 {
-        let q = &pending.questions[0];
-        let value = normalize_answer_value(q, content.trim());
-        answers.insert(q.id.clone(), serde_json::json!({ "answers": [value] }));
-        return serde_json::Value::Object(answers);
+impl StopKind {
+    fn from_command(name: &str) -> Option<Self> {
+        match name {
+            "stop" => Some(StopKind::Stop),
+            "cancel" => Some(StopKind::Cancel),
+            _ => None,
+        }
     }
 
-    for line in lines {
-        let mut parts = line.splitn(2, ':');
-        let key = parts.next().unwrap_or("").trim();
-        let value_raw = parts.next().unwrap_or("").trim();
-        if key.is_empty() || value_raw.is_empty() {
-            continue;
-        }
-        if let Some(q) = pending.questions.iter().find(|q| q.id == key) {
-            let value = normalize_answer_value(q, value_raw);
-            answers.insert(q.id.clone(), serde_json::json!({ "answers": [value] }));
+    fn command(self) -> &'static str {
+        match self {
+            StopKind::Stop => "stop",
+            StopKind::Cancel => "cancel",
         }
     }
 
-    if answers.is_empty() {
-        // Fallback: map the entire message to the first question.
-        if let Some(q) = pending.questions.first() {
-            let value = normalize_answer_value(q, content.trim());
-            answers.insert(q.id.clone(), serde_json::json!({ "answers": [value] }));
+    fn description(self) -> &'static str {
+        match self {
+            StopKind::Stop => "Stop a Phantom task and end its session",
+            StopKind::Cancel => "Cancel a Phantom task's current generation",
         }
     }
 
-    serde_json::Value::Object(answers)
-}
-
-fn normalize_answer_value(
-    question: &phantom_harness_backend::cli::UserInputQuestion,
-    raw: &str,
-) -> String {
-    if let Some(options) = question.options.as_ref() {
-        if let Some(opt) = options
-            .iter()
-            .find(|opt| opt.label.eq_ignore_ascii_case(raw))
-        {
-            return opt.label.clone();
+    /// Whether a task in `status_state` has anything for this command to stop.
+    fn applies_to(self, status_state: &str) -> bool {
+        match self {
+            StopKind::Stop => matches!(status_state, "running" | "queued" | "stopping"),
+            StopKind::Cancel => matches!(status_state, "running" | "queued"),
         }
     }
-    raw.to_string()
 }
 
-fn parse_user_input_custom_id(custom_id: &str) -> Option<(String, String, usize)> {
-    let mut parts = custom_id.split(':');
-    let prefix = parts.next()?;
-    if prefix != "user_input" {
-        return None;
+/// Choices offered while typing a task id; Discord shows at most 25.
+const MAX_TASK_CHOICES: usize = 25;
+
+impl DiscordEventHandler {
+    /// The configured channel, or one mapped to a project in `discordChannelProjects`.
+    fn is_task_channel(&self, settings: &Settings, channel_id: ChannelId) -> bool {
+        channel_id == self.channel_id
+            || settings
+                .discord_channel_projects
+                .as_ref()
+                .is_some_and(|projects| projects.contains_key(&channel_id.to_string()))
     }
-    let request_id = parts.next()?.to_string();
-    let question_id = parts.next()?.to_string();
-    let idx = parts.next()?.parse::<usize>().ok()?;
-    Some((request_id, question_id, idx))
-}
 
-fn build_answers_payload(answers: &HashMap<String, String>) -> serde_json::Value {
-    let mut map = serde_json::Map::new();
-    for (qid, value) in answers {
-        map.insert(qid.clone(), serde_json::json!({ "answers": [value] }));
+    /// All tasks, and whether each is in an allowlisted project (Discord may act only on those).
+    async fn tasks_with_access(&self, state: &AppState) -> Vec<(db::TaskRecord, bool)> {
+        let settings = state.settings.lock().await.clone();
+        let allowlist = project_allowlist(&settings);
+        let tasks = state.store.read(db::list_tasks).await.unwrap_or_default();
+        tasks
+            .into_iter()
+            .map(|task| {
+                let allowed = task
+                    .project_path
+                    .as_deref()
+                    .is_some_and(|path| crate::project_path_allowed(&allowlist, path));
+                (task, allowed)
+            })
+            .collect()
     }
-    serde_json::Value::Object(map)
-}
 
-fn pending_complete(pending: &PendingUserInput) -> bool {
-    for q in &pending.questions {
-        if q.options.is_none() && !pending.answers.contains_key(&q.id) {
-            return false;
-        }
-        if q.options.is_some() && !pending.answers.contains_key(&q.id) {
-            return false;
+    async fn autocomplete_stoppable_tasks(&self, ctx: &Context, command: &CommandInteraction) {
+        let kind = StopKind::from_command(&command.data.name).unwrap_or(StopKind::Stop);
+        let query = command
+            .data
+            .autocomplete()
+            .map(|option| option.value.trim().trim_start_matches('#').to_lowercase())
+            .unwrap_or_default();
+        let state = self.app.state::<crate::AppState>().inner().clone();
+        let mut response = CreateAutocompleteResponse::new();
+        for task in self
+            .tasks_with_access(&state)
+            .await
+            .into_iter()
+            .filter_map(|(task, allowed)| allowed.then_some(task))
+            .filter(|task| kind.applies_to(&task.status_state))
+            .filter(|task| {
+                query.is_empty()
+                    || task.id.to_lowercase().contains(&query)
+                    || task
+                        .title_summary
+                        .as_deref()
+                        .is_some_and(|title| title.to_lowercase().contains(&query))
+            })
+            .take(MAX_TASK_CHOICES)
+        {
+            let title = task
+                .title_summary
+                .as_deref()
+                .or(task.prompt.as_deref())
+                .unwrap_or("Untitled");
+            let label = format!("#{} {} ({})", short_task_id(&task.id), title, task.agent_id);
+            response = response.add_string_choice(truncate_str(&label, 100), task.id);
         }
+        let _ = command
+            .create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response))
+            .await;
     }
-    true
-}
-
-#[derive(Debug, Clone)]
-enum TaskCreateKind {
-    Project,
-    Agent,
-    Model,
-}
 
-#[derive(Debug, Clone)]
-struct TaskCreateAction {
-    kind: TaskCreateKind,
-    pending_id: String,
-    value: Option<String>,
-}
+    async fn stop_task_command(&self, ctx: &Context, command: &CommandInteraction, kind: StopKind) {
+        let state = self.app.state::<crate::AppState>().inner().clone();
+        let thread_task_id = task_for_thread(&state, command.channel_id.get()).await;
+        let settings = state.settings.lock().await.clone();
+        if !self.is_task_channel(&settings, command.channel_id) && thread_task_id.is_none() {
+            reply_ephemeral(
+                ctx,
+                command,
+                &format!(
+                    "Use /{} in the configured Discord channel or a task thread.",
+                    kind.command()
+                ),
+            )
+            .await;
+            return;
+        }
 
-fn strip_bot_mentions(content: &str, bot_user_id: UserId) -> String {
-    let mention = format!("<@{}>", bot_user_id.get());
-    let mention_nick = format!("<@!{}>", bot_user_id.get());
-    content
-        .replace(&mention, "")
-        .replace(&mention_nick, "")
-        .trim()
-        .to_string()
-}
+        let query = command
+            .data
+            .options
+            .iter()
+            .find_map(|option| match &option.value {
+                CommandDataOptionValue::String(value) if option.name == "task" => {
+                    Some(value.trim().to_string())
+                }
+                _ => None,
+            });
+        let query = match query.filter(|query| !query.is_empty()).or(thread_task_id) {
+            Some(query) => query,
+            None => {
+                reply_ephemeral(ctx, command, "Provide a task id.").await;
+                return;
+            }
+        };
 
-fn parse_task_override(content: &str) -> Option<(String, String)> {
-    for line in content.lines() {
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
-            continue;
+        let tasks = self.tasks_with_access(&state).await;
+        let ids: Vec<String> = tasks.iter().map(|(task, _)| task.id.clone()).collect();
+        let (task, allowed) = match resolve_task_match(&ids, &query) {
+            Ok(task_id) => match tasks.into_iter().find(|(task, _)| task.id == task_id) {
+                Some(found) => found,
+                None => return,
+            },
+            Err(err) => {
+                reply_ephemeral(ctx, command, &err).await;
+                return;
+            }
+        };
+        if !allowed {
+            reply_ephemeral(
+                ctx,
+                command,
+                &format!(
+                    "Task `#{}` isn't in an allowlisted project.",
+                    short_task_id(&task.id)
+                ),
+            )
+            .await;
+            return;
         }
-        let mut parts = trimmed.splitn(2, ':');
-        let key = parts.next().unwrap_or("").trim().to_lowercase();
-        let value = parts.next().unwrap_or("").trim();
-        if value.is_empty() {
-            continue;
+        if !kind.applies_to(&task.status_state) {
+            reply_ephemeral(
+                ctx,
+                command,
+                &format!(
+                    "Task `#{}` isn't running (status: {}).",
+                    short_task_id(&task.id),
+                    task.status
+                ),
+            )
+            .await;
+            return;
         }
-        if key == "agent" || key == "model" || key == "project" {
-            return Some((key, value.to_string()));
+
+        let _ = command
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::Defer(
+                    CreateInteractionResponseMessage::new().ephemeral(true),
+                ),
+            )
+            .await;
+        let result = match kind {
+            StopKind::Stop => {
+                crate::stop_task_internal(task.id.clone(), &state, self.app.clone()).await
+            }
+            StopKind::Cancel => {
+                crate::soft_stop_task_internal(task.id.clone(), &state, self.app.clone()).await
+            }
+        };
+        let reply = match result {
+            Ok(()) => {
+                let done = match kind {
+                    StopKind::Stop => "Stopped the task and ended its session",
+                    StopKind::Cancel => "Cancelled the current generation; the session stays open",
+                };
+                let thread_id = {
+                    let task_id = task.id.clone();
+                    state
+                        .store
+                        .read(move |conn| db::get_discord_thread_id(conn, &task_id))
+                        .await
+                        .ok()
+                        .flatten()
+                };
+                if let Some(thread_id) = thread_id {
+                    let note = format!("**{}** (requested by <@{}>)", done, command.user.id);
+                    let _ = ChannelId::new(thread_id)
+                        .send_message(&ctx.http, CreateMessage::new().content(note))
+                        .await;
+                }
+                format!("{} for task `#{}`.", done, short_task_id(&task.id))
+            }
+            Err(err) => format!(
+                "Failed to {} task `#{}`: {}",
+                kind.command(),
+                short_task_id(&task.id),
+                err
+            ),
+        };
+        let _ = command
+            .create_followup(
+                &ctx.http,
+                CreateInteractionResponseFollowup::new()
+                    .content(reply)
+                    .ephemeral(true),
+            )
+            .await;
+    }
+
+    /// A button on a permission prompt: answer the agent and show the decision in place.
+    async fn permission_button(
+        &self,
+        ctx: &Context,
+        component: &ComponentInteraction,
+        request_id: &str,
+        option_idx: usize,
+    ) {
+        let state = self.app.state::<crate::AppState>().inner().clone();
+        let task_id = task_for_thread(&state, component.channel_id.get()).await;
+        let Some(task_id) = task_id else {
+            reply_component_ephemeral(ctx, component, "No task bound to this thread.").await;
+            return;
+        };
+
+        let roles = component
+            .member
+            .as_ref()
+            .map(|member| member.roles.as_slice());
+        let user_id = component.user.id;
+        if !user_may_answer(&state, &task_id, user_id, roles.unwrap_or_default()).await {
+            reply_component_ephemeral(ctx, component, NOT_REQUESTER_MESSAGE).await;
+            return;
+        }
+
+        // Taken before answering so a concurrent desktop answer doesn't also edit the message.
+        let key = (task_id.clone(), request_id.to_string());
+        let Some(prompt) = state.discord_permission_prompts.lock().await.remove(&key) else {
+            reply_component_ephemeral(
+                ctx,
+                component,
+                "This permission request was already handled.",
+            )
+            .await;
+            return;
+        };
+        let Some(option) = prompt.options.get(option_idx).cloned() else {
+            state
+                .discord_permission_prompts
+                .lock()
+                .await
+                .insert(key, prompt);
+            reply_component_ephemeral(ctx, component, "Option not found.").await;
+            return;
+        };
+
+        if let Err(err) = crate::respond_to_permission_internal(
+            task_id,
+            request_id.to_string(),
+            option.id.clone(),
+            &state,
+            self.app.clone(),
+        )
+        .await
+        {
+            state
+                .discord_permission_prompts
+                .lock()
+                .await
+                .insert(key, prompt);
+            reply_component_ephemeral(ctx, component, &format!("Failed to answer: {err}")).await;
+            return;
+        }
+
+        let content = format!(
+            "{}\n\n**{}** by <@{}> <t:{}:f>",
+            prompt.content,
+            permission_choice(&option).1,
+            component.user.id,
+            Utc::now().timestamp()
+        );
+        let _ = component
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .components(permission_buttons(request_id, &prompt.options, true)),
+                ),
+            )
+            .await;
+    }
+}
+
+async fn reply_ephemeral(ctx: &Context, command: &CommandInteraction, content: &str) {
+    let _ = command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await;
+}
+
+/// Resolve a full or partial task id, such as the `#4bddf02d` suffix shown in Discord.
+async fn reply_component_ephemeral(ctx: &Context, component: &ComponentInteraction, content: &str) {
+    let _ = component
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await;
+}
+
+fn resolve_task_match(task_ids: &[String], query: &str) -> Result<String, String> {
+    let query = query.trim().trim_start_matches('#');
+    if query.is_empty() {
+        return Err("Provide a task id.".to_string());
+    }
+    if let Some(task_id) = task_ids.iter().find(|id| id.as_str() == query) {
+        return Ok(task_id.clone());
+    }
+    let query = query.to_lowercase();
+    let matches: Vec<&String> = task_ids
+        .iter()
+        .filter(|id| id.to_lowercase().contains(&query))
+        .collect();
+    match matches.as_slice() {
+        [] => Err(format!("No task matches `{}`.", query)),
+        [task_id] => Ok((*task_id).clone()),
+        _ => Err(format!(
+            "Multiple tasks match `{}`: {}. Be more specific.",
+            query,
+            matches
+                .iter()
+                .take(6)
+                .map(|id| format!("#{}", short_task_id(id)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Extracts a short, friendly identifier from a full task ID.
+/// For IDs like "task-1769976653565-4bddf02d", returns "4bddf02d".
+fn short_task_id(full_id: &str) -> &str {
+    // Split by '-' and get the last part (UUID suffix)
+    full_id.rsplit('-').next().unwrap_or(full_id)
+}
+
+/// Answers in a thread reply: the whole message for a single question, otherwise
+/// `question_id: answer` lines, falling back to the first unanswered question.
+fn parse_user_input_reply(pending: &PendingUserInput, content: &str) -> HashMap<String, String> {
+    let mut answers = HashMap::new();
+    if let [question] = pending.questions.as_slice() {
+        answers.insert(
+            question.id.clone(),
+            normalize_answer_value(question, content.trim()),
+        );
+        return answers;
+    }
+
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        if value.is_empty() {
+            continue;
+        }
+        if let Some(q) = pending.questions.iter().find(|q| q.id == key) {
+            answers.insert(q.id.clone(), normalize_answer_value(q, value));
+        }
+    }
+
+    if answers.is_empty() {
+        let unanswered = pending
+            .questions
+            .iter()
+            .find(|q| !pending.answers.contains_key(&q.id));
+        if let Some(q) = unanswered {
+            answers.insert(q.id.clone(), normalize_answer_value(q, content.trim()));
+        }
+    }
+    answers
+}
+
+fn normalize_answer_value(
+    question: &phantom_harness_backend::cli::UserInputQuestion,
+    raw: &str,
+) -> String {
+    if let Some(options) = question.options.as_ref() {
+        if let Some(opt) = options
+            .iter()
+            .find(|opt| opt.label.eq_ignore_ascii_case(raw))
+        {
+            return opt.label.clone();
+        }
+    }
+    raw.to_string()
+}
+
+/// `(request_id, question_id, option index)`; the index is None for select menus, whose
+/// choice comes with the interaction's values.
+fn parse_user_input_custom_id(custom_id: &str) -> Option<(String, String, Option<usize>)> {
+    let mut parts = custom_id.split(':');
+    let prefix = parts.next()?;
+    if prefix != "user_input" {
+        return None;
+    }
+    let request_id = parts.next()?.to_string();
+    let question_id = parts.next()?.to_string();
+    let idx = match parts.next()? {
+        "select" => None,
+        idx => Some(idx.parse::<usize>().ok()?),
+    };
+    Some((request_id, question_id, idx))
+}
+
+fn build_answers_payload(answers: &HashMap<String, String>) -> serde_json::Value {
+    let mut map = serde_json::Map::new();
+    for (qid, value) in answers {
+        map.insert(qid.clone(), serde_json::json!({ "answers": [value] }));
+    }
+    serde_json::Value::Object(map)
+}
+
+/// Discord user who started `task_id`, if it was started from Discord.
+async fn discord_requester(state: &AppState, task_id: &str) -> Option<u64> {
+    let task_id = task_id.to_string();
+    state
+        .store
+        .read(move |conn| db::get_discord_requester(conn, &task_id))
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Task bound to a Discord thread.
+async fn task_for_thread(state: &AppState, thread_id: u64) -> Option<String> {
+    state
+        .store
+        .read(move |conn| db::get_task_id_for_discord_thread(conn, thread_id))
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Role from settings whose members may answer any task's prompts.
+async fn approver_role(state: &AppState) -> Option<RoleId> {
+    let settings = state.settings.lock().await;
+    let role_id = settings
+        .discord_approver_role_id
+        .as_deref()?
+        .trim()
+        .parse()
+        .ok()?;
+    (role_id != 0).then(|| RoleId::new(role_id))
+}
+
+async fn user_may_answer(
+    state: &AppState,
+    task_id: &str,
+    user_id: UserId,
+    roles: &[RoleId],
+) -> bool {
+    let requester = discord_requester(state, task_id).await;
+    may_answer(requester, approver_role(state).await, user_id, roles)
+}
+
+/// Whether anyone can answer `task_id`'s prompts from Discord: a task started in Phantom
+/// has no requester, so without an approver role its prompts are answered in Phantom.
+pub async fn answerable_from_discord(state: &AppState, task_id: &str) -> bool {
+    discord_requester(state, task_id).await.is_some() || approver_role(state).await.is_some()
+}
+
+/// Only the requester and members of the approver role may answer.
+fn may_answer(
+    requester: Option<u64>,
+    approver_role: Option<RoleId>,
+    user_id: UserId,
+    roles: &[RoleId],
+) -> bool {
+    approver_role.is_some_and(|role| roles.contains(&role)) || requester == Some(user_id.get())
+}
+
+const NOT_REQUESTER_MESSAGE: &str =
+    "Only the person who started this task or an approver can answer.";
+
+const INPUT_EXPIRED_MESSAGE: &str = "This question timed out in Discord; answer it in Phantom.";
+
+fn input_expired(pending: &PendingUserInput) -> bool {
+    pending.requested_at.elapsed() >= DISCORD_INPUT_TIMEOUT
+}
+
+/// `(request_id, option index)` from a permission button.
+fn parse_permission_custom_id(custom_id: &str) -> Option<(&str, usize)> {
+    let (request_id, idx) = custom_id.strip_prefix("permission:")?.rsplit_once(':')?;
+    Some((request_id, idx.parse().ok()?))
+}
+
+/// Button label, decision shown once chosen, and style for a permission option.
+fn permission_choice(option: &PermissionOption) -> (&str, &str, ButtonStyle) {
+    match option.kind.as_deref() {
+        Some("allow_once") => ("Approve", "Approved", ButtonStyle::Success),
+        Some("allow_always") => ("Always allow", "Always allowed", ButtonStyle::Primary),
+        Some("reject_once") => ("Deny", "Denied", ButtonStyle::Danger),
+        Some("reject_always") => ("Always deny", "Always denied", ButtonStyle::Danger),
+        _ => (&option.label, &option.label, ButtonStyle::Secondary),
+    }
+}
+
+fn permission_buttons(
+    request_id: &str,
+    options: &[PermissionOption],
+    disabled: bool,
+) -> Vec<CreateActionRow> {
+    let buttons = options
+        .iter()
+        .take(5)
+        .enumerate()
+        .map(|(idx, option)| {
+            let (label, _, style) = permission_choice(option);
+            CreateButton::new(format!("permission:{}:{}", request_id, idx))
+                .label(truncate_str(label, 80))
+                .style(style)
+                .disabled(disabled)
+        })
+        .collect();
+    vec![CreateActionRow::Buttons(buttons)]
+}
+
+fn permission_prompt_content(request: &PermissionRequest) -> String {
+    let mut content = format!("**Permission needed:** `{}`", request.tool_name);
+    if let Some(description) = request.description.map(str::trim).filter(|d| !d.is_empty()) {
+        content.push_str(&format!("\n{}", truncate_str(description, 500)));
+    }
+    if let Some(raw_input) = request.raw_input.map(str::trim).filter(|r| !r.is_empty()) {
+        // Keep the input from closing the code block early.
+        let raw_input = raw_input.replace("```", "'''");
+        content.push_str(&format!("\n```\n{}\n```", truncate_str(&raw_input, 1200)));
+    }
+    content
+}
+
+fn pending_complete(pending: &PendingUserInput) -> bool {
+    for q in &pending.questions {
+        if q.options.is_none() && !pending.answers.contains_key(&q.id) {
+            return false;
+        }
+        if q.options.is_some() && !pending.answers.contains_key(&q.id) {
+            return false;
+        }
+    }
+    true
+}
+
+#[derive(Debug, Clone)]
+enum TaskCreateKind {
+    Project,
+    Agent,
+    Model,
+}
+
+#[derive(Debug, Clone)]
+struct TaskCreateAction {
+    kind: TaskCreateKind,
+    pending_id: String,
+    value: Option<String>,
+}
+
+fn strip_bot_mentions(content: &str, bot_user_id: UserId) -> String {
+    let mention = format!("<@{}>", bot_user_id.get());
+    let mention_nick = format!("<@!{}>", bot_user_id.get());
+    content
+        .replace(&mention, "")
+        .replace(&mention_nick, "")
+        .trim()
+        .to_string()
+}
+
+fn parse_task_override(content: &str) -> Option<(String, String)> {
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let mut parts = trimmed.splitn(2, ':');
+        let key = parts.next().unwrap_or("").trim().to_lowercase();
+        let value = parts.next().unwrap_or("").trim();
+        if value.is_empty() {
+            continue;
+        }
+        if key == "agent" || key == "model" || key == "project" {
+            return Some((key, value.to_string()));
         }
     }
     None
@@ -1206,6 +1975,74 @@ fn project_allowlist(settings: &Settings) -> Vec<String> {
         .collect()
 }
 
+/// Project mapped to `channel_id`, provided it is still allowlisted.
+fn channel_project(settings: &Settings, channel_id: ChannelId) -> Option<String> {
+    let path = settings
+        .discord_channel_projects
+        .as_ref()?
+        .get(&channel_id.to_string())?
+        .trim();
+    let allowed = crate::project_path_allowed(&project_allowlist(settings), path);
+    (allowed && !path.is_empty()).then(|| path.to_string())
+}
+
+/// Trim and check a `discordChannelProjects` map: channel ids must be numeric and each
+/// project must exist and be allowlisted. Empty entries are dropped.
+pub fn validate_channel_projects(
+    projects: &HashMap<String, String>,
+    allowlist: &[String],
+) -> Result<HashMap<String, String>, String> {
+    let mut validated = HashMap::new();
+    for (channel_id, path) in projects {
+        let (channel_id, path) = (channel_id.trim(), path.trim());
+        if channel_id.is_empty() && path.is_empty() {
+            continue;
+        }
+        if channel_id.parse::<u64>().is_err() {
+            return Err(format!(
+                "Discord channel ID must be numeric: {}",
+                channel_id
+            ));
+        }
+        if !Path::new(path).is_dir() {
+            return Err(format!(
+                "Project for Discord channel {} does not exist: {}",
+                channel_id, path
+            ));
+        }
+        if !crate::project_path_allowed(allowlist, path) {
+            return Err(format!(
+                "Project for Discord channel {} is not in the allowlist: {}",
+                channel_id, path
+            ));
+        }
+        validated.insert(channel_id.to_string(), path.to_string());
+    }
+    Ok(validated)
+}
+
+/// Most recently used task projects that fall under the allowlist.
+const RECENT_PROJECT_CHOICES: usize = 10;
+
+/// Projects offered when picking one for a Discord task: recently used allowlisted
+/// projects first, then the allowlist itself.
+async fn project_choices(state: &AppState, allowlist: &[String]) -> Vec<String> {
+    let recent = state
+        .store
+        .read(db::list_known_projects)
+        .await
+        .unwrap_or_default();
+    let mut seen = HashSet::new();
+    recent
+        .into_iter()
+        .map(|project| project.project_key)
+        .filter(|path| crate::project_path_allowed(allowlist, path))
+        .take(RECENT_PROJECT_CHOICES)
+        .chain(allowlist.iter().cloned())
+        .filter(|path| seen.insert(crate::normalize_allowlist_path(path)))
+        .collect()
+}
+
 fn normalize_project_token(value: &str) -> String {
     value
         .chars()
@@ -1395,12 +2232,12 @@ fn build_agent_action_rows(state: &AppState, pending_id: &str) -> Vec<CreateActi
     rows
 }
 
-fn build_model_action_rows(
+async fn build_model_action_rows(
     state: &AppState,
     pending_id: &str,
     agent_id: &str,
 ) -> (Vec<CreateActionRow>, bool) {
-    let options = model_options_for_agent(state, agent_id);
+    let options = model_options_for_agent(state, agent_id).await;
     let truncated = options.len() > 25;
     let mut select_options: Vec<CreateSelectMenuOption> = Vec::new();
     for option in options.into_iter().take(25) {
@@ -1434,10 +2271,15 @@ struct ModelOption {
     description: Option<String>,
 }
 
-fn model_options_for_agent(state: &AppState, agent_id: &str) -> Vec<ModelOption> {
+async fn model_options_for_agent(state: &AppState, agent_id: &str) -> Vec<ModelOption> {
     let cached_models = {
-        let conn = match state.db.lock() {
-            Ok(conn) => conn,
+        let agent_id = agent_id.to_string();
+        match state
+            .store
+            .read(move |conn| db::get_cached_models(conn, &agent_id))
+            .await
+        {
+            Ok(models) => models,
             Err(_) => {
                 return vec![ModelOption {
                     value: "default".to_string(),
@@ -1445,8 +2287,7 @@ fn model_options_for_agent(state: &AppState, agent_id: &str) -> Vec<ModelOption>
                     description: None,
                 }]
             }
-        };
-        db::get_cached_models(&conn, agent_id).unwrap_or_default()
+        }
     };
 
     let mut options: Vec<ModelOption> = Vec::new();
@@ -1514,50 +2355,132 @@ pub async fn start_discord_bot(
         .parse::<u64>()
         .map_err(|_| "Discord channel ID must be numeric".to_string())?;
 
-    let intents =
-        GatewayIntents::GUILD_MESSAGES | GatewayIntents::GUILDS | GatewayIntents::MESSAGE_CONTENT;
-
     let bot_user_id: Arc<StdMutex<Option<UserId>>> = Arc::new(StdMutex::new(None));
+    let health = DiscordHealth {
+        app: app.clone(),
+        status: Arc::default(),
+    };
 
     let handler = DiscordEventHandler {
         app,
         channel_id: ChannelId::new(channel_id),
         bot_user_id: bot_user_id.clone(),
+        health: health.clone(),
     };
 
-    let mut client = Client::builder(token, intents)
+    let client = build_client(&token, handler.clone()).await?;
+    let http = client.http.clone();
+    let handle = DiscordBotHandle {
+        http,
+        channel_id: ChannelId::new(channel_id),
+        _bot_user_id: bot_user_id,
+        shard_manager: Arc::new(Mutex::new(client.shard_manager.clone())),
+        health,
+        stopped: Arc::new(AtomicBool::new(false)),
+        running: Arc::new(AtomicBool::new(true)),
+    };
+
+    let supervisor = handle.clone();
+    tauri::async_runtime::spawn(async move {
+        supervise_client(supervisor, client, token, handler).await;
+    });
+    let monitor = handle.clone();
+    tauri::async_runtime::spawn(async move {
+        monitor_heartbeats(monitor).await;
+    });
+
+    Ok(handle)
+}
+
+async fn build_client(token: &str, handler: DiscordEventHandler) -> Result<Client, String> {
+    let intents =
+        GatewayIntents::GUILD_MESSAGES | GatewayIntents::GUILDS | GatewayIntents::MESSAGE_CONTENT;
+    Client::builder(token, intents)
         .event_handler(handler)
         .await
-        .map_err(|e| format!("Discord client init failed: {e}"))?;
+        .map_err(|e| format!("Discord client init failed: {e}"))
+}
 
-    let shard_manager = client.shard_manager.clone();
-    let http = client.http.clone();
+/// Run the gateway client, rebuilding it with exponential backoff whenever it stops,
+/// until the bot is shut down or the error can't be fixed by reconnecting.
+async fn supervise_client(
+    handle: DiscordBotHandle,
+    mut client: Client,
+    token: String,
+    handler: DiscordEventHandler,
+) {
+    let mut attempt = 0;
+    loop {
+        let started = Instant::now();
+        let result = client.start().await;
+        if handle.stopped.load(Ordering::SeqCst) {
+            break;
+        }
+        let error = match &result {
+            Ok(()) => "Gateway connection closed".to_string(),
+            Err(err) => err.to_string(),
+        };
+        println!("[Discord] Client stopped: {error}");
+        handle.health.disconnected(Some(error));
+        if result.as_ref().is_err_and(is_fatal_gateway_error) {
+            break;
+        }
 
-    tauri::async_runtime::spawn(async move {
-        if let Err(e) = client.start().await {
-            println!("[Discord] Client error: {e}");
+        if started.elapsed() >= RECONNECT_RESET_AFTER {
+            attempt = 0;
         }
-    });
+        let delay = reconnect_delay(attempt);
+        attempt += 1;
+        println!("[Discord] Reconnecting in {}s", delay.as_secs());
+        tokio::time::sleep(delay).await;
+
+        client = loop {
+            if handle.stopped.load(Ordering::SeqCst) {
+                handle.running.store(false, Ordering::SeqCst);
+                return;
+            }
+            match build_client(&token, handler.clone()).await {
+                Ok(client) => break client,
+                Err(err) => {
+                    handle.health.disconnected(Some(err));
+                    let delay = reconnect_delay(attempt);
+                    attempt += 1;
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        };
+        // Swapped under the lock so a concurrent shutdown stops the new client too.
+        let mut shard_manager = handle.shard_manager.lock().await;
+        if handle.stopped.load(Ordering::SeqCst) {
+            break;
+        }
+        *shard_manager = client.shard_manager.clone();
+    }
+    handle.running.store(false, Ordering::SeqCst);
+}
 
-    Ok(DiscordBotHandle {
-        http,
-        channel_id: ChannelId::new(channel_id),
-        _bot_user_id: bot_user_id,
-        shard_manager,
-    })
+/// Record a heartbeat whenever a shard is connected with an acked heartbeat.
+async fn monitor_heartbeats(handle: DiscordBotHandle) {
+    while handle.is_running() && !handle.stopped.load(Ordering::SeqCst) {
+        tokio::time::sleep(HEARTBEAT_CHECK_INTERVAL).await;
+        let shard_manager = handle.shard_manager.lock().await.clone();
+        let alive = shard_manager.runners.lock().await.values().any(|runner| {
+            matches!(runner.stage, ConnectionStage::Connected) && runner.latency.is_some()
+        });
+        if alive {
+            handle.health.connected();
+        }
+    }
 }
 
 pub async fn ensure_thread_for_task(
     handle: &DiscordBotHandle,
-    db_conn: Arc<StdMutex<rusqlite::Connection>>,
+    store: &AsyncDb,
     task_id: &str,
     thread_name: &str,
     intro_message: &str,
 ) -> Result<ChannelId, String> {
-    if let Some(thread_id) = {
-        let conn = db_conn.lock().map_err(|e| e.to_string())?;
-        db::get_discord_thread_id(&conn, task_id).map_err(|e| e.to_string())?
-    } {
+    if let Some(thread_id) = thread_for_task(store, task_id).await? {
         return Ok(ChannelId::new(thread_id));
     }
 
@@ -1570,35 +2493,282 @@ pub async fn ensure_thread_for_task(
         .map_err(|e| format!("Discord create_thread failed: {e}"))?;
 
     {
-        let conn = db_conn.lock().map_err(|e| e.to_string())?;
-        db::save_discord_thread(&conn, task_id, thread.id.get(), handle.channel_id().get())
-            .map_err(|e| e.to_string())?;
+        let (task_id, thread_id, channel_id) = (
+            task_id.to_string(),
+            thread.id.get(),
+            handle.channel_id().get(),
+        );
+        store
+            .write(move |conn| db::save_discord_thread(conn, &task_id, thread_id, channel_id))
+            .await?;
     }
     Ok(thread.id)
 }
 
-pub async fn post_task_notification(
+/// Thread mirroring `task_id`, if it has one.
+async fn thread_for_task(store: &AsyncDb, task_id: &str) -> Result<Option<u64>, String> {
+    let task_id = task_id.to_string();
+    store
+        .read(move |conn| db::get_discord_thread_id(conn, &task_id))
+        .await
+}
+
+/// Least time between edits of a task's status message, well inside Discord's rate limits.
+const STATUS_EDIT_INTERVAL: Duration = Duration::from_secs(3);
+/// Discord's "Unknown Message" error code.
+const UNKNOWN_MESSAGE: isize = 10008;
+
+/// How a status update moves the task's clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusChange {
+    Started,
+    Progress,
+    Finished,
+}
+
+#[derive(Default)]
+struct StatusSlot {
+    text: String,
+    /// ms since the epoch
+    started_at: Option<i64>,
+    finished_at: Option<i64>,
+    /// Changed since the message was last written
+    dirty: bool,
+    message: Option<(ChannelId, MessageId)>,
+    last_edit: Option<Instant>,
+    flushing: bool,
+}
+
+impl StatusSlot {
+    fn apply(&mut self, text: String, change: StatusChange, timestamp: i64) {
+        match change {
+            StatusChange::Started => {
+                self.started_at = Some(timestamp);
+                self.finished_at = None;
+            }
+            StatusChange::Progress => {}
+            StatusChange::Finished => self.finished_at = Some(timestamp),
+        }
+        self.text = text;
+        self.dirty = true;
+    }
+
+    fn render(&self, cost: f64) -> String {
+        let elapsed = self.started_at.map(|started_at| {
+            let ended_at = self
+                .finished_at
+                .unwrap_or_else(|| Utc::now().timestamp_millis());
+            (ended_at - started_at).max(0) / 1000
+        });
+        status_message_content(&self.text, elapsed, cost)
+    }
+}
+
+/// One pinned status message per task thread, edited in place as the task runs.
+#[derive(Clone, Default)]
+pub struct StatusMessages(Arc<StdMutex<HashMap<String, StatusSlot>>>);
+
+impl StatusMessages {
+    /// Show `text` in the task's status message. Updates arriving faster than
+    /// `STATUS_EDIT_INTERVAL` are coalesced into the next edit.
+    pub fn update(
+        &self,
+        handle: &DiscordBotHandle,
+        store: AsyncDb,
+        task_id: &str,
+        text: String,
+        change: StatusChange,
+        timestamp: i64,
+    ) {
+        {
+            let Ok(mut slots) = self.0.lock() else {
+                return;
+            };
+            let slot = slots.entry(task_id.to_string()).or_default();
+            slot.apply(text, change, timestamp);
+            if slot.flushing {
+                return;
+            }
+            slot.flushing = true;
+        }
+        let (statuses, handle, task_id) = (self.clone(), handle.clone(), task_id.to_string());
+        tauri::async_runtime::spawn(async move {
+            statuses.flush(&handle, &store, &task_id).await;
+        });
+    }
+
+    pub fn remove(&self, task_id: &str) {
+        if let Ok(mut slots) = self.0.lock() {
+            slots.remove(task_id);
+        }
+    }
+
+    /// Write the latest status until nothing new arrives between edits.
+    async fn flush(&self, handle: &DiscordBotHandle, store: &AsyncDb, task_id: &str) {
+        loop {
+            let wait = self.with_slot(task_id, |slot| {
+                slot.last_edit
+                    .map(|at| STATUS_EDIT_INTERVAL.saturating_sub(at.elapsed()))
+                    .unwrap_or_default()
+            });
+            tokio::time::sleep(wait.unwrap_or_default()).await;
+
+            let cost = {
+                let task_id = task_id.to_string();
+                store
+                    .read(move |conn| db::get_task_cost(conn, &task_id))
+                    .await
+                    .unwrap_or(0.0)
+            };
+            let Some(Some((content, message))) = self.with_slot(task_id, |slot| {
+                if !slot.dirty {
+                    slot.flushing = false;
+                    return None;
+                }
+                slot.dirty = false;
+                Some((slot.render(cost), slot.message))
+            }) else {
+                return;
+            };
+
+            let thread_id = match message {
+                Some((thread_id, _)) => Some(thread_id),
+                None => thread_for_task(store, task_id)
+                    .await
+                    .ok()
+                    .flatten()
+                    .map(ChannelId::new),
+            };
+            // No thread yet: the next update tries again.
+            let Some(thread_id) = thread_id else {
+                continue;
+            };
+            let written =
+                write_status_message(handle, thread_id, message.map(|(_, id)| id), &content).await;
+            self.with_slot(task_id, |slot| {
+                slot.last_edit = Some(Instant::now());
+                match written {
+                    Ok(message_id) => slot.message = Some((thread_id, message_id)),
+                    Err(err) => println!("[Discord] Failed to update status for {task_id}: {err}"),
+                }
+            });
+        }
+    }
+
+    /// Tasks that currently have a status message.
+    pub fn task_ids(&self) -> Vec<String> {
+        self.0
+            .lock()
+            .map(|slots| slots.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn with_slot<T>(&self, task_id: &str, f: impl FnOnce(&mut StatusSlot) -> T) -> Option<T> {
+        let mut slots = self.0.lock().ok()?;
+        slots.get_mut(task_id).map(f)
+    }
+}
+
+/// Status text for a progress event, if it changes what the status message shows.
+pub fn status_for_progress(kind: &ProgressKind) -> Option<(String, StatusChange)> {
+    let status = match kind {
+        ProgressKind::GenerationStarted { .. } => ("Working".to_string(), StatusChange::Started),
+        ProgressKind::PhaseChanged { phase } => {
+            let text = match phase {
+                Phase::Thinking => "Thinking",
+                Phase::Tool => "Running tools",
+                Phase::Responding => "Responding",
+            };
+            (text.to_string(), StatusChange::Progress)
+        }
+        ProgressKind::ToolCall { name } => (
+            format!("Running `{}`", truncate_str(name, 100)),
+            StatusChange::Progress,
+        ),
+        ProgressKind::PermissionRequested { tool_name, .. } => (
+            format!(
+                "Waiting for permission to run `{}`",
+                truncate_str(tool_name, 100)
+            ),
+            StatusChange::Progress,
+        ),
+        ProgressKind::UserInputRequested { .. } => {
+            ("Waiting for an answer".to_string(), StatusChange::Progress)
+        }
+        ProgressKind::Completed { summary, .. } => {
+            let summary = summary.trim();
+            let text = if summary.is_empty() {
+                "Completed"
+            } else {
+                summary
+            };
+            (text.to_string(), StatusChange::Finished)
+        }
+        ProgressKind::Cancelled => ("Cancelled".to_string(), StatusChange::Finished),
+        ProgressKind::Errored { error } => (
+            format!("Failed: {}", truncate_str(error, 200)),
+            StatusChange::Finished,
+        ),
+        ProgressKind::PermissionResolved { .. } => return None,
+    };
+    Some(status)
+}
+
+fn status_message_content(text: &str, elapsed_secs: Option<i64>, cost: f64) -> String {
+    let mut content = format!("**Status:** {}", truncate_str(text.trim(), 300));
+    if let Some(secs) = elapsed_secs {
+        let elapsed = match secs {
+            0..=59 => format!("{}s", secs),
+            60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+            _ => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+        };
+        content.push_str(&format!("\n**Elapsed:** {}", elapsed));
+    }
+    content.push_str(&format!("\n**Cost:** ${:.2}", cost));
+    content
+}
+
+/// Edit the status message, or post and pin a new one when there is none or a user
+/// deleted it. Returns the message now showing the status.
+async fn write_status_message(
     handle: &DiscordBotHandle,
-    db_conn: Arc<StdMutex<rusqlite::Connection>>,
-    task_id: &str,
-    thread_name: &str,
+    thread_id: ChannelId,
+    message_id: Option<MessageId>,
     content: &str,
-) -> Result<ChannelId, String> {
-    let thread_id = ensure_thread_for_task(handle, db_conn, task_id, thread_name, content).await?;
-    handle.send_thread_message(thread_id, content).await?;
-    Ok(thread_id)
+) -> Result<MessageId, String> {
+    if let Some(message_id) = message_id {
+        match thread_id
+            .edit_message(
+                &handle.http,
+                message_id,
+                EditMessage::new().content(content),
+            )
+            .await
+        {
+            Ok(_) => return Ok(message_id),
+            Err(serenity::Error::Http(HttpError::UnsuccessfulRequest(err)))
+                if err.error.code == UNKNOWN_MESSAGE => {}
+            Err(err) => return Err(format!("Discord edit_message failed: {err}")),
+        }
+    }
+    let message = thread_id
+        .send_message(&handle.http, CreateMessage::new().content(content))
+        .await
+        .map_err(|e| format!("Discord thread send_message failed: {e}"))?;
+    // Pinning needs Manage Messages; the status works without it.
+    if let Err(err) = message.pin(&handle.http).await {
+        println!("[Discord] Failed to pin status message: {err}");
+    }
+    Ok(message.id)
 }
 
 pub async fn post_to_thread(
     handle: &DiscordBotHandle,
-    db_conn: Arc<StdMutex<rusqlite::Connection>>,
+    store: &AsyncDb,
     task_id: &str,
     content: &str,
 ) -> Result<(), String> {
-    let thread_id = {
-        let conn = db_conn.lock().map_err(|e| e.to_string())?;
-        db::get_discord_thread_id(&conn, task_id).map_err(|e| e.to_string())?
-    };
+    let thread_id = thread_for_task(store, task_id).await?;
     let thread_id = match thread_id {
         Some(id) => id,
         None => return Ok(()),
@@ -1608,52 +2778,565 @@ pub async fn post_to_thread(
         .await
 }
 
+/// DM `user_id` how a task they requested ended, with a link to its thread.
+pub async fn send_completion_dm(
+    handle: &DiscordBotHandle,
+    store: &AsyncDb,
+    task_id: &str,
+    user_id: u64,
+    title: &str,
+    outcome: &str,
+    cost: f64,
+) -> Result<(), String> {
+    let thread_id = thread_for_task(store, task_id).await?;
+    let link = match thread_id {
+        Some(thread_id) => thread_link(handle, ChannelId::new(thread_id)).await,
+        None => None,
+    };
+    let content = completion_dm_content(title, outcome, cost, link.as_deref());
+    let dm = UserId::new(user_id)
+        .create_dm_channel(&handle.http)
+        .await
+        .map_err(|e| format!("Discord create_dm_channel failed: {e}"))?;
+    dm.send_message(&handle.http, CreateMessage::new().content(content))
+        .await
+        .map_err(|e| format!("Discord DM send_message failed: {e}"))?;
+    Ok(())
+}
+
+async fn thread_link(handle: &DiscordBotHandle, thread_id: ChannelId) -> Option<String> {
+    let thread = thread_id.to_channel(&handle.http).await.ok()?.guild()?;
+    Some(format!(
+        "https://discord.com/channels/{}/{}",
+        thread.guild_id, thread.id
+    ))
+}
+
+fn completion_dm_content(title: &str, outcome: &str, cost: f64, link: Option<&str>) -> String {
+    let mut content = format!(
+        "**{}**\n{}\nCost: ${:.2}",
+        truncate_str(title, 200),
+        truncate_str(outcome.trim(), 1500),
+        cost
+    );
+    if let Some(link) = link {
+        content.push_str(&format!("\n{}", link));
+    }
+    content
+}
+
 pub async fn post_user_input_question(
     handle: &DiscordBotHandle,
-    db_conn: Arc<StdMutex<rusqlite::Connection>>,
+    store: &AsyncDb,
     task_id: &str,
     request_id: &str,
     question: &phantom_harness_backend::cli::UserInputQuestion,
 ) -> Result<(), String> {
-    let thread_id = {
-        let conn = db_conn.lock().map_err(|e| e.to_string())?;
-        db::get_discord_thread_id(&conn, task_id).map_err(|e| e.to_string())?
-    };
+    let thread_id = thread_for_task(store, task_id).await?;
     let thread_id = match thread_id {
         Some(id) => id,
         None => return Ok(()),
     };
 
-    let mut rows: Vec<CreateActionRow> = Vec::new();
-    let mut current_row: Vec<CreateButton> = Vec::new();
-
+    // Free-text questions are answered by replying in the thread.
     let Some(options) = question.options.as_ref() else {
         return Ok(());
     };
 
-    for (idx, opt) in options.iter().enumerate() {
-        let custom_id = format!("user_input:{}:{}:{}", request_id, question.id, idx);
-        let button = CreateButton::new(custom_id)
-            .label(opt.label.clone())
-            .style(ButtonStyle::Primary);
-        current_row.push(button);
-        if current_row.len() == 5 {
-            rows.push(CreateActionRow::Buttons(current_row));
-            current_row = Vec::new();
-        }
-        if rows.len() == 5 {
-            break;
-        }
-    }
-    if !current_row.is_empty() && rows.len() < 5 {
-        rows.push(CreateActionRow::Buttons(current_row));
-    }
+    // One row of buttons fits five options; longer lists get a menu.
+    let row = if options.len() > 5 {
+        let options = options
+            .iter()
+            .take(MAX_TASK_CHOICES)
+            .enumerate()
+            .map(|(idx, opt)| {
+                CreateSelectMenuOption::new(truncate_str(&opt.label, 100), idx.to_string())
+            })
+            .collect();
+        let menu = CreateSelectMenu::new(
+            format!("user_input:{}:{}:select", request_id, question.id),
+            CreateSelectMenuKind::String { options },
+        )
+        .placeholder("Choose an answer")
+        .min_values(1)
+        .max_values(1);
+        CreateActionRow::SelectMenu(menu)
+    } else {
+        let buttons = options
+            .iter()
+            .enumerate()
+            .map(|(idx, opt)| {
+                CreateButton::new(format!("user_input:{}:{}:{}", request_id, question.id, idx))
+                    .label(truncate_str(&opt.label, 80))
+                    .style(ButtonStyle::Primary)
+            })
+            .collect();
+        CreateActionRow::Buttons(buttons)
+    };
 
     let content = format!(
         "**{}** (`{}`)\n{}",
         question.header, question.id, question.question
     );
     handle
-        .send_thread_message_with_components(ChannelId::new(thread_id), &content, rows)
+        .send_thread_message_with_components(ChannelId::new(thread_id), &content, vec![row])
         .await
+        .map(|_| ())
+}
+
+/// Image types Discord tasks accept, the formats agents take as image input.
+const DISCORD_IMAGE_TYPES: [&str; 4] = ["image/png", "image/jpeg", "image/gif", "image/webp"];
+/// Prefix of attachment files downloaded from Discord. Each belongs to a single task and is
+/// removed with it.
+pub const DISCORD_ATTACHMENT_PREFIX: &str = "discord-";
+
+/// An image from Discord saved in the attachments dir.
+pub struct SavedImage {
+    pub id: String,
+    pub relative_path: String,
+    pub mime_type: &'static str,
+}
+
+/// Attachments that claim to be a supported image within the size cap.
+pub fn image_attachments(attachments: &[Attachment]) -> Vec<Attachment> {
+    attachments
+        .iter()
+        .filter(|attachment| {
+            attachment
+                .content_type
+                .as_deref()
+                .and_then(accepted_image_type)
+                .is_some()
+                && u64::from(attachment.size) <= crate::MAX_ATTACHMENT_BYTES
+        })
+        .cloned()
+        .collect()
+}
+
+fn accepted_image_type(content_type: &str) -> Option<&'static str> {
+    let mime = content_type.split(';').next()?.trim().to_ascii_lowercase();
+    DISCORD_IMAGE_TYPES
+        .into_iter()
+        .find(|accepted| *accepted == mime)
+}
+
+/// Image type from the file's magic bytes, so a mislabeled upload isn't sent as an image.
+fn sniff_image_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+/// Download `images` into `dir`. Images that fail to download, exceed the cap, or whose
+/// contents don't match their content type are skipped.
+pub async fn save_image_attachments(images: &[Attachment], dir: &Path) -> Vec<SavedImage> {
+    let mut saved = Vec::new();
+    for image in images {
+        let data = match image.download().await {
+            Ok(data) => data,
+            Err(err) => {
+                println!("[Discord] Failed to download {}: {err}", image.filename);
+                continue;
+            }
+        };
+        let declared = image.content_type.as_deref().and_then(accepted_image_type);
+        let mime_type = match sniff_image_type(&data) {
+            Some(actual)
+                if declared == Some(actual) && data.len() as u64 <= crate::MAX_ATTACHMENT_BYTES =>
+            {
+                actual
+            }
+            _ => {
+                println!(
+                    "[Discord] Skipping {}: not a supported image",
+                    image.filename
+                );
+                continue;
+            }
+        };
+        let id = Uuid::new_v4().to_string();
+        let extension = match mime_type {
+            "image/jpeg" => "jpg",
+            other => other.trim_start_matches("image/"),
+        };
+        let relative_path = format!("{}{}.{}", DISCORD_ATTACHMENT_PREFIX, id, extension);
+        if let Err(err) = std::fs::write(dir.join(&relative_path), &data) {
+            println!("[Discord] Failed to save {}: {err}", image.filename);
+            continue;
+        }
+        saved.push(SavedImage {
+            id,
+            relative_path,
+            mime_type,
+        });
+    }
+    saved
+}
+
+/// Discord rejects longer messages. Byte lengths are compared, which never undercounts.
+const MAX_MESSAGE_CHARS: usize = 2000;
+/// Files listed in a diff summary; the rest are only counted.
+const MAX_DIFF_SUMMARY_FILES: usize = 50;
+
+/// Messages summarizing `git diff --numstat` output: totals and branch, then the file list
+/// in code blocks split to fit Discord's limit. Empty when nothing changed.
+pub fn diff_summary_messages(branch: Option<&str>, numstat: &str) -> Vec<String> {
+    let (additions, deletions, files) = crate::worktree::parse_numstat(numstat);
+    if files == 0 {
+        return Vec::new();
+    }
+    let mut header = format!(
+        "**Changes:** {} file{} changed, +{} -{}",
+        files,
+        if files == 1 { "" } else { "s" },
+        additions,
+        deletions
+    );
+    if let Some(branch) = branch.filter(|branch| !branch.is_empty()) {
+        header.push_str(&format!(" on `{}`", branch));
+    }
+
+    let mut lines: Vec<String> = numstat
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let (add, del, path) = (parts.next()?, parts.next()?, parts.next()?);
+            // numstat prints "-" for both counts on binary files
+            let counts = if add == "-" {
+                "binary".to_string()
+            } else {
+                format!("+{} -{}", add, del)
+            };
+            Some(format!("{:<12} {}", counts, truncate_str(path, 200)))
+        })
+        .collect();
+    if lines.len() > MAX_DIFF_SUMMARY_FILES {
+        let more = lines.len() - MAX_DIFF_SUMMARY_FILES;
+        lines.truncate(MAX_DIFF_SUMMARY_FILES);
+        lines.push(format!("... and {} more", more));
+    }
+
+    let mut messages = vec![header];
+    let mut block = String::new();
+    for line in lines {
+        // 8 bytes for the fences around the block
+        if !block.is_empty() && block.len() + line.len() + 1 + 8 > MAX_MESSAGE_CHARS {
+            messages.push(format!("```\n{}```", block));
+            block.clear();
+        }
+        block.push_str(&line);
+        block.push('\n');
+    }
+    if !block.is_empty() {
+        messages.push(format!("```\n{}```", block));
+    }
+    if messages.len() > 1 && messages[0].len() + 1 + messages[1].len() <= MAX_MESSAGE_CHARS {
+        let first_block = messages.remove(1);
+        messages[0] = format!("{}\n{}", messages[0], first_block);
+    }
+    messages
+}
+
+/// A permission request to post to a task thread.
+pub struct PermissionRequest<'a> {
+    pub request_id: &'a str,
+    pub tool_name: &'a str,
+    pub description: Option<&'a str>,
+    pub raw_input: Option<&'a str>,
+    pub options: &'a [PermissionOption],
+}
+
+/// A permission prompt in a task thread whose buttons are still live.
+#[derive(Debug, Clone)]
+pub struct DiscordPermissionPrompt {
+    request_id: String,
+    thread_id: ChannelId,
+    message_id: MessageId,
+    content: String,
+    options: Vec<PermissionOption>,
+}
+
+/// Post a permission request with a button per option. Returns the prompt to track until
+/// it is answered, or None when there is no thread or nothing to choose from Discord.
+pub async fn post_permission_request(
+    handle: &DiscordBotHandle,
+    store: &AsyncDb,
+    task_id: &str,
+    request: &PermissionRequest<'_>,
+) -> Result<Option<DiscordPermissionPrompt>, String> {
+    let thread_id = thread_for_task(store, task_id).await?;
+    let Some(thread_id) = thread_id.map(ChannelId::new) else {
+        return Ok(None);
+    };
+
+    let content = permission_prompt_content(request);
+    if request.options.is_empty() {
+        let content = format!("{}\n\nAnswer it in Phantom.", content);
+        handle.send_thread_message(thread_id, &content).await?;
+        return Ok(None);
+    }
+    let message = handle
+        .send_thread_message_with_components(
+            thread_id,
+            &content,
+            permission_buttons(request.request_id, request.options, false),
+        )
+        .await?;
+    Ok(Some(DiscordPermissionPrompt {
+        request_id: request.request_id.to_string(),
+        thread_id,
+        message_id: message.id,
+        content,
+        options: request.options.to_vec(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_task_match_accepts_partial_ids() {
+        let ids = vec![
+            "task-1769976653565-4bddf02d".to_string(),
+            "task-1769976653999-4bd00000".to_string(),
+        ];
+        assert_eq!(
+            resolve_task_match(&ids, "#4bddf02d").unwrap(),
+            "task-1769976653565-4bddf02d"
+        );
+        assert_eq!(
+            resolve_task_match(&ids, "task-1769976653999-4bd00000").unwrap(),
+            ids[1]
+        );
+        assert!(resolve_task_match(&ids, "4bd")
+            .unwrap_err()
+            .starts_with("Multiple tasks match"));
+        assert!(resolve_task_match(&ids, "nope")
+            .unwrap_err()
+            .starts_with("No task matches"));
+        assert!(resolve_task_match(&ids, " # ").is_err());
+    }
+
+    #[test]
+    fn test_thread_reply_answers_pending_questions() {
+        use phantom_harness_backend::cli::{UserInputOption, UserInputQuestion};
+        let question = |id: &str, options: Option<Vec<&str>>| UserInputQuestion {
+            id: id.to_string(),
+            header: id.to_string(),
+            question: format!("{id}?"),
+            options: options.map(|labels| {
+                labels
+                    .into_iter()
+                    .map(|label| UserInputOption {
+                        label: label.to_string(),
+                        description: String::new(),
+                    })
+                    .collect()
+            }),
+        };
+        let mut pending = PendingUserInput {
+            request_id: "r1".to_string(),
+            questions: vec![
+                question("env", Some(vec!["Staging", "Production"])),
+                question("notes", None),
+            ],
+            answers: HashMap::new(),
+            requested_at: std::time::Instant::now(),
+        };
+
+        let reply = parse_user_input_reply(&pending, "env: production\nbogus: x");
+        assert_eq!(reply.len(), 1);
+        assert_eq!(reply["env"], "Production");
+        pending.answers.extend(reply);
+        assert!(!pending_complete(&pending));
+
+        // A plain reply answers the first question still open.
+        let reply = parse_user_input_reply(&pending, "ship it after lunch");
+        assert_eq!(reply["notes"], "ship it after lunch");
+        pending.answers.extend(reply);
+        assert!(pending_complete(&pending));
+        assert!(!input_expired(&pending));
+
+        assert_eq!(
+            parse_user_input_custom_id("user_input:r1:env:select"),
+            Some(("r1".to_string(), "env".to_string(), None))
+        );
+        assert_eq!(
+            parse_user_input_custom_id("user_input:r1:env:1"),
+            Some(("r1".to_string(), "env".to_string(), Some(1)))
+        );
+        let (user, role) = (UserId::new(7), RoleId::new(9));
+        assert!(!may_answer(None, None, user, &[]), "denied by default");
+        assert!(may_answer(Some(7), None, user, &[]));
+        assert!(!may_answer(Some(8), None, user, &[]));
+        assert!(!may_answer(None, Some(role), user, &[]));
+        assert!(may_answer(None, Some(role), user, &[role]));
+        assert!(may_answer(Some(8), Some(role), user, &[role]));
+    }
+
+    #[test]
+    fn test_permission_prompt_buttons_round_trip() {
+        let option = |id: &str, kind: &str| PermissionOption {
+            id: id.to_string(),
+            label: id.to_string(),
+            kind: Some(kind.to_string()),
+            shortcut: None,
+            icon: None,
+            style: None,
+        };
+        let options = vec![
+            option("manual", "allow_once"),
+            option("deny", "reject_once"),
+        ];
+        let request = PermissionRequest {
+            request_id: "toolu:01",
+            tool_name: "Bash",
+            description: Some("  "),
+            raw_input: Some("echo ```hi```"),
+            options: &options,
+        };
+        assert_eq!(
+            permission_prompt_content(&request),
+            "**Permission needed:** `Bash`\n```\necho '''hi'''\n```"
+        );
+        assert_eq!(permission_choice(&options[1]).1, "Denied");
+        // Request ids may themselves contain colons.
+        assert_eq!(
+            parse_permission_custom_id("permission:toolu:01:1"),
+            Some(("toolu:01", 1))
+        );
+        assert_eq!(parse_permission_custom_id("user_input:r:q:1"), None);
+    }
+
+    #[test]
+    fn test_diff_summary_splits_long_file_lists() {
+        assert!(diff_summary_messages(Some("main"), "").is_empty());
+
+        let messages = diff_summary_messages(Some("feat/x"), "3\t1\tsrc/a.rs\n-\t-\tlogo.png\n");
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].starts_with("**Changes:** 2 files changed, +3 -1 on `feat/x`\n```"));
+        assert!(messages[0].contains("binary       logo.png"));
+
+        let numstat: String = (0..60)
+            .map(|i| format!("1\t0\tsrc/{}/{}.rs\n", "nested".repeat(8), i))
+            .collect();
+        let messages = diff_summary_messages(None, &numstat);
+        // The header shares a message with the first block when both fit.
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("**Changes:** 60 files changed, +60 -0\n```"));
+        assert!(messages[1].starts_with("```") && messages[1].ends_with("```"));
+        assert!(messages
+            .iter()
+            .all(|message| message.len() <= MAX_MESSAGE_CHARS));
+        assert!(messages.last().unwrap().contains("... and 10 more"));
+    }
+    #[test]
+    fn test_image_type_must_match_contents() {
+        assert_eq!(
+            accepted_image_type("image/PNG; charset=binary"),
+            Some("image/png")
+        );
+        assert_eq!(accepted_image_type("image/svg+xml"), None);
+        assert_eq!(accepted_image_type("application/pdf"), None);
+
+        assert_eq!(
+            sniff_image_type(b"\x89PNG\r\n\x1a\n...."),
+            Some("image/png")
+        );
+        assert_eq!(
+            sniff_image_type(&[0xFF, 0xD8, 0xFF, 0xE0]),
+            Some("image/jpeg")
+        );
+        assert_eq!(
+            sniff_image_type(b"RIFF\0\0\0\0WEBPVP8 "),
+            Some("image/webp")
+        );
+        assert_eq!(sniff_image_type(b"RIFF\0\0\0\0WAVEfmt "), None);
+        assert_eq!(sniff_image_type(b"<svg xmlns="), None);
+    }
+    #[test]
+    fn test_validate_channel_projects() {
+        let project = std::env::temp_dir().to_string_lossy().to_string();
+        let allowlist = vec![project.clone()];
+        let projects =
+            |channel: &str, path: &str| HashMap::from([(channel.to_string(), path.to_string())]);
+
+        let validated =
+            validate_channel_projects(&projects(" 123 ", &format!("{project} ")), &allowlist)
+                .unwrap();
+        assert_eq!(validated.get("123"), Some(&project));
+        assert!(validate_channel_projects(&projects(" ", ""), &allowlist)
+            .unwrap()
+            .is_empty());
+
+        assert!(validate_channel_projects(&projects("backend", &project), &allowlist).is_err());
+        let missing = format!("{project}/phantom-missing-project");
+        assert!(validate_channel_projects(&projects("123", &missing), &allowlist).is_err());
+        assert!(validate_channel_projects(&projects("123", &project), &[]).is_err());
+    }
+    #[test]
+    fn test_status_message_tracks_progress() {
+        let mut slot = StatusSlot::default();
+        let started = ProgressKind::GenerationStarted {
+            agent_id: "codex".to_string(),
+            prompt: "fix tests".to_string(),
+            from_discord: false,
+        };
+        let (text, change) = status_for_progress(&started).unwrap();
+        slot.apply(text, change, 1_000);
+        let tool = ProgressKind::ToolCall {
+            name: "Bash".to_string(),
+        };
+        let (text, change) = status_for_progress(&tool).unwrap();
+        slot.apply(text, change, 5_000);
+        assert!(slot.dirty && slot.finished_at.is_none());
+
+        let completed = ProgressKind::Completed {
+            summary: "Fixed the flaky test".to_string(),
+            reply: String::new(),
+        };
+        let (text, change) = status_for_progress(&completed).unwrap();
+        slot.apply(text, change, 126_000);
+        assert_eq!(
+            slot.render(0.4213),
+            "**Status:** Fixed the flaky test\n**Elapsed:** 2m 5s\n**Cost:** $0.42"
+        );
+
+        let resolved = ProgressKind::PermissionResolved {
+            request_id: "r1".to_string(),
+            option_id: "allow".to_string(),
+        };
+        assert!(status_for_progress(&resolved).is_none());
+    }
+    #[test]
+    fn test_completion_dm_content() {
+        let link = "https://discord.com/channels/1/2";
+        assert_eq!(
+            completion_dm_content("Fix login", " Completed: tests pass\n", 1.5, Some(link)),
+            "**Fix login**\nCompleted: tests pass\nCost: $1.50\nhttps://discord.com/channels/1/2"
+        );
+        assert!(!completion_dm_content("Fix login", "Failed", 0.0, None).contains("https://"));
+    }
+    #[test]
+    fn test_reconnect_delay_backs_off_to_cap() {
+        assert_eq!(reconnect_delay(0), Duration::from_secs(2));
+        assert_eq!(reconnect_delay(3), Duration::from_secs(16));
+        assert_eq!(reconnect_delay(8), RECONNECT_MAX_DELAY);
+        assert_eq!(reconnect_delay(u32::MAX), RECONNECT_MAX_DELAY);
+        assert!(is_fatal_gateway_error(&serenity::Error::Gateway(
+            GatewayError::InvalidAuthentication
+        )));
+        assert!(!is_fatal_gateway_error(&serenity::Error::Gateway(
+            GatewayError::HeartbeatFailed
+        )));
+    }
 }
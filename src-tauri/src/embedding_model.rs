@@ -4,7 +4,7 @@
 //! we keep download logic and on-disk layout here so the rest of the app can
 //! reliably "ensure assets exist" without bundling them into the DMG.
 
-use crate::{local_asr_model, AppState};
+use crate::{local_asr_model, AppHandle, AppState};
 use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
@@ -13,7 +13,7 @@ use std::sync::{
     Arc,
 };
 use std::time::{Duration, Instant};
-use tauri::{AppHandle, Emitter, Manager, State};
+use tauri::{Emitter, Manager, State};
 
 pub const DEFAULT_EMBEDDING_MODEL_ID: &str = "all-minilm-l6-v2";
 pub const EMBEDDING_STATUS_EVENT: &str = "EmbeddingModelStatus";
@@ -0,0 +1,347 @@
+//! Detects edits made to a task worktree by something other than its agent (usually the
+//! user's editor) while a generation is running, so they aren't silently clobbered.
+//!
+//! A change counts as the agent's when a tool call named the file, or when it happened
+//! while one of the agent's shell commands was running. Everything else is reported after
+//! a short settle delay, which gives a late-arriving tool call the chance to claim it.
+
+use notify::event::ModifyKind;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+/// Dependency, build and VCS directories whose churn is never reported.
+const IGNORED_DIRS: &[&str] = &[
+    ".git",
+    "node_modules",
+    "target",
+    "dist",
+    "build",
+    ".next",
+    ".venv",
+    "__pycache__",
+];
+/// Distinct paths tracked per generation; past this the report is marked truncated.
+const MAX_TRACKED_PATHS: usize = 200;
+const SETTLE_DELAY: Duration = Duration::from_secs(2);
+const FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Default)]
+struct Tracker {
+    agent_paths: HashSet<String>,
+    shell_running: bool,
+    pending: HashMap<String, Instant>,
+    reported: HashSet<String>,
+    truncated: bool,
+    truncation_reported: bool,
+}
+
+impl Tracker {
+    fn record_change(&mut self, rel: String, now: Instant) {
+        if self.shell_running {
+            self.agent_paths.insert(rel);
+            return;
+        }
+        if self.reported.contains(&rel) || self.pending.contains_key(&rel) {
+            return;
+        }
+        if self.pending.len() + self.reported.len() >= MAX_TRACKED_PATHS {
+            self.truncated = true;
+            return;
+        }
+        self.pending.insert(rel, now);
+    }
+
+    /// Settled changes no tool call claimed; `force` takes everything (watcher stopping).
+    fn take_external(&mut self, now: Instant, force: bool) -> (Vec<String>, bool) {
+        let settled: Vec<String> = self
+            .pending
+            .iter()
+            .filter(|(_, at)| force || now.duration_since(**at) >= SETTLE_DELAY)
+            .map(|(path, _)| path.clone())
+            .collect();
+        let mut files = BTreeSet::new();
+        for path in settled {
+            self.pending.remove(&path);
+            if !self.agent_paths.contains(&path) {
+                self.reported.insert(path.clone());
+                files.insert(path);
+            }
+        }
+        let newly_truncated = self.truncated && !self.truncation_reported;
+        self.truncation_reported |= newly_truncated;
+        (files.into_iter().collect(), newly_truncated)
+    }
+}
+
+/// Watches one worktree for the duration of a generation. Dropping it stops the watch
+/// and reports any changes still waiting to settle.
+pub struct ExternalEditWatcher {
+    roots: Vec<PathBuf>,
+    tracker: Arc<StdMutex<Tracker>>,
+    stopped: Arc<AtomicBool>,
+    _watcher: RecommendedWatcher,
+}
+
+impl ExternalEditWatcher {
+    /// `on_detect(files, truncated)` runs on a background thread.
+    pub fn start<F>(root: &Path, on_detect: F) -> Result<Self, String>
+    where
+        F: Fn(Vec<String>, bool) + Send + 'static,
+    {
+        // Some platforms report canonical paths (e.g. /private/var on macOS).
+        let mut roots = vec![root.to_path_buf()];
+        if let Ok(canonical) = std::fs::canonicalize(root) {
+            if canonical != root {
+                roots.push(canonical);
+            }
+        }
+
+        let tracker = Arc::new(StdMutex::new(Tracker::default()));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let event_tracker = tracker.clone();
+        let event_roots = roots.clone();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let Ok(event) = res else {
+                return;
+            };
+            if !is_content_change(&event.kind) {
+                return;
+            }
+            let now = Instant::now();
+            let Ok(mut tracker) = event_tracker.lock() else {
+                return;
+            };
+            for path in &event.paths {
+                if let Some(rel) = relative_path(&event_roots, path) {
+                    tracker.record_change(rel, now);
+                }
+            }
+        })
+        .map_err(|e| e.to_string())?;
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .map_err(|e| e.to_string())?;
+
+        let flush_tracker = tracker.clone();
+        let flush_stopped = stopped.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(FLUSH_INTERVAL);
+            let force = flush_stopped.load(Ordering::SeqCst);
+            let (files, truncated) = match flush_tracker.lock() {
+                Ok(mut tracker) => tracker.take_external(Instant::now(), force),
+                Err(_) => return,
+            };
+            if !files.is_empty() || truncated {
+                on_detect(files, truncated);
+            }
+            if force {
+                return;
+            }
+        });
+
+        Ok(Self {
+            roots,
+            tracker,
+            stopped,
+            _watcher: watcher,
+        })
+    }
+
+    /// Claim the files a tool call names; shell-like tools claim everything they touch.
+    pub fn record_tool_call(&self, name: &str, arguments: &str) {
+        let paths = tool_call_paths(arguments);
+        let Ok(mut tracker) = self.tracker.lock() else {
+            return;
+        };
+        if is_shell_tool(name) {
+            tracker.shell_running = true;
+        }
+        for path in paths {
+            let rel = match Path::new(&path).is_absolute() {
+                true => relative_path(&self.roots, Path::new(&path)),
+                false => normalize_relative(Path::new(&path)),
+            };
+            if let Some(rel) = rel {
+                tracker.agent_paths.insert(rel);
+            }
+        }
+    }
+
+    pub fn record_tool_return(&self) {
+        if let Ok(mut tracker) = self.tracker.lock() {
+            tracker.shell_running = false;
+        }
+    }
+}
+
+impl Drop for ExternalEditWatcher {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+}
+
+fn is_content_change(kind: &EventKind) -> bool {
+    match kind {
+        EventKind::Create(_) | EventKind::Remove(_) => true,
+        EventKind::Modify(ModifyKind::Metadata(_)) => false,
+        EventKind::Modify(_) => true,
+        _ => false,
+    }
+}
+
+fn is_shell_tool(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    ["bash", "shell", "exec", "command", "terminal"]
+        .iter()
+        .any(|needle| name.contains(needle))
+}
+
+/// Repo-relative path with `/` separators, or None outside the roots or in ignored dirs.
+fn relative_path(roots: &[PathBuf], path: &Path) -> Option<String> {
+    let rel = roots.iter().find_map(|root| path.strip_prefix(root).ok())?;
+    normalize_relative(rel)
+}
+
+fn normalize_relative(path: &Path) -> Option<String> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::Normal(part) => {
+                let part = part.to_string_lossy();
+                if IGNORED_DIRS.contains(&part.as_ref()) {
+                    return None;
+                }
+                parts.push(part.to_string());
+            }
+            _ => return None,
+        }
+    }
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join("/"))
+    }
+}
+
+/// File paths named in tool-call arguments: path-like fields, Codex `changes` maps, and
+/// `*** Update File:` style patch headers.
+fn tool_call_paths(arguments: &str) -> Vec<String> {
+    fn collect(value: &serde_json::Value, out: &mut Vec<String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map {
+                    match (key.as_str(), value) {
+                        (
+                            "path" | "file_path" | "filePath" | "notebook_path",
+                            serde_json::Value::String(path),
+                        ) => {
+                            out.push(path.clone());
+                        }
+                        ("changes", serde_json::Value::Object(changes)) => {
+                            out.extend(changes.keys().cloned());
+                            collect(value, out);
+                        }
+                        (_, serde_json::Value::String(text)) => out.extend(patch_paths(text)),
+                        _ => collect(value, out),
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => items.iter().for_each(|item| collect(item, out)),
+            serde_json::Value::String(text) => out.extend(patch_paths(text)),
+            _ => {}
+        }
+    }
+
+    let mut paths = Vec::new();
+    match serde_json::from_str::<serde_json::Value>(arguments) {
+        Ok(value) => collect(&value, &mut paths),
+        Err(_) => paths.extend(patch_paths(arguments)),
+    }
+    paths
+}
+
+fn patch_paths(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| {
+            [
+                "*** Add File: ",
+                "*** Update File: ",
+                "*** Delete File: ",
+                "*** Move to: ",
+            ]
+            .iter()
+            .find_map(|prefix| line.strip_prefix(prefix))
+        })
+        .map(|path| path.trim().to_string())
+        .filter(|path| !path.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_call_paths() {
+        let args = r#"{"file_path":"/repo/src/a.rs","content":"x"}"#;
+        assert_eq!(tool_call_paths(args), vec!["/repo/src/a.rs"]);
+        let patch = r#"{"input":"*** Begin Patch\n*** Update File: src/b.rs\n@@\n*** Add File: c.rs\n*** End Patch"}"#;
+        assert_eq!(tool_call_paths(patch), vec!["src/b.rs", "c.rs"]);
+        let codex = r#"{"changes":{"src/d.rs":{"type":"update"}}}"#;
+        assert_eq!(tool_call_paths(codex), vec!["src/d.rs"]);
+    }
+
+    #[test]
+    fn test_tracker_reports_only_unclaimed_settled_changes() {
+        let roots = vec![PathBuf::from("/repo")];
+        assert_eq!(
+            relative_path(&roots, Path::new("/repo/node_modules/x/index.js")),
+            None
+        );
+        assert_eq!(
+            relative_path(&roots, Path::new("/repo/src/main.rs")).as_deref(),
+            Some("src/main.rs")
+        );
+
+        let start = Instant::now();
+        let mut tracker = Tracker::default();
+        tracker.record_change("src/agent.rs".to_string(), start);
+        tracker.record_change("src/user.rs".to_string(), start);
+        tracker.shell_running = true;
+        tracker.record_change("Cargo.lock".to_string(), start);
+        tracker.shell_running = false;
+        // A tool call naming the file may arrive after the write.
+        tracker.agent_paths.insert("src/agent.rs".to_string());
+
+        assert_eq!(tracker.take_external(start, false), (Vec::new(), false));
+        assert_eq!(
+            tracker.take_external(start + SETTLE_DELAY, false),
+            (vec!["src/user.rs".to_string()], false)
+        );
+        // Already reported: further saves don't re-report.
+        tracker.record_change("src/user.rs".to_string(), start + SETTLE_DELAY);
+        assert!(tracker
+            .take_external(start + SETTLE_DELAY * 2, true)
+            .0
+            .is_empty());
+    }
+
+    #[test]
+    fn test_tracker_caps_tracked_paths() {
+        let now = Instant::now();
+        let mut tracker = Tracker::default();
+        for i in 0..MAX_TRACKED_PATHS + 50 {
+            tracker.record_change(format!("gen/{i}.txt"), now);
+        }
+        let (files, truncated) = tracker.take_external(now, true);
+        assert_eq!(files.len(), MAX_TRACKED_PATHS);
+        assert!(truncated);
+        assert_eq!(tracker.take_external(now, true), (Vec::new(), false));
+    }
+}
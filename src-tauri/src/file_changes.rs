@@ -0,0 +1,269 @@
+//! Files a generation changed, derived from its edit/write tool calls as they stream in, so
+//! the UI can show them before any git diff is computed.
+//!
+//! Only tool calls whose schema names the file are counted; anything else (shell commands,
+//! unknown tools, paths outside the worktree) yields no change rather than a guessed one.
+
+use serde::Serialize;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Deleted,
+}
+
+impl ChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Deleted => "deleted",
+        }
+    }
+
+    /// Codex `kind` values (`add`/`update`/`delete`, possibly as `{"type": ...}`).
+    fn from_codex(value: &serde_json::Value) -> Option<Self> {
+        let kind = value
+            .as_str()
+            .or_else(|| value.get("type").and_then(|t| t.as_str()))?;
+        match kind {
+            "add" | "create" | "created" => Some(ChangeKind::Created),
+            "update" | "modify" | "modified" => Some(ChangeKind::Modified),
+            "delete" | "deleted" => Some(ChangeKind::Deleted),
+            _ => None,
+        }
+    }
+
+    /// A later change to a file already changed this generation.
+    fn then(self, next: ChangeKind) -> ChangeKind {
+        match (self, next) {
+            (ChangeKind::Created, ChangeKind::Modified) => ChangeKind::Created,
+            (ChangeKind::Deleted, ChangeKind::Created) => ChangeKind::Modified,
+            (_, next) => next,
+        }
+    }
+}
+
+/// A file changed by the agent; `path` is relative to the worktree with `/` separators.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileChange {
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+/// Changed files of one generation, deduped by path in the order first touched.
+#[derive(Debug)]
+pub struct GenerationChanges {
+    /// Identifies the generation in `task_file_changes` (ms since the epoch)
+    pub started_at: i64,
+    root: Option<PathBuf>,
+    files: Vec<FileChange>,
+}
+
+impl GenerationChanges {
+    pub fn new(root: Option<PathBuf>) -> Self {
+        Self {
+            started_at: chrono::Utc::now().timestamp_millis(),
+            root,
+            files: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Record the files a tool call changes. Returns the ones that are new or whose kind
+    /// changed, i.e. what is worth emitting and saving.
+    pub fn record_tool_call(&mut self, name: &str, arguments: &str) -> Vec<FileChange> {
+        let mut updated: Vec<FileChange> = Vec::new();
+        for (path, kind) in tool_call_changes(name, arguments) {
+            let Some(path) = self.relative_path(&path) else {
+                continue;
+            };
+            let kind = kind.unwrap_or_else(|| self.write_kind(&path));
+            let change = match self.files.iter_mut().find(|file| file.path == path) {
+                Some(file) if file.kind.then(kind) == file.kind => continue,
+                Some(file) => {
+                    file.kind = file.kind.then(kind);
+                    file.clone()
+                }
+                None => {
+                    let change = FileChange { path, kind };
+                    self.files.push(change.clone());
+                    change
+                }
+            };
+            updated.retain(|file| file.path != change.path);
+            updated.push(change);
+        }
+        updated
+    }
+
+    /// Whole-file writes create the file unless it already exists.
+    fn write_kind(&self, path: &str) -> ChangeKind {
+        match &self.root {
+            Some(root) if !root.join(path).exists() => ChangeKind::Created,
+            _ => ChangeKind::Modified,
+        }
+    }
+
+    fn relative_path(&self, path: &str) -> Option<String> {
+        let path = Path::new(path);
+        let relative = if path.is_absolute() {
+            path.strip_prefix(self.root.as_ref()?).ok()?
+        } else {
+            path
+        };
+        let mut parts = Vec::new();
+        for component in relative.components() {
+            match component {
+                Component::CurDir => {}
+                Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+                _ => return None,
+            }
+        }
+        (!parts.is_empty()).then(|| parts.join("/"))
+    }
+}
+
+/// `(path, kind)` pairs named by a tool call. A None kind is a whole-file write, which is a
+/// create or a modify depending on whether the file exists.
+fn tool_call_changes(name: &str, arguments: &str) -> Vec<(String, Option<ChangeKind>)> {
+    let name = name.to_ascii_lowercase();
+    // Kind of the file named by the tool's own path field, if it is a file tool at all
+    let path_kind = if name.contains("delete") || name.contains("remove") {
+        Some(Some(ChangeKind::Deleted))
+    } else if name.contains("edit") || name.contains("replace") {
+        Some(Some(ChangeKind::Modified))
+    } else if name.contains("write") || name.contains("create") {
+        Some(None)
+    } else {
+        None
+    };
+
+    let mut changes = Vec::new();
+    match serde_json::from_str::<serde_json::Value>(arguments) {
+        Ok(serde_json::Value::Object(args)) => {
+            if let Some(path_kind) = path_kind {
+                let path = ["path", "file_path", "filePath", "notebook_path"]
+                    .iter()
+                    .find_map(|key| args.get(*key).and_then(|v| v.as_str()));
+                if let Some(path) = path {
+                    let kind = args.get("kind").and_then(ChangeKind::from_codex);
+                    changes.push((path.to_string(), kind.or(path_kind)));
+                }
+            }
+            if let Some(serde_json::Value::Object(files)) = args.get("changes") {
+                for (path, change) in files {
+                    let kind = change
+                        .get("kind")
+                        .or_else(|| change.get("type"))
+                        .and_then(ChangeKind::from_codex)
+                        .unwrap_or(ChangeKind::Modified);
+                    changes.push((path.clone(), Some(kind)));
+                }
+            }
+            for value in args.values() {
+                if let Some(text) = value.as_str() {
+                    changes.extend(patch_changes(text));
+                }
+            }
+        }
+        Ok(_) => {}
+        Err(_) => changes.extend(patch_changes(arguments)),
+    }
+    changes
+}
+
+/// Files named by `*** Add File:` style patch headers.
+fn patch_changes(text: &str) -> Vec<(String, Option<ChangeKind>)> {
+    const HEADERS: [(&str, ChangeKind); 4] = [
+        ("*** Add File: ", ChangeKind::Created),
+        ("*** Update File: ", ChangeKind::Modified),
+        ("*** Delete File: ", ChangeKind::Deleted),
+        ("*** Move to: ", ChangeKind::Created),
+    ];
+    text.lines()
+        .filter_map(|line| {
+            HEADERS.iter().find_map(|(prefix, kind)| {
+                let path = line.strip_prefix(prefix)?.trim();
+                (!path.is_empty()).then(|| (path.to_string(), Some(*kind)))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_calls_become_deduped_file_changes() {
+        let root = std::env::temp_dir().join(format!(
+            "phantom-harness-file-changes-{}",
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/existing.rs"), "").unwrap();
+        let mut changes = GenerationChanges::new(Some(root.clone()));
+        let abs = |rel: &str| root.join(rel).to_string_lossy().to_string();
+
+        let write_new = format!(r#"{{"file_path":"{}","content":"x"}}"#, abs("src/new.rs"));
+        assert_eq!(
+            changes.record_tool_call("Write", &write_new),
+            vec![FileChange {
+                path: "src/new.rs".to_string(),
+                kind: ChangeKind::Created
+            }]
+        );
+        // Editing a file created this generation keeps it "created".
+        let edit_new = format!(r#"{{"file_path":"{}"}}"#, abs("src/new.rs"));
+        assert!(changes.record_tool_call("Edit", &edit_new).is_empty());
+
+        let write_existing = r#"{"path":"src/existing.rs"}"#;
+        assert_eq!(
+            changes.record_tool_call("write_file", write_existing)[0].kind,
+            ChangeKind::Modified
+        );
+
+        let patch = r#"{"input":"*** Begin Patch\n*** Delete File: old.rs\n*** Add File: ./docs/a.md\n*** End Patch"}"#;
+        let patched: Vec<(String, ChangeKind)> = changes
+            .record_tool_call("apply_patch", patch)
+            .into_iter()
+            .map(|change| (change.path, change.kind))
+            .collect();
+        assert_eq!(
+            patched,
+            vec![
+                ("old.rs".to_string(), ChangeKind::Deleted),
+                ("docs/a.md".to_string(), ChangeKind::Created)
+            ]
+        );
+
+        let codex = r#"{"path":"lib.rs","kind":{"type":"delete"}}"#;
+        assert_eq!(
+            changes.record_tool_call("edit_file", codex)[0].kind,
+            ChangeKind::Deleted
+        );
+
+        // Reads, shell commands and paths outside the worktree change nothing.
+        assert!(changes
+            .record_tool_call("Read", r#"{"file_path":"src/x.rs"}"#)
+            .is_empty());
+        assert!(changes
+            .record_tool_call("Bash", r#"{"command":"rm -rf build"}"#)
+            .is_empty());
+        assert!(changes
+            .record_tool_call("Edit", r#"{"file_path":"/elsewhere/y.rs"}"#)
+            .is_empty());
+        assert!(changes
+            .record_tool_call("Edit", r#"{"file_path":"../y.rs"}"#)
+            .is_empty());
+        assert_eq!(changes.len(), 5);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,331 @@
+//! Line-level diffing (Myers) used for plan revisions and review diffs.
+
+/// Beyond this many edits the diff degrades to "replace everything" to bound memory.
+const MAX_EDIT_DISTANCE: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffOp {
+    Equal { old_index: usize, new_index: usize },
+    Delete { old_index: usize },
+    Insert { new_index: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnifiedDiff {
+    pub text: String,
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// Split text into lines, treating `\r\n` and `\n` the same.
+pub fn split_lines(text: &str) -> Vec<&str> {
+    text.lines().collect()
+}
+
+/// Compute the edit script turning `old` into `new`.
+pub fn diff_lines(old: &[&str], new: &[&str]) -> Vec<DiffOp> {
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut ops: Vec<DiffOp> = (0..prefix)
+        .map(|i| DiffOp::Equal {
+            old_index: i,
+            new_index: i,
+        })
+        .collect();
+
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+    let middle = myers(old_mid, new_mid).unwrap_or_else(|| {
+        (0..old_mid.len())
+            .map(|i| DiffOp::Delete { old_index: i })
+            .chain((0..new_mid.len()).map(|i| DiffOp::Insert { new_index: i }))
+            .collect()
+    });
+    ops.extend(middle.into_iter().map(|op| match op {
+        DiffOp::Equal {
+            old_index,
+            new_index,
+        } => DiffOp::Equal {
+            old_index: old_index + prefix,
+            new_index: new_index + prefix,
+        },
+        DiffOp::Delete { old_index } => DiffOp::Delete {
+            old_index: old_index + prefix,
+        },
+        DiffOp::Insert { new_index } => DiffOp::Insert {
+            new_index: new_index + prefix,
+        },
+    }));
+
+    let old_tail = old.len() - suffix;
+    let new_tail = new.len() - suffix;
+    ops.extend((0..suffix).map(|i| DiffOp::Equal {
+        old_index: old_tail + i,
+        new_index: new_tail + i,
+    }));
+    ops
+}
+
+/// Myers' O(ND) diff. Returns `None` when the edit distance exceeds `MAX_EDIT_DISTANCE`.
+fn myers(old: &[&str], new: &[&str]) -> Option<Vec<DiffOp>> {
+    let n = old.len() as isize;
+    let m = new.len() as isize;
+    let max = (n + m) as usize;
+    if max == 0 {
+        return Some(Vec::new());
+    }
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    // trace[d] holds v for diagonals -(d+1)..=(d+1) before round d.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    let mut found = false;
+    for d in 0..=(max.min(MAX_EDIT_DISTANCE) as isize) {
+        let lo = (offset - d - 1) as usize;
+        let hi = (offset + d + 1) as usize;
+        trace.push(v[lo..=hi].to_vec());
+        let mut k = -d;
+        while k <= d {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                found = true;
+                break;
+            }
+            k += 2;
+        }
+        if found {
+            break;
+        }
+    }
+    if !found {
+        return None;
+    }
+
+    let mut ops = Vec::new();
+    let mut x = n;
+    let mut y = m;
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let at = |k: isize| v[(k + d + 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = at(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            ops.push(DiffOp::Equal {
+                old_index: x as usize,
+                new_index: y as usize,
+            });
+        }
+        if d > 0 {
+            if x == prev_x {
+                ops.push(DiffOp::Insert {
+                    new_index: (y - 1) as usize,
+                });
+            } else {
+                ops.push(DiffOp::Delete {
+                    old_index: (x - 1) as usize,
+                });
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    ops.reverse();
+    Some(ops)
+}
+
+/// Render a unified diff (3 lines of context) between two texts.
+pub fn unified_diff(
+    old_text: &str,
+    new_text: &str,
+    old_label: &str,
+    new_label: &str,
+) -> UnifiedDiff {
+    const CONTEXT: usize = 3;
+    let old = split_lines(old_text);
+    let new = split_lines(new_text);
+    let ops = diff_lines(&old, &new);
+
+    let added = ops
+        .iter()
+        .filter(|op| matches!(op, DiffOp::Insert { .. }))
+        .count();
+    let removed = ops
+        .iter()
+        .filter(|op| matches!(op, DiffOp::Delete { .. }))
+        .count();
+    if added == 0 && removed == 0 {
+        return UnifiedDiff {
+            text: String::new(),
+            added,
+            removed,
+        };
+    }
+
+    // Line positions before each op, for hunk headers.
+    let mut old_pos = Vec::with_capacity(ops.len() + 1);
+    let mut new_pos = Vec::with_capacity(ops.len() + 1);
+    let (mut o, mut n) = (0usize, 0usize);
+    for op in &ops {
+        old_pos.push(o);
+        new_pos.push(n);
+        match op {
+            DiffOp::Equal { .. } => {
+                o += 1;
+                n += 1;
+            }
+            DiffOp::Delete { .. } => o += 1,
+            DiffOp::Insert { .. } => n += 1,
+        }
+    }
+    old_pos.push(o);
+    new_pos.push(n);
+
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, DiffOp::Equal { .. }))
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut text = format!("--- {}\n+++ {}\n", old_label, new_label);
+    let mut i = 0;
+    while i < changes.len() {
+        let start = changes[i].saturating_sub(CONTEXT);
+        let mut last = changes[i];
+        while i + 1 < changes.len() && changes[i + 1] - last <= CONTEXT * 2 {
+            i += 1;
+            last = changes[i];
+        }
+        let end = (last + CONTEXT + 1).min(ops.len());
+        let old_len = old_pos[end] - old_pos[start];
+        let new_len = new_pos[end] - new_pos[start];
+        let old_start = if old_len == 0 {
+            old_pos[start]
+        } else {
+            old_pos[start] + 1
+        };
+        let new_start = if new_len == 0 {
+            new_pos[start]
+        } else {
+            new_pos[start] + 1
+        };
+        text.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start, old_len, new_start, new_len
+        ));
+        for op in &ops[start..end] {
+            match *op {
+                DiffOp::Equal { old_index, .. } => {
+                    text.push(' ');
+                    text.push_str(old[old_index]);
+                }
+                DiffOp::Delete { old_index } => {
+                    text.push('-');
+                    text.push_str(old[old_index]);
+                }
+                DiffOp::Insert { new_index } => {
+                    text.push('+');
+                    text.push_str(new[new_index]);
+                }
+            }
+            text.push('\n');
+        }
+        i += 1;
+    }
+
+    UnifiedDiff {
+        text,
+        added,
+        removed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(old: &[&str], new: &[&str], ops: &[DiffOp]) -> (Vec<String>, Vec<String>) {
+        let mut rebuilt_old = Vec::new();
+        let mut rebuilt_new = Vec::new();
+        for op in ops {
+            match *op {
+                DiffOp::Equal {
+                    old_index,
+                    new_index,
+                } => {
+                    assert_eq!(old[old_index], new[new_index]);
+                    rebuilt_old.push(old[old_index].to_string());
+                    rebuilt_new.push(new[new_index].to_string());
+                }
+                DiffOp::Delete { old_index } => rebuilt_old.push(old[old_index].to_string()),
+                DiffOp::Insert { new_index } => rebuilt_new.push(new[new_index].to_string()),
+            }
+        }
+        (rebuilt_old, rebuilt_new)
+    }
+
+    #[test]
+    fn test_diff_lines_reconstructs_both_sides() {
+        let old = vec!["a", "b", "c", "d", "e", "f"];
+        let new = vec!["a", "x", "c", "d", "f", "g"];
+        let ops = diff_lines(&old, &new);
+        let (rebuilt_old, rebuilt_new) = apply(&old, &new, &ops);
+        assert_eq!(rebuilt_old, old);
+        assert_eq!(rebuilt_new, new);
+        let edits = ops
+            .iter()
+            .filter(|op| !matches!(op, DiffOp::Equal { .. }))
+            .count();
+        assert_eq!(edits, 4);
+    }
+
+    #[test]
+    fn test_unified_diff_counts_and_hunks() {
+        let old = "one\ntwo\nthree\nfour\n";
+        let new = "one\n2\nthree\nfour\nfive\n";
+        let diff = unified_diff(old, new, "a", "b");
+        assert_eq!(diff.added, 2);
+        assert_eq!(diff.removed, 1);
+        assert_eq!(
+            diff.text,
+            "--- a\n+++ b\n@@ -1,4 +1,5 @@\n one\n-two\n+2\n three\n four\n+five\n"
+        );
+    }
+
+    #[test]
+    fn test_unified_diff_ignores_line_endings() {
+        let diff = unified_diff("a\r\nb\r\n", "a\nb\n", "a", "b");
+        assert_eq!(diff.added, 0);
+        assert_eq!(diff.removed, 0);
+        assert!(diff.text.is_empty());
+    }
+}
@@ -5,11 +5,11 @@
 //! engine-agnostic model catalog and "active local model" state for the UI and
 //! transcription subsystems.
 
-use crate::{parakeet_model, whisper_model, AppState};
+use crate::{parakeet_model, whisper_model, AppHandle, AppState};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use tauri::{AppHandle, Emitter, State};
+use tauri::{Emitter, State};
 
 pub const LOCAL_ASR_STATUS_EVENT: &str = "LocalAsrModelStatus";
 pub const LOCAL_ASR_PROGRESS_EVENT: &str = "LocalAsrModelProgress";
@@ -37,10 +37,16 @@ pub struct LocalUsageTotals {
     pub last30_days_tokens: i64,
     pub average_daily_tokens: i64,
     pub cache_hit_rate_percent: f64,
+    /// Cost at API rates.
     #[serde(default)]
     pub total_cost: f64,
     pub peak_day: Option<String>,
     pub peak_day_tokens: i64,
+    /// `total_cost`, or 0 when the current login is a subscription.
+    #[serde(default)]
+    pub billed_cost: f64,
+    #[serde(default)]
+    pub auth_method: Option<String>,
 }
 
 /// Token usage breakdown by model.
@@ -135,11 +141,19 @@ const MAX_ACTIVITY_GAP_MS: i64 = 2 * 60 * 1000;
 
 /// Tauri command to fetch local usage statistics.
 #[tauri::command]
-pub async fn local_usage_snapshot(days: Option<u32>) -> Result<LocalUsageSnapshot, String> {
+pub async fn local_usage_snapshot(
+    days: Option<u32>,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<LocalUsageSnapshot, String> {
     let days = days.unwrap_or(30).clamp(1, 90);
-    let snapshot = tokio::task::spawn_blocking(move || scan_local_usage(days))
+    let auth_method = crate::current_auth_method("codex", &*state.settings.lock().await);
+    let mut snapshot = tokio::task::spawn_blocking(move || scan_local_usage(days))
         .await
         .map_err(|err| err.to_string())??;
+    if crate::pricing::is_subscription_auth(Some(auth_method)) {
+        snapshot.totals.billed_cost = 0.0;
+    }
+    snapshot.totals.auth_method = Some(auth_method.to_string());
     Ok(snapshot)
 }
 
@@ -285,6 +299,8 @@ fn build_snapshot(
             total_cost,
             peak_day,
             peak_day_tokens,
+            billed_cost: total_cost,
+            auth_method: None,
         },
         top_models,
     }
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use std::convert::Infallible;
+use std::future::Future;
 use std::net::SocketAddr;
+use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -8,21 +10,81 @@ use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Method, Request, Response, Server, StatusCode};
 use serde::Deserialize;
 use serde_json::{json, Value};
-use tauri::AppHandle;
 use tokio::sync::{mpsc, Mutex};
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use tokio_stream::StreamExt;
 use url::form_urlencoded;
 
 use crate::db;
+use crate::progress::ProgressEvent;
 use crate::worktree;
 use crate::{
-    create_agent_session_internal, delete_task_internal, get_task_history_internal,
-    project_path_allowed, resolve_repo_root, send_chat_message_internal, soft_stop_task_internal,
-    start_task_internal, stop_task_internal, AppState, CreateAgentPayload,
+    create_agent_session_internal, delete_task_internal, ensure_discord_thread,
+    get_task_history_internal, project_path_allowed, resolve_repo_root, send_chat_message_internal,
+    soft_stop_task_internal, start_task_internal, stop_task_internal, summarize,
+    task_launch_defaults, AppHandle, AppState, CreateAgentPayload, TAURI_COMMANDS,
 };
 
 const DEFAULT_PROTOCOL_VERSION: &str = "2025-03-26";
+/// Version of the headless `/api/v1` surface; bump on breaking changes.
+const HEADLESS_API_VERSION: u32 = 1;
+const HEADLESS_API_PREFIX: &str = "/api/v1/commands";
+/// Reason given for registered Tauri commands that have no headless handler or UI-only mark.
+const NOT_HEADLESS_REASON: &str = "not exposed to headless callers";
+
+type CommandFuture = Pin<Box<dyn Future<Output = Result<Value, String>> + Send>>;
+
+enum CommandHandler {
+    Run(fn(Value, McpServerState) -> CommandFuture),
+    /// Tauri commands that only make sense with a window in front of the user.
+    /// Headless callers get a `requires_ui` error instead of an unknown-command miss.
+    RequiresUi(&'static str),
+}
+
+struct CommandSpec {
+    name: &'static str,
+    description: &'static str,
+    input_schema: fn() -> Value,
+    handler: CommandHandler,
+}
+
+#[derive(Debug, PartialEq)]
+enum CommandError {
+    Unknown(String),
+    RequiresUi {
+        command: String,
+        reason: &'static str,
+    },
+    Failed(String),
+}
+
+impl CommandError {
+    fn code(&self) -> &'static str {
+        match self {
+            CommandError::Unknown(_) => "unknown_command",
+            CommandError::RequiresUi { .. } => "requires_ui",
+            CommandError::Failed(_) => "command_failed",
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            CommandError::Unknown(name) => format!("Unknown tool: {name}"),
+            CommandError::RequiresUi { command, reason } => {
+                format!("{command} requires the Phantom UI ({reason})")
+            }
+            CommandError::Failed(err) => err.clone(),
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            CommandError::Unknown(_) => StatusCode::NOT_FOUND,
+            CommandError::RequiresUi { .. } => StatusCode::NOT_IMPLEMENTED,
+            CommandError::Failed(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+}
 
 #[derive(Clone)]
 pub(crate) struct McpConfig {
@@ -44,12 +106,14 @@ pub(crate) async fn start_mcp_server(
     config: McpConfig,
 ) -> anyhow::Result<()> {
     let addr = SocketAddr::from(([127, 0, 0, 1], config.port));
+    let shutdown = state.app_shutdown.clone();
     let server_state = McpServerState {
         app,
         state,
         config,
         sessions: Arc::new(Mutex::new(HashMap::new())),
     };
+    spawn_progress_notifier(&server_state);
 
     let make_svc = make_service_fn(move |_conn| {
         let server_state = server_state.clone();
@@ -78,7 +142,10 @@ pub(crate) async fn start_mcp_server(
         "[Harness] MCP server listening on http://127.0.0.1:{}/mcp",
         addr.port()
     );
-    server.serve(make_svc).await?;
+    server
+        .serve(make_svc)
+        .with_graceful_shutdown(async move { shutdown.cancelled().await })
+        .await?;
     Ok(())
 }
 
@@ -98,6 +165,19 @@ async fn handle_request(
     }
 
     match (req.method(), req.uri().path()) {
+        (&Method::GET, HEADLESS_API_PREFIX) => {
+            Ok(response_json(StatusCode::OK, headless_listing()))
+        }
+        (&Method::POST, path) if path.starts_with(HEADLESS_API_PREFIX) => {
+            let Some(command) = headless_command_from_path(path) else {
+                return Ok(response_json(
+                    StatusCode::NOT_FOUND,
+                    json!({"error": "not_found"}),
+                ));
+            };
+            let command = command.to_string();
+            Ok(handle_headless_command(req, command, server_state).await)
+        }
         (&Method::GET, "/sse") => Ok(handle_sse(req, server_state).await),
         (&Method::POST, "/mcp") => Ok(handle_mcp_post(req, server_state).await),
         (&Method::GET, "/mcp") => {
@@ -235,9 +315,14 @@ async fn handle_sse(req: Request<Body>, server_state: McpServerState) -> Respons
 
 async fn handle_streamable_sse(
     _req: Request<Body>,
-    _server_state: McpServerState,
+    server_state: McpServerState,
 ) -> Response<Body> {
     let (tx, rx) = mpsc::unbounded_channel();
+    // Registered only so progress notifications reach this stream
+    {
+        let mut sessions = server_state.sessions.lock().await;
+        sessions.insert(uuid::Uuid::new_v4().to_string(), tx.clone());
+    }
     spawn_keepalive(tx.clone());
 
     let stream = UnboundedReceiverStream::new(rx).map(Ok::<_, Infallible>);
@@ -302,6 +387,90 @@ async fn handle_mcp_post(req: Request<Body>, server_state: McpServerState) -> Re
     response_json(StatusCode::OK, json_response_for(responses))
 }
 
+/// `POST /api/v1/commands/<name>` with the tool arguments as the JSON body.
+async fn handle_headless_command(
+    req: Request<Body>,
+    command: String,
+    server_state: McpServerState,
+) -> Response<Body> {
+    let body_bytes = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(bytes) => bytes,
+        Err(_) => return response_json(StatusCode::BAD_REQUEST, json!({"error": "invalid_body"})),
+    };
+    if body_bytes.len() > 1_000_000 {
+        return response_json(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            json!({"error": "payload_too_large"}),
+        );
+    }
+    let arguments: Value = if body_bytes.iter().all(|b| b.is_ascii_whitespace()) {
+        json!({})
+    } else {
+        match serde_json::from_slice(&body_bytes) {
+            Ok(value) => value,
+            Err(_) => {
+                return response_json(StatusCode::BAD_REQUEST, json!({"error": "invalid_json"}))
+            }
+        }
+    };
+
+    match dispatch_command(&command, arguments, server_state).await {
+        Ok(result) => response_json(
+            StatusCode::OK,
+            json!({"version": HEADLESS_API_VERSION, "command": command, "result": result}),
+        ),
+        Err(err) => response_json(
+            err.status(),
+            json!({
+                "version": HEADLESS_API_VERSION,
+                "command": command,
+                "error": {"code": err.code(), "message": err.message()}
+            }),
+        ),
+    }
+}
+
+fn headless_command_from_path(path: &str) -> Option<&str> {
+    let name = path.strip_prefix(HEADLESS_API_PREFIX)?.strip_prefix('/')?;
+    let valid = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then_some(name)
+}
+
+/// Machine-readable listing of the headless surface: the runnable `COMMANDS`, and every
+/// other registered Tauri command (`TAURI_COMMANDS`) as requiring the UI.
+fn headless_listing() -> Value {
+    let ui_only: Vec<Value> = TAURI_COMMANDS
+        .iter()
+        .filter_map(|name| match find_command(name) {
+            Some(CommandSpec {
+                handler: CommandHandler::Run(_),
+                ..
+            }) => None,
+            Some(CommandSpec {
+                description,
+                handler: CommandHandler::RequiresUi(reason),
+                ..
+            }) => Some(json!({
+                "name": name,
+                "description": description,
+                "requiresUi": true,
+                "reason": reason,
+            })),
+            None => Some(json!({
+                "name": name,
+                "requiresUi": true,
+                "reason": NOT_HEADLESS_REASON,
+            })),
+        })
+        .collect();
+    json!({
+        "version": HEADLESS_API_VERSION,
+        "endpoint": format!("{HEADLESS_API_PREFIX}/{{name}}"),
+        "commands": tool_definitions(),
+        "uiOnly": ui_only,
+    })
+}
+
 async fn handle_rpc_payload(
     payload: Value,
     server_state: McpServerState,
@@ -430,6 +599,41 @@ fn sse_event(event: &str, data: &str) -> String {
     format!("event: {event}\ndata: {data}\n\n")
 }
 
+/// Forward generation progress to every open SSE stream as a
+/// `notifications/phantom/progress` notification, dropping streams whose client went away.
+fn spawn_progress_notifier(server_state: &McpServerState) {
+    let mut events = server_state.state.progress.subscribe();
+    let sessions = server_state.sessions.clone();
+    let shutdown = server_state.state.app_shutdown.clone();
+    tokio::spawn(async move {
+        loop {
+            let event = tokio::select! {
+                _ = shutdown.cancelled() => break,
+                event = events.recv() => event,
+            };
+            let event = match event {
+                Ok(event) => event,
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                    eprintln!("[Harness] MCP progress notifier skipped {skipped} events");
+                    continue;
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            };
+            let message = sse_event("message", &progress_notification(&event).to_string());
+            let mut sessions = sessions.lock().await;
+            sessions.retain(|_, sender| sender.send(message.clone()).is_ok());
+        }
+    });
+}
+
+fn progress_notification(event: &ProgressEvent) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/phantom/progress",
+        "params": event,
+    })
+}
+
 fn spawn_keepalive(sender: mpsc::UnboundedSender<String>) {
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(Duration::from_secs(15));
@@ -493,6 +697,29 @@ struct TaskIdArgs {
     task_id: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct TaskDefaultsArgs {
+    #[serde(default)]
+    agent_id: Option<String>,
+    #[serde(default)]
+    plan_mode: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DiscordThreadArgs {
+    task_id: String,
+    #[serde(default)]
+    intro_message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SummarizeArgs {
+    kind: String,
+    text: String,
+    #[serde(default)]
+    agent_id: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct SendMessageArgs {
     task_id: String,
@@ -520,35 +747,43 @@ async fn handle_tool_call(params: Value, server_state: McpServerState) -> Result
         .ok_or_else(|| "Missing tool name".to_string())?;
     let arguments = params.get("arguments").cloned().unwrap_or(Value::Null);
 
-    let result = match name {
-        // Superset-compatible tools
-        "create_task" => tool_create_task(arguments, server_state).await,
-        "update_task" => tool_update_task(arguments, server_state).await,
-        "list_tasks" => tool_list_tasks(server_state).await,
-        "get_task" => tool_get_task(arguments, server_state).await,
-        "delete_task" => tool_delete_task(arguments, server_state).await,
-        "list_task_statuses" => tool_list_task_statuses(server_state).await,
-        "create_workspace" => tool_create_workspace(arguments, server_state).await,
-        "switch_workspace" => tool_switch_workspace(arguments, server_state).await,
-        "delete_workspace" => tool_delete_workspace(arguments, server_state).await,
-        "list_workspaces" => tool_list_workspaces(server_state).await,
-        "navigate_to_workspace" => tool_navigate_workspace(arguments, server_state).await,
-        // Phantom-specific tools
-        "phantom_start_task" => tool_start_task(arguments, server_state).await,
-        "phantom_stop_task" => tool_stop_task(arguments, server_state).await,
-        "phantom_soft_stop_task" => tool_soft_stop_task(arguments, server_state).await,
-        "phantom_send_chat_message" => tool_send_chat_message(arguments, server_state).await,
-        "phantom_get_task_history" => tool_get_task_history(arguments, server_state).await,
-        "phantom_list_agents" => tool_list_agents(server_state).await,
-        _ => Err(format!("Unknown tool: {name}")),
-    };
-
-    match result {
+    match dispatch_command(name, arguments, server_state).await {
         Ok(value) => Ok(tool_result(value)),
-        Err(err) => Ok(tool_error(err)),
+        Err(err) => Ok(tool_error(err.message())),
     }
 }
 
+/// Shared by MCP `tools/call` and the headless HTTP API so both get the same validation.
+async fn dispatch_command(
+    name: &str,
+    arguments: Value,
+    server_state: McpServerState,
+) -> Result<Value, CommandError> {
+    let Some(command) = find_command(name) else {
+        return Err(if TAURI_COMMANDS.contains(&name) {
+            CommandError::RequiresUi {
+                command: name.to_string(),
+                reason: NOT_HEADLESS_REASON,
+            }
+        } else {
+            CommandError::Unknown(name.to_string())
+        });
+    };
+    match command.handler {
+        CommandHandler::Run(run) => run(arguments, server_state)
+            .await
+            .map_err(CommandError::Failed),
+        CommandHandler::RequiresUi(reason) => Err(CommandError::RequiresUi {
+            command: command.name.to_string(),
+            reason,
+        }),
+    }
+}
+
+fn find_command(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|command| command.name == name)
+}
+
 fn tool_result(value: Value) -> Value {
     let text = serde_json::to_string_pretty(&value).unwrap_or_else(|_| value.to_string());
     json!({
@@ -581,15 +816,7 @@ async fn tool_create_task(arguments: Value, server_state: McpServerState) -> Res
         .or_else(|| settings.task_last_agent.clone())
         .unwrap_or_else(|| "codex".to_string());
 
-    let plan_mode = args
-        .plan_mode
-        .unwrap_or_else(|| settings.task_plan_mode.unwrap_or(false));
-    let thinking = args
-        .thinking
-        .unwrap_or_else(|| settings.task_thinking.unwrap_or(true));
-    let use_worktree = args
-        .use_worktree
-        .unwrap_or_else(|| settings.task_use_worktree.unwrap_or(true));
+    let defaults = task_launch_defaults(&settings, &agent_id, args.plan_mode);
 
     let allowlist = settings
         .task_project_allowlist
@@ -624,18 +851,21 @@ async fn tool_create_task(arguments: Value, server_state: McpServerState) -> Res
         prompt: args.prompt,
         context_id: None,
         project_path: args.project_path.clone(),
-        base_branch: args.base_branch.clone(),
-        plan_mode,
-        thinking,
-        use_worktree,
+        base_branch: args.base_branch.clone().or(defaults.base_branch),
+        plan_mode: defaults.plan_mode,
+        thinking: args.thinking.unwrap_or(defaults.thinking),
+        use_worktree: args.use_worktree.unwrap_or(defaults.use_worktree),
+        sparse_worktree: settings.task_sparse_worktree.unwrap_or(false),
+        reuse_worktree_task_id: None,
+        git_hooks: None,
         permission_mode: args
             .permission_mode
             .clone()
-            .unwrap_or_else(|| "default".to_string()),
+            .unwrap_or(defaults.permission_mode),
         exec_model,
-        reasoning_effort: args.reasoning_effort.clone(),
-        agent_mode: args.agent_mode.clone(),
-        codex_mode: args.codex_mode.clone(),
+        reasoning_effort: args.reasoning_effort.clone().or(defaults.reasoning_effort),
+        agent_mode: args.agent_mode.clone().or(defaults.agent_mode),
+        codex_mode: args.codex_mode.clone().or(defaults.codex_mode),
         claude_runtime: None,
         multi_create: false,
         suppress_notifications: false,
@@ -665,46 +895,46 @@ async fn tool_create_task(arguments: Value, server_state: McpServerState) -> Res
     Ok(json!({
         "task_id": created.task_id,
         "session_id": created.session_id,
-        "worktree_path": created.worktree_path
+        "worktree_path": created.worktree_path,
+        "model_warning": created.model_warning
     }))
 }
 
 async fn tool_update_task(arguments: Value, server_state: McpServerState) -> Result<Value, String> {
     let args: UpdateTaskArgs =
         serde_json::from_value(arguments).map_err(|e| format!("Invalid update_task args: {e}"))?;
-    let conn = server_state.state.db.lock().map_err(|e| e.to_string())?;
-    let tasks = db::list_tasks(&conn).map_err(|e| e.to_string())?;
-    let task = tasks
-        .iter()
-        .find(|task| task.id == args.task_id)
-        .cloned()
+    let task = crate::load_task(&server_state.state, &args.task_id)
+        .await?
         .ok_or_else(|| "Task not found".to_string())?;
+    let task_id = task.id.clone();
 
-    if args.status.is_some() || args.status_state.is_some() {
-        let status = args.status.as_deref().unwrap_or(&task.status);
-        let status_state = args
-            .status_state
-            .as_deref()
-            .unwrap_or(&task.status_state)
-            .to_string();
-        let _ = db::update_task_status(&conn, &task.id, status, &status_state);
-    }
-    if let Some(title) = args.title_summary.as_ref() {
-        let _ = db::update_task_title_summary(&conn, &task.id, title);
-    }
-    if let Some(branch) = args.branch.as_ref() {
-        let _ = db::update_task_branch(&conn, &task.id, branch);
-    }
-    if let Some(cost) = args.cost {
-        let _ = db::update_task_cost(&conn, &task.id, cost);
-    }
+    server_state
+        .state
+        .store
+        .write(move |conn| {
+            if args.status.is_some() || args.status_state.is_some() {
+                let status = args.status.as_deref().unwrap_or(&task.status);
+                let status_state = args.status_state.as_deref().unwrap_or(&task.status_state);
+                let _ = db::update_task_status(conn, &task.id, status, status_state);
+            }
+            if let Some(title) = args.title_summary.as_ref() {
+                let _ = db::update_task_title_summary(conn, &task.id, title);
+            }
+            if let Some(branch) = args.branch.as_ref() {
+                let _ = db::update_task_branch(conn, &task.id, branch);
+            }
+            if let Some(cost) = args.cost {
+                let _ = db::update_task_cost(conn, &task.id, cost);
+            }
+            Ok(())
+        })
+        .await?;
 
-    Ok(json!({"updated": true, "task_id": task.id}))
+    Ok(json!({"updated": true, "task_id": task_id}))
 }
 
 async fn tool_list_tasks(server_state: McpServerState) -> Result<Value, String> {
-    let conn = server_state.state.db.lock().map_err(|e| e.to_string())?;
-    let tasks = db::list_tasks(&conn).map_err(|e| e.to_string())?;
+    let tasks = server_state.state.store.read(db::list_tasks).await?;
     let out: Vec<_> = tasks
         .into_iter()
         .map(|task| {
@@ -732,11 +962,11 @@ async fn tool_list_tasks(server_state: McpServerState) -> Result<Value, String>
 async fn tool_get_task(arguments: Value, server_state: McpServerState) -> Result<Value, String> {
     let args: TaskIdArgs =
         serde_json::from_value(arguments).map_err(|e| format!("Invalid get_task args: {e}"))?;
-    let conn = server_state.state.db.lock().map_err(|e| e.to_string())?;
-    let tasks = db::list_tasks(&conn).map_err(|e| e.to_string())?;
-    let task = tasks
-        .into_iter()
-        .find(|task| task.id == args.task_id)
+    let task = server_state
+        .state
+        .store
+        .read(move |conn| db::get_task(conn, &args.task_id))
+        .await?
         .ok_or_else(|| "Task not found".to_string())?;
     Ok(json!({
         "id": task.id,
@@ -761,18 +991,23 @@ async fn tool_get_task(arguments: Value, server_state: McpServerState) -> Result
 async fn tool_delete_task(arguments: Value, server_state: McpServerState) -> Result<Value, String> {
     let args: TaskIdArgs =
         serde_json::from_value(arguments).map_err(|e| format!("Invalid delete_task args: {e}"))?;
-    delete_task_internal(
+    let result = delete_task_internal(
         args.task_id.clone(),
         &server_state.state,
         server_state.app.clone(),
     )
     .await?;
-    Ok(json!({"deleted": true, "task_id": args.task_id}))
+    Ok(json!({
+        "deleted": true,
+        "task_id": args.task_id,
+        "deleted_branch": result.deleted_branch,
+        "kept_branch": result.kept_branch,
+        "open_pr": result.open_pr,
+    }))
 }
 
 async fn tool_list_task_statuses(server_state: McpServerState) -> Result<Value, String> {
-    let conn = server_state.state.db.lock().map_err(|e| e.to_string())?;
-    let tasks = db::list_tasks(&conn).map_err(|e| e.to_string())?;
+    let tasks = server_state.state.store.read(db::list_tasks).await?;
     let out: Vec<_> = tasks
         .into_iter()
         .map(|task| {
@@ -788,7 +1023,7 @@ async fn tool_list_task_statuses(server_state: McpServerState) -> Result<Value,
 
 async fn tool_create_workspace(
     arguments: Value,
-    _server_state: McpServerState,
+    server_state: McpServerState,
 ) -> Result<Value, String> {
     let args: CreateWorkspaceArgs = serde_json::from_value(arguments)
         .map_err(|e| format!("Invalid create_workspace args: {e}"))?;
@@ -796,16 +1031,15 @@ async fn tool_create_workspace(
     let repo_root = resolve_repo_root(&source_path).await;
     let sync_source = repo_root.as_deref().unwrap_or(&source_path);
     let repo_slug = worktree::repo_slug(sync_source);
-    let root = worktree::workspace_root_dir()?;
-    let repo_dir = root.join(&repo_slug);
-    std::fs::create_dir_all(&repo_dir)
-        .map_err(|e| format!("Failed to create workspace repo dir: {e}"))?;
+    let base_dir = crate::worktree_base_dir(&*server_state.state.settings.lock().await);
+    let root = worktree::workspace_root_dir(base_dir.as_deref())?;
+    let repo_dir = worktree::workspace_repo_dir(&root, &repo_slug)?;
 
     let workspace_path = if let Some(name) = args.name.as_ref() {
         let workspace_name = unique_workspace_name(&repo_dir, name);
         repo_dir.join(workspace_name)
     } else {
-        worktree::build_workspace_path(&repo_slug)?
+        worktree::build_workspace_path(base_dir.as_deref(), &repo_slug)?
     };
     if !workspace_path.exists() {
         std::fs::create_dir_all(&workspace_path)
@@ -838,41 +1072,33 @@ async fn tool_switch_workspace(
 
 async fn tool_delete_workspace(
     arguments: Value,
-    _server_state: McpServerState,
+    server_state: McpServerState,
 ) -> Result<Value, String> {
     let args: WorkspacePathArgs = serde_json::from_value(arguments)
         .map_err(|e| format!("Invalid delete_workspace args: {e}"))?;
     let path = std::path::PathBuf::from(args.path.clone());
-    worktree::remove_workspace_dir(&path)?;
+    let base_dir = crate::worktree_base_dir(&*server_state.state.settings.lock().await);
+    worktree::remove_workspace_dir(&path, base_dir.as_deref())?;
     Ok(json!({"deleted": true, "path": args.path}))
 }
 
-async fn tool_list_workspaces(_server_state: McpServerState) -> Result<Value, String> {
-    let root = worktree::workspace_root_dir()?;
-    let mut workspaces = Vec::new();
-    if root.exists() {
-        for repo_entry in std::fs::read_dir(&root).map_err(|e| format!("read_dir: {e}"))? {
-            let repo_entry = repo_entry.map_err(|e| format!("repo entry: {e}"))?;
-            let repo_path = repo_entry.path();
-            if !repo_path.is_dir() {
-                continue;
-            }
-            for ws_entry in
-                std::fs::read_dir(&repo_path).map_err(|e| format!("workspace dir: {e}"))?
-            {
-                let ws_entry = ws_entry.map_err(|e| format!("workspace entry: {e}"))?;
-                let ws_path = ws_entry.path();
-                if !ws_path.is_dir() {
-                    continue;
-                }
-                workspaces.push(json!({
-                    "id": ws_path.to_string_lossy().to_string(),
-                    "path": ws_path.to_string_lossy().to_string(),
-                    "repo": repo_path.to_string_lossy().to_string(),
-                }));
-            }
-        }
-    }
+async fn tool_list_workspaces(server_state: McpServerState) -> Result<Value, String> {
+    let base_dir = crate::worktree_base_dir(&*server_state.state.settings.lock().await);
+    let root = worktree::workspace_root_dir(base_dir.as_deref())?;
+    let workspaces: Vec<Value> = worktree::list_workspace_dirs(&root)
+        .into_iter()
+        .map(|ws_path| {
+            let repo_path = ws_path
+                .parent()
+                .map(std::path::Path::to_path_buf)
+                .unwrap_or_default();
+            json!({
+                "id": ws_path.to_string_lossy().to_string(),
+                "path": ws_path.to_string_lossy().to_string(),
+                "repo": repo_path.to_string_lossy().to_string(),
+            })
+        })
+        .collect();
     Ok(json!({"workspaces": workspaces}))
 }
 
@@ -935,6 +1161,7 @@ async fn tool_send_chat_message(
         args.task_id.clone(),
         args.message.clone(),
         None,
+        Vec::new(),
         &server_state.state,
         server_state.app.clone(),
         crate::MessageOrigin::Ui,
@@ -949,10 +1176,102 @@ async fn tool_get_task_history(
 ) -> Result<Value, String> {
     let args: TaskIdArgs = serde_json::from_value(arguments)
         .map_err(|e| format!("Invalid phantom_get_task_history args: {e}"))?;
-    let history = get_task_history_internal(&args.task_id, &server_state.state).await?;
+    let history = get_task_history_internal(&args.task_id, None, None, &server_state.state).await?;
     Ok(history)
 }
 
+async fn tool_export_task(arguments: Value, server_state: McpServerState) -> Result<Value, String> {
+    let args: TaskIdArgs = serde_json::from_value(arguments)
+        .map_err(|e| format!("Invalid phantom_export_task args: {e}"))?;
+    let exported = server_state
+        .state
+        .store
+        .read(move |conn| {
+            let Some(task) = db::get_task(conn, &args.task_id)? else {
+                return Ok(None);
+            };
+            let messages = db::get_messages(conn, &task.id)?;
+            let review_comments = db::list_review_comments(conn, &task.id, None)?;
+            Ok(Some((task, messages, review_comments)))
+        })
+        .await?;
+    let (task, messages, review_comments) = exported.ok_or_else(|| "Task not found".to_string())?;
+    Ok(json!({
+        "version": HEADLESS_API_VERSION,
+        "exported_at": chrono::Utc::now().to_rfc3339(),
+        "task": task,
+        "messages": messages,
+        "review_comments": review_comments,
+    }))
+}
+
+/// The launch options `create_task` fills in when they are left out.
+async fn tool_task_defaults(
+    arguments: Value,
+    server_state: McpServerState,
+) -> Result<Value, String> {
+    let args: TaskDefaultsArgs = serde_json::from_value(arguments)
+        .map_err(|e| format!("Invalid task_defaults args: {e}"))?;
+    let settings = server_state.state.settings.lock().await.clone();
+    let agent_id = args
+        .agent_id
+        .or_else(|| settings.task_last_agent.clone())
+        .unwrap_or_else(|| "codex".to_string());
+    let defaults = task_launch_defaults(&settings, &agent_id, args.plan_mode);
+    Ok(json!({"agent_id": agent_id, "defaults": defaults}))
+}
+
+async fn tool_ensure_discord_thread(
+    arguments: Value,
+    server_state: McpServerState,
+) -> Result<Value, String> {
+    let args: DiscordThreadArgs = serde_json::from_value(arguments)
+        .map_err(|e| format!("Invalid ensure_discord_thread args: {e}"))?;
+    crate::load_task(&server_state.state, &args.task_id)
+        .await?
+        .ok_or_else(|| "Task not found".to_string())?;
+    let intro = args
+        .intro_message
+        .unwrap_or_else(|| format!("**Task** `{}`", args.task_id));
+    let thread = ensure_discord_thread(&server_state.state, &args.task_id, &intro)
+        .await
+        .ok_or_else(|| "Discord thread unavailable (is the bot connected?)".to_string())?;
+    Ok(json!({"task_id": args.task_id, "thread_id": thread.get().to_string()}))
+}
+
+/// Summarize with the same agent choice as the app (`summariesAgent` overrides the task agent).
+async fn tool_summarize(arguments: Value, server_state: McpServerState) -> Result<Value, String> {
+    let args: SummarizeArgs =
+        serde_json::from_value(arguments).map_err(|e| format!("Invalid summarize args: {e}"))?;
+    let settings = server_state.state.settings.lock().await.clone();
+    if !settings.ai_summaries_enabled.unwrap_or(true) {
+        return Err("AI summaries are disabled in settings".to_string());
+    }
+    let agent_id = args
+        .agent_id
+        .or_else(|| settings.task_last_agent.clone())
+        .unwrap_or_else(|| "codex".to_string());
+    let summaries_agent = settings.summaries_agent.as_deref();
+    let summary = match args.kind.as_str() {
+        "title" => {
+            summarize::summarize_title_with_override(&args.text, &agent_id, summaries_agent).await
+        }
+        "status" => {
+            summarize::summarize_status_with_override(&args.text, &agent_id, summaries_agent).await
+        }
+        other => {
+            return Err(format!(
+                "Unknown summary kind: {other} (use title or status)"
+            ))
+        }
+    };
+    Ok(json!({
+        "kind": args.kind,
+        "agent_id": summarize::resolve_summaries_agent(&agent_id, summaries_agent),
+        "summary": summary,
+    }))
+}
+
 async fn tool_list_agents(server_state: McpServerState) -> Result<Value, String> {
     let agents = server_state
         .state
@@ -972,11 +1291,14 @@ async fn tool_list_agents(server_state: McpServerState) -> Result<Value, String>
     Ok(json!({"agents": agents}))
 }
 
-fn tool_definitions() -> Vec<Value> {
-    vec![
-        tool_schema(
-            "create_task",
-            "Create a new Phantom task (optionally starts immediately).",
+/// Every command the MCP and headless HTTP surfaces know about. Dispatch, `tools/list`
+/// and the `/api/v1/commands` listing are all generated from this table.
+const COMMANDS: &[CommandSpec] = &[
+    // Superset-compatible tools
+    CommandSpec {
+        name: "create_task",
+        description: "Create a new Phantom task (optionally starts immediately).",
+        input_schema: || {
             json!({
                 "type": "object",
                 "properties": {
@@ -996,11 +1318,14 @@ fn tool_definitions() -> Vec<Value> {
                     "start": {"type": "boolean"}
                 },
                 "required": ["prompt"]
-            }),
-        ),
-        tool_schema(
-            "update_task",
-            "Update task metadata (status, title, branch, cost).",
+            })
+        },
+        handler: CommandHandler::Run(|args, s| Box::pin(tool_create_task(args, s))),
+    },
+    CommandSpec {
+        name: "update_task",
+        description: "Update task metadata (status, title, branch, cost).",
+        input_schema: || {
             json!({
                 "type": "object",
                 "properties": {
@@ -1012,39 +1337,50 @@ fn tool_definitions() -> Vec<Value> {
                     "cost": {"type": "number"}
                 },
                 "required": ["task_id"]
-            }),
-        ),
-        tool_schema(
-            "list_tasks",
-            "List tasks stored in Phantom.",
-            json!({"type": "object", "properties": {}}),
-        ),
-        tool_schema(
-            "get_task",
-            "Fetch a single task by id.",
+            })
+        },
+        handler: CommandHandler::Run(|args, s| Box::pin(tool_update_task(args, s))),
+    },
+    CommandSpec {
+        name: "list_tasks",
+        description: "List tasks stored in Phantom.",
+        input_schema: || json!({"type": "object", "properties": {}}),
+        handler: CommandHandler::Run(|_, s| Box::pin(tool_list_tasks(s))),
+    },
+    CommandSpec {
+        name: "get_task",
+        description: "Fetch a single task by id.",
+        input_schema: || {
             json!({
                 "type": "object",
                 "properties": {"task_id": {"type": "string"}},
                 "required": ["task_id"]
-            }),
-        ),
-        tool_schema(
-            "delete_task",
-            "Delete a task by id.",
+            })
+        },
+        handler: CommandHandler::Run(|args, s| Box::pin(tool_get_task(args, s))),
+    },
+    CommandSpec {
+        name: "delete_task",
+        description: "Delete a task by id.",
+        input_schema: || {
             json!({
                 "type": "object",
                 "properties": {"task_id": {"type": "string"}},
                 "required": ["task_id"]
-            }),
-        ),
-        tool_schema(
-            "list_task_statuses",
-            "List statuses for all tasks.",
-            json!({"type": "object", "properties": {}}),
-        ),
-        tool_schema(
-            "create_workspace",
-            "Create a local workspace from a project path.",
+            })
+        },
+        handler: CommandHandler::Run(|args, s| Box::pin(tool_delete_task(args, s))),
+    },
+    CommandSpec {
+        name: "list_task_statuses",
+        description: "List statuses for all tasks.",
+        input_schema: || json!({"type": "object", "properties": {}}),
+        handler: CommandHandler::Run(|_, s| Box::pin(tool_list_task_statuses(s))),
+    },
+    CommandSpec {
+        name: "create_workspace",
+        description: "Create a local workspace from a project path.",
+        input_schema: || {
             json!({
                 "type": "object",
                 "properties": {
@@ -1053,70 +1389,93 @@ fn tool_definitions() -> Vec<Value> {
                     "sync": {"type": "boolean"}
                 },
                 "required": ["project_path"]
-            }),
-        ),
-        tool_schema(
-            "switch_workspace",
-            "Set the active project path in Phantom settings.",
+            })
+        },
+        handler: CommandHandler::Run(|args, s| Box::pin(tool_create_workspace(args, s))),
+    },
+    CommandSpec {
+        name: "switch_workspace",
+        description: "Set the active project path in Phantom settings.",
+        input_schema: || {
             json!({
                 "type": "object",
                 "properties": {"path": {"type": "string"}},
                 "required": ["path"]
-            }),
-        ),
-        tool_schema(
-            "delete_workspace",
-            "Delete a workspace directory.",
+            })
+        },
+        handler: CommandHandler::Run(|args, s| Box::pin(tool_switch_workspace(args, s))),
+    },
+    CommandSpec {
+        name: "delete_workspace",
+        description: "Delete a workspace directory.",
+        input_schema: || {
             json!({
                 "type": "object",
                 "properties": {"path": {"type": "string"}},
                 "required": ["path"]
-            }),
-        ),
-        tool_schema(
-            "list_workspaces",
-            "List local workspaces.",
-            json!({"type": "object", "properties": {}}),
-        ),
-        tool_schema(
-            "navigate_to_workspace",
-            "Return the workspace path for navigation.",
+            })
+        },
+        handler: CommandHandler::Run(|args, s| Box::pin(tool_delete_workspace(args, s))),
+    },
+    CommandSpec {
+        name: "list_workspaces",
+        description: "List local workspaces.",
+        input_schema: || json!({"type": "object", "properties": {}}),
+        handler: CommandHandler::Run(|_, s| Box::pin(tool_list_workspaces(s))),
+    },
+    CommandSpec {
+        name: "navigate_to_workspace",
+        description: "Return the workspace path for navigation.",
+        input_schema: || {
             json!({
                 "type": "object",
                 "properties": {"path": {"type": "string"}},
                 "required": ["path"]
-            }),
-        ),
-        tool_schema(
-            "phantom_start_task",
-            "Start an existing task.",
+            })
+        },
+        handler: CommandHandler::Run(|args, s| Box::pin(tool_navigate_workspace(args, s))),
+    },
+    // Phantom-specific tools
+    CommandSpec {
+        name: "phantom_start_task",
+        description: "Start an existing task.",
+        input_schema: || {
             json!({
                 "type": "object",
                 "properties": {"task_id": {"type": "string"}},
                 "required": ["task_id"]
-            }),
-        ),
-        tool_schema(
-            "phantom_stop_task",
-            "Stop an existing task.",
+            })
+        },
+        handler: CommandHandler::Run(|args, s| Box::pin(tool_start_task(args, s))),
+    },
+    CommandSpec {
+        name: "phantom_stop_task",
+        description: "Stop an existing task.",
+        input_schema: || {
             json!({
                 "type": "object",
                 "properties": {"task_id": {"type": "string"}},
                 "required": ["task_id"]
-            }),
-        ),
-        tool_schema(
-            "phantom_soft_stop_task",
-            "Cancel current generation without killing the task session.",
+            })
+        },
+        handler: CommandHandler::Run(|args, s| Box::pin(tool_stop_task(args, s))),
+    },
+    CommandSpec {
+        name: "phantom_soft_stop_task",
+        description: "Cancel current generation without killing the task session.",
+        input_schema: || {
             json!({
                 "type": "object",
                 "properties": {"task_id": {"type": "string"}},
                 "required": ["task_id"]
-            }),
-        ),
-        tool_schema(
-            "phantom_send_chat_message",
-            "Send a message to a running task.",
+            })
+        },
+        handler: CommandHandler::Run(|args, s| Box::pin(tool_soft_stop_task(args, s))),
+    },
+    CommandSpec {
+        name: "phantom_send_chat_message",
+        description: "Send a message to a running task.",
+        input_schema: || {
             json!({
                 "type": "object",
                 "properties": {
@@ -1124,23 +1483,160 @@ fn tool_definitions() -> Vec<Value> {
                     "message": {"type": "string"}
                 },
                 "required": ["task_id", "message"]
-            }),
-        ),
-        tool_schema(
-            "phantom_get_task_history",
-            "Fetch task history and messages.",
+            })
+        },
+        handler: CommandHandler::Run(|args, s| Box::pin(tool_send_chat_message(args, s))),
+    },
+    CommandSpec {
+        name: "phantom_get_task_history",
+        description: "Fetch task history and messages.",
+        input_schema: || {
             json!({
                 "type": "object",
                 "properties": {"task_id": {"type": "string"}},
                 "required": ["task_id"]
-            }),
-        ),
-        tool_schema(
-            "phantom_list_agents",
-            "List configured Phantom agents.",
-            json!({"type": "object", "properties": {}}),
-        ),
-    ]
+            })
+        },
+        handler: CommandHandler::Run(|args, s| Box::pin(tool_get_task_history(args, s))),
+    },
+    CommandSpec {
+        name: "phantom_export_task",
+        description: "Export a task record with its full message history and review comments.",
+        input_schema: || {
+            json!({
+                "type": "object",
+                "properties": {"task_id": {"type": "string"}},
+                "required": ["task_id"]
+            })
+        },
+        handler: CommandHandler::Run(|args, s| Box::pin(tool_export_task(args, s))),
+    },
+    CommandSpec {
+        name: "phantom_list_agents",
+        description: "List configured Phantom agents.",
+        input_schema: || json!({"type": "object", "properties": {}}),
+        handler: CommandHandler::Run(|_, s| Box::pin(tool_list_agents(s))),
+    },
+    CommandSpec {
+        name: "phantom_task_defaults",
+        description: "Launch options create_task uses when they are omitted (settings plus the agent's saved preferences).",
+        input_schema: || {
+            json!({
+                "type": "object",
+                "properties": {
+                    "agent_id": {"type": "string"},
+                    "plan_mode": {"type": "boolean"}
+                }
+            })
+        },
+        handler: CommandHandler::Run(|args, s| Box::pin(tool_task_defaults(args, s))),
+    },
+    CommandSpec {
+        name: "phantom_ensure_discord_thread",
+        description: "Create (or find) the Discord thread for a task.",
+        input_schema: || {
+            json!({
+                "type": "object",
+                "properties": {
+                    "task_id": {"type": "string"},
+                    "intro_message": {"type": "string"}
+                },
+                "required": ["task_id"]
+            })
+        },
+        handler: CommandHandler::Run(|args, s| Box::pin(tool_ensure_discord_thread(args, s))),
+    },
+    CommandSpec {
+        name: "phantom_summarize",
+        description: "Summarize text as a task title or status using the configured summaries agent.",
+        input_schema: || {
+            json!({
+                "type": "object",
+                "properties": {
+                    "kind": {"type": "string", "enum": ["title", "status"]},
+                    "text": {"type": "string"},
+                    "agent_id": {"type": "string"}
+                },
+                "required": ["kind", "text"]
+            })
+        },
+        handler: CommandHandler::Run(|args, s| Box::pin(tool_summarize(args, s))),
+    },
+    // Tauri commands that only make sense with a window in front of the user
+    CommandSpec {
+        name: "open_chat_window",
+        description: "Open a task's chat window.",
+        input_schema: || json!({"type": "object", "properties": {}}),
+        handler: CommandHandler::RequiresUi("opens a chat webview window"),
+    },
+    CommandSpec {
+        name: "pick_project_path",
+        description: "Pick a project folder.",
+        input_schema: || json!({"type": "object", "properties": {}}),
+        handler: CommandHandler::RequiresUi("uses the native folder picker"),
+    },
+    CommandSpec {
+        name: "open_task_directory",
+        description: "Open a task's directory.",
+        input_schema: || json!({"type": "object", "properties": {}}),
+        handler: CommandHandler::RequiresUi("opens Finder or an editor"),
+    },
+    CommandSpec {
+        name: "open_external_url",
+        description: "Open a URL in the browser.",
+        input_schema: || json!({"type": "object", "properties": {}}),
+        handler: CommandHandler::RequiresUi("opens the system browser"),
+    },
+    CommandSpec {
+        name: "codex_login",
+        description: "Sign in to Codex.",
+        input_schema: || json!({"type": "object", "properties": {}}),
+        handler: CommandHandler::RequiresUi("completes a browser sign-in flow"),
+    },
+    CommandSpec {
+        name: "codex_account_login",
+        description: "Sign in to a saved Codex account.",
+        input_schema: || json!({"type": "object", "properties": {}}),
+        handler: CommandHandler::RequiresUi("completes a browser sign-in flow"),
+    },
+    CommandSpec {
+        name: "claude_login",
+        description: "Sign in to Claude.",
+        input_schema: || json!({"type": "object", "properties": {}}),
+        handler: CommandHandler::RequiresUi("completes a browser sign-in flow"),
+    },
+    CommandSpec {
+        name: "dismiss_notifications_for_task",
+        description: "Close a task's notification windows.",
+        input_schema: || json!({"type": "object", "properties": {}}),
+        handler: CommandHandler::RequiresUi("closes notification windows"),
+    },
+    CommandSpec {
+        name: "dictation_start",
+        description: "Start dictation.",
+        input_schema: || json!({"type": "object", "properties": {}}),
+        handler: CommandHandler::RequiresUi("types into the focused app"),
+    },
+    CommandSpec {
+        name: "dictation_stop",
+        description: "Stop dictation.",
+        input_schema: || json!({"type": "object", "properties": {}}),
+        handler: CommandHandler::RequiresUi("types into the focused app"),
+    },
+    CommandSpec {
+        name: "dictation_request_accessibility",
+        description: "Ask for the accessibility permission dictation needs.",
+        input_schema: || json!({"type": "object", "properties": {}}),
+        handler: CommandHandler::RequiresUi("shows a system permission prompt"),
+    },
+];
+
+fn tool_definitions() -> Vec<Value> {
+    COMMANDS
+        .iter()
+        .filter(|command| matches!(command.handler, CommandHandler::Run(_)))
+        .map(|command| tool_schema(command.name, command.description, (command.input_schema)()))
+        .collect()
 }
 
 fn tool_schema(name: &str, description: &str, input_schema: Value) -> Value {
@@ -1170,7 +1666,17 @@ fn unique_workspace_name(repo_dir: &std::path::Path, base: &str) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::{extract_bearer_token, origin_allowed};
+    use super::{
+        dispatch_command, extract_bearer_token, headless_command_from_path, headless_listing,
+        origin_allowed, tool_definitions, CommandError, CommandHandler, McpConfig, McpServerState,
+        COMMANDS, NOT_HEADLESS_REASON, TAURI_COMMANDS,
+    };
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tauri::Manager;
+    use tokio::sync::Mutex;
 
     #[test]
     fn test_origin_allowed_local() {
@@ -1190,4 +1696,215 @@ mod tests {
         assert_eq!(token, "abc123");
         assert!(extract_bearer_token("Basic abc").is_none());
     }
+
+    #[test]
+    fn test_headless_command_from_path() {
+        assert_eq!(
+            headless_command_from_path("/api/v1/commands/create_task"),
+            Some("create_task")
+        );
+        assert_eq!(headless_command_from_path("/api/v1/commands/"), None);
+        assert_eq!(headless_command_from_path("/api/v1/commands"), None);
+        assert_eq!(headless_command_from_path("/api/v1/commands/../mcp"), None);
+    }
+
+    #[test]
+    fn test_headless_listing_covers_lifecycle_and_ui_only() {
+        let listing = headless_listing();
+        assert_eq!(listing["version"], 1);
+        let names: Vec<&str> = listing["commands"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| tool["name"].as_str().unwrap())
+            .collect();
+        for lifecycle in [
+            "create_task",
+            "phantom_start_task",
+            "phantom_send_chat_message",
+            "phantom_stop_task",
+            "delete_task",
+            "phantom_export_task",
+        ] {
+            assert!(names.contains(&lifecycle), "missing {lifecycle}");
+        }
+        assert_eq!(names.len(), tool_definitions().len());
+
+        // Every registered Tauri command is either headless or listed as requiring the UI.
+        let ui_only: Vec<&str> = listing["uiOnly"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .inspect(|entry| assert_eq!(entry["requiresUi"], true))
+            .map(|entry| entry["name"].as_str().unwrap())
+            .collect();
+        assert!(ui_only.iter().all(|name| !names.contains(name)));
+        for name in TAURI_COMMANDS {
+            assert!(
+                names.contains(name) || ui_only.contains(name),
+                "{name} missing from the listing"
+            );
+        }
+        assert!(ui_only.contains(&"get_settings"));
+
+        // Explicit UI-only marks must name registered commands.
+        for command in COMMANDS {
+            if let CommandHandler::RequiresUi(_) = command.handler {
+                assert!(TAURI_COMMANDS.contains(&command.name), "{}", command.name);
+            }
+        }
+
+        let err = CommandError::RequiresUi {
+            command: "pick_project_path".to_string(),
+            reason: "uses the native folder picker",
+        };
+        assert_eq!(err.code(), "requires_ui");
+        assert_eq!(err.status().as_u16(), 501);
+    }
+
+    /// Poll `get_task` until the task leaves `running`.
+    #[cfg(unix)]
+    async fn wait_until_idle(server_state: &McpServerState, task_id: &str) -> Value {
+        for _ in 0..200 {
+            let task = dispatch_command(
+                "get_task",
+                json!({"task_id": task_id}),
+                server_state.clone(),
+            )
+            .await
+            .unwrap();
+            if task["status_state"] != "running" {
+                return task;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("task {task_id} never finished");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_headless_lifecycle_against_fake_agent() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!(
+            "phantom-headless-lifecycle-{}",
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let project = dir.join("project");
+        std::fs::create_dir_all(&project).unwrap();
+        let agent = crate::test_support::fake_agent(&dir, "fake-session", "Fake agent reply");
+        let state = crate::test_support::app_state(&dir, vec![agent]);
+        let app = tauri::test::mock_app();
+        app.manage(state.clone());
+        let server_state = McpServerState {
+            app: app.handle().clone(),
+            state,
+            config: McpConfig {
+                port: 0,
+                token: "test".to_string(),
+            },
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+        };
+
+        assert_eq!(
+            dispatch_command("get_settings", json!({}), server_state.clone()).await,
+            Err(CommandError::RequiresUi {
+                command: "get_settings".to_string(),
+                reason: NOT_HEADLESS_REASON,
+            })
+        );
+        assert!(matches!(
+            dispatch_command("no_such_command", json!({}), server_state.clone()).await,
+            Err(CommandError::Unknown(_))
+        ));
+        let defaults = dispatch_command(
+            "phantom_task_defaults",
+            json!({"agent_id": "fake", "plan_mode": true}),
+            server_state.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(defaults["defaults"]["plan_mode"], true);
+        assert_eq!(defaults["defaults"]["permission_mode"], "default");
+
+        let created = dispatch_command(
+            "create_task",
+            json!({
+                "prompt": "First prompt",
+                "agent_id": "fake",
+                "project_path": project.to_string_lossy(),
+                "use_worktree": false,
+            }),
+            server_state.clone(),
+        )
+        .await
+        .unwrap();
+        let task_id = created["task_id"].as_str().unwrap().to_string();
+        let task = wait_until_idle(&server_state, &task_id).await;
+        assert_eq!(task["status_state"], "completed");
+        assert_eq!(task["agent_session_id"], "fake-session");
+
+        dispatch_command(
+            "phantom_send_chat_message",
+            json!({"task_id": task_id, "message": "Second prompt"}),
+            server_state.clone(),
+        )
+        .await
+        .unwrap();
+        let task = wait_until_idle(&server_state, &task_id).await;
+        assert_eq!(task["status_state"], "completed");
+
+        let exported = dispatch_command(
+            "phantom_export_task",
+            json!({"task_id": task_id}),
+            server_state.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(exported["task"]["id"], task_id.as_str());
+        let contents: Vec<&str> = exported["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|message| message["content"].as_str())
+            .collect();
+        for expected in ["First prompt", "Second prompt"] {
+            assert!(
+                contents.contains(&expected),
+                "missing {expected}: {contents:?}"
+            );
+        }
+        let replies = contents
+            .iter()
+            .filter(|c| **c == "Fake agent reply")
+            .count();
+        assert_eq!(replies, 2, "{contents:?}");
+
+        dispatch_command(
+            "phantom_stop_task",
+            json!({"task_id": task_id}),
+            server_state.clone(),
+        )
+        .await
+        .unwrap();
+        dispatch_command(
+            "delete_task",
+            json!({"task_id": task_id}),
+            server_state.clone(),
+        )
+        .await
+        .unwrap();
+        let missing = dispatch_command(
+            "get_task",
+            json!({"task_id": task_id}),
+            server_state.clone(),
+        )
+        .await;
+        assert_eq!(
+            missing,
+            Err(CommandError::Failed("Task not found".to_string()))
+        );
+
+        drop(server_state);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
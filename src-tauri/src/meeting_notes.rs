@@ -4,14 +4,16 @@
 //! transcription.  Each meeting session is persisted in SQLite and transcription
 //! segments are streamed to the frontend via Tauri events.
 
-use crate::{audio_capture, db, local_asr_model, parakeet_model, whisper_model, AppState};
+use crate::{
+    audio_capture, db, local_asr_model, parakeet_model, whisper_model, AppHandle, AppState,
+};
 
 use rusqlite::Connection;
 use serde::Serialize;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{mpsc, Arc, Mutex as StdMutex};
 use std::time::{Duration, Instant};
-use tauri::{AppHandle, Emitter, State};
+use tauri::{Emitter, State};
 use transcribe_rs::engines::parakeet::{
     ParakeetEngine, ParakeetInferenceParams, ParakeetModelParams, TimestampGranularity,
 };
@@ -720,10 +722,13 @@ pub async fn meeting_update_title(
         .filter(|t| !t.is_empty())
         .map(|t| t.to_string());
 
-    let db = state.db.lock().map_err(|e| format!("Lock error: {}", e))?;
-    db::update_meeting_session_title(&db, &session_id, normalized.as_deref())
-        .map_err(|e| format!("DB error: {}", e))?;
-    Ok(())
+    state
+        .store
+        .write(move |conn| {
+            db::update_meeting_session_title(conn, &session_id, normalized.as_deref())
+        })
+        .await
+        .map_err(|e| format!("DB error: {}", e))
 }
 
 #[tauri::command]
@@ -755,8 +760,11 @@ pub(crate) struct MeetingSessionDto {
 pub async fn meeting_list_sessions(
     state: State<'_, AppState>,
 ) -> Result<Vec<MeetingSessionDto>, String> {
-    let db = state.db.lock().map_err(|e| format!("Lock error: {}", e))?;
-    let sessions = db::list_meeting_sessions(&db).map_err(|e| format!("DB error: {}", e))?;
+    let sessions = state
+        .store
+        .read(db::list_meeting_sessions)
+        .await
+        .map_err(|e| format!("DB error: {}", e))?;
     Ok(sessions
         .into_iter()
         .map(|s| MeetingSessionDto {
@@ -811,13 +819,19 @@ pub async fn meeting_create_text_note(
         .map(|t| t.to_string())
         .unwrap_or_default();
 
-    let db_guard = state.db.lock().map_err(|e| format!("Lock error: {}", e))?;
-    db::insert_meeting_session(&db_guard, &record).map_err(|e| format!("DB error: {}", e))?;
-
-    if !normalized_content.trim().is_empty() {
-        db::save_meeting_segment(&db_guard, &session_id, &normalized_content, 0, 0, None)
-            .map_err(|e| format!("DB error: {}", e))?;
-        db::touch_meeting_session_updated_at(&db_guard, &session_id)
+    {
+        let record = record.clone();
+        state
+            .store
+            .write(move |conn| {
+                db::insert_meeting_session(conn, &record)?;
+                if !normalized_content.trim().is_empty() {
+                    db::save_meeting_segment(conn, &session_id, &normalized_content, 0, 0, None)?;
+                    db::touch_meeting_session_updated_at(conn, &session_id)?;
+                }
+                Ok(())
+            })
+            .await
             .map_err(|e| format!("DB error: {}", e))?;
     }
 
@@ -843,24 +857,30 @@ pub async fn meeting_update_text_note(
 ) -> Result<(), String> {
     let normalized_content = content.unwrap_or_default();
 
-    let db_guard = state.db.lock().map_err(|e| format!("Lock error: {}", e))?;
-    let sess = db::get_meeting_session(&db_guard, &session_id)
-        .map_err(|e| format!("DB error: {}", e))?
-        .ok_or_else(|| "Session not found".to_string())?;
+    let sess = {
+        let session_id = session_id.clone();
+        state
+            .store
+            .read(move |conn| db::get_meeting_session(conn, &session_id))
+            .await
+            .map_err(|e| format!("DB error: {}", e))?
+            .ok_or_else(|| "Session not found".to_string())?
+    };
     if sess.status != "text" {
         return Err("Not a text note session".to_string());
     }
 
-    db::delete_meeting_segments_for_session(&db_guard, &session_id)
-        .map_err(|e| format!("DB error: {}", e))?;
-    if !normalized_content.trim().is_empty() {
-        db::save_meeting_segment(&db_guard, &session_id, &normalized_content, 0, 0, None)
-            .map_err(|e| format!("DB error: {}", e))?;
-    }
-    db::touch_meeting_session_updated_at(&db_guard, &session_id)
-        .map_err(|e| format!("DB error: {}", e))?;
-
-    Ok(())
+    state
+        .store
+        .write(move |conn| {
+            db::delete_meeting_segments_for_session(conn, &session_id)?;
+            if !normalized_content.trim().is_empty() {
+                db::save_meeting_segment(conn, &session_id, &normalized_content, 0, 0, None)?;
+            }
+            db::touch_meeting_session_updated_at(conn, &session_id)
+        })
+        .await
+        .map_err(|e| format!("DB error: {}", e))
 }
 
 #[derive(Serialize)]
@@ -879,9 +899,11 @@ pub async fn meeting_get_transcript(
     state: State<'_, AppState>,
     session_id: String,
 ) -> Result<serde_json::Value, String> {
-    let db = state.db.lock().map_err(|e| format!("Lock error: {}", e))?;
-    let segments =
-        db::get_meeting_segments(&db, &session_id).map_err(|e| format!("DB error: {}", e))?;
+    let segments = state
+        .store
+        .read(move |conn| db::get_meeting_segments(conn, &session_id))
+        .await
+        .map_err(|e| format!("DB error: {}", e))?;
     let dto: Vec<MeetingSegmentDto> = segments
         .into_iter()
         .map(|s| MeetingSegmentDto {
@@ -902,8 +924,11 @@ pub async fn meeting_delete_session(
     state: State<'_, AppState>,
     session_id: String,
 ) -> Result<(), String> {
-    let db = state.db.lock().map_err(|e| format!("Lock error: {}", e))?;
-    db::delete_meeting_session(&db, &session_id).map_err(|e| format!("DB error: {}", e))
+    state
+        .store
+        .write(move |conn| db::delete_meeting_session(conn, &session_id))
+        .await
+        .map_err(|e| format!("DB error: {}", e))
 }
 
 #[tauri::command]
@@ -913,13 +938,18 @@ pub async fn meeting_export_transcript(
     format: String,
 ) -> Result<String, String> {
     let (session, segments) = {
-        let db_guard = state.db.lock().map_err(|e| format!("Lock error: {}", e))?;
-        let sess = db::get_meeting_session(&db_guard, &session_id)
-            .map_err(|e| format!("DB error: {}", e))?
-            .ok_or_else(|| "Session not found".to_string())?;
-        let segs = db::get_meeting_segments(&db_guard, &session_id)
+        let session_id = session_id.clone();
+        let (sess, segs) = state
+            .store
+            .read(move |conn| {
+                Ok((
+                    db::get_meeting_session(conn, &session_id)?,
+                    db::get_meeting_segments(conn, &session_id)?,
+                ))
+            })
+            .await
             .map_err(|e| format!("DB error: {}", e))?;
-        (sess, segs)
+        (sess.ok_or_else(|| "Session not found".to_string())?, segs)
     };
 
     if session.status == "text" {
@@ -0,0 +1,118 @@
+//! Checks a requested model id against an agent's cached model catalog.
+
+const MAX_SUGGESTIONS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CatalogCheck {
+    Exact,
+    /// Only differs by case; `canonical` is the catalog spelling.
+    CaseMismatch {
+        canonical: String,
+    },
+    Unknown {
+        suggestions: Vec<String>,
+    },
+}
+
+pub fn check_model(requested: &str, catalog: &[String]) -> CatalogCheck {
+    let requested = requested.trim();
+    if catalog.iter().any(|value| value == requested) {
+        return CatalogCheck::Exact;
+    }
+    if let Some(canonical) = catalog
+        .iter()
+        .find(|value| value.eq_ignore_ascii_case(requested))
+    {
+        return CatalogCheck::CaseMismatch {
+            canonical: canonical.clone(),
+        };
+    }
+    CatalogCheck::Unknown {
+        suggestions: suggest(requested, catalog),
+    }
+}
+
+/// Close catalog entries: shared prefix (renamed/suffixed ids) or small edit distance (typos).
+fn suggest(requested: &str, catalog: &[String]) -> Vec<String> {
+    let needle = requested.to_ascii_lowercase();
+    let max_distance = (needle.chars().count() / 4).max(2);
+    let mut scored: Vec<(usize, &String)> = catalog
+        .iter()
+        .filter_map(|value| {
+            let candidate = value.to_ascii_lowercase();
+            let distance = levenshtein(&needle, &candidate);
+            let prefix_match = needle.len() >= 3
+                && (candidate.starts_with(&needle) || needle.starts_with(&candidate));
+            (prefix_match || distance <= max_distance).then_some((distance, value))
+        })
+        .collect();
+    scored.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(b.1)));
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, value)| value.clone())
+        .collect()
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            current[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(current[j] + 1);
+        }
+        prev = current;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalog() -> Vec<String> {
+        ["gpt-5-codex", "gpt-5", "gpt-5-mini", "o3"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_check_model_exact_match() {
+        assert_eq!(check_model("gpt-5-codex", &catalog()), CatalogCheck::Exact);
+    }
+
+    #[test]
+    fn test_check_model_case_mismatch() {
+        assert_eq!(
+            check_model("GPT-5-Codex", &catalog()),
+            CatalogCheck::CaseMismatch {
+                canonical: "gpt-5-codex".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_check_model_unknown_suggests_close_matches() {
+        match check_model("gpt-5-codex-preview", &catalog()) {
+            CatalogCheck::Unknown { suggestions } => {
+                assert_eq!(suggestions.first().map(String::as_str), Some("gpt-5-codex"));
+            }
+            other => panic!("expected unknown model, got {:?}", other),
+        }
+        match check_model("gtp-5-mini", &catalog()) {
+            CatalogCheck::Unknown { suggestions } => {
+                assert_eq!(suggestions.first().map(String::as_str), Some("gpt-5-mini"));
+            }
+            other => panic!("expected unknown model, got {:?}", other),
+        }
+        assert_eq!(
+            check_model("claude-opus", &catalog()),
+            CatalogCheck::Unknown {
+                suggestions: Vec::new()
+            }
+        );
+    }
+}
@@ -3,14 +3,14 @@
 //! Parakeet inference is provided via `transcribe-rs` (ONNX Runtime). Models are
 //! hosted on Hugging Face and downloaded on-demand.
 
-use crate::{local_asr_model, whisper_model, AppState};
+use crate::{local_asr_model, whisper_model, AppHandle, AppState};
 use futures_util::StreamExt;
 use serde::Serialize;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tauri::{AppHandle, Manager, State};
+use tauri::{Manager, State};
 use tokio::task::JoinHandle;
 
 const HF_BASE_URL: &str = "https://huggingface.co/istupakov/parakeet-tdt-0.6b-v3-onnx/resolve/main";
@@ -0,0 +1,216 @@
+//! Version chain for plan files agents rewrite during a task (PlanContent updates).
+
+use crate::db;
+use crate::line_diff;
+use rusqlite::Connection;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlanRevision {
+    /// Same content as the latest stored version.
+    Unchanged { version: i64 },
+    Changed {
+        version: i64,
+        previous_version: Option<i64>,
+        added: usize,
+        removed: usize,
+        diff: Option<String>,
+    },
+}
+
+/// Line endings and trailing whitespace don't count as plan changes.
+fn normalize(content: &str) -> String {
+    let lines: Vec<&str> = content.lines().map(|line| line.trim_end()).collect();
+    lines.join("\n").trim_end_matches('\n').to_string()
+}
+
+/// Stable FNV-1a hash of the normalized plan text.
+pub fn content_hash(content: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in normalize(content).bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Append `content` to the version chain for `file_path`, diffing against the previous version.
+pub fn record_revision(
+    conn: &Connection,
+    task_id: &str,
+    file_path: &str,
+    content: &str,
+) -> rusqlite::Result<PlanRevision> {
+    let hash = content_hash(content);
+    let previous = db::get_latest_plan_version(conn, task_id, file_path)?;
+    if let Some(prev) = previous.as_ref() {
+        if prev.content_hash == hash {
+            return Ok(PlanRevision::Unchanged {
+                version: prev.version,
+            });
+        }
+    }
+
+    let version = previous.as_ref().map(|p| p.version + 1).unwrap_or(1);
+    db::insert_plan_version(conn, task_id, file_path, version, &hash, content)?;
+
+    Ok(match previous {
+        Some(prev) => {
+            let diff = line_diff::unified_diff(
+                &normalize(&prev.content),
+                &normalize(content),
+                &format!("{} (v{})", file_path, prev.version),
+                &format!("{} (v{})", file_path, version),
+            );
+            PlanRevision::Changed {
+                version,
+                previous_version: Some(prev.version),
+                added: diff.added,
+                removed: diff.removed,
+                diff: Some(diff.text),
+            }
+        }
+        None => PlanRevision::Changed {
+            version,
+            previous_version: None,
+            added: 0,
+            removed: 0,
+            diff: None,
+        },
+    })
+}
+
+/// `plan_content` message body: file path, full content and version info when available.
+pub fn plan_content_payload(
+    file_path: &str,
+    content: &str,
+    revision: Option<&PlanRevision>,
+) -> serde_json::Value {
+    let mut payload = serde_json::json!({
+        "file_path": file_path,
+        "content": content
+    });
+    match revision {
+        Some(PlanRevision::Changed {
+            version,
+            previous_version,
+            added,
+            removed,
+            diff,
+        }) => {
+            payload["version"] = serde_json::json!(version);
+            if let Some(previous_version) = previous_version {
+                payload["previous_version"] = serde_json::json!(previous_version);
+                payload["added"] = serde_json::json!(added);
+                payload["removed"] = serde_json::json!(removed);
+                payload["diff"] = serde_json::json!(diff);
+            }
+        }
+        Some(PlanRevision::Unchanged { version }) => {
+            payload["version"] = serde_json::json!(version);
+            payload["unchanged"] = serde_json::json!(true);
+            payload["content"] = serde_json::Value::Null;
+        }
+        None => {}
+    }
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_temp_db(name: &str) -> (Connection, std::path::PathBuf) {
+        let suffix = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_nanos();
+        let path =
+            std::env::temp_dir().join(format!("phantom-plan-versions-{name}-{suffix}.sqlite"));
+        (db::init_db(&path).expect("init db"), path)
+    }
+
+    fn cleanup(path: &std::path::Path) {
+        let _ = std::fs::remove_file(path);
+        let _ = std::fs::remove_file(path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(path.with_extension("sqlite-shm"));
+    }
+
+    #[test]
+    fn test_plan_revisions_chain_and_diff() {
+        let (conn, path) = open_temp_db("chain");
+        let v1 = "# Plan\n- step one\n- step two\n";
+        let v2 = "# Plan\n- step one\n- step two (revised)\n- step three\n";
+
+        let first = record_revision(&conn, "task-1", "PLAN.md", v1).unwrap();
+        assert_eq!(
+            first,
+            PlanRevision::Changed {
+                version: 1,
+                previous_version: None,
+                added: 0,
+                removed: 0,
+                diff: None
+            }
+        );
+
+        match record_revision(&conn, "task-1", "PLAN.md", v2).unwrap() {
+            PlanRevision::Changed {
+                version,
+                previous_version,
+                added,
+                removed,
+                diff,
+            } => {
+                assert_eq!(version, 2);
+                assert_eq!(previous_version, Some(1));
+                assert_eq!((added, removed), (2, 1));
+                let diff = diff.unwrap();
+                assert!(diff.contains("--- PLAN.md (v1)\n+++ PLAN.md (v2)\n"));
+                assert!(diff.contains("-- step two\n+- step two (revised)\n+- step three\n"));
+            }
+            other => panic!("expected a new version, got {:?}", other),
+        }
+
+        // Identical re-emission collapses to the current version.
+        assert_eq!(
+            record_revision(&conn, "task-1", "PLAN.md", v2).unwrap(),
+            PlanRevision::Unchanged { version: 2 }
+        );
+        // A separate file gets its own chain.
+        assert!(matches!(
+            record_revision(&conn, "task-1", "NOTES.md", v2).unwrap(),
+            PlanRevision::Changed { version: 1, .. }
+        ));
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_plan_revisions_whitespace_changes() {
+        let (conn, path) = open_temp_db("whitespace");
+        let base = "# Plan\n- step one\n  - detail\n";
+        record_revision(&conn, "task-1", "PLAN.md", base).unwrap();
+
+        // Trailing whitespace and CRLF rewrites are not new versions.
+        let trailing = "# Plan  \r\n- step one\r\n  - detail\t\r\n\r\n";
+        assert_eq!(
+            record_revision(&conn, "task-1", "PLAN.md", trailing).unwrap(),
+            PlanRevision::Unchanged { version: 1 }
+        );
+
+        // Indentation changes nesting in markdown, so it is a real revision.
+        let reindented = "# Plan\n- step one\n- detail\n";
+        match record_revision(&conn, "task-1", "PLAN.md", reindented).unwrap() {
+            PlanRevision::Changed {
+                version,
+                added,
+                removed,
+                ..
+            } => {
+                assert_eq!(version, 2);
+                assert_eq!((added, removed), (1, 1));
+            }
+            other => panic!("expected a new version, got {:?}", other),
+        }
+        cleanup(&path);
+    }
+}
@@ -0,0 +1,321 @@
+//! Unresolved pull request review feedback, fetched with `gh api graphql` and turned into
+//! a prompt the task's agent can work from.
+
+use serde::{Deserialize, Serialize};
+
+/// Review threads and review summaries, newest PR state first.
+pub const REVIEW_FEEDBACK_QUERY: &str = r#"query($owner: String!, $repo: String!, $number: Int!) {
+  repository(owner: $owner, name: $repo) {
+    pullRequest(number: $number) {
+      number
+      url
+      reviewThreads(first: 100) {
+        nodes {
+          isResolved
+          isOutdated
+          path
+          line
+          originalLine
+          comments(first: 50) {
+            nodes { author { login } body diffHunk }
+          }
+        }
+      }
+      reviews(last: 50) {
+        nodes { author { login } state body }
+      }
+    }
+  }
+}"#;
+
+/// Lines of the diff hunk kept above each commented line.
+const EXCERPT_LINES: usize = 6;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrFeedback {
+    pub pr_number: u32,
+    pub pr_url: String,
+    pub threads: Vec<ReviewThread>,
+    pub reviews: Vec<ReviewSummary>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewThread {
+    pub path: String,
+    pub line: Option<u32>,
+    pub outdated: bool,
+    pub excerpt: String,
+    pub comments: Vec<ReviewComment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewComment {
+    pub author: String,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewSummary {
+    pub author: String,
+    pub state: String,
+    pub body: String,
+}
+
+impl PrFeedback {
+    pub fn is_empty(&self) -> bool {
+        self.threads.is_empty() && self.reviews.is_empty()
+    }
+
+    pub fn comment_count(&self) -> usize {
+        self.threads.iter().map(|t| t.comments.len()).sum::<usize>() + self.reviews.len()
+    }
+
+    /// Prompt asking the agent to address every thread and review summary.
+    pub fn to_prompt(&self) -> String {
+        let mut prompt = format!(
+            "Address the unresolved review feedback on PR #{} ({}). Make the changes in this worktree; if you disagree with a comment, explain why instead of changing the code.\n",
+            self.pr_number, self.pr_url
+        );
+        if !self.threads.is_empty() {
+            prompt.push_str("\n## Review comments\n");
+        }
+        for thread in &self.threads {
+            let location = match thread.line {
+                Some(line) => format!("{}:{}", thread.path, line),
+                None => thread.path.clone(),
+            };
+            let outdated = if thread.outdated {
+                " (outdated: the code may have moved)"
+            } else {
+                ""
+            };
+            prompt.push_str(&format!("\n### {}{}\n", location, outdated));
+            if !thread.excerpt.is_empty() {
+                prompt.push_str(&format!("```diff\n{}\n```\n", thread.excerpt));
+            }
+            for comment in &thread.comments {
+                prompt.push_str(&format!(
+                    "- @{}: {}\n",
+                    comment.author,
+                    indent_continuation(&comment.body)
+                ));
+            }
+        }
+        if !self.reviews.is_empty() {
+            prompt.push_str("\n## Review summaries\n");
+        }
+        for review in &self.reviews {
+            prompt.push_str(&format!(
+                "- @{} ({}): {}\n",
+                review.author,
+                review.state.to_lowercase().replace('_', " "),
+                indent_continuation(&review.body)
+            ));
+        }
+        prompt
+    }
+}
+
+fn indent_continuation(text: &str) -> String {
+    text.trim().lines().collect::<Vec<_>>().join("\n  ")
+}
+
+#[derive(Deserialize)]
+struct GraphqlResponse {
+    data: Option<GraphqlData>,
+}
+
+#[derive(Deserialize)]
+struct GraphqlData {
+    repository: Option<GraphqlRepository>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphqlRepository {
+    pull_request: Option<GraphqlPullRequest>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphqlPullRequest {
+    number: u32,
+    url: String,
+    review_threads: Nodes<GraphqlThread>,
+    reviews: Nodes<GraphqlReview>,
+}
+
+#[derive(Deserialize)]
+struct Nodes<T> {
+    nodes: Vec<T>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphqlThread {
+    is_resolved: bool,
+    #[serde(default)]
+    is_outdated: bool,
+    path: String,
+    line: Option<u32>,
+    original_line: Option<u32>,
+    comments: Nodes<GraphqlComment>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphqlComment {
+    author: Option<GraphqlAuthor>,
+    body: String,
+    #[serde(default)]
+    diff_hunk: String,
+}
+
+#[derive(Deserialize)]
+struct GraphqlReview {
+    author: Option<GraphqlAuthor>,
+    state: String,
+    body: String,
+}
+
+#[derive(Deserialize)]
+struct GraphqlAuthor {
+    login: String,
+}
+
+fn author_login(author: Option<GraphqlAuthor>) -> String {
+    author
+        .map(|a| a.login)
+        .unwrap_or_else(|| "ghost".to_string())
+}
+
+/// Parse `gh api graphql` output for `REVIEW_FEEDBACK_QUERY`, keeping unresolved threads
+/// and reviews that left a message.
+pub fn parse_review_feedback(output: &str) -> Result<PrFeedback, String> {
+    let response: GraphqlResponse =
+        serde_json::from_str(output).map_err(|e| format!("Failed to parse gh output: {}", e))?;
+    let pr = response
+        .data
+        .and_then(|data| data.repository)
+        .and_then(|repo| repo.pull_request)
+        .ok_or_else(|| "Pull request not found".to_string())?;
+
+    let threads = pr
+        .review_threads
+        .nodes
+        .into_iter()
+        .filter(|thread| !thread.is_resolved)
+        .filter_map(|thread| {
+            let excerpt = thread
+                .comments
+                .nodes
+                .first()
+                .map(|c| hunk_excerpt(&c.diff_hunk))
+                .unwrap_or_default();
+            let comments: Vec<ReviewComment> = thread
+                .comments
+                .nodes
+                .into_iter()
+                .filter(|c| !c.body.trim().is_empty())
+                .map(|c| ReviewComment {
+                    author: author_login(c.author),
+                    body: c.body.trim().to_string(),
+                })
+                .collect();
+            if comments.is_empty() {
+                return None;
+            }
+            Some(ReviewThread {
+                path: thread.path,
+                line: thread.line.or(thread.original_line),
+                outdated: thread.is_outdated,
+                excerpt,
+                comments,
+            })
+        })
+        .collect();
+
+    let reviews = pr
+        .reviews
+        .nodes
+        .into_iter()
+        .filter(|review| !review.body.trim().is_empty() && review.state != "DISMISSED")
+        .map(|review| ReviewSummary {
+            author: author_login(review.author),
+            state: review.state,
+            body: review.body.trim().to_string(),
+        })
+        .collect();
+
+    Ok(PrFeedback {
+        pr_number: pr.number,
+        pr_url: pr.url,
+        threads,
+        reviews,
+    })
+}
+
+/// The last few lines of a diff hunk, which end at the commented line.
+fn hunk_excerpt(diff_hunk: &str) -> String {
+    let lines: Vec<&str> = diff_hunk
+        .lines()
+        .filter(|line| !line.starts_with("@@"))
+        .collect();
+    lines[lines.len().saturating_sub(EXCERPT_LINES)..].join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = r#"{"data":{"repository":{"pullRequest":{
+        "number": 12,
+        "url": "https://github.com/o/r/pull/12",
+        "reviewThreads": {"nodes": [
+            {"isResolved": false, "isOutdated": false, "path": "src/auth.ts", "line": 42, "originalLine": 40,
+             "comments": {"nodes": [
+                {"author": {"login": "alice"}, "body": "Handle the null token here.", "diffHunk": "@@ -38,3 +38,5 @@\n const a = 1;\n+const token = read();\n+use(token);"},
+                {"author": null, "body": "Agreed.\nAlso log it.", "diffHunk": ""}
+             ]}},
+            {"isResolved": true, "isOutdated": false, "path": "src/old.ts", "line": 3, "originalLine": 3,
+             "comments": {"nodes": [{"author": {"login": "bob"}, "body": "Fixed", "diffHunk": ""}]}}
+        ]},
+        "reviews": {"nodes": [
+            {"author": {"login": "alice"}, "state": "CHANGES_REQUESTED", "body": "A couple of issues."},
+            {"author": {"login": "bob"}, "state": "APPROVED", "body": ""}
+        ]}
+    }}}}"#;
+
+    #[test]
+    fn test_parse_review_feedback_keeps_unresolved() {
+        let feedback = parse_review_feedback(OUTPUT).unwrap();
+        assert_eq!(feedback.pr_number, 12);
+        assert_eq!(feedback.threads.len(), 1);
+        let thread = &feedback.threads[0];
+        assert_eq!(
+            (thread.path.as_str(), thread.line),
+            ("src/auth.ts", Some(42))
+        );
+        assert_eq!(
+            thread.excerpt,
+            " const a = 1;\n+const token = read();\n+use(token);"
+        );
+        assert_eq!(thread.comments[1].author, "ghost");
+        assert_eq!(feedback.reviews.len(), 1);
+        assert_eq!(feedback.comment_count(), 3);
+    }
+
+    #[test]
+    fn test_feedback_prompt() {
+        let prompt = parse_review_feedback(OUTPUT).unwrap().to_prompt();
+        assert!(prompt.contains("PR #12 (https://github.com/o/r/pull/12)"));
+        assert!(prompt.contains("### src/auth.ts:42\n```diff\n"));
+        assert!(prompt.contains("- @ghost: Agreed.\n  Also log it.\n"));
+        assert!(prompt.contains("- @alice (changes requested): A couple of issues."));
+        assert!(!prompt.contains("src/old.ts"));
+    }
+}
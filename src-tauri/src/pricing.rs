@@ -0,0 +1,500 @@
+//! Per-model token pricing: built-in table, overridable from `agents.toml` and a
+//! user-editable `pricing.toml` in the app config dir.
+//!
+//! Both files use the same shape:
+//!
+//! ```toml
+//! [[pricing]]
+//! pattern = "gpt-5.1-mini"   # case-insensitive substring of the model id
+//! input = 0.40               # USD per 1M input tokens
+//! output = 1.60              # USD per 1M output tokens
+//! cached_input = 0.10        # optional; cache reads (default 50% of input, 10% for Claude)
+//! cache_write = 0.50         # optional; cache creation (default input, 125% for Claude)
+//! ```
+//!
+//! When remote pricing is enabled in settings, a JSON sheet (`{"pricing": [...]}` with
+//! the same fields) is fetched from the configured URL and cached as
+//! `pricing-remote.json`. The cached sheet replaces the built-in rates; the two local
+//! files still override it.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+
+/// Built-in rates (USD per 1M tokens): (model_pattern, input_rate, output_rate)
+const BUILTIN_PRICING: &[(&str, f64, f64)] = &[
+    // OpenAI models (https://openai.com/api/pricing/)
+    ("gpt-5.1-mini", 0.40, 1.60),
+    ("gpt-5.1", 2.50, 10.00),
+    ("gpt-5", 5.00, 15.00),
+    ("o4-mini", 1.10, 4.40),
+    ("o3-mini", 1.10, 4.40),
+    ("o3", 10.00, 40.00),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("gpt-4.1-nano", 0.10, 0.40),
+    ("gpt-4.1", 2.50, 10.00),
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4o", 2.50, 10.00),
+    ("gpt-4-turbo", 10.00, 30.00),
+    ("gpt-4", 30.00, 60.00),
+    ("gpt-3.5-turbo", 0.50, 1.50),
+    // Anthropic models (https://www.anthropic.com/pricing)
+    ("claude-opus-4", 15.00, 75.00),
+    ("claude-sonnet-4", 3.00, 15.00),
+    ("claude-3-5-sonnet", 3.00, 15.00),
+    ("claude-3-opus", 15.00, 75.00),
+    ("claude-3-sonnet", 3.00, 15.00),
+    ("claude-3-haiku", 0.25, 1.25),
+];
+
+/// Rates for models no pattern matches (roughly GPT-4o).
+const DEFAULT_RATES: (f64, f64) = (2.50, 10.00);
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PriceEntry {
+    pub pattern: String,
+    pub input: f64,
+    pub output: f64,
+    #[serde(default)]
+    pub cached_input: Option<f64>,
+    #[serde(default)]
+    pub cache_write: Option<f64>,
+}
+
+/// Resolved USD-per-1M rates for one model.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelRates {
+    pub input: f64,
+    pub output: f64,
+    pub cached_input: f64,
+    pub cache_write: f64,
+}
+
+/// Cost of one usage report, split by token class.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CostBreakdown {
+    pub input_cost: f64,
+    pub cached_input_cost: f64,
+    pub cache_write_cost: f64,
+    pub output_cost: f64,
+    pub total: f64,
+}
+
+impl CostBreakdown {
+    /// The part of `total` spent on cache reads and writes.
+    pub fn cache_cost(&self) -> f64 {
+        self.cached_input_cost + self.cache_write_cost
+    }
+}
+
+/// ChatGPT-plan and Claude OAuth logins are covered by a subscription, not billed per
+/// token. `auth_method` is a task's effective method (see `resolve_auth_method`).
+pub fn is_subscription_auth(auth_method: Option<&str>) -> bool {
+    matches!(auth_method, Some("chatgpt") | Some("oauth"))
+}
+
+/// Anthropic reports cache tokens separately from `input_tokens` and bills reads at 10%
+/// and writes at 125%; OpenAI counts cached tokens inside `input_tokens` at 50%.
+fn is_anthropic_model(model: &str) -> bool {
+    model.to_lowercase().contains("claude")
+}
+
+fn with_cache_defaults(model: &str, input: f64, output: f64) -> ModelRates {
+    let (read, write) = if is_anthropic_model(model) {
+        (0.10, 1.25)
+    } else {
+        (0.50, 1.0)
+    };
+    ModelRates {
+        input,
+        output,
+        cached_input: input * read,
+        cache_write: input * write,
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PricingFile {
+    #[serde(default)]
+    pricing: Vec<PriceEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PricingReload {
+    pub entries: usize,
+    #[serde(rename = "userEntries")]
+    pub user_entries: usize,
+    pub path: String,
+    #[serde(rename = "remoteEntries")]
+    pub remote_entries: usize,
+    /// When the cached remote sheet in use was fetched (unix seconds).
+    #[serde(rename = "remoteFetchedAt")]
+    pub remote_fetched_at: Option<i64>,
+}
+
+/// Remote sheet as cached on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteSheet {
+    url: String,
+    fetched_at: i64,
+    pricing: Vec<PriceEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RemoteSheetBody {
+    Wrapped { pricing: Vec<PriceEntry> },
+    Bare(Vec<PriceEntry>),
+}
+
+/// Refetch a cached sheet older than this in the background.
+const REMOTE_MAX_AGE_SECS: i64 = 24 * 60 * 60;
+const REMOTE_TIMEOUT: Duration = Duration::from_secs(10);
+const REMOTE_MAX_BYTES: usize = 512 * 1024;
+const REMOTE_MAX_ENTRIES: usize = 1000;
+/// Anything pricier than this per 1M tokens is treated as a broken sheet.
+const REMOTE_MAX_RATE: f64 = 10_000.0;
+
+static PRICING: OnceLock<RwLock<Vec<PriceEntry>>> = OnceLock::new();
+
+fn table() -> &'static RwLock<Vec<PriceEntry>> {
+    PRICING.get_or_init(|| RwLock::new(merge(&[])))
+}
+
+pub fn pricing_path() -> Result<PathBuf, String> {
+    let base = dirs::config_dir().ok_or_else(|| "config dir unavailable".to_string())?;
+    Ok(base.join("phantom-harness").join("pricing.toml"))
+}
+
+fn remote_cache_path() -> Result<PathBuf, String> {
+    pricing_path().map(|path| path.with_file_name("pricing-remote.json"))
+}
+
+/// Built-in table with `overrides` applied in order; a later entry replaces an
+/// earlier one with the same (case-insensitive) pattern.
+fn merge(overrides: &[&[PriceEntry]]) -> Vec<PriceEntry> {
+    let mut entries: Vec<PriceEntry> = BUILTIN_PRICING
+        .iter()
+        .map(|(pattern, input, output)| PriceEntry {
+            pattern: pattern.to_string(),
+            input: *input,
+            output: *output,
+            cached_input: None,
+            cache_write: None,
+        })
+        .collect();
+    for entry in overrides.iter().flat_map(|list| list.iter()) {
+        let pattern = entry.pattern.trim().to_lowercase();
+        if pattern.is_empty() || entry.input < 0.0 || entry.output < 0.0 {
+            continue;
+        }
+        entries.retain(|existing| existing.pattern.to_lowercase() != pattern);
+        entries.push(PriceEntry {
+            pattern,
+            ..entry.clone()
+        });
+    }
+    entries
+}
+
+fn read_pricing_file(path: &Path) -> Result<Vec<PriceEntry>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(raw) => toml::from_str::<PricingFile>(&raw)
+            .map(|file| file.pricing)
+            .map_err(|e| format!("{}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+/// Validate a downloaded sheet; rejects empty sheets and implausible rates.
+fn parse_remote_sheet(raw: &str) -> Result<Vec<PriceEntry>, String> {
+    if raw.len() > REMOTE_MAX_BYTES {
+        return Err("price sheet is too large".to_string());
+    }
+    let entries = match serde_json::from_str::<RemoteSheetBody>(raw)
+        .map_err(|e| format!("invalid price sheet: {}", e))?
+    {
+        RemoteSheetBody::Wrapped { pricing } | RemoteSheetBody::Bare(pricing) => pricing,
+    };
+    if entries.is_empty() || entries.len() > REMOTE_MAX_ENTRIES {
+        return Err(format!("price sheet has {} entries", entries.len()));
+    }
+    let valid_rate = |rate: f64| rate.is_finite() && (0.0..=REMOTE_MAX_RATE).contains(&rate);
+    for entry in &entries {
+        let rates = [
+            Some(entry.input),
+            Some(entry.output),
+            entry.cached_input,
+            entry.cache_write,
+        ];
+        if entry.pattern.trim().is_empty() || !rates.into_iter().flatten().all(valid_rate) {
+            return Err(format!("invalid price sheet entry {:?}", entry.pattern));
+        }
+    }
+    Ok(entries)
+}
+
+fn read_remote_cache() -> Option<RemoteSheet> {
+    let raw = std::fs::read_to_string(remote_cache_path().ok()?).ok()?;
+    serde_json::from_str(&raw).ok()
+}
+
+/// True when there is no cached sheet for `url` or it is older than `REMOTE_MAX_AGE_SECS`.
+pub fn remote_cache_is_stale(url: &str) -> bool {
+    read_remote_cache().is_none_or(|sheet| {
+        sheet.url != url || chrono::Utc::now().timestamp() - sheet.fetched_at > REMOTE_MAX_AGE_SECS
+    })
+}
+
+/// Fetch and validate the sheet at `url`, then cache it. Returns the entry count; on any
+/// failure the previous cache is kept.
+pub async fn refresh_remote(url: &str) -> Result<usize, String> {
+    let client = reqwest::Client::builder()
+        .timeout(REMOTE_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client.get(url).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("price sheet request failed: {}", response.status()));
+    }
+    let raw = response.text().await.map_err(|e| e.to_string())?;
+    let pricing = parse_remote_sheet(&raw)?;
+    let count = pricing.len();
+    let sheet = RemoteSheet {
+        url: url.to_string(),
+        fetched_at: chrono::Utc::now().timestamp(),
+        pricing,
+    };
+    let path = remote_cache_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let tmp_path = path.with_extension("json.tmp");
+    let payload = serde_json::to_string_pretty(&sheet).map_err(|e| e.to_string())?;
+    std::fs::write(&tmp_path, payload).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp_path, &path).map_err(|e| e.to_string())?;
+    Ok(count)
+}
+
+/// (Re)build the active table from the cached remote sheet (when `use_remote`),
+/// `agents_pricing` and the user's `pricing.toml`. On a parse error the current table
+/// is left untouched.
+pub fn load(agents_pricing: &[PriceEntry], use_remote: bool) -> Result<PricingReload, String> {
+    let path = pricing_path()?;
+    let user = read_pricing_file(&path)?;
+    let remote = if use_remote {
+        read_remote_cache()
+    } else {
+        None
+    };
+    let remote_entries = remote.as_ref().map_or(&[][..], |sheet| &sheet.pricing[..]);
+    let entries = merge(&[remote_entries, agents_pricing, &user]);
+    let count = entries.len();
+    *table().write().map_err(|e| e.to_string())? = entries;
+    Ok(PricingReload {
+        entries: count,
+        user_entries: user.len(),
+        path: path.display().to_string(),
+        remote_entries: remote_entries.len(),
+        remote_fetched_at: remote.map(|sheet| sheet.fetched_at),
+    })
+}
+
+/// Longest matching pattern wins, so "gpt-4.1-mini" never falls through to "gpt-4".
+fn rates_in(entries: &[PriceEntry], model: &str) -> ModelRates {
+    let model_lower = model.to_lowercase();
+    let entry = entries
+        .iter()
+        .filter(|entry| model_lower.contains(&entry.pattern.to_lowercase()))
+        .max_by_key(|entry| entry.pattern.len());
+    match entry {
+        Some(entry) => {
+            let defaults = with_cache_defaults(model, entry.input, entry.output);
+            ModelRates {
+                cached_input: entry.cached_input.unwrap_or(defaults.cached_input),
+                cache_write: entry.cache_write.unwrap_or(defaults.cache_write),
+                ..defaults
+            }
+        }
+        None => with_cache_defaults(model, DEFAULT_RATES.0, DEFAULT_RATES.1),
+    }
+}
+
+/// Get pricing rates for a model (returns default rates if model not found)
+pub fn get_model_rates(model: &str) -> ModelRates {
+    match table().read() {
+        Ok(entries) => rates_in(&entries, model),
+        Err(_) => with_cache_defaults(model, DEFAULT_RATES.0, DEFAULT_RATES.1),
+    }
+}
+
+fn breakdown_with(
+    model: &str,
+    rates: ModelRates,
+    input_tokens: i64,
+    cached_input_tokens: i64,
+    cache_write_tokens: i64,
+    output_tokens: i64,
+) -> CostBreakdown {
+    let per_token = |tokens: i64, rate: f64| (tokens.max(0) as f64) * rate / 1_000_000.0;
+    // OpenAI-style usage counts cached tokens inside input_tokens; a cached count larger
+    // than the input can only mean they were reported separately.
+    let cached_in_input = !is_anthropic_model(model) && cached_input_tokens <= input_tokens;
+    let uncached_input = if cached_in_input {
+        input_tokens - cached_input_tokens
+    } else {
+        input_tokens
+    };
+    let input_cost = per_token(uncached_input, rates.input);
+    let cached_input_cost = per_token(cached_input_tokens, rates.cached_input);
+    let cache_write_cost = per_token(cache_write_tokens, rates.cache_write);
+    let output_cost = per_token(output_tokens, rates.output);
+    CostBreakdown {
+        input_cost,
+        cached_input_cost,
+        cache_write_cost,
+        output_cost,
+        total: input_cost + cached_input_cost + cache_write_cost + output_cost,
+    }
+}
+
+/// Price one usage report for `model` with the active table.
+pub fn cost_breakdown(
+    model: &str,
+    input_tokens: i64,
+    cached_input_tokens: i64,
+    cache_write_tokens: i64,
+    output_tokens: i64,
+) -> CostBreakdown {
+    breakdown_with(
+        model,
+        get_model_rates(model),
+        input_tokens,
+        cached_input_tokens,
+        cache_write_tokens,
+        output_tokens,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pattern: &str, input: f64, output: f64) -> PriceEntry {
+        PriceEntry {
+            pattern: pattern.to_string(),
+            input,
+            output,
+            cached_input: None,
+            cache_write: None,
+        }
+    }
+
+    fn rates(entries: &[PriceEntry], model: &str) -> (f64, f64) {
+        let rates = rates_in(entries, model);
+        (rates.input, rates.output)
+    }
+
+    #[test]
+    fn test_longest_pattern_wins() {
+        let builtin = merge(&[]);
+        assert_eq!(rates(&builtin, "gpt-4.1-mini-2025-04-14"), (0.40, 1.60));
+        assert_eq!(rates(&builtin, "gpt-4-0613"), (30.00, 60.00));
+        assert_eq!(rates(&builtin, "GPT-5.1-Mini"), (0.40, 1.60));
+        assert_eq!(rates(&builtin, "o3-mini-high"), (1.10, 4.40));
+        assert_eq!(rates(&builtin, "mystery-model"), DEFAULT_RATES);
+
+        // Order in the table does not matter, only pattern length.
+        let reversed: Vec<PriceEntry> = builtin.into_iter().rev().collect();
+        assert_eq!(rates(&reversed, "gpt-4.1-mini"), (0.40, 1.60));
+        assert_eq!(rates(&reversed, "gpt-4o-mini"), (0.15, 0.60));
+    }
+
+    #[test]
+    fn test_overrides_replace_and_extend_builtin() {
+        let agents = vec![entry("gpt-5", 1.25, 10.00)];
+        let user = vec![
+            entry("GPT-5", 1.00, 8.00),
+            entry("gpt-5.2-codex", 1.75, 14.00),
+            entry("  ", 9.0, 9.0),
+        ];
+        let merged = merge(&[&agents, &user]);
+        assert_eq!(rates(&merged, "gpt-5"), (1.00, 8.00));
+        assert_eq!(rates(&merged, "gpt-5.2-codex"), (1.75, 14.00));
+        assert_eq!(rates(&merged, "gpt-5.1-mini"), (0.40, 1.60));
+        assert_eq!(merged.iter().filter(|e| e.pattern == "gpt-5").count(), 1);
+    }
+
+    #[test]
+    fn test_cached_tokens_priced_by_provider() {
+        let builtin = merge(&[]);
+        // OpenAI: 1M input of which 800k cached at 50%.
+        let openai = breakdown_with(
+            "gpt-4o",
+            rates_in(&builtin, "gpt-4o"),
+            1_000_000,
+            800_000,
+            0,
+            0,
+        );
+        assert!((openai.input_cost - 0.50).abs() < 1e-9);
+        assert!((openai.cached_input_cost - 1.00).abs() < 1e-9);
+        assert!((openai.total - 1.50).abs() < 1e-9);
+
+        // Anthropic: cache reads/writes are reported on top of input_tokens.
+        let claude = breakdown_with(
+            "claude-sonnet-4-5",
+            rates_in(&builtin, "claude-sonnet-4-5"),
+            1_000,
+            1_000_000,
+            100_000,
+            0,
+        );
+        assert!((claude.input_cost - 0.003).abs() < 1e-9);
+        assert!((claude.cached_input_cost - 0.30).abs() < 1e-9);
+        assert!((claude.cache_write_cost - 0.375).abs() < 1e-9);
+        assert!((claude.cache_cost() - 0.675).abs() < 1e-9);
+
+        // Explicit per-model cache rates win over the provider default.
+        let custom = PriceEntry {
+            cached_input: Some(0.125),
+            ..entry("gpt-5.2", 1.25, 10.0)
+        };
+        let merged = merge(&[&[custom]]);
+        assert_eq!(rates_in(&merged, "gpt-5.2-codex").cached_input, 0.125);
+    }
+
+    #[test]
+    fn test_pricing_file_parses() {
+        let file: PricingFile = toml::from_str(
+            "[[pricing]]\npattern = \"claude-opus-4-5\"\ninput = 5.0\noutput = 25.0\n",
+        )
+        .expect("parse pricing");
+        assert_eq!(file.pricing, vec![entry("claude-opus-4-5", 5.0, 25.0)]);
+    }
+
+    #[test]
+    fn test_remote_sheet_validation() {
+        let sheet = parse_remote_sheet(
+            r#"{"pricing": [{"pattern": "gpt-5", "input": 1.25, "output": 10.0, "cached_input": 0.125}]}"#,
+        )
+        .expect("wrapped sheet");
+        assert_eq!(sheet[0].cached_input, Some(0.125));
+        assert!(parse_remote_sheet(r#"[{"pattern": "o3", "input": 2.0, "output": 8.0}]"#).is_ok());
+
+        assert!(parse_remote_sheet(r#"{"pricing": []}"#).is_err());
+        assert!(parse_remote_sheet("<html>").is_err());
+        assert!(parse_remote_sheet(r#"[{"pattern": "", "input": 1.0, "output": 1.0}]"#).is_err());
+        assert!(parse_remote_sheet(r#"[{"pattern": "x", "input": -1.0, "output": 1.0}]"#).is_err());
+        assert!(parse_remote_sheet(r#"[{"pattern": "x", "input": 1.0, "output": 1e9}]"#).is_err());
+
+        // Local overrides still win over the remote sheet.
+        let user = vec![entry("gpt-5", 1.0, 8.0)];
+        let merged = merge(&[&sheet, &user]);
+        assert_eq!(rates(&merged, "gpt-5"), (1.0, 8.0));
+    }
+}
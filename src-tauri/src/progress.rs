@@ -0,0 +1,272 @@
+//! Generation progress published on an in-process broadcast channel, so consumers like the
+//! Discord bot and MCP clients follow tasks without a webview or the stream emit thread.
+//!
+//! Publishing never blocks; a consumer that falls behind misses events (`Lagged`) rather
+//! than slowing the generation down. Events a user has to see (`must_deliver`) are also
+//! queued for reliable subscribers, which never miss them.
+
+use phantom_harness_backend::cli::{PermissionOption, UserInputQuestion};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, mpsc};
+
+/// Events kept for consumers that fall behind before older ones are dropped.
+const PROGRESS_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    Thinking,
+    Tool,
+    Responding,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ProgressKind {
+    GenerationStarted {
+        agent_id: String,
+        prompt: String,
+        /// The prompt came from Discord, so it needn't be posted back there
+        from_discord: bool,
+    },
+    PhaseChanged {
+        phase: Phase,
+    },
+    ToolCall {
+        name: String,
+    },
+    PermissionRequested {
+        request_id: String,
+        tool_name: String,
+        description: Option<String>,
+        raw_input: Option<String>,
+        options: Vec<PermissionOption>,
+    },
+    /// A permission request was answered, from any client
+    PermissionResolved {
+        request_id: String,
+        option_id: String,
+    },
+    UserInputRequested {
+        request_id: String,
+        questions: Vec<UserInputQuestion>,
+    },
+    Completed {
+        summary: String,
+        reply: String,
+    },
+    Cancelled,
+    Errored {
+        error: String,
+    },
+}
+
+impl ProgressKind {
+    /// Events chat mirrors post: the prompt, requests that need an answer and their
+    /// resolution, and the result. Few per generation.
+    pub fn must_deliver(&self) -> bool {
+        matches!(
+            self,
+            ProgressKind::GenerationStarted { .. }
+                | ProgressKind::Completed { .. }
+                | ProgressKind::PermissionRequested { .. }
+                | ProgressKind::PermissionResolved { .. }
+                | ProgressKind::UserInputRequested { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressEvent {
+    pub task_id: String,
+    /// ms since the epoch
+    pub timestamp: i64,
+    #[serde(flatten)]
+    pub kind: ProgressKind,
+}
+
+#[derive(Clone)]
+pub struct ProgressBus {
+    tx: broadcast::Sender<ProgressEvent>,
+    reliable: Arc<Mutex<Vec<mpsc::UnboundedSender<ProgressEvent>>>>,
+}
+
+impl Default for ProgressBus {
+    fn default() -> Self {
+        let (tx, _) = broadcast::channel(PROGRESS_CAPACITY);
+        Self {
+            tx,
+            reliable: Arc::default(),
+        }
+    }
+}
+
+impl ProgressBus {
+    /// Send an event to current subscribers; a no-op when there are none.
+    pub fn publish(&self, task_id: &str, kind: ProgressKind) {
+        let event = ProgressEvent {
+            task_id: task_id.to_string(),
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            kind,
+        };
+        if event.kind.must_deliver() {
+            if let Ok(mut reliable) = self.reliable.lock() {
+                reliable.retain(|tx| tx.send(event.clone()).is_ok());
+            }
+        }
+        let _ = self.tx.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ProgressEvent> {
+        self.tx.subscribe()
+    }
+
+    /// Receive every `must_deliver` event, however far behind the consumer is. They are
+    /// rare enough that the queue stays small.
+    pub fn subscribe_reliable(&self) -> mpsc::UnboundedReceiver<ProgressEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        if let Ok(mut reliable) = self.reliable.lock() {
+            reliable.push(tx);
+        }
+        rx
+    }
+}
+
+/// Phase a streamed update puts the generation in, if it is a progress update at all.
+pub fn phase_of(update: &phantom_harness_backend::cli::StreamingUpdate) -> Option<Phase> {
+    use phantom_harness_backend::cli::StreamingUpdate;
+    match update {
+        StreamingUpdate::ReasoningChunk { .. } => Some(Phase::Thinking),
+        StreamingUpdate::ToolCall { .. } | StreamingUpdate::ToolReturn { .. } => Some(Phase::Tool),
+        StreamingUpdate::TextChunk { .. } => Some(Phase::Responding),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscribers_receive_tagged_events() {
+        let bus = ProgressBus::default();
+        // Nobody listening yet: dropped without error.
+        bus.publish("t0", ProgressKind::Cancelled);
+
+        let mut rx = bus.subscribe();
+        bus.publish(
+            "t1",
+            ProgressKind::PhaseChanged {
+                phase: Phase::Thinking,
+            },
+        );
+        bus.publish(
+            "t1",
+            ProgressKind::PermissionRequested {
+                request_id: "r1".to_string(),
+                tool_name: "Bash".to_string(),
+                description: None,
+                raw_input: Some("ls".to_string()),
+                options: Vec::new(),
+            },
+        );
+
+        let event = rx.try_recv().unwrap();
+        assert_eq!(event.task_id, "t1");
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "phaseChanged");
+        assert_eq!(json["phase"], "thinking");
+        assert_eq!(json["taskId"], "t1");
+        assert!(json["timestamp"].as_i64().unwrap() > 0);
+
+        let json = serde_json::to_value(rx.try_recv().unwrap()).unwrap();
+        assert_eq!(json["type"], "permissionRequested");
+        assert_eq!(json["requestId"], "r1");
+        assert_eq!(json["toolName"], "Bash");
+        assert_eq!(json["rawInput"], "ls");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_reliable_subscribers_never_lag() {
+        let bus = ProgressBus::default();
+        let mut lossy = bus.subscribe();
+        let mut reliable = bus.subscribe_reliable();
+        bus.publish(
+            "t1",
+            ProgressKind::Completed {
+                summary: "done".to_string(),
+                reply: "ok".to_string(),
+            },
+        );
+        for _ in 0..PROGRESS_CAPACITY * 2 {
+            bus.publish(
+                "t1",
+                ProgressKind::ToolCall {
+                    name: "Bash".into(),
+                },
+            );
+        }
+
+        assert!(matches!(
+            lossy.try_recv(),
+            Err(broadcast::error::TryRecvError::Lagged(_))
+        ));
+        let event = reliable.try_recv().unwrap();
+        assert!(matches!(event.kind, ProgressKind::Completed { .. }));
+        assert!(reliable.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_reliable_subscribers_keep_posted_events_in_order() {
+        let bus = ProgressBus::default();
+        let mut reliable = bus.subscribe_reliable();
+        let kinds = [
+            ProgressKind::GenerationStarted {
+                agent_id: "codex".into(),
+                prompt: "hi".into(),
+                from_discord: false,
+            },
+            ProgressKind::PhaseChanged {
+                phase: Phase::Thinking,
+            },
+            ProgressKind::PermissionRequested {
+                request_id: "r1".into(),
+                tool_name: "Bash".into(),
+                description: None,
+                raw_input: None,
+                options: Vec::new(),
+            },
+            ProgressKind::PermissionResolved {
+                request_id: "r1".into(),
+                option_id: "allow".into(),
+            },
+            ProgressKind::Completed {
+                summary: "done".into(),
+                reply: "ok".into(),
+            },
+        ];
+        for kind in kinds {
+            bus.publish("t1", kind);
+        }
+
+        let received: Vec<_> = std::iter::from_fn(|| reliable.try_recv().ok())
+            .map(|event| serde_json::to_value(event).unwrap()["type"].clone())
+            .collect();
+        assert_eq!(
+            received,
+            [
+                "generationStarted",
+                "permissionRequested",
+                "permissionResolved",
+                "completed"
+            ]
+        );
+    }
+}
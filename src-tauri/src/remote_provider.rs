@@ -0,0 +1,185 @@
+//! Identifies the hosting provider behind a git remote URL (GitHub, GitLab, Bitbucket)
+//! so branch and PR helpers can pick the right CLI and web URLs.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderKind {
+    GitHub,
+    GitLab,
+    Bitbucket,
+}
+
+impl ProviderKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProviderKind::GitHub => "github",
+            ProviderKind::GitLab => "gitlab",
+            ProviderKind::Bitbucket => "bitbucket",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ProviderKind::GitHub => "GitHub",
+            ProviderKind::GitLab => "GitLab",
+            ProviderKind::Bitbucket => "Bitbucket",
+        }
+    }
+
+    fn from_host(host: &str) -> Option<Self> {
+        let host = host.to_ascii_lowercase();
+        if host == "github.com" || host.starts_with("github.") {
+            Some(ProviderKind::GitHub)
+        } else if host.contains("gitlab") {
+            Some(ProviderKind::GitLab)
+        } else if host.contains("bitbucket") {
+            Some(ProviderKind::Bitbucket)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteProvider {
+    pub kind: ProviderKind,
+    pub host: String,
+    /// Owner or namespace; GitLab allows nested groups ("group/subgroup").
+    pub owner: String,
+    pub repo: String,
+}
+
+impl RemoteProvider {
+    /// Parse scp-style (`git@host:owner/repo.git`), `ssh://`, `https://` and `git://`
+    /// remotes. Returns None for hosts that aren't a known provider.
+    pub fn parse(remote_url: &str) -> Option<Self> {
+        let trimmed = remote_url.trim().trim_end_matches('/');
+        let trimmed = trimmed.strip_suffix(".git").unwrap_or(trimmed);
+
+        let (authority, path) = match trimmed.split_once("://") {
+            Some((_, rest)) => rest.split_once('/')?,
+            // scp-style: [user@]host:path
+            None => trimmed.split_once(':')?,
+        };
+        let host = authority.rsplit('@').next()?;
+        let host = host.split(':').next()?.trim();
+        let kind = ProviderKind::from_host(host)?;
+
+        let mut segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        // Bitbucket Server serves HTTPS clones from /scm/<project>/<repo>.
+        if kind == ProviderKind::Bitbucket && segments.first() == Some(&"scm") {
+            segments.remove(0);
+        }
+        let (owner, repo) = match kind {
+            ProviderKind::GitLab if segments.len() >= 2 => {
+                let repo = segments.pop()?;
+                (segments.join("/"), repo.to_string())
+            }
+            _ if segments.len() >= 2 => (segments[0].to_string(), segments[1].to_string()),
+            _ => return None,
+        };
+
+        Some(Self {
+            kind,
+            host: host.to_string(),
+            owner,
+            repo,
+        })
+    }
+
+    pub fn web_url(&self) -> String {
+        format!("https://{}/{}/{}", self.host, self.owner, self.repo)
+    }
+
+    /// Web page for opening a pull/merge request from `head` into `base`.
+    pub fn compare_url(&self, base: &str, head: &str) -> String {
+        let encode = |value: &str| urlencoding::encode(value).into_owned();
+        match self.kind {
+            ProviderKind::GitHub => {
+                format!("{}/compare/{}...{}?expand=1", self.web_url(), base, head)
+            }
+            ProviderKind::GitLab => format!(
+                "{}/-/merge_requests/new?merge_request%5Bsource_branch%5D={}&merge_request%5Btarget_branch%5D={}",
+                self.web_url(),
+                encode(head),
+                encode(base)
+            ),
+            ProviderKind::Bitbucket if self.host == "bitbucket.org" => format!(
+                "{}/pull-requests/new?source={}&dest={}",
+                self.web_url(),
+                encode(head),
+                encode(base)
+            ),
+            ProviderKind::Bitbucket => format!(
+                "https://{}/projects/{}/repos/{}/pull-requests?create&sourceBranch={}&targetBranch={}",
+                self.host,
+                self.owner,
+                self.repo,
+                encode(&format!("refs/heads/{}", head)),
+                encode(&format!("refs/heads/{}", base))
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_remote_providers() {
+        let github = RemoteProvider::parse("git@github.com:zruss11/Phantom.git").unwrap();
+        assert_eq!(github.kind, ProviderKind::GitHub);
+        assert_eq!(
+            (github.owner.as_str(), github.repo.as_str()),
+            ("zruss11", "Phantom")
+        );
+
+        let gitlab = RemoteProvider::parse("git@gitlab.com:acme/platform/api.git").unwrap();
+        assert_eq!(gitlab.kind, ProviderKind::GitLab);
+        assert_eq!(
+            (gitlab.owner.as_str(), gitlab.repo.as_str()),
+            ("acme/platform", "api")
+        );
+
+        let hosted = RemoteProvider::parse("https://ci@gitlab.acme.dev:8443/team/web").unwrap();
+        assert_eq!(hosted.kind, ProviderKind::GitLab);
+        assert_eq!(hosted.host, "gitlab.acme.dev");
+        assert_eq!(hosted.web_url(), "https://gitlab.acme.dev/team/web");
+
+        let bitbucket = RemoteProvider::parse("https://user@bitbucket.org/team/app.git").unwrap();
+        assert_eq!(bitbucket.kind, ProviderKind::Bitbucket);
+        assert_eq!(
+            (bitbucket.owner.as_str(), bitbucket.repo.as_str()),
+            ("team", "app")
+        );
+
+        let server =
+            RemoteProvider::parse("https://bitbucket.acme.dev/scm/PROJ/service.git").unwrap();
+        assert_eq!(
+            (server.owner.as_str(), server.repo.as_str()),
+            ("PROJ", "service")
+        );
+
+        assert_eq!(RemoteProvider::parse("git@git.acme.dev:team/app.git"), None);
+        assert_eq!(RemoteProvider::parse("/srv/git/app.git"), None);
+    }
+
+    #[test]
+    fn test_compare_urls() {
+        let github = RemoteProvider::parse("https://github.com/o/r").unwrap();
+        assert_eq!(
+            github.compare_url("main", "feat/x"),
+            "https://github.com/o/r/compare/main...feat/x?expand=1"
+        );
+        let gitlab = RemoteProvider::parse("git@gitlab.com:g/sub/r.git").unwrap();
+        assert_eq!(
+            gitlab.compare_url("main", "feat/x"),
+            "https://gitlab.com/g/sub/r/-/merge_requests/new?merge_request%5Bsource_branch%5D=feat%2Fx&merge_request%5Btarget_branch%5D=main"
+        );
+        let bitbucket = RemoteProvider::parse("git@bitbucket.org:t/r.git").unwrap();
+        assert_eq!(
+            bitbucket.compare_url("main", "fix"),
+            "https://bitbucket.org/t/r/pull-requests/new?source=fix&dest=main"
+        );
+    }
+}
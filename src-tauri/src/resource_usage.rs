@@ -0,0 +1,110 @@
+//! CPU and memory of agent and terminal process trees, sampled with one `ps` call.
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessSample {
+    pub pid: u32,
+    pub ppid: u32,
+    pub rss_kb: u64,
+    pub cpu_percent: f64,
+}
+
+/// Totals for a set of processes and all their descendants.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessTreeUsage {
+    pub processes: usize,
+    pub rss_bytes: u64,
+    pub cpu_percent: f64,
+}
+
+/// Parse `ps -A -o pid=,ppid=,rss=,%cpu=` output; malformed lines are skipped.
+pub fn parse_ps_output(text: &str) -> Vec<ProcessSample> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let ppid = fields.next()?.parse().ok()?;
+            let rss_kb = fields.next()?.parse().ok()?;
+            let cpu_percent = fields.next()?.replace(',', ".").parse().ok()?;
+            Some(ProcessSample {
+                pid,
+                ppid,
+                rss_kb,
+                cpu_percent,
+            })
+        })
+        .collect()
+}
+
+/// Snapshot every process on the machine.
+pub fn sample_processes() -> Result<Vec<ProcessSample>, String> {
+    if !cfg!(unix) {
+        return Err("Resource usage is only available on macOS and Linux".to_string());
+    }
+    let output = std::process::Command::new("ps")
+        .args(["-A", "-o", "pid=,ppid=,rss=,%cpu="])
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(parse_ps_output(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Sum `roots` and their descendants. Roots that aren't children of `parent` are ignored,
+/// so a pid reused by an unrelated process is never counted.
+pub fn tree_usage(samples: &[ProcessSample], parent: u32, roots: &[u32]) -> ProcessTreeUsage {
+    let mut children: HashMap<u32, Vec<&ProcessSample>> = HashMap::new();
+    for sample in samples {
+        children.entry(sample.ppid).or_default().push(sample);
+    }
+    let mut usage = ProcessTreeUsage::default();
+    let mut stack: Vec<&ProcessSample> = children
+        .get(&parent)
+        .into_iter()
+        .flatten()
+        .filter(|sample| roots.contains(&sample.pid))
+        .copied()
+        .collect();
+    while let Some(sample) = stack.pop() {
+        usage.processes += 1;
+        usage.rss_bytes += sample.rss_kb * 1024;
+        usage.cpu_percent += sample.cpu_percent;
+        if let Some(kids) = children.get(&sample.pid) {
+            stack.extend(kids.iter().copied());
+        }
+    }
+    usage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tree_usage_sums_descendants_of_known_roots() {
+        let samples = parse_ps_output(
+            "  100     1  50000  1.0\n\
+               200   100  20000 10.5\n\
+               201   200   4000  2,5\n\
+               300   100   8000  0.0\n\
+               400     1   9999 99.0\n\
+             garbage line\n",
+        );
+        assert_eq!(samples.len(), 5);
+
+        let usage = tree_usage(&samples, 100, &[200]);
+        assert_eq!(usage.processes, 2);
+        assert_eq!(usage.rss_bytes, 24000 * 1024);
+        assert!((usage.cpu_percent - 13.0).abs() < 1e-9);
+
+        // A root that isn't our child (e.g. a reused pid) is not counted.
+        assert_eq!(
+            tree_usage(&samples, 100, &[400]),
+            ProcessTreeUsage::default()
+        );
+    }
+}
@@ -0,0 +1,117 @@
+//! In-memory cache for Review Center diff results, invalidated by git state fingerprints.
+
+use std::collections::HashMap;
+
+/// Files-list results are small; file diffs can be large, so keep fewer.
+pub const MAX_FILE_LIST_ENTRIES: usize = 64;
+pub const MAX_FILE_DIFF_ENTRIES: usize = 256;
+
+struct Entry<V> {
+    fingerprint: String,
+    value: V,
+    last_used: u64,
+}
+
+/// Values keyed by `cache_key(task_id, ..)`. An entry is only served while the
+/// fingerprint it was computed under still matches; the least recently used entry
+/// is evicted once `max_entries` is reached.
+pub struct DiffCache<V> {
+    max_entries: usize,
+    entries: HashMap<String, Entry<V>>,
+    tick: u64,
+}
+
+/// Join key parts; the task id always comes first so `invalidate_task` can match it.
+pub fn cache_key(task_id: &str, parts: &[&str]) -> String {
+    let mut key = task_id.to_string();
+    for part in parts {
+        key.push('\x1f');
+        key.push_str(part);
+    }
+    key
+}
+
+impl<V: Clone> DiffCache<V> {
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries: max_entries.max(1),
+            entries: HashMap::new(),
+            tick: 0,
+        }
+    }
+
+    pub fn get(&mut self, key: &str, fingerprint: &str) -> Option<V> {
+        self.tick += 1;
+        match self.entries.get_mut(key) {
+            Some(entry) if entry.fingerprint == fingerprint => {
+                entry.last_used = self.tick;
+                Some(entry.value.clone())
+            }
+            Some(_) => {
+                self.entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&mut self, key: String, fingerprint: String, value: V) {
+        self.tick += 1;
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.max_entries {
+            if let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(
+            key,
+            Entry {
+                fingerprint,
+                value,
+                last_used: self.tick,
+            },
+        );
+    }
+
+    pub fn invalidate_task(&mut self, task_id: &str) {
+        let prefix = format!("{}\x1f", task_id);
+        self.entries
+            .retain(|key, _| key != task_id && !key.starts_with(&prefix));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_cache_fingerprint_and_eviction() {
+        let mut cache: DiffCache<u32> = DiffCache::new(2);
+        let a = cache_key("task-1", &["base", "src/a.rs"]);
+        let b = cache_key("task-1", &["base", "src/b.rs"]);
+        let c = cache_key("task-2", &["head"]);
+
+        cache.insert(a.clone(), "fp1".to_string(), 1);
+        assert_eq!(cache.get(&a, "fp1"), Some(1));
+        // Worktree changed: stale entry is dropped.
+        assert_eq!(cache.get(&a, "fp2"), None);
+        assert_eq!(cache.get(&a, "fp1"), None);
+
+        cache.insert(a.clone(), "fp2".to_string(), 2);
+        cache.insert(b.clone(), "fp2".to_string(), 3);
+        assert_eq!(cache.get(&a, "fp2"), Some(2));
+        // `b` is least recently used and gets evicted.
+        cache.insert(c.clone(), "fp9".to_string(), 4);
+        assert_eq!(cache.get(&b, "fp2"), None);
+        assert_eq!(cache.get(&a, "fp2"), Some(2));
+        assert_eq!(cache.get(&c, "fp9"), Some(4));
+
+        cache.invalidate_task("task-1");
+        assert_eq!(cache.get(&a, "fp2"), None);
+        assert_eq!(cache.get(&c, "fp9"), Some(4));
+    }
+}
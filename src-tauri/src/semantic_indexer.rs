@@ -4,14 +4,14 @@
 //! indexing rather than breaking user flows. Callers should schedule indexing
 //! via `schedule_index_entity` (debounced).
 
-use crate::{embedding_inference, embedding_model, semantic_search, AppState};
+use crate::{embedding_inference, embedding_model, semantic_search, AppHandle, AppState};
 use chrono::Utc;
 use rusqlite::{params, Connection, OptionalExtension};
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::time::Duration;
 use std::time::{SystemTime, UNIX_EPOCH};
-use tauri::{AppHandle, Manager};
+use tauri::Manager;
 
 const DEFAULT_MODEL_ID: &str = embedding_model::DEFAULT_EMBEDDING_MODEL_ID;
 const DEFAULT_MAX_SEQ_LEN: usize = 128;
@@ -0,0 +1,380 @@
+//! Daily spend summaries built from the persisted cost ledger.
+
+use crate::db::{CostLedgerEntry, TurnUsageRecord};
+use chrono::{DateTime, Duration, NaiveDate, TimeZone};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendBucket {
+    pub agent_id: String,
+    /// Empty for per-agent buckets.
+    pub model: String,
+    pub cost: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendDay {
+    /// Local date, `YYYY-MM-DD`.
+    pub date: String,
+    pub total: f64,
+    pub by_agent: Vec<SpendBucket>,
+    pub by_model: Vec<SpendBucket>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendSummary {
+    pub range_days: u32,
+    pub total: f64,
+    pub by_agent: Vec<SpendBucket>,
+    pub by_model: Vec<SpendBucket>,
+    /// Oldest first, one entry per day including days with no spend.
+    pub days: Vec<SpendDay>,
+}
+
+/// Accepts "7d"/"week" and "30d"/"month"; anything else is an error.
+pub fn parse_range(range: Option<&str>) -> Result<u32, String> {
+    match range.map(|r| r.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("") | Some("7d") | Some("week") => Ok(7),
+        Some("30d") | Some("month") => Ok(30),
+        Some(other) => Err(format!("Unknown spend range: {}", other)),
+    }
+}
+
+/// Start of the first day in a `days`-long window ending today, as unix seconds.
+pub fn range_start<Tz: TimeZone>(now: &DateTime<Tz>, days: u32) -> i64 {
+    let first = now.date_naive() - Duration::days(days.saturating_sub(1) as i64);
+    let midnight = first.and_hms_opt(0, 0, 0).expect("valid midnight");
+    now.timezone()
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|dt| dt.timestamp())
+        .unwrap_or_else(|| midnight.and_utc().timestamp())
+}
+
+fn local_date<Tz: TimeZone>(now: &DateTime<Tz>, timestamp: i64) -> Option<NaiveDate> {
+    now.timezone()
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .map(|dt| dt.date_naive())
+}
+
+#[derive(Default)]
+struct Totals {
+    total: f64,
+    by_agent: HashMap<String, f64>,
+    by_model: HashMap<(String, String), f64>,
+}
+
+impl Totals {
+    fn add(&mut self, entry: &CostLedgerEntry) {
+        self.total += entry.delta;
+        *self.by_agent.entry(entry.agent_id.clone()).or_default() += entry.delta;
+        *self
+            .by_model
+            .entry((entry.agent_id.clone(), entry.model.clone()))
+            .or_default() += entry.delta;
+    }
+
+    fn agent_buckets(&self) -> Vec<SpendBucket> {
+        sorted(
+            self.by_agent
+                .iter()
+                .map(|(agent_id, cost)| SpendBucket {
+                    agent_id: agent_id.clone(),
+                    model: String::new(),
+                    cost: *cost,
+                })
+                .collect(),
+        )
+    }
+
+    fn model_buckets(&self) -> Vec<SpendBucket> {
+        sorted(
+            self.by_model
+                .iter()
+                .map(|((agent_id, model), cost)| SpendBucket {
+                    agent_id: agent_id.clone(),
+                    model: model.clone(),
+                    cost: *cost,
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Most expensive first; ties by name so output is stable.
+fn sorted(mut buckets: Vec<SpendBucket>) -> Vec<SpendBucket> {
+    buckets.sort_by(|a, b| {
+        b.cost
+            .total_cmp(&a.cost)
+            .then_with(|| a.agent_id.cmp(&b.agent_id))
+            .then_with(|| a.model.cmp(&b.model))
+    });
+    buckets
+}
+
+/// Bucket ledger entries into the `days` local dates ending at `now`.
+pub fn summarize<Tz: TimeZone>(
+    entries: &[CostLedgerEntry],
+    days: u32,
+    now: &DateTime<Tz>,
+) -> SpendSummary {
+    let today = now.date_naive();
+    let first = today - Duration::days(days.saturating_sub(1) as i64);
+    let mut overall = Totals::default();
+    let mut per_day: HashMap<NaiveDate, Totals> = HashMap::new();
+    for entry in entries {
+        let Some(date) = local_date(now, entry.created_at) else {
+            continue;
+        };
+        if date < first || date > today {
+            continue;
+        }
+        overall.add(entry);
+        per_day.entry(date).or_default().add(entry);
+    }
+
+    let days = first
+        .iter_days()
+        .take_while(|date| *date <= today)
+        .map(|date| {
+            let totals = per_day.remove(&date).unwrap_or_default();
+            SpendDay {
+                date: date.format("%Y-%m-%d").to_string(),
+                total: totals.total,
+                by_agent: totals.agent_buckets(),
+                by_model: totals.model_buckets(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    SpendSummary {
+        range_days: days.len() as u32,
+        total: overall.total,
+        by_agent: overall.agent_buckets(),
+        by_model: overall.model_buckets(),
+        days,
+    }
+}
+
+/// One CSV row: a task's usage on one local day.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UsageRow {
+    pub date: String,
+    pub task_id: String,
+    pub task_title: String,
+    pub agent_id: String,
+    /// Models used that day, `;`-separated.
+    pub model: String,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cached_input_tokens: i64,
+    pub cost: f64,
+}
+
+/// Join ledger costs and turn token counts per task per day. `tasks` maps task id to
+/// `(agent_id, title)`; deleted tasks keep the agent recorded in the ledger.
+pub fn usage_rows<Tz: TimeZone>(
+    ledger: &[CostLedgerEntry],
+    turns: &[TurnUsageRecord],
+    tasks: &HashMap<String, (String, String)>,
+    days: u32,
+    now: &DateTime<Tz>,
+) -> Vec<UsageRow> {
+    let today = now.date_naive();
+    let first = today - Duration::days(days.saturating_sub(1) as i64);
+    let in_range =
+        |timestamp: i64| local_date(now, timestamp).filter(|date| *date >= first && *date <= today);
+
+    type RowMap = BTreeMap<(NaiveDate, String), (UsageRow, BTreeSet<String>)>;
+    fn row_for<'a>(
+        rows: &'a mut RowMap,
+        tasks: &HashMap<String, (String, String)>,
+        date: NaiveDate,
+        task_id: &str,
+        agent_id: &str,
+    ) -> &'a mut (UsageRow, BTreeSet<String>) {
+        rows.entry((date, task_id.to_string())).or_insert_with(|| {
+            let (agent, title) = tasks
+                .get(task_id)
+                .cloned()
+                .unwrap_or_else(|| (agent_id.to_string(), String::new()));
+            let row = UsageRow {
+                date: date.format("%Y-%m-%d").to_string(),
+                task_id: task_id.to_string(),
+                task_title: title,
+                agent_id: agent,
+                ..Default::default()
+            };
+            (row, BTreeSet::new())
+        })
+    }
+
+    let mut rows = RowMap::new();
+    for entry in ledger {
+        if let Some(date) = in_range(entry.created_at) {
+            let (row, models) = row_for(&mut rows, tasks, date, &entry.task_id, &entry.agent_id);
+            row.cost += entry.delta;
+            models.insert(entry.model.clone());
+        }
+    }
+    for turn in turns {
+        if let Some(date) = in_range(turn.created_at) {
+            let (row, models) = row_for(&mut rows, tasks, date, &turn.task_id, "");
+            row.input_tokens += turn.input_tokens;
+            row.output_tokens += turn.output_tokens;
+            row.cached_input_tokens += turn.cached_input_tokens;
+            models.insert(turn.model.clone());
+        }
+    }
+
+    rows.into_values()
+        .map(|(mut row, models)| {
+            row.model = models
+                .into_iter()
+                .filter(|m| !m.is_empty())
+                .collect::<Vec<_>>()
+                .join(";");
+            row
+        })
+        .collect()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn to_csv(rows: &[UsageRow]) -> String {
+    let mut out = String::from(
+        "date,task_id,task,agent,model,input_tokens,output_tokens,cached_input_tokens,cost_usd\n",
+    );
+    for row in rows {
+        out.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{:.6}\n",
+            row.date,
+            csv_field(&row.task_id),
+            csv_field(&row.task_title),
+            csv_field(&row.agent_id),
+            csv_field(&row.model),
+            row.input_tokens,
+            row.output_tokens,
+            row.cached_input_tokens,
+            row.cost
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn entry(agent_id: &str, model: &str, delta: f64, created_at: i64) -> CostLedgerEntry {
+        ledger_entry("task", agent_id, model, delta, created_at)
+    }
+
+    fn ledger_entry(
+        task_id: &str,
+        agent_id: &str,
+        model: &str,
+        delta: f64,
+        created_at: i64,
+    ) -> CostLedgerEntry {
+        CostLedgerEntry {
+            task_id: task_id.to_string(),
+            agent_id: agent_id.to_string(),
+            model: model.to_string(),
+            delta,
+            created_at,
+        }
+    }
+
+    #[test]
+    fn test_summarize_buckets_by_day_agent_and_model() {
+        // 2024-03-10 12:00 UTC
+        let now = Utc.timestamp_opt(1_710_072_000, 0).unwrap();
+        let day = 86_400;
+        let entries = vec![
+            entry("codex", "gpt-5", 1.0, 1_710_072_000 - 8 * day),
+            entry("codex", "gpt-5", 0.5, 1_710_072_000 - day),
+            entry("codex", "o3", 0.25, 1_710_072_000 - day),
+            entry("claude-code", "sonnet", 2.0, 1_710_072_000),
+            entry("codex", "gpt-5", -0.5, 1_710_072_000),
+        ];
+        assert_eq!(range_start(&now, 7), 1_710_072_000 - 6 * day - 12 * 3600);
+
+        let summary = summarize(&entries, 7, &now);
+        assert_eq!(summary.days.len(), 7);
+        assert_eq!(summary.days[0].date, "2024-03-04");
+        assert_eq!(summary.days[6].date, "2024-03-10");
+        assert!((summary.total - 2.25).abs() < 1e-9);
+        assert_eq!(summary.by_agent[0].agent_id, "claude-code");
+        assert!((summary.by_agent[1].cost - 0.25).abs() < 1e-9);
+        assert_eq!(summary.by_model.len(), 3);
+
+        let yesterday = &summary.days[5];
+        assert!((yesterday.total - 0.75).abs() < 1e-9);
+        assert_eq!(yesterday.by_model[0].model, "gpt-5");
+        assert_eq!(summary.days[4].total, 0.0);
+
+        assert_eq!(parse_range(Some("30d")), Ok(30));
+        assert!(parse_range(Some("year")).is_err());
+    }
+
+    #[test]
+    fn test_usage_rows_join_cost_and_tokens_per_task_day() {
+        let now = Utc.timestamp_opt(1_710_072_000, 0).unwrap();
+        let turn = |task_id: &str, model: &str, input: i64, created_at: i64| TurnUsageRecord {
+            id: 0,
+            task_id: task_id.to_string(),
+            model: model.to_string(),
+            input_tokens: input,
+            cached_input_tokens: 5,
+            cache_write_tokens: 0,
+            output_tokens: 2,
+            cost: 0.0,
+            equivalent_cost: 0.0,
+            auth_method: None,
+            message_seq: 0,
+            created_at,
+        };
+        let mut tasks = HashMap::new();
+        tasks.insert(
+            "t1".to_string(),
+            ("codex".to_string(), "Fix \"login\", again".to_string()),
+        );
+        let ledger = vec![
+            ledger_entry("t1", "codex", "gpt-5", 0.5, 1_710_072_000),
+            ledger_entry("gone", "amp", "", 0.1, 1_710_072_000),
+        ];
+        let turns = vec![
+            turn("t1", "gpt-5", 100, 1_710_072_000),
+            turn("t1", "o3", 50, 1_710_072_000),
+        ];
+
+        let rows = usage_rows(&ledger, &turns, &tasks, 7, &now);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].task_id, "gone");
+        assert_eq!(rows[0].agent_id, "amp");
+        assert_eq!(rows[1].model, "gpt-5;o3");
+        assert_eq!(rows[1].input_tokens, 150);
+        assert_eq!(rows[1].cached_input_tokens, 10);
+
+        let csv = to_csv(&rows);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[2],
+            "2024-03-10,t1,\"Fix \"\"login\"\", again\",codex,gpt-5;o3,150,4,10,0.500000"
+        );
+    }
+}
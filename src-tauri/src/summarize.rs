@@ -7,7 +7,7 @@ use std::time::Duration;
 
 /// Resolve which agent to use for summarization.
 /// Returns the configured agent, or falls back to the task's agent if "auto" or None.
-fn resolve_summaries_agent<'a>(task_agent: &'a str, configured: Option<&'a str>) -> &'a str {
+pub fn resolve_summaries_agent<'a>(task_agent: &'a str, configured: Option<&'a str>) -> &'a str {
     match configured {
         Some("auto") | None => task_agent,
         Some(agent) => agent,
@@ -34,6 +34,91 @@ pub async fn summarize_status_with_override(
     summarize_status(response, agent_id).await
 }
 
+/// Generate a commit message using the configured summaries agent (or fallback to task agent)
+pub async fn summarize_commit_message_with_override(
+    diff_stat: &str,
+    diff: &str,
+    task_agent_id: &str,
+    summaries_agent: Option<&str>,
+) -> String {
+    let agent_id = resolve_summaries_agent(task_agent_id, summaries_agent);
+    summarize_commit_message(diff_stat, diff, agent_id).await
+}
+
+/// Generate a pull request body using the configured summaries agent (or fallback to task agent)
+pub async fn summarize_pr_body_with_override(
+    commit_subjects: &[String],
+    template: Option<&str>,
+    task_agent_id: &str,
+    summaries_agent: Option<&str>,
+) -> String {
+    let agent_id = resolve_summaries_agent(task_agent_id, summaries_agent);
+    let timeout = summarize_timeout(agent_id).max(Duration::from_secs(20));
+    let result = tokio::time::timeout(
+        timeout,
+        generate_pr_body(commit_subjects, template, agent_id),
+    )
+    .await;
+
+    match result {
+        Ok(Ok(body)) if !body.is_empty() => body,
+        Ok(Ok(_)) => fallback_pr_body(commit_subjects, template),
+        Ok(Err(e)) => {
+            println!("[Summarize] PR body generation failed: {}", e);
+            fallback_pr_body(commit_subjects, template)
+        }
+        Err(_) => {
+            println!("[Summarize] PR body generation timed out");
+            fallback_pr_body(commit_subjects, template)
+        }
+    }
+}
+
+/// Fold a chunk of conversation into the rolling summary of a task's history.
+/// Unlike the other summaries there is no local fallback, so failures are returned to the caller.
+pub async fn summarize_history_chunk_with_override(
+    previous_summary: Option<&str>,
+    transcript: &str,
+    task_agent_id: &str,
+    summaries_agent: Option<&str>,
+) -> Result<String, String> {
+    let agent_id = resolve_summaries_agent(task_agent_id, summaries_agent);
+    let timeout = summarize_timeout(agent_id).max(Duration::from_secs(30));
+    let result = tokio::time::timeout(
+        timeout,
+        generate_history_summary(previous_summary, transcript, agent_id),
+    )
+    .await;
+
+    match result {
+        Ok(Ok(summary)) if !summary.is_empty() => Ok(summary),
+        Ok(Ok(_)) => Err("History summary was empty".to_string()),
+        Ok(Err(e)) => Err(e),
+        Err(_) => Err("History summary timed out".to_string()),
+    }
+}
+
+/// Generate a commit message from a staged diff (async with timeout)
+pub async fn summarize_commit_message(diff_stat: &str, diff: &str, agent_id: &str) -> String {
+    // Diffs are much longer than prompts; allow more time than titles/statuses.
+    let timeout = summarize_timeout(agent_id).max(Duration::from_secs(20));
+    let result =
+        tokio::time::timeout(timeout, generate_commit_message(diff_stat, diff, agent_id)).await;
+
+    match result {
+        Ok(Ok(message)) if !message.is_empty() => message,
+        Ok(Ok(_)) => fallback_commit_message(diff_stat),
+        Ok(Err(e)) => {
+            println!("[Summarize] Commit message generation failed: {}", e);
+            fallback_commit_message(diff_stat)
+        }
+        Err(_) => {
+            println!("[Summarize] Commit message generation timed out");
+            fallback_commit_message(diff_stat)
+        }
+    }
+}
+
 /// Generate a short title from a task prompt (async with timeout)
 pub async fn summarize_title(prompt: &str, agent_id: &str) -> String {
     let timeout = summarize_timeout(agent_id);
@@ -109,6 +194,79 @@ async fn generate_status(response: &str, agent_id: &str) -> Result<String, Strin
     }
 }
 
+async fn generate_commit_message(
+    diff_stat: &str,
+    diff: &str,
+    agent_id: &str,
+) -> Result<String, String> {
+    // Keep the stat whole (it covers every file) and truncate the patch (safe for UTF-8)
+    let truncated = safe_prefix(diff, 6000);
+    let full_prompt = format!(
+        "Write a git commit message for this diff. Use an imperative subject line under 72 characters, optionally followed by a blank line and a short body. Return ONLY the commit message, no quotes or code fences.\n\n{}\n\n{}",
+        diff_stat, truncated
+    );
+
+    let response = match agent_id {
+        "codex" => call_codex_api(&full_prompt).await,
+        "opencode" => call_opencode_cli(&full_prompt).await,
+        "amp" => call_amp_cli(&full_prompt).await,
+        _ => call_claude_api(&full_prompt).await,
+    }?;
+    Ok(clean_commit_message(&response))
+}
+
+async fn generate_pr_body(
+    commit_subjects: &[String],
+    template: Option<&str>,
+    agent_id: &str,
+) -> Result<String, String> {
+    let commits = safe_prefix(&commit_subjects.join("\n"), 4000).to_string();
+    let full_prompt = match template {
+        Some(template) => format!(
+            "Write a GitHub pull request description for these commits by filling in the repository's PR template below. Keep its headings, drop sections that don't apply, and leave checkboxes unchecked. Return ONLY the markdown body, no code fences.\n\nCommits:\n{}\n\nTemplate:\n{}",
+            commits,
+            safe_prefix(template, 4000)
+        ),
+        None => format!(
+            "Write a short GitHub pull request description for these commits: one or two sentences on what changes and why, then a bullet list of the notable changes. Return ONLY the markdown body, no code fences.\n\nCommits:\n{}",
+            commits
+        ),
+    };
+
+    let response = match agent_id {
+        "codex" => call_codex_api(&full_prompt).await,
+        "opencode" => call_opencode_cli(&full_prompt).await,
+        "amp" => call_amp_cli(&full_prompt).await,
+        _ => call_claude_api(&full_prompt).await,
+    }?;
+    Ok(clean_commit_message(&response))
+}
+
+async fn generate_history_summary(
+    previous_summary: Option<&str>,
+    transcript: &str,
+    agent_id: &str,
+) -> Result<String, String> {
+    let full_prompt = format!(
+        "You keep a running summary of a coding agent's conversation so the task can be resumed in a new session. Merge the new excerpt into the existing summary. Keep the user's goals and constraints, decisions made, files changed, commands run and their outcomes, and open problems. Stay under 400 words. Return ONLY the updated summary.\n\nExisting summary:\n{}\n\nNew excerpt:\n{}",
+        previous_summary.unwrap_or("(none)"),
+        safe_prefix(transcript, 32_000)
+    );
+
+    match agent_id {
+        "codex" => {
+            call_codex_api_with_instructions(
+                &full_prompt,
+                "You summarize coding agent conversations.",
+            )
+            .await
+        }
+        "opencode" => call_opencode_cli(&full_prompt).await,
+        "amp" => call_amp_cli(&full_prompt).await,
+        _ => call_claude_api_with_max_tokens(&full_prompt, 1024).await,
+    }
+}
+
 fn summarize_timeout(agent_id: &str) -> Duration {
     match agent_id {
         "opencode" => Duration::from_secs(30),
@@ -119,11 +277,15 @@ fn summarize_timeout(agent_id: &str) -> Duration {
 /// Call Claude API using OAuth token
 /// IMPORTANT: OAuth requires the anthropic-beta header!
 async fn call_claude_api(prompt: &str) -> Result<String, String> {
+    call_claude_api_with_max_tokens(prompt, 100).await
+}
+
+async fn call_claude_api_with_max_tokens(prompt: &str, max_tokens: u32) -> Result<String, String> {
     let token = crate::get_claude_oauth_token().ok_or("Claude OAuth token not found")?;
 
     let payload = serde_json::json!({
         "model": "claude-haiku-4-5-20251001",
-        "max_tokens": 100,
+        "max_tokens": max_tokens,
         "messages": [{"role": "user", "content": prompt}]
     });
 
@@ -162,6 +324,13 @@ async fn call_claude_api(prompt: &str) -> Result<String, String> {
 /// Call Codex API for summarization using the ChatGPT backend Responses API
 /// Requires specific headers discovered from clawdbot integration
 async fn call_codex_api(prompt: &str) -> Result<String, String> {
+    call_codex_api_with_instructions(prompt, "You create short titles for coding tasks.").await
+}
+
+async fn call_codex_api_with_instructions(
+    prompt: &str,
+    instructions: &str,
+) -> Result<String, String> {
     let (token, account_id) = get_codex_auth()?;
 
     let account_id = account_id.ok_or("Codex account_id required for API calls")?;
@@ -170,7 +339,7 @@ async fn call_codex_api(prompt: &str) -> Result<String, String> {
     // Based on clawdbot's integration: https://github.com/clawdbot/clawdbot
     let payload = serde_json::json!({
         "model": "gpt-5.1-codex-mini",
-        "instructions": "You create short titles for coding tasks.",
+        "instructions": instructions,
         "input": [{
             "role": "user",
             "content": [{"type": "input_text", "text": prompt}]
@@ -415,6 +584,45 @@ fn fallback_title(prompt: &str) -> String {
         .join(" ")
 }
 
+/// Strip code fences/quotes an LLM may wrap around a commit message
+fn clean_commit_message(text: &str) -> String {
+    let lines: Vec<&str> = text
+        .trim()
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .collect();
+    clean_response(&lines.join("\n"))
+}
+
+/// Fallback commit message from `git diff --stat` output
+pub fn fallback_commit_message(diff_stat: &str) -> String {
+    let files: Vec<&str> = diff_stat
+        .lines()
+        .filter_map(|line| line.split_once('|').map(|(path, _)| path.trim()))
+        .collect();
+    match files.as_slice() {
+        [] => "Update files".to_string(),
+        [file] => format!("Update {}", file),
+        _ => format!("Update {} files", files.len()),
+    }
+}
+
+/// Fallback pull request body: the commit subjects as a list, followed by the PR template
+pub fn fallback_pr_body(commit_subjects: &[String], template: Option<&str>) -> String {
+    let mut body = commit_subjects
+        .iter()
+        .map(|subject| format!("- {}", subject))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if let Some(template) = template.map(str::trim).filter(|t| !t.is_empty()) {
+        if !body.is_empty() {
+            body.push_str("\n\n");
+        }
+        body.push_str(template);
+    }
+    body
+}
+
 /// Fallback status: Truncate to a short preview with ellipsis
 fn truncate_status(text: &str) -> String {
     // Take first line only
@@ -433,6 +641,36 @@ fn truncate_status(text: &str) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_commit_message_cleanup_and_fallback() {
+        assert_eq!(
+            clean_commit_message("```\nFix login redirect\n\nKeep the return URL.\n```"),
+            "Fix login redirect\n\nKeep the return URL."
+        );
+        assert_eq!(
+            fallback_commit_message(" src/auth.ts | 4 ++--\n 1 file changed"),
+            "Update src/auth.ts"
+        );
+        assert_eq!(
+            fallback_commit_message(" a.rs | 1 +\n b.rs | 2 +-\n 2 files changed"),
+            "Update 2 files"
+        );
+    }
+
+    #[test]
+    fn test_fallback_pr_body() {
+        let commits = vec!["Fix login redirect".to_string(), "Add tests".to_string()];
+        assert_eq!(
+            fallback_pr_body(&commits, None),
+            "- Fix login redirect\n- Add tests"
+        );
+        assert_eq!(
+            fallback_pr_body(&commits, Some("## Summary\n\n## Test plan\n")),
+            "- Fix login redirect\n- Add tests\n\n## Summary\n\n## Test plan"
+        );
+        assert_eq!(fallback_pr_body(&[], Some("  ")), "");
+    }
+
     #[test]
     fn test_fallback_title() {
         assert_eq!(
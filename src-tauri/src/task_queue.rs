@@ -0,0 +1,217 @@
+//! Start queue enforcing `max_parallel` from agents.toml. Starts past the limit wait here
+//! in FIFO order and are handed out again as in-flight tasks finish or are stopped.
+
+use std::collections::{HashMap, VecDeque};
+
+/// Concurrency limits; `None`/missing means unlimited.
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    pub global: Option<usize>,
+    pub per_agent: HashMap<String, usize>,
+}
+
+impl Limits {
+    /// Whether one more task for `agent_id` fits next to `in_flight` (task id -> agent id).
+    pub fn allows(&self, in_flight: &HashMap<String, String>, agent_id: &str) -> bool {
+        if self.global.is_some_and(|max| in_flight.len() >= max) {
+            return false;
+        }
+        match self.per_agent.get(agent_id) {
+            Some(&max) => in_flight.values().filter(|a| *a == agent_id).count() < max,
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QueuedStart {
+    pub task_id: String,
+    pub agent_id: String,
+    /// `(status, status_state)` shown before queueing, restored if the start is cancelled.
+    pub previous_status: (String, String),
+    pub queued_at: i64,
+}
+
+#[derive(Debug, Default)]
+pub struct TaskQueue {
+    queued: VecDeque<QueuedStart>,
+    /// Tasks admitted by the queue whose start is still running (task id -> agent id).
+    starting: HashMap<String, String>,
+}
+
+impl TaskQueue {
+    pub fn queued(&self) -> impl Iterator<Item = &QueuedStart> {
+        self.queued.iter()
+    }
+
+    pub fn starting(&self) -> &HashMap<String, String> {
+        &self.starting
+    }
+
+    /// Number of queued starts ahead of `task_id`, if it is queued.
+    pub fn ahead_of(&self, task_id: &str) -> Option<usize> {
+        self.queued
+            .iter()
+            .position(|entry| entry.task_id == task_id)
+    }
+
+    /// Try to start `task_id` now. Returns `None` when admitted (it counts as starting until
+    /// `finish`), otherwise queues it and returns how many starts are ahead of it.
+    /// A queued start that could already run keeps priority over a new one.
+    pub fn admit_or_enqueue(
+        &mut self,
+        entry: QueuedStart,
+        in_flight: &HashMap<String, String>,
+        limits: &Limits,
+    ) -> Option<usize> {
+        if let Some(ahead) = self.ahead_of(&entry.task_id) {
+            return Some(ahead);
+        }
+        let mut in_flight = in_flight.clone();
+        in_flight.extend(self.starting.clone());
+        let queued_can_run = self
+            .queued
+            .iter()
+            .any(|queued| limits.allows(&in_flight, &queued.agent_id));
+        if !queued_can_run && limits.allows(&in_flight, &entry.agent_id) {
+            self.starting.insert(entry.task_id, entry.agent_id);
+            return None;
+        }
+        self.queued.push_back(entry);
+        Some(self.queued.len() - 1)
+    }
+
+    /// Pop every queued start that fits, oldest first, and mark them as starting. Starts
+    /// blocked by their agent's own limit don't hold up other agents behind them.
+    pub fn take_ready(
+        &mut self,
+        in_flight: &HashMap<String, String>,
+        limits: &Limits,
+    ) -> Vec<QueuedStart> {
+        let mut in_flight = in_flight.clone();
+        in_flight.extend(self.starting.clone());
+        let mut ready = Vec::new();
+        let mut waiting = VecDeque::new();
+        while let Some(entry) = self.queued.pop_front() {
+            if limits.allows(&in_flight, &entry.agent_id) {
+                in_flight.insert(entry.task_id.clone(), entry.agent_id.clone());
+                self.starting
+                    .insert(entry.task_id.clone(), entry.agent_id.clone());
+                ready.push(entry);
+            } else {
+                waiting.push_back(entry);
+            }
+        }
+        self.queued = waiting;
+        ready
+    }
+
+    /// Drop a queued start (e.g. the task was stopped before it got a slot).
+    pub fn remove(&mut self, task_id: &str) -> Option<QueuedStart> {
+        let index = self.ahead_of(task_id)?;
+        self.queued.remove(index)
+    }
+
+    /// The start of `task_id` has ended and no longer holds a slot on its own.
+    pub fn finish(&mut self, task_id: &str) {
+        self.starting.remove(task_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start(task_id: &str, agent_id: &str) -> QueuedStart {
+        QueuedStart {
+            task_id: task_id.to_string(),
+            agent_id: agent_id.to_string(),
+            previous_status: ("Ready".to_string(), "idle".to_string()),
+            queued_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_queue_respects_global_and_agent_limits() {
+        let limits = Limits {
+            global: Some(2),
+            per_agent: HashMap::from([("codex".to_string(), 1)]),
+        };
+        let mut queue = TaskQueue::default();
+        let none = HashMap::new();
+
+        assert_eq!(
+            queue.admit_or_enqueue(start("a", "codex"), &none, &limits),
+            None
+        );
+        // Codex is at its own limit; another agent still fits.
+        assert_eq!(
+            queue.admit_or_enqueue(start("b", "codex"), &none, &limits),
+            Some(0)
+        );
+        assert_eq!(
+            queue.admit_or_enqueue(start("c", "claude-code"), &none, &limits),
+            None
+        );
+        assert_eq!(
+            queue.admit_or_enqueue(start("d", "amp"), &none, &limits),
+            Some(1)
+        );
+        // Re-starting a queued task keeps its place.
+        assert_eq!(
+            queue.admit_or_enqueue(start("b", "codex"), &none, &limits),
+            Some(0)
+        );
+
+        // A generating session outside the queue also takes a slot.
+        queue.finish("c");
+        let busy = HashMap::from([("chat".to_string(), "amp".to_string())]);
+        assert!(queue.take_ready(&busy, &limits).is_empty());
+
+        // With a free slot, "b" is still blocked by codex's limit, so "d" goes first.
+        let ready = queue.take_ready(&none, &limits);
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].task_id, "d");
+        assert_eq!(queue.ahead_of("b"), Some(0));
+
+        queue.finish("a");
+        queue.finish("d");
+        let ready: Vec<String> = queue
+            .take_ready(&none, &limits)
+            .into_iter()
+            .map(|entry| entry.task_id)
+            .collect();
+        assert_eq!(ready, vec!["b"]);
+        assert_eq!(queue.queued().count(), 0);
+    }
+
+    #[test]
+    fn test_removing_queued_start_keeps_running_ones() {
+        let limits = Limits {
+            global: Some(1),
+            per_agent: HashMap::new(),
+        };
+        let mut queue = TaskQueue::default();
+        let none = HashMap::new();
+        assert_eq!(
+            queue.admit_or_enqueue(start("a", "codex"), &none, &limits),
+            None
+        );
+        assert_eq!(
+            queue.admit_or_enqueue(start("b", "codex"), &none, &limits),
+            Some(0)
+        );
+        assert_eq!(
+            queue.admit_or_enqueue(start("c", "codex"), &none, &limits),
+            Some(1)
+        );
+
+        assert_eq!(
+            queue.remove("b").map(|entry| entry.task_id).as_deref(),
+            Some("b")
+        );
+        assert!(queue.remove("a").is_none());
+        assert!(queue.starting().contains_key("a"));
+        assert_eq!(queue.ahead_of("c"), Some(0));
+    }
+}
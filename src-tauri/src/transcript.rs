@@ -0,0 +1,768 @@
+//! Task transcript export and import. Messages are read row by row and written straight
+//! to the output, so long histories never have to fit in memory.
+
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use serde_json::json;
+use std::borrow::Cow;
+use std::io::Write;
+
+use crate::db::{self, TaskRecord};
+
+/// Configured secrets shorter than this are not redacted (too likely to match normal text).
+const MIN_SECRET_LEN: usize = 8;
+const REDACTED: &str = "[REDACTED]";
+/// JSON transcript schema version, written on export and required on import.
+pub const TRANSCRIPT_VERSION: u64 = 1;
+/// Message types the importer restores; anything else in a transcript is skipped.
+const IMPORTABLE_MESSAGE_TYPES: &[&str] = &[
+    "user_message",
+    "assistant_message",
+    "reasoning_message",
+    "tool_call",
+    "tool_call_message",
+    "tool_return",
+    "tool_return_message",
+    "plan_update",
+    "plan_content",
+    "permission_request",
+    "user_input_request",
+    "pr_feedback",
+    "system",
+    "app_restart",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TranscriptFormat {
+    Markdown,
+    Json,
+}
+
+impl TranscriptFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "markdown" | "md" => Ok(Self::Markdown),
+            "json" => Ok(Self::Json),
+            other => Err(format!("Unsupported transcript format: {}", other)),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Json => "json",
+        }
+    }
+}
+
+/// Replaces configured API keys and tokens with `[REDACTED]`.
+pub struct Redactor {
+    secrets: Vec<String>,
+}
+
+impl Redactor {
+    pub fn new(secrets: impl IntoIterator<Item = String>) -> Self {
+        let mut secrets: Vec<String> = secrets
+            .into_iter()
+            .map(|s| s.trim().to_string())
+            .filter(|s| s.len() >= MIN_SECRET_LEN)
+            .collect();
+        // Longest first, so a secret containing another is replaced whole.
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        secrets.dedup();
+        Self { secrets }
+    }
+
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut out = Cow::Borrowed(text);
+        for secret in &self.secrets {
+            if out.contains(secret.as_str()) {
+                out = Cow::Owned(out.replace(secret.as_str(), REDACTED));
+            }
+        }
+        out
+    }
+
+    fn redact_opt(&self, text: Option<&str>) -> Option<String> {
+        text.map(|t| self.redact(t).into_owned())
+    }
+}
+
+struct MessageRow {
+    message_type: String,
+    content: Option<String>,
+    reasoning: Option<String>,
+    tool_name: Option<String>,
+    tool_arguments: Option<String>,
+    tool_return: Option<String>,
+    timestamp: String,
+}
+
+/// Write `task`'s history to `out`. Reasoning is skipped unless `include_reasoning`.
+pub fn write_transcript<W: Write>(
+    conn: &Connection,
+    task: &TaskRecord,
+    format: TranscriptFormat,
+    include_reasoning: bool,
+    redactor: &Redactor,
+    out: &mut W,
+) -> Result<(), String> {
+    let usage = db::list_task_turn_usage(conn, &task.id).map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT message_type, content, reasoning, tool_name, tool_arguments, tool_return, timestamp
+             FROM messages WHERE task_id = ?1 ORDER BY seq ASC, id ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    let mut rows = stmt.query(params![task.id]).map_err(|e| e.to_string())?;
+    let mut writer = match format {
+        TranscriptFormat::Markdown => Writer::Markdown,
+        TranscriptFormat::Json => Writer::Json { first: true },
+    };
+
+    writer
+        .header(task, redactor, out)
+        .map_err(|e| e.to_string())?;
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        let message = MessageRow {
+            message_type: row.get(0).map_err(|e| e.to_string())?,
+            content: row.get(1).map_err(|e| e.to_string())?,
+            reasoning: row.get(2).map_err(|e| e.to_string())?,
+            tool_name: row.get(3).map_err(|e| e.to_string())?,
+            tool_arguments: row.get(4).map_err(|e| e.to_string())?,
+            tool_return: row.get(5).map_err(|e| e.to_string())?,
+            timestamp: row.get(6).map_err(|e| e.to_string())?,
+        };
+        let is_reasoning = message.message_type == "reasoning_message";
+        if is_reasoning && !include_reasoning {
+            continue;
+        }
+        writer
+            .message(&message, include_reasoning, redactor, out)
+            .map_err(|e| e.to_string())?;
+    }
+    writer
+        .footer(task, &usage, out)
+        .map_err(|e| e.to_string())?;
+    out.flush().map_err(|e| e.to_string())
+}
+
+enum Writer {
+    Markdown,
+    Json { first: bool },
+}
+
+impl Writer {
+    fn header<W: Write>(
+        &self,
+        task: &TaskRecord,
+        redactor: &Redactor,
+        out: &mut W,
+    ) -> std::io::Result<()> {
+        let prompt = redactor.redact_opt(task.prompt.as_deref());
+        let title = redactor.redact_opt(task.title_summary.as_deref());
+        match self {
+            Writer::Markdown => {
+                let heading = title
+                    .clone()
+                    .or_else(|| {
+                        prompt
+                            .as_deref()
+                            .and_then(|p| p.lines().next())
+                            .map(|line| line.chars().take(80).collect())
+                    })
+                    .unwrap_or_else(|| task.id.clone());
+                writeln!(out, "# {}\n", heading)?;
+                writeln!(out, "- Task: `{}`", task.id)?;
+                writeln!(out, "- Agent: {} ({})", task.agent_id, task.model)?;
+                if let Some(branch) = task.branch.as_deref() {
+                    writeln!(out, "- Branch: `{}`", branch)?;
+                }
+                if let Some(url) = task.pr_url.as_deref() {
+                    writeln!(out, "- Pull request: {}", url)?;
+                }
+                writeln!(out, "- Created: {}", format_unix(task.created_at))?;
+                writeln!(out, "- Exported: {}\n", chrono::Utc::now().to_rfc3339())?;
+                if let Some(prompt) = prompt.as_deref().filter(|p| !p.trim().is_empty()) {
+                    writeln!(out, "## Prompt\n\n{}\n", prompt.trim())?;
+                }
+                writeln!(out, "## Transcript")
+            }
+            Writer::Json { .. } => {
+                let header = json!({
+                    "id": task.id,
+                    "title": title,
+                    "prompt": prompt,
+                    "agentId": task.agent_id,
+                    "model": task.model,
+                    "branch": task.branch,
+                    "prUrl": task.pr_url,
+                    "createdAt": format_unix(task.created_at),
+                    "exportedAt": chrono::Utc::now().to_rfc3339(),
+                });
+                write!(
+                    out,
+                    "{{\"version\":{},\"task\":{},\"messages\":[",
+                    TRANSCRIPT_VERSION, header
+                )
+            }
+        }
+    }
+
+    fn message<W: Write>(
+        &mut self,
+        message: &MessageRow,
+        include_reasoning: bool,
+        redactor: &Redactor,
+        out: &mut W,
+    ) -> std::io::Result<()> {
+        let content = redactor.redact_opt(message.content.as_deref());
+        let reasoning = redactor
+            .redact_opt(message.reasoning.as_deref())
+            .filter(|_| include_reasoning);
+        let arguments = redactor.redact_opt(message.tool_arguments.as_deref());
+        let tool_return = redactor.redact_opt(message.tool_return.as_deref());
+        match self {
+            Writer::Json { first } => {
+                if !*first {
+                    write!(out, ",")?;
+                }
+                *first = false;
+                let value = json!({
+                    "type": message.message_type,
+                    "timestamp": message.timestamp,
+                    "content": content,
+                    "reasoning": reasoning,
+                    "toolName": message.tool_name,
+                    "toolArguments": arguments,
+                    "toolReturn": tool_return,
+                });
+                write!(out, "{}", value)
+            }
+            Writer::Markdown => {
+                let time = message
+                    .timestamp
+                    .split_once('T')
+                    .map(|(_, t)| t.get(..8).unwrap_or(t))
+                    .unwrap_or(&message.timestamp);
+                let content = content.as_deref().unwrap_or("").trim();
+                match message.message_type.as_str() {
+                    "user_message" => writeln!(out, "\n### User · {}\n\n{}", time, content),
+                    "assistant_message" | "plan_content" => {
+                        writeln!(out, "\n### Assistant · {}\n\n{}", time, content)
+                    }
+                    "reasoning_message" => {
+                        let text = reasoning.as_deref().unwrap_or(content).trim();
+                        writeln!(
+                            out,
+                            "\n<details><summary>Reasoning</summary>\n\n{}\n\n</details>",
+                            text
+                        )
+                    }
+                    "tool_call" => {
+                        let name = message.tool_name.as_deref().unwrap_or("tool");
+                        writeln!(out, "\n#### Tool call: `{}` · {}", name, time)?;
+                        if let Some(args) = arguments.as_deref().filter(|a| !a.trim().is_empty()) {
+                            write_code_block(out, "json", &pretty_json(args))?;
+                        }
+                        Ok(())
+                    }
+                    "tool_return" => {
+                        let text = tool_return.as_deref().unwrap_or(content);
+                        writeln!(out, "\nResult:")?;
+                        write_code_block(out, "", text.trim_end())
+                    }
+                    "plan_update" => write_plan(out, content),
+                    other => {
+                        if content.is_empty() {
+                            return Ok(());
+                        }
+                        writeln!(
+                            out,
+                            "\n*{}* · {}\n\n{}",
+                            other.replace('_', " "),
+                            time,
+                            content
+                        )
+                    }
+                }
+            }
+        }
+    }
+
+    fn footer<W: Write>(
+        &self,
+        task: &TaskRecord,
+        usage: &[db::TurnUsageRecord],
+        out: &mut W,
+    ) -> std::io::Result<()> {
+        let input: i64 = usage.iter().map(|u| u.input_tokens).sum();
+        let cached: i64 = usage.iter().map(|u| u.cached_input_tokens).sum();
+        let output: i64 = usage.iter().map(|u| u.output_tokens).sum();
+        let equivalent: f64 = usage.iter().map(|u| u.equivalent_cost).sum();
+        match self {
+            Writer::Json { .. } => {
+                let turns: Vec<_> = usage
+                    .iter()
+                    .map(|u| {
+                        json!({
+                            "model": u.model,
+                            "inputTokens": u.input_tokens,
+                            "cachedInputTokens": u.cached_input_tokens,
+                            "outputTokens": u.output_tokens,
+                            "cost": u.cost,
+                            "equivalentCost": u.equivalent_cost,
+                        })
+                    })
+                    .collect();
+                let summary = json!({
+                    "turns": turns,
+                    "inputTokens": input,
+                    "cachedInputTokens": cached,
+                    "outputTokens": output,
+                    "cost": task.cost,
+                    "equivalentCost": equivalent,
+                });
+                writeln!(out, "],\"usage\":{}}}", summary)
+            }
+            Writer::Markdown => {
+                writeln!(out, "\n## Usage\n")?;
+                if !usage.is_empty() {
+                    writeln!(out, "| Turn | Model | Input | Cached | Output | Cost |")?;
+                    writeln!(out, "| ---: | --- | ---: | ---: | ---: | ---: |")?;
+                    for (i, u) in usage.iter().enumerate() {
+                        writeln!(
+                            out,
+                            "| {} | {} | {} | {} | {} | ${:.4} |",
+                            i + 1,
+                            u.model,
+                            u.input_tokens,
+                            u.cached_input_tokens,
+                            u.output_tokens,
+                            u.cost
+                        )?;
+                    }
+                    writeln!(out)?;
+                }
+                writeln!(
+                    out,
+                    "Total: {} input ({} cached), {} output tokens · ${:.2}",
+                    input, cached, output, task.cost
+                )?;
+                if equivalent > task.cost + 0.005 {
+                    writeln!(out, "(${:.2} at API rates)", equivalent)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TranscriptFile {
+    task: TranscriptTask,
+    #[serde(default)]
+    messages: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TranscriptTask {
+    title: Option<String>,
+    prompt: Option<String>,
+    agent_id: String,
+    model: Option<String>,
+    created_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TranscriptMessage {
+    #[serde(rename = "type")]
+    message_type: String,
+    timestamp: Option<String>,
+    content: Option<String>,
+    reasoning: Option<String>,
+    tool_name: Option<String>,
+    tool_arguments: Option<String>,
+    tool_return: Option<String>,
+}
+
+pub struct ImportedTranscript {
+    pub task: TaskRecord,
+    pub imported: usize,
+    pub skipped: usize,
+}
+
+/// Create task `task_id` from a JSON transcript written by `write_transcript`.
+/// The task has no agent session, so the first run reconnects with history injection.
+/// Usage totals are not imported; cost starts at zero on this machine.
+pub fn import_transcript(
+    conn: &mut Connection,
+    data: &[u8],
+    task_id: &str,
+    project_path: Option<String>,
+) -> Result<ImportedTranscript, String> {
+    let raw: serde_json::Value =
+        serde_json::from_slice(data).map_err(|e| format!("Not a JSON transcript: {}", e))?;
+    match raw.get("version").and_then(|v| v.as_u64()) {
+        Some(TRANSCRIPT_VERSION) => {}
+        Some(other) => return Err(format!("Unsupported transcript version: {}", other)),
+        None => return Err("Not a Phantom transcript (missing version)".to_string()),
+    }
+    let file: TranscriptFile =
+        serde_json::from_value(raw).map_err(|e| format!("Invalid transcript: {}", e))?;
+
+    let now = chrono::Utc::now();
+    let created_at = file
+        .task
+        .created_at
+        .as_deref()
+        .and_then(|ts| chrono::DateTime::parse_from_rfc3339(ts).ok())
+        .map(|dt| dt.timestamp())
+        .unwrap_or_else(|| now.timestamp());
+    let task = TaskRecord {
+        id: task_id.to_string(),
+        agent_id: file.task.agent_id,
+        codex_account_id: None,
+        model: file
+            .task
+            .model
+            .filter(|m| !m.trim().is_empty())
+            .unwrap_or_else(|| "default".to_string()),
+        prompt: file.task.prompt,
+        project_path,
+        project_key: None,
+        worktree_path: None,
+        branch: None,
+        context_id: None,
+        // "Stopped" makes start_task resume with "Continue" rather than re-running the prompt.
+        status: "Stopped".to_string(),
+        status_state: "idle".to_string(),
+        cost: 0.0,
+        created_at,
+        updated_at: now.timestamp(),
+        title_summary: file.task.title,
+        agent_session_id: None,
+        total_tokens: None,
+        context_window: None,
+        claude_runtime: None,
+        claude_team_name: None,
+        claude_agent_name: None,
+        codex_mode: None,
+        model_applied: None,
+        base_branch: None,
+        branch_ahead: None,
+        branch_behind: None,
+        git_hooks: None,
+        pr_number: None,
+        pr_url: None,
+        pr_state: None,
+        pr_checks: None,
+        archived: false,
+        pinned: false,
+        sparse_worktree: false,
+    };
+
+    let fallback_timestamp = now.to_rfc3339();
+    let (mut imported, mut skipped) = (0, 0);
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    db::insert_task(&tx, &task).map_err(|e| e.to_string())?;
+    for value in file.messages {
+        let message = match serde_json::from_value::<TranscriptMessage>(value) {
+            Ok(message) if IMPORTABLE_MESSAGE_TYPES.contains(&message.message_type.as_str()) => {
+                message
+            }
+            Ok(message) => {
+                eprintln!(
+                    "[Harness] import_transcript: skipping unknown message type {}",
+                    message.message_type
+                );
+                skipped += 1;
+                continue;
+            }
+            Err(err) => {
+                eprintln!(
+                    "[Harness] import_transcript: skipping malformed message: {}",
+                    err
+                );
+                skipped += 1;
+                continue;
+            }
+        };
+        db::save_message(
+            &tx,
+            task_id,
+            &message.message_type,
+            message.content.as_deref(),
+            message.reasoning.as_deref(),
+            message.tool_name.as_deref(),
+            message.tool_arguments.as_deref(),
+            message.tool_return.as_deref(),
+            message.timestamp.as_deref().unwrap_or(&fallback_timestamp),
+        )
+        .map_err(|e| e.to_string())?;
+        imported += 1;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(ImportedTranscript {
+        task,
+        imported,
+        skipped,
+    })
+}
+
+fn write_plan<W: Write>(out: &mut W, content: &str) -> std::io::Result<()> {
+    let Ok(payload) = serde_json::from_str::<serde_json::Value>(content) else {
+        return Ok(());
+    };
+    writeln!(out, "\n#### Plan update")?;
+    if let Some(explanation) = payload.get("explanation").and_then(|v| v.as_str()) {
+        writeln!(out, "\n{}", explanation.trim())?;
+    }
+    writeln!(out)?;
+    for step in payload
+        .get("plan")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+    {
+        let text = step.get("step").and_then(|v| v.as_str()).unwrap_or("");
+        let mark = match step.get("status").and_then(|v| v.as_str()) {
+            Some("completed") => "x",
+            Some("in_progress") => "~",
+            _ => " ",
+        };
+        writeln!(out, "- [{}] {}", mark, text)?;
+    }
+    Ok(())
+}
+
+/// Fenced block whose fence is longer than any backtick run inside `text`.
+fn write_code_block<W: Write>(out: &mut W, lang: &str, text: &str) -> std::io::Result<()> {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    writeln!(out, "\n{}{}\n{}\n{}", fence, lang, text, fence)
+}
+
+fn pretty_json(text: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(text)
+        .ok()
+        .and_then(|v| serde_json::to_string_pretty(&v).ok())
+        .unwrap_or_else(|| text.to_string())
+}
+
+fn format_unix(ts: i64) -> String {
+    chrono::DateTime::from_timestamp(ts, 0)
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redactor_replaces_configured_secrets() {
+        let redactor = Redactor::new(vec![
+            "sk-ant-abcdef123456".to_string(),
+            "short".to_string(),
+            "sk-ant-abcdef".to_string(),
+        ]);
+        assert_eq!(
+            redactor.redact("key=sk-ant-abcdef123456 and sk-ant-abcdef, short"),
+            "key=[REDACTED] and [REDACTED], short"
+        );
+        assert!(matches!(redactor.redact("nothing here"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_markdown_transcript() {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "phantom-harness-transcript-{}.sqlite",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let conn = db::init_db(&path).expect("init db");
+        conn.execute(
+            "INSERT INTO tasks (id, agent_id, model, prompt, cost, created_at, updated_at) VALUES ('task-1', 'codex', 'gpt-5', 'Rotate the key sk-secret-123456', 0.5, 0, 0)",
+            [],
+        )
+        .unwrap();
+        // (type, content, reasoning, tool name, tool arguments, tool return)
+        type Row<'a> = (
+            &'a str,
+            Option<&'a str>,
+            Option<&'a str>,
+            Option<&'a str>,
+            Option<&'a str>,
+            Option<&'a str>,
+        );
+        let messages: [Row; 4] = [
+            (
+                "reasoning_message",
+                None,
+                Some("thinking hard"),
+                None,
+                None,
+                None,
+            ),
+            (
+                "tool_call",
+                None,
+                None,
+                Some("shell"),
+                Some(r#"{"cmd":"echo sk-secret-123456"}"#),
+                None,
+            ),
+            ("tool_return", None, None, Some("shell"), None, Some("ok")),
+            ("assistant_message", Some("Done."), None, None, None, None),
+        ];
+        for (kind, content, reasoning, tool, args, ret) in messages {
+            db::save_message(
+                &conn,
+                "task-1",
+                kind,
+                content,
+                reasoning,
+                tool,
+                args,
+                ret,
+                "2026-01-01T10:00:00Z",
+            )
+            .unwrap();
+        }
+        let task = db::list_tasks(&conn).unwrap().remove(0);
+        let redactor = Redactor::new(vec!["sk-secret-123456".to_string()]);
+
+        let mut out = Vec::new();
+        write_transcript(
+            &conn,
+            &task,
+            TranscriptFormat::Markdown,
+            false,
+            &redactor,
+            &mut out,
+        )
+        .unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("## Prompt\n\nRotate the key [REDACTED]"));
+        assert!(text.contains("#### Tool call: `shell` · 10:00:00"));
+        assert!(text.contains("\"cmd\": \"echo [REDACTED]\""));
+        assert!(text.contains("### Assistant · 10:00:00\n\nDone."));
+        assert!(!text.contains("thinking hard"));
+        assert!(!text.contains("sk-secret"));
+
+        let mut out = Vec::new();
+        write_transcript(
+            &conn,
+            &task,
+            TranscriptFormat::Json,
+            true,
+            &redactor,
+            &mut out,
+        )
+        .unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(value["messages"].as_array().unwrap().len(), 4);
+        assert_eq!(value["messages"][0]["reasoning"], "thinking hard");
+        assert_eq!(value["usage"]["cost"], 0.5);
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_import_round_trip() {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "phantom-harness-transcript-import-{}.sqlite",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let mut conn = db::init_db(&path).expect("init db");
+        conn.execute(
+            "INSERT INTO tasks (id, agent_id, model, prompt, title_summary, cost, created_at, updated_at) VALUES ('task-1', 'codex', 'gpt-5', 'Fix the build', 'Build fix', 1.0, 1767261600, 0)",
+            [],
+        )
+        .unwrap();
+        for (kind, content, ts) in [
+            ("user_message", "Fix the build", "2026-01-01T10:00:00Z"),
+            ("assistant_message", "Fixed.", "2026-01-01T10:05:00Z"),
+        ] {
+            db::save_message(
+                &conn,
+                "task-1",
+                kind,
+                Some(content),
+                None,
+                None,
+                None,
+                None,
+                ts,
+            )
+            .unwrap();
+        }
+        let task = db::list_tasks(&conn).unwrap().remove(0);
+        let mut out = Vec::new();
+        write_transcript(
+            &conn,
+            &task,
+            TranscriptFormat::Json,
+            false,
+            &Redactor::new(Vec::new()),
+            &mut out,
+        )
+        .unwrap();
+
+        // Unknown and malformed entries are skipped, not fatal.
+        let mut value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let messages = value["messages"].as_array_mut().unwrap();
+        messages.push(json!({"type": "hologram", "content": "?"}));
+        messages.push(json!({"content": "no type"}));
+        let data = serde_json::to_vec(&value).unwrap();
+
+        let result =
+            import_transcript(&mut conn, &data, "task-2", Some("/tmp/repo".into())).unwrap();
+        assert_eq!((result.imported, result.skipped), (2, 2));
+        let imported = db::list_tasks(&conn)
+            .unwrap()
+            .into_iter()
+            .find(|t| t.id == "task-2")
+            .unwrap();
+        assert_eq!(imported.status_state, "idle");
+        assert_eq!(imported.agent_session_id, None);
+        assert_eq!(imported.title_summary.as_deref(), Some("Build fix"));
+        assert_eq!(imported.project_path.as_deref(), Some("/tmp/repo"));
+        assert_eq!(imported.created_at, 1767261600);
+        let records = db::get_message_records(&conn, "task-2").unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].content.as_deref(), Some("Fixed."));
+        assert_eq!(records[1].timestamp, "2026-01-01T10:05:00Z");
+
+        value["version"] = json!(2);
+        let data = serde_json::to_vec(&value).unwrap();
+        let err = import_transcript(&mut conn, &data, "task-3", None)
+            .err()
+            .unwrap();
+        assert!(err.contains("version"), "{err}");
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_code_block_fence_outgrows_content() {
+        let mut out = Vec::new();
+        write_code_block(&mut out, "", "```rust\nfn main() {}\n```").unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("\n````\n"));
+        assert!(text.ends_with("\n````\n"));
+    }
+}
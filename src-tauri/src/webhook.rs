@@ -13,6 +13,7 @@ const AVATAR_URL: &str =
 /// Colors used in webhooks (decimal format for Discord)
 const COLOR_MAROON: &str = "#5b2738"; // Test/nostalgic checkout
 const COLOR_GREEN: &str = "#00FF7F"; // Success/agent notification
+const COLOR_AMBER: &str = "#FFB000"; // Cost alert
 
 /// Slack-compatible payload structure for Discord webhooks
 #[derive(Debug, Clone, Serialize)]
@@ -205,6 +206,53 @@ pub fn build_agent_notification_payload(
     }
 }
 
+/// Build a cost alert payload for when a task crosses a spend threshold
+pub fn build_cost_alert_payload(
+    task_id: &str,
+    task_title: &str,
+    model: &str,
+    cost: f64,
+    threshold: f64,
+) -> SlackPayload {
+    let now = chrono::Utc::now().timestamp();
+
+    SlackPayload {
+        username: "Phantom Harness".to_string(),
+        icon_url: AVATAR_URL.to_string(),
+        attachments: vec![SlackAttachment {
+            fallback: format!("{} passed ${:.2}", task_title, threshold),
+            color: COLOR_AMBER.to_string(),
+            author_name: format!("Cost Alert - ${:.2}", threshold),
+            author_icon: AVATAR_URL.to_string(),
+            fields: vec![
+                SlackField {
+                    title: "Task".to_string(),
+                    value: task_title.to_string(),
+                    short: true,
+                },
+                SlackField {
+                    title: "Task ID".to_string(),
+                    value: task_id.to_string(),
+                    short: true,
+                },
+                SlackField {
+                    title: "Model".to_string(),
+                    value: model.to_string(),
+                    short: true,
+                },
+                SlackField {
+                    title: "Cost".to_string(),
+                    value: format!("${:.2}", cost),
+                    short: true,
+                },
+            ],
+            footer: "Phantom Harness".to_string(),
+            footer_icon: AVATAR_URL.to_string(),
+            ts: now,
+        }],
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -239,4 +287,14 @@ mod tests {
         assert!(preview_field.value.len() <= 203); // 200 + "..."
         assert!(preview_field.value.ends_with("..."));
     }
+
+    #[test]
+    fn test_build_cost_alert_payload() {
+        let payload = build_cost_alert_payload("task-1", "Fix login", "gpt-5", 10.456, 10.0);
+        let fields = &payload.attachments[0].fields;
+        assert_eq!(payload.attachments[0].author_name, "Cost Alert - $10.00");
+        assert_eq!(fields[1].value, "task-1");
+        assert_eq!(fields[2].value, "gpt-5");
+        assert_eq!(fields[3].value, "$10.46");
+    }
 }
@@ -4,6 +4,7 @@
 //! track of a user-selected active model.
 
 use crate::local_asr_model;
+use crate::AppHandle;
 use crate::AppState;
 use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
@@ -12,7 +13,7 @@ use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tauri::{AppHandle, Emitter, Manager, State};
+use tauri::{Emitter, Manager, State};
 use tokio::task::JoinHandle;
 
 const HF_BASE_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
@@ -8,18 +8,34 @@ use rand::seq::SliceRandom;
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex as StdMutex, OnceLock};
-use std::time::Duration;
-use tokio::io::AsyncWriteExt;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::process::Command;
 
 const GIT_COMMAND_TIMEOUT_SECS: u64 = 20;
 const GIT_WORKTREE_TIMEOUT_SECS: u64 = 120;
 const RSYNC_TIMEOUT_SECS: u64 = 120;
+const GIT_SUBMODULE_TIMEOUT_SECS: u64 = 600;
+const GIT_PUSH_TIMEOUT_SECS: u64 = 600;
+/// Kept short so naming a branch never stalls on a slow or unreachable remote.
+const GIT_REMOTE_CHECK_TIMEOUT_SECS: u64 = 5;
+/// Renames attempted before giving up when chosen names keep getting taken.
+const BRANCH_RENAME_ATTEMPTS: usize = 5;
 
 static REPO_LOCKS: OnceLock<StdMutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>> =
     OnceLock::new();
 
+/// Conflicting paths keyed by (base tip, branch tip); commit ids make entries immutable.
+type ConflictCache = HashMap<(String, String), Vec<String>>;
+static MERGE_CONFLICT_CACHE: OnceLock<StdMutex<ConflictCache>> = OnceLock::new();
+const MERGE_CONFLICT_CACHE_MAX: usize = 512;
+
+/// Worktree sizes by path, reused for `DISK_USAGE_TTL` (walking node_modules is slow).
+static DISK_USAGE_CACHE: OnceLock<StdMutex<HashMap<PathBuf, (Instant, u64)>>> = OnceLock::new();
+const DISK_USAGE_TTL: Duration = Duration::from_secs(5 * 60);
+
 /// Sanitize branch name for git and filesystem safety.
 ///
 /// Normalizes the branch name to:
@@ -108,7 +124,7 @@ pub async fn run_git_command(repo_path: &PathBuf, args: &[&str]) -> Result<Strin
     }
 }
 
-async fn run_git_command_bytes(repo_path: &PathBuf, args: &[&str]) -> Result<Vec<u8>, String> {
+pub async fn run_git_command_bytes(repo_path: &PathBuf, args: &[&str]) -> Result<Vec<u8>, String> {
     let output = run_git_command_raw(repo_path, args).await?;
     if output.status.success() {
         Ok(output.stdout)
@@ -148,48 +164,91 @@ async fn run_git_diff_bytes(repo_path: &PathBuf, args: &[&str]) -> Result<Vec<u8
     }
 }
 
-pub async fn diff_stats(repo_path: &PathBuf) -> Result<(u64, u64, u64), String> {
-    // Use a single numstat against the repo base to avoid double-counting partially staged files.
-    // Note: git diff may return exit code 1 when there are differences.
-    async fn numstat_against(repo_path: &PathBuf, base: &str) -> Result<String, String> {
-        let args = ["diff", "--numstat", "--no-color", base];
-        let out = run_git_diff_bytes(repo_path, &args).await?;
-        Ok(String::from_utf8_lossy(&out).to_string())
-    }
-
-    fn parse_numstat(text: &str) -> (u64, u64, u64) {
-        let mut additions: u64 = 0;
-        let mut deletions: u64 = 0;
-        let mut files: u64 = 0;
-
-        for line in text.lines() {
-            let line = line.trim();
-            if line.is_empty() {
-                continue;
-            }
-            let parts: Vec<&str> = line.split('\t').collect();
-            if parts.len() < 3 {
-                continue;
-            }
-            files += 1;
-            let add = parts[0];
-            let del = parts[1];
-            // Binary changes show '-' in numstat.
-            if add != "-" {
-                if let Ok(n) = add.parse::<u64>() {
-                    additions += n;
-                }
+/// Unified diff of one file between `base_ref` and `new_ref` (the working tree when None;
+/// untracked files then diff against the null device). Output is left untrimmed so it stays
+/// `git apply`-able.
+pub async fn file_diff_against_ref(
+    repo_path: &PathBuf,
+    base_ref: &str,
+    new_ref: Option<&str>,
+    file_path: &str,
+    context_lines: u32,
+    ignore_whitespace: bool,
+) -> Result<String, String> {
+    let context_arg = format!("-U{}", context_lines);
+    let mut args = vec!["diff", "--no-color", "--no-ext-diff", context_arg.as_str()];
+    if ignore_whitespace {
+        args.push("-w");
+    }
+
+    let mut tracked_args = args.clone();
+    tracked_args.push(base_ref);
+    tracked_args.extend(new_ref);
+    tracked_args.extend(["--", file_path]);
+    let diff = run_git_diff_bytes(repo_path, &tracked_args).await?;
+    if !diff.is_empty() || new_ref.is_some() {
+        return Ok(String::from_utf8_lossy(&diff).into_owned());
+    }
+
+    let tracked = run_git_command(repo_path, &["ls-files", "--", file_path]).await?;
+    if tracked.is_empty() && repo_path.join(file_path).is_file() {
+        args.extend(["--no-index", "--", null_device_path(), file_path]);
+        let diff = run_git_diff_bytes(repo_path, &args).await?;
+        return Ok(String::from_utf8_lossy(&diff).into_owned());
+    }
+    Ok(String::new())
+}
+
+/// `git diff --numstat` from `base` to `new_ref` (the working tree when None).
+/// Note: git diff may return exit code 1 when there are differences.
+pub async fn numstat_between(
+    repo_path: &PathBuf,
+    base: &str,
+    new_ref: Option<&str>,
+) -> Result<String, String> {
+    let mut args = vec!["diff", "--numstat", "--no-color", base];
+    args.extend(new_ref);
+    let out = run_git_diff_bytes(repo_path, &args).await?;
+    Ok(String::from_utf8_lossy(&out).to_string())
+}
+
+/// Sum numstat output into (additions, deletions, files).
+pub fn parse_numstat(text: &str) -> (u64, u64, u64) {
+    let mut additions: u64 = 0;
+    let mut deletions: u64 = 0;
+    let mut files: u64 = 0;
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let parts: Vec<&str> = line.split('\t').collect();
+        if parts.len() < 3 {
+            continue;
+        }
+        files += 1;
+        let add = parts[0];
+        let del = parts[1];
+        // Binary changes show '-' in numstat.
+        if add != "-" {
+            if let Ok(n) = add.parse::<u64>() {
+                additions += n;
             }
-            if del != "-" {
-                if let Ok(n) = del.parse::<u64>() {
-                    deletions += n;
-                }
+        }
+        if del != "-" {
+            if let Ok(n) = del.parse::<u64>() {
+                deletions += n;
             }
         }
-
-        (additions, deletions, files)
     }
 
+    (additions, deletions, files)
+}
+
+/// Numstat of the working tree against HEAD (the empty tree before the first commit).
+pub async fn diff_numstat(repo_path: &PathBuf) -> Result<String, String> {
+    // Use a single numstat against the repo base to avoid double-counting partially staged files.
     let base = if run_git_command(repo_path, &["rev-parse", "--verify", "HEAD"])
         .await
         .is_ok()
@@ -200,15 +259,52 @@ pub async fn diff_stats(repo_path: &PathBuf) -> Result<(u64, u64, u64), String>
     };
 
     if base.is_empty() {
-        return Ok((0, 0, 0));
+        return Ok(String::new());
     }
 
-    let combined = numstat_against(repo_path, &base).await.unwrap_or_default();
+    numstat_between(repo_path, &base, None).await
+}
+
+pub async fn diff_stats(repo_path: &PathBuf) -> Result<(u64, u64, u64), String> {
+    let combined = diff_numstat(repo_path).await.unwrap_or_default();
     let (additions, deletions, files) = parse_numstat(&combined);
 
     Ok((additions, deletions, files))
 }
 
+/// Fingerprint of the git state diffs depend on: the commits `refs` resolve to plus
+/// `git status --porcelain` and the size/mtime of each dirty path (a file edited twice
+/// keeps the same status line).
+pub async fn state_fingerprint(repo_path: &PathBuf, refs: &[&str]) -> Result<String, String> {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for git_ref in refs {
+        let sha = run_git_command(repo_path, &["rev-parse", "--verify", git_ref])
+            .await
+            .unwrap_or_default();
+        sha.hash(&mut hasher);
+    }
+    // Raw bytes: the trimmed variant would eat the first line's leading status column.
+    let status = run_git_command_bytes(
+        repo_path,
+        &["status", "--porcelain", "--untracked-files=all"],
+    )
+    .await?;
+    for line in String::from_utf8_lossy(&status).lines() {
+        line.hash(&mut hasher);
+        let path = line.get(3..).unwrap_or_default();
+        let path = path.rsplit(" -> ").next().unwrap_or(path);
+        if let Ok(meta) = std::fs::metadata(repo_path.join(path)) {
+            meta.len().hash(&mut hasher);
+            if let Ok(modified) = meta.modified() {
+                modified.hash(&mut hasher);
+            }
+        }
+    }
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
 fn repo_lock_key(repo_path: &PathBuf) -> String {
     std::fs::canonicalize(repo_path)
         .unwrap_or_else(|_| repo_path.clone())
@@ -386,7 +482,7 @@ async fn run_git_command_raw_with_input_timeout(
         .map_err(|e| format!("Failed to execute git: {}", e))
 }
 
-async fn git_empty_tree_hash(repo_path: &PathBuf) -> Result<String, String> {
+pub async fn git_empty_tree_hash(repo_path: &PathBuf) -> Result<String, String> {
     let output =
         run_git_command_raw_with_input(repo_path, &["hash-object", "-t", "tree", "--stdin"], b"")
             .await?;
@@ -447,21 +543,84 @@ pub async fn remote_branch_exists(
     Ok(!output.trim().is_empty())
 }
 
-/// Generate a unique branch name by appending -2, -3, etc. if the branch exists.
-pub async fn unique_branch_name(repo_path: &PathBuf, desired: &str) -> Result<String, String> {
-    let sanitized = sanitize_branch_name(desired);
+/// Whether `origin` has `branch`; None when there is no origin or it can't be reached
+/// in time (e.g. offline), so callers can fall back to local checks only.
+async fn origin_has_branch(repo_path: &PathBuf, branch: &str) -> Option<bool> {
+    let output = run_git_command_raw_with_timeout(
+        repo_path,
+        &[
+            "ls-remote",
+            "--heads",
+            "origin",
+            &format!("refs/heads/{}", branch),
+        ],
+        GIT_REMOTE_CHECK_TIMEOUT_SECS,
+    )
+    .await
+    .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(!String::from_utf8_lossy(&output.stdout).trim().is_empty())
+}
 
-    // Check if base name is available
-    if !branch_exists(repo_path, &sanitized).await? {
-        return Ok(sanitized);
+/// Default branch of `origin` from its HEAD symref, falling back to the local
+/// `origin/HEAD` when the remote can't be reached.
+pub async fn remote_default_branch(repo_path: &PathBuf) -> Option<String> {
+    if let Ok(output) = run_git_command_raw_with_timeout(
+        repo_path,
+        &["ls-remote", "--symref", "origin", "HEAD"],
+        GIT_REMOTE_CHECK_TIMEOUT_SECS,
+    )
+    .await
+    {
+        if output.status.success() {
+            if let Some(branch) = parse_symref_head(&String::from_utf8_lossy(&output.stdout)) {
+                return Some(branch);
+            }
+        }
     }
+    run_git_command(
+        repo_path,
+        &["symbolic-ref", "--short", "refs/remotes/origin/HEAD"],
+    )
+    .await
+    .ok()
+    .and_then(|head| head.strip_prefix("origin/").map(str::to_string))
+}
 
-    // Try with numeric suffix
-    for i in 2..100 {
-        let candidate = format!("{}-{}", sanitized, i);
-        if !branch_exists(repo_path, &candidate).await? {
-            return Ok(candidate);
+/// Branch from `git ls-remote --symref <remote> HEAD` output ("ref: refs/heads/main\tHEAD").
+fn parse_symref_head(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        line.strip_prefix("ref: refs/heads/")?
+            .strip_suffix("\tHEAD")
+            .map(str::to_string)
+    })
+}
+
+/// Generate a unique branch name by appending -v2, -v3, etc. if the branch exists
+/// locally or on origin. Names in `excluded` are treated as taken.
+pub async fn unique_branch_name(
+    repo_path: &PathBuf,
+    desired: &str,
+    excluded: &HashSet<String>,
+) -> Result<String, String> {
+    let sanitized = sanitize_branch_name(desired);
+
+    for i in 1..100 {
+        let candidate = if i == 1 {
+            sanitized.clone()
+        } else {
+            format!("{}-v{}", sanitized, i)
+        };
+        if excluded.contains(&candidate) || branch_exists(repo_path, &candidate).await? {
+            continue;
+        }
+        // An unreachable origin counts as free so naming still works offline.
+        if origin_has_branch(repo_path, &candidate).await == Some(true) {
+            continue;
         }
+        return Ok(candidate);
     }
 
     Err("Could not find unique branch name after 100 attempts".to_string())
@@ -540,23 +699,74 @@ pub fn repo_slug(path: &Path) -> String {
     sanitize_workspace_slug(name)
 }
 
-/// Resolve the Phantom Harness workspace root: ~/phantom-harness/workspaces
-pub fn workspace_root_dir() -> Result<PathBuf, String> {
+/// Default Phantom Harness workspace root: ~/phantom-harness/workspaces
+pub fn default_workspace_root() -> Result<PathBuf, String> {
     let home = dirs::home_dir().ok_or("Could not determine home directory")?;
-    let root = home.join("phantom-harness").join("workspaces");
+    Ok(home.join("phantom-harness").join("workspaces"))
+}
+
+/// Resolve the workspace root (`base_dir` if configured, else the default) and create it.
+pub fn workspace_root_dir(base_dir: Option<&Path>) -> Result<PathBuf, String> {
+    let root = match base_dir {
+        Some(dir) => dir.to_path_buf(),
+        None => default_workspace_root()?,
+    };
     std::fs::create_dir_all(&root)
         .map_err(|e| format!("Failed to create workspace root: {}", e))?;
     Ok(root)
 }
 
-const ANIMAL_NAMES_RAW: &str = include_str!("../data/animals_az_15.txt");
+/// Marker Phantom writes into each `<root>/<repo>` directory it creates. A custom base
+/// dir can be shared with unrelated folders; cleanup only scans marked repo dirs.
+const WORKSPACE_MARKER: &str = ".phantom-workspaces";
 
-/// Build a workspace path under ~/phantom-harness/workspaces/<repo>/<animal(-vN)>
-pub fn build_workspace_path(repo_slug: &str) -> Result<PathBuf, String> {
-    let root = workspace_root_dir()?;
+/// Create (and mark) the `<root>/<repo_slug>` directory that holds a repo's workspaces.
+pub fn workspace_repo_dir(root: &Path, repo_slug: &str) -> Result<PathBuf, String> {
     let repo_dir = root.join(repo_slug);
     std::fs::create_dir_all(&repo_dir)
         .map_err(|e| format!("Failed to create repo workspace dir: {}", e))?;
+    let marker = repo_dir.join(WORKSPACE_MARKER);
+    if !marker.exists() {
+        std::fs::write(&marker, b"")
+            .map_err(|e| format!("Failed to mark repo workspace dir: {}", e))?;
+    }
+    Ok(repo_dir)
+}
+
+/// Check that a custom workspace base directory is absolute, exists (creating it if
+/// needed), and is writable.
+pub fn validate_workspace_base_dir(dir: &Path) -> Result<(), String> {
+    if !dir.is_absolute() {
+        return Err(format!(
+            "Worktree base directory must be an absolute path: {}",
+            dir.display()
+        ));
+    }
+    std::fs::create_dir_all(dir).map_err(|e| {
+        format!(
+            "Worktree base directory {} could not be created: {}",
+            dir.display(),
+            e
+        )
+    })?;
+    let probe = dir.join(format!(".phantom-write-test-{}", std::process::id()));
+    std::fs::write(&probe, b"ok").map_err(|e| {
+        format!(
+            "Worktree base directory {} is not writable: {}",
+            dir.display(),
+            e
+        )
+    })?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(())
+}
+
+const ANIMAL_NAMES_RAW: &str = include_str!("../data/animals_az_15.txt");
+
+/// Build a workspace path under <workspace root>/<repo>/<animal(-vN)>
+pub fn build_workspace_path(base_dir: Option<&Path>, repo_slug: &str) -> Result<PathBuf, String> {
+    let root = workspace_root_dir(base_dir)?;
+    let repo_dir = workspace_repo_dir(&root, repo_slug)?;
 
     let base_name = random_animal_name()?;
     let unique = unique_workspace_name(&repo_dir, base_name)?;
@@ -601,32 +811,122 @@ fn unique_workspace_name(repo_dir: &Path, base: &str) -> Result<String, String>
     Err("Failed to find an available workspace name".to_string())
 }
 
-/// Remove a workspace directory safely (only under ~/phantom/workspaces).
-pub fn remove_workspace_dir(path: &PathBuf) -> Result<(), String> {
+/// Remove a workspace directory safely (only under the default or configured
+/// workspace root, so workspaces created before the base dir changed still clean up).
+pub fn remove_workspace_dir(path: &PathBuf, base_dir: Option<&Path>) -> Result<(), String> {
     if !path.exists() {
         return Ok(());
     }
-    let root = workspace_root_dir()?;
-    let canonical_root = std::fs::canonicalize(&root)
-        .map_err(|e| format!("Failed to resolve workspace root: {}", e))?;
     let canonical_path = std::fs::canonicalize(path)
         .map_err(|e| format!("Failed to resolve workspace path: {}", e))?;
-    if !canonical_path.starts_with(&canonical_root) {
+    let mut roots = vec![default_workspace_root()?];
+    roots.extend(base_dir.map(Path::to_path_buf));
+    let canonical_roots: Vec<PathBuf> = roots
+        .iter()
+        .filter_map(|root| std::fs::canonicalize(root).ok())
+        .collect();
+    if canonical_roots.contains(&canonical_path) {
+        return Err("Refusing to remove workspace root".to_string());
+    }
+    if !canonical_roots
+        .iter()
+        .any(|root| canonical_path.starts_with(root))
+    {
         return Err(format!(
             "Refusing to remove non-workspace path: {}",
             canonical_path.display()
         ));
     }
-    if canonical_path == canonical_root {
-        return Err("Refusing to remove workspace root".to_string());
-    }
     std::fs::remove_dir_all(&canonical_path)
         .map_err(|e| format!("Failed to remove workspace: {}", e))?;
     Ok(())
 }
 
+/// List workspace directories (`<root>/<repo>/<name>`) under the workspace root. Outside
+/// the default root only marked repo dirs are scanned, so unrelated folders under a shared
+/// base dir are never listed.
+pub fn list_workspace_dirs(root: &Path) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    let Ok(repos) = std::fs::read_dir(root) else {
+        return dirs;
+    };
+    let owned_root = default_workspace_root().is_ok_and(|default| default == root);
+    for repo in repos.flatten() {
+        if !repo.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            continue;
+        }
+        if !owned_root && !repo.path().join(WORKSPACE_MARKER).is_file() {
+            continue;
+        }
+        let Ok(entries) = std::fs::read_dir(repo.path()) else {
+            continue;
+        };
+        dirs.extend(
+            entries
+                .flatten()
+                .filter(|entry| entry.file_type().map(|t| t.is_dir()).unwrap_or(false))
+                .map(|entry| entry.path()),
+        );
+    }
+    dirs.sort();
+    dirs
+}
+
+/// Workspace directories that no path in `referenced` points at (or into).
+pub fn orphaned_workspace_dirs(dirs: &[PathBuf], referenced: &[PathBuf]) -> Vec<PathBuf> {
+    let canonical = |p: &Path| std::fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+    let referenced: Vec<PathBuf> = referenced.iter().map(|p| canonical(p)).collect();
+    dirs.iter()
+        .filter(|dir| {
+            let dir = canonical(dir);
+            !referenced.iter().any(|r| r.starts_with(&dir))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Total size in bytes of the files under `path` (symlinks are not followed).
+pub fn dir_size(path: &Path) -> u64 {
+    dir_size_cancellable(path, &AtomicBool::new(false)).unwrap_or(0)
+}
+
+/// Like `dir_size`, but gives up (returning `None`) once `cancel` is set.
+pub fn dir_size_cancellable(path: &Path, cancel: &AtomicBool) -> Option<u64> {
+    let mut total = 0;
+    for entry in walkdir::WalkDir::new(path).into_iter().flatten() {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        if let Ok(meta) = entry.metadata() {
+            if meta.is_file() {
+                total += meta.len();
+            }
+        }
+    }
+    Some(total)
+}
+
+/// `dir_size_cancellable` with results cached per path for a few minutes.
+/// Returns the size and whether it came from the cache.
+pub fn cached_dir_size(path: &Path, cancel: &AtomicBool) -> Option<(u64, bool)> {
+    let cache = DISK_USAGE_CACHE.get_or_init(|| StdMutex::new(HashMap::new()));
+    if let Some((at, bytes)) = cache.lock().ok().and_then(|c| c.get(path).copied()) {
+        if at.elapsed() < DISK_USAGE_TTL {
+            return Some((bytes, true));
+        }
+    }
+    let bytes = dir_size_cancellable(path, cancel)?;
+    if let Ok(mut cache) = cache.lock() {
+        cache.retain(|_, (at, _)| at.elapsed() < DISK_USAGE_TTL);
+        cache.insert(path.to_path_buf(), (Instant::now(), bytes));
+    }
+    Some((bytes, false))
+}
+
 /// Sync workspace contents from source directory to destination.
-/// Attempts rsync first, then falls back to a filesystem-based sync.
+/// Attempts rsync first, then falls back to a filesystem-based sync. `.git` entries are
+/// skipped at every depth, so checked-out submodule contents are copied without their
+/// gitlink files.
 pub async fn sync_workspace_from_source(src: &Path, dest: &Path) -> Result<(), String> {
     if let Err(err) = run_rsync(src, dest).await {
         eprintln!("[worktree] rsync failed, falling back: {}", err);
@@ -641,22 +941,41 @@ pub async fn sync_workspace_from_source(src: &Path, dest: &Path) -> Result<(), S
 
 /// Apply uncommitted changes from source repo to a newly created worktree.
 /// This preserves tracked + untracked modifications without copying the whole tree.
+/// `scope` limits the patch to one repo-relative directory (sparse worktrees).
 pub async fn apply_uncommitted_changes(
     source_repo: &Path,
     worktree_path: &Path,
+    scope: Option<&Path>,
 ) -> Result<(), String> {
     let repo = source_repo.to_path_buf();
     let worktree = worktree_path.to_path_buf();
+    let scope = scope.map(git_pathspec);
+    let pathspec = scope.as_deref().unwrap_or(".");
 
-    let staged = run_git_diff_bytes(&repo, &["diff", "--binary", "--no-color", "--cached"]).await?;
-    let unstaged = run_git_diff_bytes(&repo, &["diff", "--binary", "--no-color"]).await?;
+    let staged = run_git_diff_bytes(
+        &repo,
+        &["diff", "--binary", "--no-color", "--cached", "--", pathspec],
+    )
+    .await?;
+    let unstaged =
+        run_git_diff_bytes(&repo, &["diff", "--binary", "--no-color", "--", pathspec]).await?;
 
     let mut patch: Vec<u8> = Vec::new();
     patch.extend_from_slice(&staged);
     patch.extend_from_slice(&unstaged);
 
-    let untracked =
-        run_git_command_bytes(&repo, &["ls-files", "--others", "--exclude-standard", "-z"]).await?;
+    let untracked = run_git_command_bytes(
+        &repo,
+        &[
+            "ls-files",
+            "--others",
+            "--exclude-standard",
+            "-z",
+            "--",
+            pathspec,
+        ],
+    )
+    .await?;
     for raw_path in untracked.split(|byte| *byte == 0) {
         if raw_path.is_empty() {
             continue;
@@ -863,6 +1182,115 @@ fn copy_recursive(src: &Path, dest: &Path) -> Result<(), String> {
     Ok(())
 }
 
+/// Whether a checkout declares git submodules.
+pub fn has_submodules(path: &Path) -> bool {
+    path.join(".gitmodules").is_file()
+}
+
+fn is_git_auth_error(stderr: &str) -> bool {
+    let lower = stderr.to_lowercase();
+    lower.contains("terminal prompts disabled")
+        || lower.contains("could not read username")
+        || lower.contains("could not read password")
+        || lower.contains("authentication failed")
+        || lower.contains("permission denied (publickey")
+        || lower.contains("host key verification failed")
+        || lower.contains("repository not found")
+}
+
+/// Run `git submodule update --init --recursive` in a new worktree. Credential
+/// prompts are disabled (see `run_git_command_raw_with_timeout`), so submodules that
+/// need auth fail fast with an explanatory error instead of hanging.
+pub async fn init_submodules(worktree_path: &PathBuf) -> Result<(), String> {
+    let output = run_git_command_raw_with_timeout(
+        worktree_path,
+        &["submodule", "update", "--init", "--recursive"],
+        GIT_SUBMODULE_TIMEOUT_SECS,
+    )
+    .await?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if is_git_auth_error(&stderr) {
+        return Err(format!(
+            "Submodules need credentials git can't prompt for. Set up an SSH key or credential helper for the submodule remotes, then run `git submodule update --init --recursive` in {}. ({})",
+            worktree_path.to_string_lossy(),
+            stderr.lines().last().unwrap_or_default()
+        ));
+    }
+    Err(format!("Failed to initialize submodules: {}", stderr))
+}
+
+/// How a task worktree treats the repository's git hooks. Worktrees inherit
+/// `core.hooksPath` by default, so husky/pre-commit hooks run on agent commits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitHooksMode {
+    Inherit,
+    /// Point the worktree at an empty hooks directory.
+    Disabled,
+    /// Snapshot the repository's hooks into the worktree's own git dir.
+    Copied,
+}
+
+impl GitHooksMode {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim() {
+            "" | "inherit" => Ok(GitHooksMode::Inherit),
+            "disable" | "disabled" => Ok(GitHooksMode::Disabled),
+            "copy" | "copied" => Ok(GitHooksMode::Copied),
+            other => Err(format!("Invalid git hooks mode: {}", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GitHooksMode::Inherit => "inherit",
+            GitHooksMode::Disabled => "disabled",
+            GitHooksMode::Copied => "copied",
+        }
+    }
+}
+
+/// Set a worktree-local `core.hooksPath` for `mode` (enabling `extensions.worktreeConfig`
+/// so the main checkout's config is untouched). Safe to repeat, e.g. on reconnect;
+/// copied hooks are only snapshotted the first time.
+pub async fn apply_git_hooks_mode(
+    repo_root: &PathBuf,
+    worktree_path: &PathBuf,
+    mode: GitHooksMode,
+) -> Result<(), String> {
+    let hooks_dir = match mode {
+        GitHooksMode::Inherit => return Ok(()),
+        GitHooksMode::Disabled => "phantom-hooks-disabled",
+        GitHooksMode::Copied => "phantom-hooks",
+    };
+    let git_dir =
+        PathBuf::from(run_git_command(worktree_path, &["rev-parse", "--absolute-git-dir"]).await?);
+    let target = git_dir.join(hooks_dir);
+    if !target.is_dir() {
+        std::fs::create_dir_all(&target)
+            .map_err(|e| format!("Failed to create hooks directory: {}", e))?;
+        if mode == GitHooksMode::Copied {
+            // Resolves `core.hooksPath` (e.g. `.husky`) relative to the main checkout.
+            let source = run_git_command(repo_root, &["rev-parse", "--git-path", "hooks"]).await?;
+            let source = repo_root.join(source);
+            if source.is_dir() {
+                copy_recursive(&source, &target)?;
+            }
+        }
+    }
+
+    run_git_command(repo_root, &["config", "extensions.worktreeConfig", "true"]).await?;
+    let target = target.to_string_lossy().to_string();
+    run_git_command(
+        worktree_path,
+        &["config", "--worktree", "core.hooksPath", &target],
+    )
+    .await?;
+    Ok(())
+}
+
 /// Create a worktree with a new branch based on a specified base branch.
 ///
 /// # Arguments
@@ -870,11 +1298,13 @@ fn copy_recursive(src: &Path, dest: &Path) -> Result<(), String> {
 /// * `worktree_path` - Path where the worktree will be created
 /// * `branch` - Name of the new branch to create
 /// * `base_branch` - Branch to base the new branch on
+/// * `sparse_dir` - Repo-relative directory to limit the checkout to (cone mode)
 pub async fn create_worktree(
     repo_path: &PathBuf,
     worktree_path: &PathBuf,
     branch: &str,
     base_branch: &str,
+    sparse_dir: Option<&Path>,
 ) -> Result<(), String> {
     // Ensure parent directory exists
     if let Some(parent) = worktree_path.parent() {
@@ -884,14 +1314,13 @@ pub async fn create_worktree(
 
     // Create worktree with new branch based on base_branch
     // git worktree add -b <new-branch> <path> <base-branch>
-    let args = [
-        "worktree",
-        "add",
-        "-b",
-        branch,
-        &worktree_path.to_string_lossy(),
-        base_branch,
-    ];
+    // Sparse worktrees skip the initial checkout; apply_sparse_checkout populates them.
+    let worktree_arg = worktree_path.to_string_lossy();
+    let mut args = vec!["worktree", "add"];
+    if sparse_dir.is_some() {
+        args.push("--no-checkout");
+    }
+    args.extend(["-b", branch, &worktree_arg, base_branch]);
     let output =
         run_git_command_raw_with_timeout(repo_path, &args, GIT_WORKTREE_TIMEOUT_SECS).await?;
 
@@ -923,16 +1352,8 @@ pub async fn create_worktree(
 
         if looks_like_lfs {
             // Retry without hooks so missing git-lfs hooks don't block worktree creation.
-            let retry_args = [
-                "-c",
-                "core.hooksPath=/dev/null",
-                "worktree",
-                "add",
-                "-b",
-                branch,
-                &worktree_path.to_string_lossy(),
-                base_branch,
-            ];
+            let mut retry_args = vec!["-c", "core.hooksPath=/dev/null"];
+            retry_args.extend(&args);
             let retry_output =
                 run_git_command_raw_with_timeout(repo_path, &retry_args, GIT_WORKTREE_TIMEOUT_SECS)
                     .await?;
@@ -956,6 +1377,34 @@ pub async fn create_worktree(
         }
     }
 
+    if let Some(dir) = sparse_dir {
+        if let Err(err) = apply_sparse_checkout(worktree_path, dir).await {
+            let _ = remove_worktree(repo_path, worktree_path).await;
+            return Err(format!("Failed to set up sparse checkout: {}", err));
+        }
+    }
+
+    Ok(())
+}
+
+/// Convert a repo-relative path into a git pathspec (forward slashes).
+fn git_pathspec(relative: &Path) -> String {
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+/// Limit a `--no-checkout` worktree to `relative` and check it out. Cone mode always
+/// includes files at the repo root (manifests, lockfiles, configs).
+pub async fn apply_sparse_checkout(worktree_path: &PathBuf, relative: &Path) -> Result<(), String> {
+    let relative = git_pathspec(relative);
+    run_git_command(worktree_path, &["sparse-checkout", "init", "--cone"]).await?;
+    run_git_command(worktree_path, &["sparse-checkout", "set", &relative]).await?;
+    let output =
+        run_git_command_raw_with_timeout(worktree_path, &["checkout"], GIT_WORKTREE_TIMEOUT_SECS)
+            .await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git checkout failed: {}", stderr.trim()));
+    }
     Ok(())
 }
 
@@ -982,26 +1431,31 @@ fn worktree_candidate_name(base: &str, suffix: usize) -> String {
 }
 
 /// Create a worktree using an animal name, falling back to -v1, -v2, etc. on conflicts.
+/// `sparse_dir` limits the checkout to one repo-relative directory.
 pub async fn create_worktree_with_animal_name(
     repo_path: &PathBuf,
+    base_dir: Option<&Path>,
     repo_slug: &str,
     base_branch: &str,
+    sparse_dir: Option<&Path>,
 ) -> Result<(PathBuf, String), String> {
     const MAX_SUFFIX_ATTEMPTS: usize = 100;
 
     let repo_path = repo_path.clone();
+    let base_dir = base_dir.map(Path::to_path_buf);
     let repo_slug = repo_slug.to_string();
     let base_branch = base_branch.to_string();
+    let sparse_dir = sparse_dir.map(Path::to_path_buf);
 
     with_repo_lock(&repo_path.clone(), || {
         let repo_path = repo_path.clone();
+        let base_dir = base_dir.clone();
         let repo_slug = repo_slug.clone();
         let base_branch = base_branch.clone();
+        let sparse_dir = sparse_dir.clone();
         async move {
-            let root = workspace_root_dir()?;
-            let repo_dir = root.join(&repo_slug);
-            std::fs::create_dir_all(&repo_dir)
-                .map_err(|e| format!("Failed to create repo workspace dir: {}", e))?;
+            let root = workspace_root_dir(base_dir.as_deref())?;
+            let repo_dir = workspace_repo_dir(&root, &repo_slug)?;
 
             // Best-effort prune to clear stale worktree metadata before creating new ones.
             let _ = run_git_command_raw_with_timeout(
@@ -1048,8 +1502,14 @@ pub async fn create_worktree_with_animal_name(
                         continue;
                     }
 
-                    match create_worktree(&repo_path, &worktree_path, &candidate, &base_branch)
-                        .await
+                    match create_worktree(
+                        &repo_path,
+                        &worktree_path,
+                        &candidate,
+                        &base_branch,
+                        sparse_dir.as_deref(),
+                    )
+                    .await
                     {
                         Ok(()) => return Ok((worktree_path, candidate)),
                         Err(err) => {
@@ -1097,7 +1557,7 @@ pub async fn rename_worktree_branch(
         return Err("Worktree path no longer exists".to_string());
     }
 
-    // Use checkout -B + branch -d instead of branch -m to avoid the temp file
+    // Use checkout -b + branch -D instead of branch -m to avoid the temp file
     // race condition that occurs when multiple worktrees rename concurrently.
     // The issue: `git branch -m` uses a shared .tmp-renamed-log file in the
     // main repo's .git directory, which fails with concurrent operations.
@@ -1107,8 +1567,9 @@ pub async fn rename_worktree_branch(
         old_branch, new_branch, worktree_path
     );
 
-    // Step 1: Create the new branch at the same commit (checkout -B forces creation)
-    run_git_command(worktree_path, &["checkout", "-B", new_branch]).await?;
+    // Step 1: Create the new branch at the same commit. Plain -b fails if another
+    // task claimed the name in the meantime instead of resetting its branch.
+    run_git_command(worktree_path, &["checkout", "-b", new_branch]).await?;
 
     // Step 2: Delete the old branch (now safe since we're on the new one)
     // Use -D to force delete in case the branch isn't fully merged
@@ -1128,6 +1589,40 @@ pub async fn rename_worktree_branch(
     Ok(())
 }
 
+/// Rename `old_branch` to a unique name derived from `desired`, moving on to the next
+/// -v2, -v3 suffix when the chosen name turns out to be taken. Returns the new name.
+pub async fn rename_worktree_branch_unique(
+    repo_path: &PathBuf,
+    worktree_path: &PathBuf,
+    old_branch: &str,
+    desired: &str,
+) -> Result<String, String> {
+    let mut taken = HashSet::new();
+    let mut last_err = String::new();
+    for _ in 0..BRANCH_RENAME_ATTEMPTS {
+        let candidate = unique_branch_name(repo_path, desired, &taken).await?;
+        if candidate == old_branch {
+            return Ok(candidate);
+        }
+        match rename_worktree_branch(worktree_path, old_branch, &candidate).await {
+            Ok(()) => return Ok(candidate),
+            Err(err) if is_branch_conflict_error(&err) => {
+                eprintln!(
+                    "[worktree] Branch name {} was taken, retrying: {}",
+                    candidate, err
+                );
+                taken.insert(candidate);
+                last_err = err;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Err(format!(
+        "Could not rename branch after {} attempts: {}",
+        BRANCH_RENAME_ATTEMPTS, last_err
+    ))
+}
+
 /// Check if a worktree has uncommitted changes (staged, unstaged, or untracked).
 /// Returns Ok(true) if there are uncommitted changes, Ok(false) if clean.
 pub async fn has_uncommitted_changes(worktree_path: &PathBuf) -> Result<bool, String> {
@@ -1154,53 +1649,712 @@ pub async fn remove_worktree(repo_path: &PathBuf, worktree_path: &PathBuf) -> Re
     Ok(())
 }
 
-/// List all worktrees for a repository.
-#[allow(dead_code)]
-pub async fn list_worktrees(repo_path: &PathBuf) -> Result<Vec<String>, String> {
-    let output = run_git_command(repo_path, &["worktree", "list", "--porcelain"]).await?;
-
-    let worktrees: Vec<String> = output
-        .lines()
-        .filter(|line| line.starts_with("worktree "))
-        .map(|line| line.strip_prefix("worktree ").unwrap_or("").to_string())
-        .collect();
-
-    Ok(worktrees)
+/// Result of rebasing a worktree onto its base branch.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RebaseOutcome {
+    UpToDate,
+    Rebased,
+    /// Rebase stopped on conflicts; the worktree is left mid-rebase.
+    Conflicts(Vec<String>),
 }
 
-/// Push a branch to the remote and create a PR using gh CLI.
-///
-/// Returns the PR URL on success.
-#[allow(dead_code)]
-pub async fn create_pull_request(
+/// Fetch `origin/<base_branch>` and rebase the worktree's branch onto it (with
+/// `--autostash`). On conflicts the rebase is left in progress for manual resolution.
+pub async fn rebase_onto_remote_base(
     worktree_path: &PathBuf,
-    branch: &str,
-    _base_branch: &str,
-) -> Result<String, String> {
-    // Push branch to origin
-    run_git_command(worktree_path, &["push", "-u", "origin", branch]).await?;
-
-    // Create PR using gh CLI with --fill to auto-generate title/body from commits
-    let gh_path = resolve_gh_binary()?;
-    let output = Command::new(&gh_path)
-        .args(["pr", "create", "--fill", "--head", branch])
-        .current_dir(worktree_path)
-        .output()
-        .await
-        .map_err(|e| format!("Failed to execute gh: {}", e))?;
+    base_branch: &str,
+) -> Result<RebaseOutcome, String> {
+    for state_dir in ["rebase-merge", "rebase-apply"] {
+        if let Ok(dir) =
+            run_git_command(worktree_path, &["rev-parse", "--git-path", state_dir]).await
+        {
+            if worktree_path.join(dir).exists() {
+                return Err("A rebase is already in progress in this worktree".to_string());
+            }
+        }
+    }
 
-    if output.status.success() {
-        let url = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        Ok(url)
-    } else {
-        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-        Err(format!("gh pr create failed: {}", stderr))
+    let output = run_git_command_raw_with_timeout(
+        worktree_path,
+        &["fetch", "origin", base_branch],
+        GIT_WORKTREE_TIMEOUT_SECS,
+    )
+    .await?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to fetch origin/{}: {}",
+            base_branch,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    let upstream = format!("origin/{}", base_branch);
+    let merged = run_git_command_raw(
+        worktree_path,
+        &["merge-base", "--is-ancestor", &upstream, "HEAD"],
+    )
+    .await?;
+    if merged.status.success() {
+        return Ok(RebaseOutcome::UpToDate);
+    }
+
+    let output = run_git_command_raw_with_timeout(
+        worktree_path,
+        &["rebase", "--autostash", &upstream],
+        GIT_WORKTREE_TIMEOUT_SECS,
+    )
+    .await?;
+    // Conflicts can also come from re-applying the autostash after a clean rebase.
+    let conflicted = unmerged_files(worktree_path).await;
+    if !conflicted.is_empty() {
+        return Ok(RebaseOutcome::Conflicts(conflicted));
+    }
+    if output.status.success() {
+        return Ok(RebaseOutcome::Rebased);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let detail = if stderr.trim().is_empty() {
+        stdout.trim()
+    } else {
+        stderr.trim()
+    };
+    Err(format!("Rebase failed: {}", detail))
+}
+
+/// How a task branch is landed on its base branch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MergeStrategy {
+    /// Merge commit (`--no-ff`).
+    Merge,
+    /// Single squashed commit.
+    Squash,
+    /// Replay the branch's commits on top of the base (linear history).
+    RebaseMerge,
+}
+
+impl MergeStrategy {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim() {
+            "merge" => Ok(Self::Merge),
+            "squash" => Ok(Self::Squash),
+            "rebase-merge" => Ok(Self::RebaseMerge),
+            other => Err(format!(
+                "Unknown merge strategy '{}'. Valid values are: merge, squash, rebase-merge",
+                other
+            )),
+        }
+    }
+}
+
+async fn unmerged_files(repo_path: &PathBuf) -> Vec<String> {
+    run_git_command(repo_path, &["diff", "--name-only", "--diff-filter=U"])
+        .await
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Trial-merge `branch_ref` into the checked-out branch (`--no-commit --no-ff`) and
+/// abort it. Returns the files that would conflict (empty when the merge is clean).
+pub async fn merge_dry_run(repo_path: &PathBuf, branch_ref: &str) -> Result<Vec<String>, String> {
+    let output = run_git_command_raw_with_timeout(
+        repo_path,
+        &["merge", "--no-commit", "--no-ff", branch_ref],
+        GIT_WORKTREE_TIMEOUT_SECS,
+    )
+    .await?;
+    let conflicted = unmerged_files(repo_path).await;
+    let _ = run_git_command_raw(repo_path, &["merge", "--abort"]).await;
+    if !output.status.success() && conflicted.is_empty() {
+        return Err(format!(
+            "Trial merge failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(conflicted)
+}
+
+/// Land `branch_ref` on the checked-out branch of `repo_path` using `strategy`.
+/// Any failure mid-way is aborted so the checkout is left as it was.
+pub async fn merge_branch(
+    repo_path: &PathBuf,
+    branch_ref: &str,
+    strategy: MergeStrategy,
+    message: &str,
+) -> Result<(), String> {
+    let (args, abort): (Vec<&str>, &[&str]) = match strategy {
+        MergeStrategy::Merge => (
+            vec!["merge", "--no-ff", "-m", message, branch_ref],
+            &["merge", "--abort"],
+        ),
+        MergeStrategy::Squash => (vec!["merge", "--squash", branch_ref], &["reset", "--merge"]),
+        MergeStrategy::RebaseMerge => {
+            let range = format!("HEAD..{}", branch_ref);
+            let output = run_git_command_raw_with_timeout(
+                repo_path,
+                &["cherry-pick", &range],
+                GIT_WORKTREE_TIMEOUT_SECS,
+            )
+            .await?;
+            if output.status.success() {
+                return Ok(());
+            }
+            let _ = run_git_command_raw(repo_path, &["cherry-pick", "--abort"]).await;
+            return Err(format!(
+                "Rebase-merge failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    };
+    let output =
+        run_git_command_raw_with_timeout(repo_path, &args, GIT_WORKTREE_TIMEOUT_SECS).await?;
+    if !output.status.success() {
+        let _ = run_git_command_raw(repo_path, abort).await;
+        return Err(format!(
+            "Merge failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    if strategy == MergeStrategy::Squash {
+        if let Err(err) = run_git_command(repo_path, &["commit", "-m", message]).await {
+            let _ = run_git_command_raw(repo_path, abort).await;
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// Parse `git merge-tree --write-tree --name-only --no-messages` output: the tree id,
+/// then one conflicted path per line.
+fn parse_merge_tree_conflicts(stdout: &str) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    for line in stdout.lines().skip(1) {
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if !paths.iter().any(|p| p == line) {
+            paths.push(line.to_string());
+        }
+    }
+    paths
+}
+
+/// Paths that would conflict when merging `branch_tip` into `base_tip`. Uses
+/// `git merge-tree --write-tree` (git 2.38+), so no index or worktree is touched.
+/// Returns the paths and whether the result came from the cache.
+pub async fn merge_conflicts_between(
+    repo_path: &PathBuf,
+    base_tip: &str,
+    branch_tip: &str,
+) -> Result<(Vec<String>, bool), String> {
+    let key = (base_tip.to_string(), branch_tip.to_string());
+    let cache = MERGE_CONFLICT_CACHE.get_or_init(|| StdMutex::new(HashMap::new()));
+    if let Some(paths) = cache.lock().ok().and_then(|c| c.get(&key).cloned()) {
+        return Ok((paths, true));
+    }
+
+    let output = run_git_command_raw(
+        repo_path,
+        &[
+            "merge-tree",
+            "--write-tree",
+            "--name-only",
+            "--no-messages",
+            base_tip,
+            branch_tip,
+        ],
+    )
+    .await?;
+    let paths = match output.status.code() {
+        Some(0) => Vec::new(),
+        Some(1) => parse_merge_tree_conflicts(&String::from_utf8_lossy(&output.stdout)),
+        _ => {
+            return Err(format!(
+                "git merge-tree failed (requires git 2.38+): {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    };
+    if let Ok(mut cache) = cache.lock() {
+        if cache.len() >= MERGE_CONFLICT_CACHE_MAX {
+            cache.clear();
+        }
+        cache.insert(key, paths.clone());
+    }
+    Ok((paths, false))
+}
+
+/// Restore one repo-relative file (index and working tree) to its content at `rev`.
+/// Files that don't exist at `rev` are deleted, then any parent directories left empty
+/// are pruned; non-empty directories are never removed.
+pub async fn restore_file_to_rev(repo_root: &PathBuf, rev: &str, path: &str) -> Result<(), String> {
+    if !is_safe_relative_path(path) {
+        return Err(format!("Invalid file path: {}", path));
+    }
+    let pathspec = format!(":(literal){}", path);
+    let exists_at_rev =
+        run_git_command_raw(repo_root, &["cat-file", "-e", &format!("{}:{}", rev, path)])
+            .await?
+            .status
+            .success();
+    if exists_at_rev {
+        let source = format!("--source={}", rev);
+        run_git_command(
+            repo_root,
+            &[
+                "restore",
+                &source,
+                "--staged",
+                "--worktree",
+                "--",
+                &pathspec,
+            ],
+        )
+        .await?;
+        return Ok(());
+    }
+
+    run_git_command(
+        repo_root,
+        &[
+            "rm",
+            "--cached",
+            "--quiet",
+            "--ignore-unmatch",
+            "--",
+            &pathspec,
+        ],
+    )
+    .await?;
+    let full_path = repo_root.join(path);
+    match std::fs::symlink_metadata(&full_path) {
+        Ok(meta) if meta.is_dir() => {
+            return Err(format!("{} is a directory", path));
+        }
+        Ok(_) => std::fs::remove_file(&full_path).map_err(|e| e.to_string())?,
+        Err(_) => {}
+    }
+    let mut dir = full_path.parent();
+    while let Some(current) = dir {
+        if current == repo_root.as_path() || std::fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+    Ok(())
+}
+
+/// Relative path that stays inside the repository (no `..`, root, or prefix components).
+fn is_safe_relative_path(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)))
+}
+
+/// Stage every change in the worktree (`git add -A`); returns false when nothing is staged.
+pub async fn stage_all_changes(worktree_path: &PathBuf) -> Result<bool, String> {
+    run_git_command(worktree_path, &["add", "-A"]).await?;
+    let output = run_git_command_raw(worktree_path, &["diff", "--cached", "--quiet"]).await?;
+    Ok(!output.status.success())
+}
+
+/// `--stat` summary and full patch of the staged changes.
+pub async fn staged_diff(worktree_path: &PathBuf) -> Result<(String, String), String> {
+    let stat = run_git_command(worktree_path, &["diff", "--cached", "--stat"]).await?;
+    let patch = run_git_command(worktree_path, &["diff", "--cached", "--no-color"]).await?;
+    Ok((stat, patch))
+}
+
+/// Commit the staged changes and return the new commit sha. Hooks may run (unless
+/// `no_verify`), so this uses the longer worktree timeout.
+pub async fn commit_staged(
+    worktree_path: &PathBuf,
+    message: &str,
+    no_verify: bool,
+) -> Result<String, String> {
+    let mut args = vec!["commit", "-m", message];
+    if no_verify {
+        args.push("--no-verify");
+    }
+    let output =
+        run_git_command_raw_with_timeout(worktree_path, &args, GIT_WORKTREE_TIMEOUT_SECS).await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        return Err(format!(
+            "Commit failed: {}",
+            if stderr.is_empty() { stdout } else { stderr }
+        ));
+    }
+    run_git_command(worktree_path, &["rev-parse", "HEAD"]).await
+}
+
+/// Push `branch` to origin and set it as the upstream.
+pub async fn push_branch(worktree_path: &PathBuf, branch: &str) -> Result<(), String> {
+    let output = run_git_command_raw_with_timeout(
+        worktree_path,
+        &["push", "-u", "origin", branch],
+        GIT_WORKTREE_TIMEOUT_SECS,
+    )
+    .await?;
+    if !output.status.success() {
+        return Err(format!(
+            "Push failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// `git push -u origin <branch>` reporting each progress line git prints to `on_progress`.
+/// `force` pushes with `--force-with-lease`. Authentication failures come back as a hint to
+/// log in rather than git's raw output.
+pub async fn push_branch_with_progress<F>(
+    worktree_path: &PathBuf,
+    branch: &str,
+    force: bool,
+    mut on_progress: F,
+) -> Result<(), String>
+where
+    F: FnMut(&str),
+{
+    let mut args = vec!["push", "--progress", "-u"];
+    if force {
+        args.push("--force-with-lease");
+    }
+    args.extend(["origin", branch]);
+
+    let git_path = resolve_git_binary()?;
+    let mut child = Command::new(&git_path)
+        .args(&args)
+        .current_dir(worktree_path)
+        .env("PATH", git_env_path())
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env("GIT_SSH_COMMAND", "ssh -o BatchMode=yes")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to execute git: {}", e))?;
+    let mut stderr = child
+        .stderr
+        .take()
+        .ok_or_else(|| "Failed to capture git output".to_string())?;
+
+    // Progress meters rewrite their line with '\r', so split on both line endings.
+    let mut output = String::new();
+    let mut flush = |line: &mut Vec<u8>| {
+        let text = String::from_utf8_lossy(line).trim().to_string();
+        line.clear();
+        if !text.is_empty() {
+            on_progress(&text);
+            output.push_str(&text);
+            output.push('\n');
+        }
+    };
+    let run = async {
+        let mut buf = [0u8; 4096];
+        let mut line = Vec::new();
+        loop {
+            let n = stderr
+                .read(&mut buf)
+                .await
+                .map_err(|e| format!("Failed to read git output: {}", e))?;
+            if n == 0 {
+                flush(&mut line);
+                break;
+            }
+            for &byte in &buf[..n] {
+                if byte == b'\r' || byte == b'\n' {
+                    flush(&mut line);
+                } else {
+                    line.push(byte);
+                }
+            }
+        }
+        child
+            .wait()
+            .await
+            .map_err(|e| format!("Failed to execute git: {}", e))
+    };
+    let status = tokio::time::timeout(Duration::from_secs(GIT_PUSH_TIMEOUT_SECS), run)
+        .await
+        .map_err(|_| format!("git push timed out after {}s", GIT_PUSH_TIMEOUT_SECS))??;
+    if status.success() {
+        return Ok(());
+    }
+    if is_push_auth_error(&output) {
+        return Err(
+            "Push failed: origin rejected the credentials. Run `gh auth login` (it also sets up git for HTTPS remotes) or check your SSH key, then try again."
+                .to_string(),
+        );
+    }
+    // Keep git's final messages (the rejection reason), not the progress meters.
+    let detail: Vec<&str> = output.lines().filter(|line| !line.contains('%')).collect();
+    Err(format!(
+        "Push failed: {}",
+        detail[detail.len().saturating_sub(10)..].join("\n")
+    ))
+}
+
+fn is_push_auth_error(output: &str) -> bool {
+    let lower = output.to_lowercase();
+    [
+        "authentication failed",
+        "could not read username",
+        "could not read password",
+        "terminal prompts disabled",
+        "permission denied (publickey",
+        "invalid username or password",
+        "http basic: access denied",
+        "password authentication was removed",
+        "the requested url returned error: 403",
+    ]
+    .iter()
+    .any(|needle| lower.contains(needle))
+}
+
+/// Push `branch` unless its upstream already has every local commit. Returns whether it pushed.
+pub async fn push_branch_if_needed(worktree_path: &PathBuf, branch: &str) -> Result<bool, String> {
+    let unpushed =
+        match run_git_command(worktree_path, &["rev-list", "--count", "@{upstream}..HEAD"]).await {
+            Ok(count) => count.parse::<u32>().unwrap_or(1),
+            // No upstream yet
+            Err(_) => 1,
+        };
+    if unpushed == 0 {
+        return Ok(false);
+    }
+    push_branch(worktree_path, branch).await?;
+    Ok(true)
+}
+
+/// Subjects of the commits on HEAD that `base_ref` doesn't have, oldest first.
+pub async fn branch_commit_subjects(
+    worktree_path: &PathBuf,
+    base_ref: &str,
+) -> Result<Vec<String>, String> {
+    let output = run_git_command(
+        worktree_path,
+        &[
+            "log",
+            "--reverse",
+            "--format=%s",
+            &format!("{}..HEAD", base_ref),
+        ],
+    )
+    .await?;
+    Ok(output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Fetch `origin/<base_branch>` into `repo_path` while holding the repository lock, so
+/// background refreshes of several tasks never contend on git's ref locks. Returns the ref
+/// to compare against: the remote branch, or the local one when there is no `origin`.
+pub async fn fetch_base_branch(repo_path: &PathBuf, base_branch: &str) -> Result<String, String> {
+    let repo = repo_path.clone();
+    let base = base_branch.to_string();
+    with_repo_lock(repo_path, || async move {
+        if run_git_command(&repo, &["remote", "get-url", "origin"])
+            .await
+            .is_err()
+        {
+            return Ok(format!("refs/heads/{}", base));
+        }
+        let output = run_git_command_raw_with_timeout(
+            &repo,
+            &["fetch", "--quiet", "origin", &base],
+            GIT_WORKTREE_TIMEOUT_SECS,
+        )
+        .await?;
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to fetch origin/{}: {}",
+                base,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(format!("refs/remotes/origin/{}", base))
+    })
+    .await
+}
+
+/// Commits the worktree's HEAD is (ahead of, behind) `upstream`.
+pub async fn branch_divergence(
+    worktree_path: &PathBuf,
+    upstream: &str,
+) -> Result<(u32, u32), String> {
+    let range = format!("HEAD...{}", upstream);
+    let output = run_git_command(
+        worktree_path,
+        &["rev-list", "--left-right", "--count", &range],
+    )
+    .await?;
+    parse_left_right_count(&output)
+        .ok_or_else(|| format!("Unexpected rev-list output: {}", output.trim()))
+}
+
+fn parse_left_right_count(output: &str) -> Option<(u32, u32)> {
+    let mut parts = output.split_whitespace();
+    let ahead = parts.next()?.parse().ok()?;
+    let behind = parts.next()?.parse().ok()?;
+    Some((ahead, behind))
+}
+
+/// Outcome of `delete_branch_if_safe`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BranchDeletion {
+    Deleted,
+    /// No such local branch
+    Missing,
+    /// Kept because this many commits exist only on the branch
+    Unpushed(u32),
+}
+
+/// Commits on `branch` that are on no remote-tracking branch and not in `base_branch`.
+pub async fn unsaved_branch_commits(
+    repo_path: &PathBuf,
+    branch: &str,
+    base_branch: Option<&str>,
+) -> Result<u32, String> {
+    let branch_ref = format!("refs/heads/{}", branch);
+    let mut args = vec![
+        "rev-list",
+        "--count",
+        branch_ref.as_str(),
+        "--not",
+        "--remotes",
+    ];
+    let base_ref = base_branch.map(|base| format!("refs/heads/{}", base));
+    if let Some(base_ref) = base_ref.as_deref() {
+        if run_git_command(repo_path, &["rev-parse", "--verify", "--quiet", base_ref])
+            .await
+            .is_ok()
+        {
+            args.push(base_ref);
+        }
+    }
+    let count = run_git_command(repo_path, &args).await?;
+    count
+        .trim()
+        .parse::<u32>()
+        .map_err(|e| format!("Failed to count branch commits: {}", e))
+}
+
+/// Delete a local branch with `git branch -D`, unless (without `force`) it has commits
+/// that were neither merged into `base_branch` nor pushed anywhere.
+pub async fn delete_branch_if_safe(
+    repo_path: &PathBuf,
+    branch: &str,
+    base_branch: Option<&str>,
+    force: bool,
+) -> Result<BranchDeletion, String> {
+    if !branch_exists(repo_path, branch).await? {
+        return Ok(BranchDeletion::Missing);
+    }
+    if !force {
+        let unsaved = unsaved_branch_commits(repo_path, branch, base_branch).await?;
+        if unsaved > 0 {
+            return Ok(BranchDeletion::Unpushed(unsaved));
+        }
+    }
+    // Under the repo lock: bulk deletes remove several branches of one repo at once.
+    let repo = repo_path.clone();
+    let branch = branch.to_string();
+    with_repo_lock(repo_path, || async move {
+        run_git_command(&repo, &["branch", "-D", &branch]).await
+    })
+    .await?;
+    Ok(BranchDeletion::Deleted)
+}
+
+/// List all worktrees for a repository.
+pub async fn list_worktrees(repo_path: &PathBuf) -> Result<Vec<String>, String> {
+    let output = run_git_command(repo_path, &["worktree", "list", "--porcelain"]).await?;
+
+    let worktrees: Vec<String> = output
+        .lines()
+        .filter(|line| line.starts_with("worktree "))
+        .map(|line| line.strip_prefix("worktree ").unwrap_or("").to_string())
+        .collect();
+
+    Ok(worktrees)
+}
+
+/// Branches checked out in any worktree of the repository, the main one included.
+pub async fn checked_out_branches(repo_path: &PathBuf) -> Result<Vec<String>, String> {
+    let output = run_git_command(repo_path, &["worktree", "list", "--porcelain"]).await?;
+    Ok(parse_worktree_branches(&output))
+}
+
+fn parse_worktree_branches(porcelain: &str) -> Vec<String> {
+    porcelain
+        .lines()
+        .filter_map(|line| line.strip_prefix("branch refs/heads/"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Push a branch to the remote and create a PR using gh CLI.
+///
+/// Returns the PR URL on success.
+#[allow(dead_code)]
+pub async fn create_pull_request(
+    worktree_path: &PathBuf,
+    branch: &str,
+    _base_branch: &str,
+) -> Result<String, String> {
+    // Push branch to origin
+    run_git_command(worktree_path, &["push", "-u", "origin", branch]).await?;
+
+    // Create PR using gh CLI with --fill to auto-generate title/body from commits
+    let gh_path = resolve_gh_binary()?;
+    let output = Command::new(&gh_path)
+        .args(["pr", "create", "--fill", "--head", branch])
+        .current_dir(worktree_path)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to execute gh: {}", e))?;
+
+    if output.status.success() {
+        let url = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Ok(url)
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        Err(format!("gh pr create failed: {}", stderr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_auth_error_detection() {
+        assert!(is_push_auth_error(
+            "remote: Invalid username or password.\nfatal: Authentication failed for 'https://github.com/o/r.git/'"
+        ));
+        assert!(is_push_auth_error(
+            "fatal: could not read Username for 'https://github.com': terminal prompts disabled"
+        ));
+        assert!(is_push_auth_error(
+            "git@github.com: Permission denied (publickey).\nfatal: Could not read from remote repository."
+        ));
+        assert!(!is_push_auth_error(
+            " ! [rejected]        feat/x -> feat/x (non-fast-forward)\nerror: failed to push some refs"
+        ));
+    }
 
     #[test]
     fn test_sanitize_branch_name_basic() {
@@ -1272,6 +2426,192 @@ mod tests {
         assert!(!result.ends_with('-'));
     }
 
+    #[test]
+    fn test_orphaned_workspace_dirs() {
+        let root = std::env::temp_dir().join(format!(
+            "phantom-harness-workspaces-{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        workspace_repo_dir(&root, "repo").unwrap();
+        workspace_repo_dir(&root, "other").unwrap();
+        for dir in ["repo/otter", "repo/lynx", "other/heron/src"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(root.join("repo/lynx/big.bin"), vec![0u8; 2048]).unwrap();
+
+        let dirs = list_workspace_dirs(&root);
+        assert_eq!(dirs.len(), 3);
+        let referenced = vec![root.join("repo/otter"), root.join("other/heron/src")];
+        let orphans = orphaned_workspace_dirs(&dirs, &referenced);
+        assert_eq!(orphans, vec![root.join("repo/lynx")]);
+        assert_eq!(dir_size(&orphans[0]), 2048);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_cleanup_keeps_unowned_dirs_under_base() {
+        let base = std::env::temp_dir().join(format!(
+            "phantom-harness-shared-base-{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        // A user-chosen base like ~/dev holds unrelated projects next to Phantom's.
+        std::fs::create_dir_all(base.join("website/src")).unwrap();
+        std::fs::create_dir_all(base.join("tools/scripts")).unwrap();
+        let orphan = build_workspace_path(Some(&base), "repo").unwrap();
+        std::fs::create_dir_all(&orphan).unwrap();
+
+        let dirs = list_workspace_dirs(&base);
+        assert_eq!(dirs, vec![orphan.clone()]);
+        for dir in orphaned_workspace_dirs(&dirs, &[]) {
+            remove_workspace_dir(&dir, Some(&base)).unwrap();
+        }
+        assert!(!orphan.exists());
+        assert!(base.join("website/src").exists());
+        assert!(base.join("tools/scripts").exists());
+        // Unmarked dirs are never listed, even after the marked ones are gone.
+        assert!(list_workspace_dirs(&base).is_empty());
+
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_validate_workspace_base_dir() {
+        let dir = std::env::temp_dir().join(format!(
+            "phantom-harness-base-{}/nested",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        assert!(validate_workspace_base_dir(Path::new("relative/dir")).is_err());
+        validate_workspace_base_dir(&dir).expect("creatable dir is valid");
+        assert!(dir.is_dir());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        let _ = std::fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn test_parse_merge_tree_conflicts() {
+        let out = "4b825dc642cb6eb9a060e54bf8d69288fbee4904\nsrc/a.rs\nsrc/a.rs\nREADME.md\n";
+        assert_eq!(
+            parse_merge_tree_conflicts(out),
+            vec!["src/a.rs".to_string(), "README.md".to_string()]
+        );
+        assert!(parse_merge_tree_conflicts("4b825dc\n").is_empty());
+    }
+
+    #[test]
+    fn test_git_hooks_mode_parse() {
+        assert_eq!(GitHooksMode::parse("").unwrap(), GitHooksMode::Inherit);
+        assert_eq!(
+            GitHooksMode::parse("disable").unwrap(),
+            GitHooksMode::Disabled
+        );
+        assert_eq!(
+            GitHooksMode::parse(GitHooksMode::Copied.as_str()).unwrap(),
+            GitHooksMode::Copied
+        );
+        assert!(GitHooksMode::parse("skip").is_err());
+    }
+
+    #[test]
+    fn test_is_safe_relative_path() {
+        assert!(is_safe_relative_path("src/main.rs"));
+        assert!(is_safe_relative_path("a b/c.txt"));
+        assert!(!is_safe_relative_path(""));
+        assert!(!is_safe_relative_path("../outside.txt"));
+        assert!(!is_safe_relative_path("src/../../outside.txt"));
+        assert!(!is_safe_relative_path("/etc/passwd"));
+    }
+
+    #[test]
+    fn test_parse_left_right_count() {
+        assert_eq!(parse_left_right_count("3\t12\n"), Some((3, 12)));
+        assert_eq!(parse_left_right_count("0 0"), Some((0, 0)));
+        assert_eq!(parse_left_right_count("fatal"), None);
+        assert_eq!(parse_left_right_count("4"), None);
+    }
+
+    #[test]
+    fn test_is_git_auth_error() {
+        assert!(is_git_auth_error(
+            "fatal: could not read Username for 'https://github.com': terminal prompts disabled"
+        ));
+        assert!(is_git_auth_error(
+            "git@github.com: Permission denied (publickey)."
+        ));
+        assert!(!is_git_auth_error(
+            "fatal: reference is not a tree: 0123abcd"
+        ));
+    }
+
+    #[test]
+    fn test_sync_with_fs_copies_submodule_contents() {
+        let root = std::env::temp_dir().join(format!(
+            "phantom-harness-sync-{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let src = root.join("src");
+        std::fs::create_dir_all(src.join(".git")).unwrap();
+        std::fs::create_dir_all(src.join("vendor/lib")).unwrap();
+        std::fs::write(
+            src.join("vendor/lib/.git"),
+            "gitdir: ../../.git/modules/lib",
+        )
+        .unwrap();
+        std::fs::write(src.join("vendor/lib/lib.c"), "int x;").unwrap();
+
+        let dest = root.join("dest");
+        sync_with_fs(&src, &dest).unwrap();
+        assert!(dest.join("vendor/lib/lib.c").is_file());
+        assert!(!dest.join("vendor/lib/.git").exists());
+        assert!(!dest.join(".git").exists());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_cached_dir_size() {
+        let dir = std::env::temp_dir().join(format!(
+            "phantom-harness-usage-{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(dir.join("node_modules/pkg")).unwrap();
+        std::fs::write(dir.join("node_modules/pkg/index.js"), vec![0u8; 1000]).unwrap();
+
+        assert_eq!(dir_size_cancellable(&dir, &AtomicBool::new(true)), None);
+        let idle = AtomicBool::new(false);
+        assert_eq!(cached_dir_size(&dir, &idle), Some((1000, false)));
+        std::fs::write(dir.join("extra.bin"), vec![0u8; 24]).unwrap();
+        assert_eq!(cached_dir_size(&dir, &idle), Some((1000, true)));
+        assert_eq!(dir_size(&dir), 1024);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_merge_strategy_parse() {
+        assert_eq!(MergeStrategy::parse("squash"), Ok(MergeStrategy::Squash));
+        assert_eq!(
+            MergeStrategy::parse("rebase-merge"),
+            Ok(MergeStrategy::RebaseMerge)
+        );
+        assert!(MergeStrategy::parse("octopus").is_err());
+    }
+
     #[test]
     fn test_sanitize_workspace_slug() {
         assert_eq!(
@@ -1318,4 +2658,146 @@ mod tests {
         assert_eq!(worktree_candidate_name("otter", 1), "otter-v1");
         assert_eq!(worktree_candidate_name("otter", 2), "otter-v2");
     }
+
+    #[test]
+    fn test_parse_worktree_branches() {
+        let porcelain = "worktree /repo\nHEAD abc\nbranch refs/heads/main\n\n\
+                         worktree /ws/otter\nHEAD def\nbranch refs/heads/feature/otter\n\n\
+                         worktree /ws/detached\nHEAD 123\ndetached\n";
+        assert_eq!(
+            parse_worktree_branches(porcelain),
+            vec!["main".to_string(), "feature/otter".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_symref_head() {
+        let output = "ref: refs/heads/develop\tHEAD\n1234abcd\tHEAD\n";
+        assert_eq!(parse_symref_head(output).as_deref(), Some("develop"));
+        assert_eq!(parse_symref_head("1234abcd\tHEAD\n"), None);
+    }
+
+    #[test]
+    fn test_unique_branch_name_checks_origin_and_works_offline() {
+        let root = std::env::temp_dir().join(format!(
+            "phantom-harness-branch-names-{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let repo = root.join("repo");
+        let origin = root.join("origin.git");
+        std::fs::create_dir_all(&repo).unwrap();
+        let git = |dir: &Path, args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+                .args(args)
+                .current_dir(dir)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?}", args);
+        };
+        git(&repo, &["init", "-q"]);
+        git(&repo, &["commit", "-q", "--allow-empty", "-m", "init"]);
+        git(&repo, &["branch", "feat/local"]);
+        git(&root, &["init", "-q", "--bare", "origin.git"]);
+        git(
+            &repo,
+            &["remote", "add", "origin", origin.to_str().unwrap()],
+        );
+        git(
+            &repo,
+            &["push", "-q", "origin", "HEAD:refs/heads/feat/remote"],
+        );
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let none = HashSet::new();
+        rt.block_on(async {
+            let unique = |desired: &'static str| unique_branch_name(&repo, desired, &none);
+            assert_eq!(unique("feat/local").await.unwrap(), "feat/local-v2");
+            assert_eq!(unique("feat/remote").await.unwrap(), "feat/remote-v2");
+            assert_eq!(unique("feat/free").await.unwrap(), "feat/free");
+
+            // Unreachable origin (offline): only local branches count.
+            std::fs::remove_dir_all(&origin).unwrap();
+            assert_eq!(unique("feat/remote").await.unwrap(), "feat/remote");
+            assert_eq!(unique("feat/local").await.unwrap(), "feat/local-v2");
+            let taken = HashSet::from(["feat/local-v2".to_string()]);
+            assert_eq!(
+                unique_branch_name(&repo, "feat/local", &taken)
+                    .await
+                    .unwrap(),
+                "feat/local-v3"
+            );
+        });
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_sparse_worktree_checks_out_subdirectory() {
+        let root = std::env::temp_dir().join(format!(
+            "phantom-harness-sparse-{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let repo = root.join("repo");
+        let worktree = root.join("worktree");
+        std::fs::create_dir_all(repo.join("apps/web")).unwrap();
+        std::fs::create_dir_all(repo.join("apps/api")).unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+                .args(args)
+                .current_dir(&repo)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?}", args);
+        };
+        std::fs::write(repo.join("package.json"), "{}\n").unwrap();
+        std::fs::write(repo.join("apps/web/index.js"), "one\n").unwrap();
+        std::fs::write(repo.join("apps/api/main.rs"), "one\n").unwrap();
+        git(&["init", "-q"]);
+        git(&["add", "-A"]);
+        git(&["commit", "-q", "-m", "init"]);
+        std::fs::write(repo.join("apps/web/index.js"), "two\n").unwrap();
+        std::fs::write(repo.join("apps/web/new.js"), "new\n").unwrap();
+        std::fs::write(repo.join("apps/api/main.rs"), "two\n").unwrap();
+
+        let sparse = Path::new("apps/web");
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let status = rt.block_on(async {
+            create_worktree(&repo, &worktree, "sparse", "HEAD", Some(sparse))
+                .await
+                .unwrap();
+            apply_uncommitted_changes(&repo, &worktree, Some(sparse))
+                .await
+                .unwrap();
+            run_git_command(&worktree, &["status", "--porcelain"])
+                .await
+                .unwrap()
+        });
+
+        assert!(worktree.join("package.json").exists());
+        assert_eq!(
+            std::fs::read_to_string(worktree.join("apps/web/index.js")).unwrap(),
+            "two\n"
+        );
+        assert!(worktree.join("apps/web/new.js").exists());
+        assert!(!worktree.join("apps/api").exists());
+        // Files outside the cone must not show up as deleted.
+        let mut changed: Vec<&str> = status
+            .lines()
+            .filter_map(|line| line.split_whitespace().last())
+            .collect();
+        changed.sort();
+        assert_eq!(changed, ["apps/web/index.js", "apps/web/new.js"]);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
 }